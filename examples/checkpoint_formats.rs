@@ -0,0 +1,91 @@
+// Compares save and load times, file sizes and peak memory of the checkpoint
+// formats on a large flock. Each load happens in a fresh child process so the
+// peak resident set size belongs to that format alone.
+//
+// cargo run --release --features rkyv --example checkpoint_formats [BOIDS]
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::colour::Colour;
+use boids::state::{self, SaveFile};
+use boids::Boid;
+
+const FILES: [&str; 4] = ["state.json", "state.bin", "state.bin.zst", "state.rkyv"];
+
+// Linux only, the high water mark of resident memory for this process
+fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+fn load_child(path: &Path) {
+    let before = peak_rss_kb();
+    let now = Instant::now();
+    let save = state::load(path).expect("Unable to load state");
+    let elapsed = now.elapsed();
+    println!(
+        "{:>14}: load {:>8.1?}, {} boids, peak RSS {} MB (was {} MB before loading)",
+        path.file_name().unwrap().to_string_lossy(),
+        elapsed,
+        save.boids.len(),
+        peak_rss_kb().map_or("?".into(), |kb| (kb / 1024).to_string()),
+        before.map_or("?".into(), |kb| (kb / 1024).to_string()),
+    );
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let [_, flag, path] = &args[..]
+        && flag == "--load"
+    {
+        return load_child(Path::new(path));
+    }
+    let count = args
+        .get(1)
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(2_000_000);
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let boids: Vec<Boid> = (0..count)
+        .map(|id| {
+            Boid::new(
+                id,
+                Vector2::new(rng.random_range(0.0..1920.0), rng.random_range(0.0..1080.0)),
+                Vector2::new(rng.random_range(-1.5..1.5), rng.random_range(-1.5..1.5)),
+                Colour([rng.random(), rng.random(), rng.random()]),
+            )
+        })
+        .collect();
+    let save = SaveFile::new(1920, 1080, boids);
+
+    let dir = env::temp_dir().join(format!("boids_checkpoints_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<PathBuf> = FILES.iter().map(|file| dir.join(file)).collect();
+    for path in &paths {
+        let now = Instant::now();
+        state::save(path, &save).expect("Unable to save state");
+        println!(
+            "{:>14}: save {:>8.1?}, {:.1} MB",
+            path.file_name().unwrap().to_string_lossy(),
+            now.elapsed(),
+            fs::metadata(path).unwrap().len() as f64 / 1_000_000.0
+        );
+    }
+    drop(save);
+
+    let exe = env::current_exe().unwrap();
+    for path in &paths {
+        let status = Command::new(&exe).arg("--load").arg(path).status().unwrap();
+        assert!(status.success());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
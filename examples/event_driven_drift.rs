@@ -0,0 +1,81 @@
+// Compares EventDrivenUpdate against the full update_boids at a range of
+// update thresholds, reporting how far the boids drift from the reference
+// positions and how much of the flock was re-steered each frame.
+//
+// cargo run --release --example event_driven_drift
+use std::time::{Duration, Instant};
+
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::EventDrivenUpdate;
+use boids::colour::Colour;
+use boids::{update_boids, Boid, BoidId, Parameters, World};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+const BOIDS: usize = 20000;
+const FRAMES: usize = 200;
+
+fn main() {
+    let parameters = Parameters::default();
+    let mut rng = StdRng::seed_from_u64(42);
+    let start: Vec<Boid> = (0..BOIDS)
+        .map(|id| {
+            Boid::new(
+                id as BoidId,
+                Vector2::new(
+                    rng.random_range(0..WIDTH) as f32,
+                    rng.random_range(0..HEIGHT) as f32,
+                ),
+                Vector2::new(rng.random_range(-1.5..1.5), rng.random_range(-1.5..1.5)),
+                Colour([255, 255, 255]),
+            )
+        })
+        .collect();
+
+    let world = World::from_pixels(WIDTH, HEIGHT);
+    let mut reference = start.clone();
+    let mut reference_time = Duration::ZERO;
+    let mut reference_frames = Vec::with_capacity(FRAMES);
+    for _ in 0..FRAMES {
+        let now = Instant::now();
+        update_boids(&mut reference, &world, &parameters);
+        reference_time += now.elapsed();
+        reference_frames.push(reference.iter().map(|b| b.pos).collect::<Vec<_>>());
+    }
+    println!(
+        "full update: {:.1?} per frame",
+        reference_time / FRAMES as u32
+    );
+
+    for threshold in [0.25, 0.5, 1.0, 2.0, 4.0, 8.0] {
+        let parameters = Parameters {
+            update_threshold: threshold,
+            ..parameters.clone()
+        };
+        let mut boids = start.clone();
+        let mut updater = EventDrivenUpdate::new();
+        let mut elapsed = Duration::ZERO;
+        let mut dirty = 0;
+        let mut drift = 0.0;
+        for expected in &reference_frames {
+            let now = Instant::now();
+            updater.update(&mut boids, &world, &parameters);
+            elapsed += now.elapsed();
+            dirty += updater.dirty_count();
+            drift += boids
+                .iter()
+                .zip(expected)
+                .map(|(boid, pos)| (boid.pos - pos).norm())
+                .sum::<f32>()
+                / BOIDS as f32;
+        }
+        println!(
+            "threshold {threshold:>5}: {:.1?} per frame, {:>5.1}% re-steered, mean drift {:.2}px",
+            elapsed / FRAMES as u32,
+            100.0 * dirty as f32 / (BOIDS * FRAMES) as f32,
+            drift / FRAMES as f32,
+        );
+    }
+}
@@ -0,0 +1,143 @@
+#ifndef BOIDS_H
+#define BOIDS_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * How a call went
+ */
+typedef enum BoidsStatus {
+  BOIDS_STATUS_OK = 0,
+  /**
+   * A pointer that had to be set was null
+   */
+  BOIDS_STATUS_ERROR_NULL = 1,
+  /**
+   * Something went wrong inside the library, and the simulation
+   * shouldn't be used again
+   */
+  BOIDS_STATUS_ERROR_PANIC = 2,
+} BoidsStatus;
+
+/**
+ * A flock, only ever handled through a pointer
+ */
+typedef struct BoidsSim BoidsSim;
+
+/**
+ * The `Parameters` that are plain numbers and flags. Those that aren't,
+ * such as the boundary shape and speed zones, are left at their defaults.
+ */
+typedef struct BoidsParameters {
+  float max_speed;
+  float min_speed;
+  uint32_t margin;
+  float visible_range;
+  float protected_range;
+  float avoid_factor;
+  float matching_factor;
+  float centering_factor;
+  float turn_factor;
+  float cell_size;
+  int32_t draw_radius;
+  float update_threshold;
+  float global_centering_factor;
+  float render_smoothing;
+  /**
+   * 0 for no limit
+   */
+  size_t max_neighbors_for_early_exit;
+  bool aspect_cells;
+  float colour_rotation_speed;
+  bool per_boid_colour_rotation;
+  bool voronoi_neighbors;
+  size_t heading_histogram_bins;
+  bool draw_flock_hulls;
+  /**
+   * 0 to keep no frames to rewind to
+   */
+  size_t rewind_depth;
+  float perturbation_fraction;
+  float perturbation_strength;
+  size_t trail_length;
+  /**
+   * 0 to nudge a stopped boid, 1 to send it off along its heading, 2 to
+   * leave it stopped. Anything else is taken as 0.
+   */
+  uint32_t stall_policy;
+} BoidsParameters;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Fills `out` with the default parameters, to change before passing to
+ * `boids_sim_new`
+ *
+ * # Safety
+ *
+ * `out` has to be null or point to memory a `BoidsParameters` can be
+ * written to.
+ */
+enum BoidsStatus boids_parameters_default(struct BoidsParameters *out);
+
+/**
+ * `count` boids spread over a `width` x `height` world from `seed`, flying
+ * by `parameters`, or the defaults if it's null. Returns null if the world
+ * is empty or the parameters don't make sense for it. Free the flock with
+ * `boids_sim_free`.
+ *
+ * # Safety
+ *
+ * `parameters` has to be null or point to a `BoidsParameters`.
+ */
+struct BoidsSim *boids_sim_new(uint32_t width,
+                               uint32_t height,
+                               size_t count,
+                               uint64_t seed,
+                               const struct BoidsParameters *parameters);
+
+/**
+ * Moves the flock on by a frame
+ *
+ * # Safety
+ *
+ * `sim` has to be null or a flock from `boids_sim_new` that hasn't been
+ * freed, and not in use anywhere else during the call.
+ */
+enum BoidsStatus boids_sim_step(struct BoidsSim *sim);
+
+/**
+ * Writes each boid's x and y in turn into `out`, as many as fit in its
+ * `cap` floats, and returns how many boids there are, so a call with a
+ * `cap` of 0 finds the size to allocate. Returns 0 for a null `sim`.
+ *
+ * # Safety
+ *
+ * `sim` has to be null or a flock from `boids_sim_new` that hasn't been
+ * freed. `out` has to be null or point to `cap` floats.
+ */
+size_t boids_sim_positions(const struct BoidsSim *sim, float *out, size_t cap);
+
+/**
+ * Frees a flock from `boids_sim_new`. Null is ignored.
+ *
+ * # Safety
+ *
+ * `sim` has to be null or a flock from `boids_sim_new` that hasn't
+ * already been freed, and isn't used again.
+ */
+void boids_sim_free(struct BoidsSim *sim);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BOIDS_H */
@@ -0,0 +1,214 @@
+//! Python bindings, for driving a flock from a notebook.
+//!
+//! ```python
+//! import boids_py
+//!
+//! sim = boids_py.Simulation(1920, 1080, 1000, seed=7)
+//! sim.step(100)
+//! positions = sim.positions()  # (1000, 2) float32
+//! ```
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use boids::parameters::{ParametersBuilder, ValidationError};
+use boids::state::{self, Metadata, SaveFile};
+use boids::{Boid, Error, Parameters, Simulation as Inner, SimulationConfig, World};
+
+#[pymodule]
+fn boids_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Simulation>()
+}
+
+/// A flock in a `width` x `height` world. `parameters` is a dict of any
+/// parameters to change from the defaults.
+#[pyclass(module = "boids_py")]
+struct Simulation {
+    // Locked so a step can run with the GIL released
+    inner: Mutex<Inner>,
+}
+
+#[pymethods]
+impl Simulation {
+    #[new]
+    #[pyo3(signature = (width, height, count, seed = 0, parameters = None))]
+    fn new(
+        width: u32,
+        height: u32,
+        count: usize,
+        seed: u64,
+        parameters: Option<&Bound<'_, PyDict>>,
+    ) -> PyResult<Self> {
+        let parameters = match parameters {
+            Some(changes) => with_changes(&Parameters::default(), changes)?,
+            None => Parameters::default(),
+        };
+        let config = SimulationConfig {
+            seed,
+            ..SimulationConfig::new(World::from_pixels(width, height), parameters, count)
+        };
+        let inner = Inner::new(config).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Simulation {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Carries on with the flock saved at `path`, in any format the command
+    /// line tool saves. It flies by the parameters saved with it, or the
+    /// defaults for saves without them, with any in `parameters` changed.
+    #[staticmethod]
+    #[pyo3(signature = (path, parameters = None))]
+    fn load(path: PathBuf, parameters: Option<&Bound<'_, PyDict>>) -> PyResult<Self> {
+        let save = state::load(&path).map_err(to_py_err)?;
+        let world = save.world().ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "{} doesn't record the size of its world",
+                path.display()
+            ))
+        })?;
+        let saved = save.metadata.parameters.clone().unwrap_or_default();
+        let parameters = match parameters {
+            Some(changes) => with_changes(&saved, changes)?,
+            None => saved,
+        };
+        let count = save.boids.len();
+        let config = SimulationConfig {
+            seed: save.metadata.seed.unwrap_or_default(),
+            ..SimulationConfig::new(world, parameters, count)
+        };
+        let frame = save.metadata.frame.unwrap_or_default() as usize;
+        let inner = Inner::from_boids(config, save.boids, frame)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(Simulation {
+            inner: Mutex::new(inner),
+        })
+    }
+
+    /// Saves the flock to `path`, in the format its extension picks
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        let inner = self.lock();
+        let (width, height) = inner.world().pixels();
+        let save = SaveFile {
+            metadata: Metadata {
+                seed: Some(inner.seed()),
+                frame: Some(inner.frame() as u64),
+                parameters: Some(inner.parameters().clone()),
+                ..Metadata::now()
+            },
+            ..SaveFile::new(width, height, inner.boids().to_vec())
+        };
+        state::save(&path, &save).map_err(to_py_err)
+    }
+
+    /// Runs `frames` steps, letting other Python threads carry on meanwhile
+    #[pyo3(signature = (frames = 1))]
+    fn step(&self, py: Python<'_>, frames: usize) {
+        py.detach(|| {
+            let mut inner = self.lock();
+            for _ in 0..frames {
+                inner.step();
+            }
+        });
+    }
+
+    /// Each boid's x and y as an (N, 2) float32 array, a copy of the flock
+    /// as it is now
+    fn positions<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        self.columns(py, |boid| [boid.pos.x, boid.pos.y])
+    }
+
+    /// Each boid's velocity as an (N, 2) float32 array, like `positions`
+    fn velocities<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        self.columns(py, |boid| {
+            let vel = boid.velocity();
+            [vel.x, vel.y]
+        })
+    }
+
+    /// Every parameter, as a dict that `set_parameters` takes back
+    fn parameters<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let json = serde_json::to_string(self.lock().parameters())
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        py.import("json")?.call_method1("loads", (json,))
+    }
+
+    /// Changes the parameters named in `changes` from the next step on,
+    /// leaving the rest as they are. Raises `ValueError` without changing
+    /// any if they don't make sense together.
+    fn set_parameters(&self, changes: &Bound<'_, PyDict>) -> PyResult<()> {
+        let mut inner = self.lock();
+        let parameters = with_changes(inner.parameters(), changes)?;
+        inner.set_parameters(parameters);
+        Ok(())
+    }
+
+    /// How many steps have been run, counting any before a save was loaded
+    #[getter]
+    fn frame(&self) -> usize {
+        self.lock().frame()
+    }
+
+    fn __len__(&self) -> usize {
+        self.lock().boids().len()
+    }
+}
+
+impl Simulation {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // A panic mid-step leaves nothing half written worth refusing over
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // An (N, 2) array with a row for each boid
+    fn columns<'py>(
+        &self,
+        py: Python<'py>,
+        row: impl Fn(&Boid) -> [f32; 2],
+    ) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        let inner = self.lock();
+        let values: Vec<f32> = inner.boids().iter().flat_map(row).collect();
+        let rows = inner.boids().len();
+        // The Vec is handed to NumPy as it is, without another copy
+        PyArray1::from_vec(py, values).reshape([rows, 2])
+    }
+}
+
+// `parameters` with any named in `changes` replaced, checked together
+fn with_changes(parameters: &Parameters, changes: &Bound<'_, PyDict>) -> PyResult<Parameters> {
+    let json: String = changes
+        .py()
+        .import("json")?
+        .call_method1("dumps", (changes,))?
+        .extract()?;
+    let invalid = |err: serde_json::Error| PyValueError::new_err(err.to_string());
+    let mut value = serde_json::to_value(parameters).map_err(invalid)?;
+    let serde_json::Value::Object(changes) = serde_json::from_str(&json).map_err(invalid)? else {
+        return Err(PyValueError::new_err("parameters must be a dict"));
+    };
+    let fields = value
+        .as_object_mut()
+        .expect("parameters serialize to an object");
+    for (name, change) in changes {
+        if !fields.contains_key(&name) {
+            return Err(PyValueError::new_err(format!("unknown parameter {name}")));
+        }
+        fields.insert(name, change);
+    }
+    let parameters: Parameters = serde_json::from_value(value).map_err(invalid)?;
+    ParametersBuilder::from(parameters)
+        .build()
+        .map_err(|errors| PyValueError::new_err(ValidationError::join(&errors)))
+}
+
+fn to_py_err(err: Error) -> PyErr {
+    match err {
+        Error::Io { .. } => PyIOError::new_err(err.to_string()),
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
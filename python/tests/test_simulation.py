@@ -0,0 +1,101 @@
+import json
+
+import numpy as np
+import pytest
+
+import boids_py
+
+
+def run(seed, frames=20):
+    sim = boids_py.Simulation(400, 300, 200, seed=seed)
+    sim.step(frames)
+    return sim
+
+
+def test_positions_are_one_row_per_boid():
+    sim = boids_py.Simulation(400, 300, 50, seed=1)
+    positions = sim.positions()
+    assert positions.shape == (50, 2)
+    assert positions.dtype == np.float32
+    assert sim.velocities().shape == (50, 2)
+    assert len(sim) == 50
+    assert ((positions >= 0) & (positions <= [400, 300])).all()
+
+
+def test_rows_are_each_boids_position_and_velocity(tmp_path):
+    sim = run(2)
+    path = tmp_path / "flock.json"
+    sim.save(str(path))
+    boids = json.loads(path.read_text())["boids"]
+    np.testing.assert_array_equal(
+        sim.positions(), np.array([boid["pos"] for boid in boids], dtype=np.float32)
+    )
+    np.testing.assert_array_equal(
+        sim.velocities(), np.array([boid["vel"] for boid in boids], dtype=np.float32)
+    )
+
+
+def test_arrays_are_copies():
+    sim = run(2)
+    positions, velocities = sim.positions(), sim.velocities()
+    positions[:] = -1.0
+    velocities[:] = 0.0
+    assert (sim.positions() >= 0).all()
+    assert np.linalg.norm(sim.velocities(), axis=1).min() > 0
+    # Nor do they follow the flock as it moves on
+    before = sim.positions()
+    sim.step(1)
+    assert not np.array_equal(before, sim.positions())
+    assert positions.flags.writeable and positions.flags.c_contiguous
+
+
+def test_a_seed_repeats_the_run():
+    first, second = run(3), run(3)
+    assert first.frame == 20
+    np.testing.assert_array_equal(first.positions(), second.positions())
+    np.testing.assert_array_equal(first.velocities(), second.velocities())
+    assert not np.array_equal(first.positions(), run(4).positions())
+
+
+def test_parameters_round_trip_through_a_dict():
+    sim = boids_py.Simulation(400, 300, 10, seed=1, parameters={"visible_range": 30.0})
+    parameters = sim.parameters()
+    assert parameters["visible_range"] == 30.0
+
+    parameters["max_speed"] = 2.5
+    sim.set_parameters(parameters)
+    assert sim.parameters() == parameters
+
+    sim.step(5)
+    speeds = np.linalg.norm(sim.velocities(), axis=1)
+    assert (speeds <= 2.5 + 1e-5).all()
+
+
+def test_bad_parameters_change_nothing():
+    sim = boids_py.Simulation(400, 300, 10, seed=1)
+    before = sim.parameters()
+    with pytest.raises(ValueError):
+        sim.set_parameters({"cell_size": 0.0})
+    with pytest.raises(ValueError, match="unknown parameter"):
+        sim.set_parameters({"visible_rnage": 30.0})
+    assert sim.parameters() == before
+
+
+@pytest.mark.parametrize("name", ["flock.json", "flock.ron", "flock.bin"])
+def test_saves_load_back(tmp_path, name):
+    sim = run(5)
+    path = tmp_path / name
+    sim.save(str(path))
+    loaded = boids_py.Simulation.load(str(path))
+    assert loaded.frame == sim.frame
+    np.testing.assert_array_equal(loaded.positions(), sim.positions())
+
+    # Carrying on gives the same flock either way
+    sim.step(5)
+    loaded.step(5)
+    np.testing.assert_array_equal(loaded.positions(), sim.positions())
+
+
+def test_missing_saves_are_os_errors(tmp_path):
+    with pytest.raises(OSError):
+        boids_py.Simulation.load(str(tmp_path / "missing.json"))
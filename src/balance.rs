@@ -0,0 +1,89 @@
+//! Splitting the flock between threads by how long each part took to steer
+//! last frame, rather than by boid count, so a dense patch of the flock or
+//! a slow core doesn't leave the others waiting on one straggler.
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::parallel;
+
+/// Times each of `n_threads` runs of boids as they're steered, and moves
+/// boids out of the slow runs and into the quick ones for the next frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadBalancer {
+    /// How long each run of the last partition took
+    pub chunk_times: Vec<Duration>,
+    pub n_threads: usize,
+    // The runs those times are for
+    partition: Vec<Range<usize>>,
+}
+
+impl Default for LoadBalancer {
+    /// Balanced over as many threads as rayon has
+    fn default() -> Self {
+        LoadBalancer::new(parallel::current_num_threads())
+    }
+}
+
+impl LoadBalancer {
+    pub fn new(n_threads: usize) -> Self {
+        LoadBalancer {
+            chunk_times: Vec::new(),
+            n_threads: n_threads.max(1),
+            partition: Vec::new(),
+        }
+    }
+
+    /// Up to `n_threads` runs covering `boid_count` boids in order, each
+    /// expected to take as long as the others. Boids are taken to cost as
+    /// much as the average in the run they were last timed in, and runs
+    /// are even in size until there are times for the same number of boids.
+    pub fn next_partition(&self, boid_count: usize) -> Vec<Range<usize>> {
+        let chunks = self.n_threads.min(boid_count).max(1);
+        let timed = self.partition.last().map(|run| run.end) == Some(boid_count)
+            && self.chunk_times.len() == self.partition.len();
+        let total: f64 = self.chunk_times.iter().map(Duration::as_secs_f64).sum();
+        if !timed || total <= 0.0 {
+            return (0..chunks)
+                .map(|chunk| chunk * boid_count / chunks..(chunk + 1) * boid_count / chunks)
+                .collect();
+        }
+        let target = total / chunks as f64;
+        let mut partition = Vec::with_capacity(chunks);
+        let mut start = 0;
+        let mut spent = 0.0;
+        for (run, time) in self.partition.iter().zip(&self.chunk_times) {
+            // A run too quick to time still costs something
+            let cost = time.as_secs_f64().max(1e-9) / run.len() as f64;
+            for idx in run.clone() {
+                spent += cost;
+                if spent >= target * (partition.len() + 1) as f64 && partition.len() + 1 < chunks {
+                    partition.push(start..idx + 1);
+                    start = idx + 1;
+                }
+            }
+        }
+        if start < boid_count {
+            partition.push(start..boid_count);
+        }
+        partition
+    }
+
+    /// Remembers how long each run of `partition` took, for the next one
+    pub fn record(&mut self, partition: Vec<Range<usize>>, chunk_times: Vec<Duration>) {
+        self.partition = partition;
+        self.chunk_times = chunk_times;
+    }
+
+    /// The slowest run's time over the average, 1 when every run took as
+    /// long as the others, and 0 before any have been timed
+    pub fn imbalance(&self) -> f32 {
+        let Some(slowest) = self.chunk_times.iter().max() else {
+            return 0.0;
+        };
+        let mean = self.chunk_times.iter().sum::<Duration>() / self.chunk_times.len() as u32;
+        if mean.is_zero() {
+            return 1.0;
+        }
+        (slowest.as_secs_f64() / mean.as_secs_f64()) as f32
+    }
+}
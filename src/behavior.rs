@@ -0,0 +1,64 @@
+//! Forces pushed onto the flock from outside the usual rules, such as a gust
+//! of wind for a few frames, without touching the `Parameters`.
+//!
+//! ```
+//! use boids::behavior::{BoidBehavior, Wind};
+//! use boids::simulation::SimulationState;
+//!
+//! # let mut state = SimulationState::new(
+//! #     Vec::new(),
+//! #     boids::Parameters::default(),
+//! #     boids::World::from_pixels(200, 100),
+//! # );
+//! let gust: [Box<dyn BoidBehavior>; 1] = [Box::new(Wind(nalgebra::Vector2::new(0.5, 0.0)))];
+//! for frame in 0..300 {
+//!     if frame == 200 {
+//!         state.step_with_forces(&gust);
+//!     } else {
+//!         state.step();
+//!     }
+//! }
+//! ```
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+use crate::world::World;
+use crate::Parameters;
+
+/// Something that pushes on each boid, added to the velocity it steers to by
+/// the usual rules and before speed limits are applied
+pub trait BoidBehavior: Send + Sync {
+    fn force(&self, boid: &Boid, world: &World, parameters: &Parameters) -> Vector2<f32>;
+}
+
+impl<F> BoidBehavior for F
+where
+    F: Fn(&Boid, &World, &Parameters) -> Vector2<f32> + Send + Sync,
+{
+    fn force(&self, boid: &Boid, world: &World, parameters: &Parameters) -> Vector2<f32> {
+        self(boid, world, parameters)
+    }
+}
+
+/// The same push on every boid, wherever it is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wind(pub Vector2<f32>);
+
+impl BoidBehavior for Wind {
+    fn force(&self, _: &Boid, _: &World, _: &Parameters) -> Vector2<f32> {
+        self.0
+    }
+}
+
+/// The total of every force in `behaviors` on `boid`
+pub(crate) fn total_force(
+    behaviors: &[Box<dyn BoidBehavior>],
+    boid: &Boid,
+    world: &World,
+    parameters: &Parameters,
+) -> Vector2<f32> {
+    behaviors
+        .iter()
+        .map(|behavior| behavior.force(boid, world, parameters))
+        .sum()
+}
@@ -1,193 +1,1391 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f32::consts::{PI, TAU};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-use image::Rgb;
+use log::trace;
 use nalgebra::Vector2;
 use rand::prelude::*;
-use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::Parameters;
+use crate::balance::LoadBalancer;
+use crate::behavior::{total_force, BoidBehavior};
+use crate::colour::Colour;
+use crate::flock::NO_FLOCK;
+use crate::parallel::*;
+use crate::query::{Grid, SpatialQuery};
+use crate::rules::{self, Neighborhood, SteeringRule, CLASSIC_RULES};
+use crate::world::World;
+use crate::zone::SpeedLimitZone;
+use crate::{in_span, Parameters};
+
+/// What a boid is known by. The large-ids feature makes it 64 bits wide
+/// everywhere, for ids handed out across more than one machine.
+#[cfg(feature = "large-ids")]
+pub type BoidId = u64;
+/// What a boid is known by. The large-ids feature makes it 64 bits wide
+/// everywhere, for ids handed out across more than one machine.
+#[cfg(not(feature = "large-ids"))]
+pub type BoidId = usize;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct Boid {
-    id: usize,
+    pub(crate) id: BoidId,
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv_with::Vector2AsArray))]
     pub pos: Vector2<f32>,
+    // Everything past the position falls back to a default, so hand written
+    // or older states still load. A stopped boid gets nudged on its first
+    // update, and the speed is worked out from the velocity then.
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv_with::Vector2AsArray))]
+    #[serde(default)]
+    pub(crate) vel: Vector2<f32>,
+    #[serde(default)]
+    pub(crate) current_speed: f32,
+    #[serde(default = "white")]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv_with::ColourAsArray))]
+    pub colour: Colour,
+    /// Degrees the hue has turned through with `ColourMode::Rotating`. Not
+    /// saved, so a loaded flock starts its cycle again.
+    #[serde(skip)]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub(crate) hue_offset: f32,
+    /// Set by `FlockTracker`, `NO_FLOCK` until then. Not saved either.
+    #[serde(skip)]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub(crate) flock_id: u32,
+    /// Headings the boid had when last coloured with
+    /// `ColourMode::TrajectoryEntropy`, oldest first. Not saved either.
+    #[serde(skip)]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub(crate) heading_history: VecDeque<f32>,
+    /// Heading the boid had when it last came to a stop, to set off on
+    /// again with `StallPolicy::KeepHeading`. Not saved either.
+    #[serde(skip)]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub(crate) last_heading: Option<Vector2<f32>>,
+}
+
+/// A boid being put together by `Boid::at`. Anything not given is what a
+/// boid loaded without it gets: id 0, standing still, and white.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoidBuilder {
+    id: BoidId,
+    pos: Vector2<f32>,
     vel: Vector2<f32>,
-    current_speed: f32,
-    #[serde(with = "rgb_serde")]
-    pub colour: Rgb<u8>,
+    colour: Colour,
+}
+
+impl BoidBuilder {
+    pub fn with_id(mut self, id: BoidId) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_velocity(mut self, vel: Vector2<f32>) -> Self {
+        self.vel = vel;
+        self
+    }
+
+    pub fn with_colour(mut self, colour: impl Into<Colour>) -> Self {
+        self.colour = colour.into();
+        self
+    }
+
+    pub fn build(self) -> Boid {
+        Boid::new(self.id, self.pos, self.vel, self.colour)
+    }
 }
 
 impl Boid {
+    /// A boid with every part given, the speed being worked out from `vel`
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let boid = Boid::new(4, Vector2::new(10.0, 20.0), Vector2::new(0.0, 2.0), Colour::WHITE);
+    /// assert_eq!(boid.speed(), 2.0);
+    /// ```
     pub fn new(
-        id: usize,
+        id: BoidId,
         pos: Vector2<f32>,
         vel: Vector2<f32>,
-        current_speed: f32,
-        colour: Rgb<u8>,
+        colour: impl Into<Colour>,
     ) -> Self {
         Boid {
             id,
             pos,
             vel,
-            current_speed,
-            colour,
+            current_speed: vel.norm(),
+            colour: colour.into(),
+            hue_offset: 0.0,
+            flock_id: NO_FLOCK,
+            heading_history: VecDeque::new(),
+            last_heading: None,
         }
     }
+
+    /// Starts on a boid at `pos`, with only the parts that matter given
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let boid = Boid::at(Vector2::new(10.0, 20.0))
+    ///     .with_velocity(Vector2::new(0.0, 2.0))
+    ///     .with_id(4)
+    ///     .build();
+    /// assert_eq!(boid, Boid::new(4, Vector2::new(10.0, 20.0), Vector2::new(0.0, 2.0), Colour::WHITE));
+    /// ```
+    pub fn at(pos: Vector2<f32>) -> BoidBuilder {
+        BoidBuilder {
+            id: 0,
+            pos,
+            vel: Vector2::zeros(),
+            colour: Colour::WHITE,
+        }
+    }
+
+    /// Unique within a flock, and kept for the whole run
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let boid = Boid::new(7, Vector2::zeros(), Vector2::zeros(), Colour::BLACK);
+    /// assert_eq!(boid.id(), 7);
+    /// ```
+    pub fn id(&self) -> BoidId {
+        self.id
+    }
+
+    /// The flock `FlockTracker` last put this boid in, if any
+    pub fn flock_id(&self) -> Option<u32> {
+        (self.flock_id != NO_FLOCK).then_some(self.flock_id)
+    }
+
+    // The id as 64 bits, whichever width `BoidId` is
+    #[allow(clippy::unnecessary_cast)]
+    pub(crate) fn id_u64(&self) -> u64 {
+        self.id as u64
+    }
+
+    // Keeps the heading the boid's on if `next_vel` stops it, for
+    // `StallPolicy::KeepHeading` to set off on again
+    pub(crate) fn remember_heading(&mut self, next_vel: Vector2<f32>) {
+        if next_vel == Vector2::zeros() && self.vel != Vector2::zeros() {
+            self.last_heading = self.vel.try_normalize(0.0);
+        }
+    }
+
+    /// Pixels moved each frame
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let boid = Boid::new(0, Vector2::zeros(), Vector2::new(3.0, 4.0), Colour::BLACK);
+    /// assert_eq!(boid.velocity(), Vector2::new(3.0, 4.0));
+    /// ```
+    pub fn velocity(&self) -> Vector2<f32> {
+        self.vel
+    }
+
+    /// Length of the velocity, worked out from it so it's right however the
+    /// boid was made
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let boid = Boid::new(0, Vector2::zeros(), Vector2::new(3.0, 4.0), Colour::BLACK);
+    /// assert_eq!(boid.speed(), 5.0);
+    /// ```
+    pub fn speed(&self) -> f32 {
+        self.vel.norm()
+    }
+
+    /// Sets the velocity, and the speed to go with it
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use boids::colour::Colour;
+    /// # use nalgebra::Vector2;
+    /// let mut boid = Boid::new(0, Vector2::zeros(), Vector2::zeros(), Colour::BLACK);
+    /// boid.set_velocity(Vector2::new(0.0, -2.0));
+    /// assert_eq!(boid.velocity(), Vector2::new(0.0, -2.0));
+    /// assert_eq!(boid.speed(), 2.0);
+    /// ```
+    pub fn set_velocity(&mut self, vel: Vector2<f32>) {
+        self.vel = vel;
+        self.current_speed = vel.norm();
+    }
+
+    /// Moves the boid to `pos` at once. It's clamped back into the world on
+    /// its next update if that's outside it.
+    pub fn set_position(&mut self, pos: Vector2<f32>) {
+        self.pos = pos;
+    }
+
+    /// Adds `dv` to the velocity, such as to knock a boid off course. Speed
+    /// limits catch up with it on its next update.
+    ///
+    /// ```
+    /// # use boids::Boid;
+    /// # use nalgebra::Vector2;
+    /// let mut boid = Boid::at(Vector2::zeros()).with_velocity(Vector2::new(3.0, 0.0)).build();
+    /// boid.nudge(Vector2::new(0.0, 4.0));
+    /// assert_eq!(boid.velocity(), Vector2::new(3.0, 4.0));
+    /// assert_eq!(boid.speed(), 5.0);
+    /// ```
+    pub fn nudge(&mut self, dv: Vector2<f32>) {
+        self.set_velocity(self.vel + dv);
+    }
+
+    /// Radians from the +x axis to the velocity, from -pi to pi as `atan2`
+    /// gives them. y grows down the frame, so positive headings turn
+    /// clockwise on screen. A boid that isn't moving heads along 0.
+    pub fn heading(&self) -> f32 {
+        if self.vel == Vector2::zeros() {
+            return 0.0;
+        }
+        self.vel.y.atan2(self.vel.x)
+    }
+
+    pub fn distance_squared_to(&self, other: &Boid) -> f32 {
+        (other.pos - self.pos).norm_squared()
+    }
+
+    pub fn distance_to(&self, other: &Boid) -> f32 {
+        self.distance_squared_to(other).sqrt()
+    }
+
+    /// Whether `other` is closer than `range`, not counting exactly `range`
+    pub fn is_within(&self, other: &Boid, range: f32) -> bool {
+        self.distance_squared_to(other) < range * range
+    }
+
+    /// Radians to turn from this boid's heading to face `other`, in
+    /// `[-pi, pi)`, so 0 is dead ahead and -pi right behind. A boid in the
+    /// same place is dead ahead.
+    pub fn relative_bearing_to(&self, other: &Boid) -> f32 {
+        let offset = other.pos - self.pos;
+        if offset == Vector2::zeros() {
+            return 0.0;
+        }
+        (offset.y.atan2(offset.x) - self.heading() + PI).rem_euclid(TAU) - PI
+    }
 }
 
-pub fn populate_grid(boids: &[Boid], cell_size: f32) -> HashMap<(u32, u32), Vec<usize>> {
-    let mut grid: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+/// One line for logs and debugging, in a stable format:
+///
+/// ```
+/// # use boids::Boid;
+/// # use boids::colour::Colour;
+/// # use nalgebra::Vector2;
+/// let boid = Boid::new(3, Vector2::new(512.34, 98.1), Vector2::new(1.2, -0.4), Colour::BLACK);
+/// assert_eq!(boid.to_string(), "#3 pos=(512.3, 98.1) vel=(1.2, -0.4) spd=1.26");
+/// ```
+impl fmt::Display for Boid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} pos=({:.1}, {:.1}) vel=({:.1}, {:.1}) spd={:.2}",
+            self.id,
+            self.pos.x,
+            self.pos.y,
+            self.vel.x,
+            self.vel.y,
+            self.speed()
+        )
+    }
+}
+
+/// Boid indices bucketed by the `cell_w` x `cell_h` rectangle they fall in,
+/// covering a world of a known size.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    pub cells: HashMap<(u32, u32), Vec<usize>>,
+    pub grid_cols: u32,
+    pub grid_rows: u32,
+    pub cell_w: f32,
+    pub cell_h: f32,
+}
+
+impl SpatialGrid {
+    /// The boids in a cell, or `None` if the cell is empty or outside the world
+    pub fn get_cell(&self, cx: u32, cy: u32) -> Option<&[usize]> {
+        if cx >= self.grid_cols || cy >= self.grid_rows {
+            return None;
+        }
+        self.cells.get(&(cx, cy)).map(Vec::as_slice)
+    }
+
+    /// How many boids are in the grid, which should always match the flock
+    pub fn total_boids(&self) -> usize {
+        self.cells.values().map(Vec::len).sum()
+    }
+
+    /// `(count, (cell_x, cell_y))` for every occupied cell, busiest first
+    pub fn cell_counts(&self) -> Vec<(usize, (u32, u32))> {
+        let mut counts: Vec<(usize, (u32, u32))> = self
+            .cells
+            .iter()
+            .map(|(cell, indices)| (indices.len(), *cell))
+            .collect();
+        counts.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        counts
+    }
+
+    /// The most boids in any one cell
+    pub fn max_occupancy(&self) -> usize {
+        self.cells.values().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Average boids per occupied cell
+    pub fn mean_occupancy(&self) -> f32 {
+        if self.cells.is_empty() {
+            return 0.0;
+        }
+        self.total_boids() as f32 / self.cells.len() as f32
+    }
+
+    /// Fraction of all the cells in the world holding at least one boid
+    pub fn occupied_fraction(&self) -> f32 {
+        let total_cells = self.grid_cols as usize * self.grid_rows as usize;
+        if total_cells == 0 {
+            return 0.0;
+        }
+        self.cells.len() as f32 / total_cells as f32
+    }
+
+    /// Panics unless every index in `0..boid_count` is in exactly one cell
+    pub fn assert_valid(&self, boid_count: usize) {
+        let mut present = vec![false; boid_count];
+        for (cell, indices) in &self.cells {
+            for &index in indices {
+                assert!(
+                    index < boid_count,
+                    "cell {cell:?} holds boid {index} but there are only {boid_count} boids"
+                );
+                assert!(
+                    !present[index],
+                    "boid {index} appears in the grid more than once"
+                );
+                present[index] = true;
+            }
+        }
+        if let Some(missing) = present.iter().position(|present| !present) {
+            panic!("boid {missing} is missing from the grid");
+        }
+    }
+
+    /// The cell a boid should be in, on a grid of `cell_size` squares
+    pub fn cell_of(boid_idx: usize, boids: &[Boid], cell_size: f32) -> (u32, u32) {
+        cell_for(boids[boid_idx].pos, cell_size, cell_size)
+    }
+
+    /// The cell of this grid a position falls in
+    pub fn cell_at(&self, pos: Vector2<f32>) -> (u32, u32) {
+        cell_for(pos, self.cell_w, self.cell_h)
+    }
+
+    /// Checks every boid is in the cell its position says it should be
+    pub fn verify_boid_placement(&self, boids: &[Boid]) -> bool {
+        self.cells.iter().all(|(cell, indices)| {
+            indices
+                .iter()
+                .all(|&index| index < boids.len() && self.cell_at(boids[index].pos) == *cell)
+        })
+    }
+}
+
+/// A grid of `cell_size` squares
+pub fn populate_grid(boids: &[Boid], cell_size: f32, width: u32, height: u32) -> SpatialGrid {
+    populate_grid_rect(boids, cell_size, cell_size, width, height)
+}
+
+/// A grid of `cell_w` x `cell_h` rectangles. Both need to be at least the
+/// visible range for the 3x3 neighbourhood search to find every flockmate.
+pub fn populate_grid_rect(
+    boids: &[Boid],
+    cell_w: f32,
+    cell_h: f32,
+    width: u32,
+    height: u32,
+) -> SpatialGrid {
+    let mut grid = SpatialGrid {
+        cells: HashMap::new(),
+        grid_cols: (width as f32 / cell_w).ceil() as u32,
+        grid_rows: (height as f32 / cell_h).ceil() as u32,
+        cell_w,
+        cell_h,
+    };
     for (index, boid) in boids.iter().enumerate() {
-        let cell_x: u32 = (boid.pos.x / cell_size).floor() as u32;
-        let cell_y: u32 = (boid.pos.y / cell_size).floor() as u32;
-        grid.entry((cell_x, cell_y)).or_default().push(index);
+        grid.cells
+            .entry(grid.cell_at(boid.pos))
+            .or_default()
+            .push(index);
+    }
+    if cfg!(debug_assertions) {
+        grid.assert_valid(boids.len());
     }
     grid
 }
 
-pub fn update_boids(boids: &mut Vec<Boid>, height: u32, width: u32, parameters: Parameters) {
-    let protected_range_squared = parameters.protected_range * parameters.protected_range;
-    let visible_range_squared = parameters.visible_range * parameters.visible_range;
-    let grid = populate_grid(boids, parameters.cell_size);
-    // For rust, we'll need to gather all the changes, then apply
-    let new_boid_states: Vec<(Vector2<f32>, Vector2<f32>, f32)> = boids
+/// Centre of mass of the whole flock
+pub fn flock_centroid(boids: &[Boid]) -> Vector2<f32> {
+    if boids.is_empty() {
+        return Vector2::zeros();
+    }
+    boids.par_iter().map(|boid| boid.pos).sum::<Vector2<f32>>() / boids.len() as f32
+}
+
+/// How much the flock is heading the same way, from 0 when headings cancel
+/// out to 1 when every boid flies in the same direction. Boids that aren't
+/// moving count as pointing nowhere.
+pub fn polarization(boids: &[Boid]) -> f32 {
+    if boids.is_empty() {
+        return 0.0;
+    }
+    let total: Vector2<f32> = boids
         .par_iter()
-        .enumerate()
-        .map(|(boid_idx, boid)| {
-            let mut rng = rand::rng();
-            let mut pos_avg = Vector2::zeros();
-            let mut vel_avg = Vector2::zeros();
-            let mut close_offset = Vector2::zeros();
-
-            let mut neighboring_boids: usize = 0;
-
-            let boid_cell_x: i32 = (boid.pos.x / parameters.cell_size).floor() as i32;
-            let boid_cell_y: i32 = (boid.pos.y / parameters.cell_size).floor() as i32;
-            for x_offset in -1..=1 {
-                for y_offset in -1..=1 {
-                    let new_x = boid_cell_x + x_offset;
-                    let new_y = boid_cell_y + y_offset;
-                    if new_x >= 0 && new_y >= 0 {
-                        let key = (new_x as u32, new_y as u32);
-                        if let Some(near_boids) = grid.get(&key) {
-                            for otherboid_idx in near_boids {
-                                if *otherboid_idx == boid_idx {
-                                    continue;
-                                }
-                                let otherboid = &boids[*otherboid_idx];
-
-                                let offset = boid.pos - otherboid.pos;
-                                // Only consider those within our visible box
-                                if offset.x.abs() < parameters.visible_range
-                                    && offset.y.abs() < parameters.visible_range
-                                {
-                                    let dist_sq = offset.norm_squared();
-                                    if dist_sq < protected_range_squared {
-                                        close_offset += offset;
-                                    } else if dist_sq < visible_range_squared {
-                                        pos_avg += otherboid.pos;
-                                        vel_avg += otherboid.vel;
-                                        neighboring_boids += 1;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+        .map(|boid| boid.vel.try_normalize(0.0).unwrap_or_else(Vector2::zeros))
+        .sum();
+    total.norm() / boids.len() as f32
+}
 
-            let mut next_vel = boid.vel;
-            if neighboring_boids > 0 {
-                let n = neighboring_boids as f32;
-                pos_avg /= n;
-                vel_avg /= n;
-                next_vel += (pos_avg - boid.pos) * parameters.centering_factor
-                    + (vel_avg - boid.vel) * parameters.matching_factor;
-            }
-            next_vel += close_offset * parameters.avoid_factor;
+/// The grid `parameters` asks for over `world`
+pub fn grid_for(boids: &[Boid], parameters: &Parameters, world: &World) -> SpatialGrid {
+    let (width, height) = world.pixels();
+    let (cell_w, cell_h) = parameters.cell_dimensions(width, height);
+    populate_grid_rect(boids, cell_w, cell_h, width, height)
+}
 
-            // Turn if approaching the edge of the screen
-            if boid.pos.y > (height - parameters.margin) as f32 {
-                next_vel.y -= parameters.turn_factor;
+/// How much the flock is circling its centre of mass, from 0 when it isn't
+/// at all to 1 when every boid goes round it the same way
+pub fn angular_momentum(boids: &[Boid]) -> f32 {
+    if boids.is_empty() {
+        return 0.0;
+    }
+    let centroid = flock_centroid(boids);
+    let total: f32 = boids
+        .par_iter()
+        .map(|boid| {
+            let radius = (boid.pos - centroid).try_normalize(0.0);
+            let heading = boid.vel.try_normalize(0.0);
+            match (radius, heading) {
+                (Some(radius), Some(heading)) => radius.perp(&heading),
+                _ => 0.0,
             }
-            if boid.pos.x > (width - parameters.margin) as f32 {
-                next_vel.x -= parameters.turn_factor;
+        })
+        .sum();
+    (total / boids.len() as f32).abs()
+}
+
+/// Average speed over the flock
+pub fn mean_speed(boids: &[Boid]) -> f32 {
+    if boids.is_empty() {
+        return 0.0;
+    }
+    boids.par_iter().map(|boid| boid.vel.norm()).sum::<f32>() / boids.len() as f32
+}
+
+/// Running totals over some of a flock, which add up to the totals over all
+/// of it, so can be summed as the flock is steered rather than in another
+/// pass
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlockTally {
+    count: usize,
+    pos_sum: Vector2<f32>,
+    heading_sum: Vector2<f32>,
+    speed_sum: f32,
+    speed_squares: f32,
+    bounds: Option<(Vector2<f32>, Vector2<f32>)>,
+    neighbor_sum: usize,
+    max_neighbors: usize,
+    stalled: usize,
+}
+
+impl FlockTally {
+    /// The totals for one boid at `pos` flying at `vel` with `neighbors`
+    /// flockmates
+    pub fn new(pos: Vector2<f32>, vel: Vector2<f32>, neighbors: usize) -> Self {
+        let speed = vel.norm();
+        FlockTally {
+            count: 1,
+            pos_sum: pos,
+            heading_sum: vel.try_normalize(0.0).unwrap_or_else(Vector2::zeros),
+            speed_sum: speed,
+            speed_squares: speed * speed,
+            bounds: Some((pos, pos)),
+            neighbor_sum: neighbors,
+            max_neighbors: neighbors,
+            stalled: 0,
+        }
+    }
+
+    /// Totals over `boids`, with the flockmates of each by place in the
+    /// flock. Boids past the end of `neighbor_counts` had none.
+    pub fn of(boids: &[Boid], neighbor_counts: &[usize]) -> Self {
+        boids
+            .par_iter()
+            .enumerate()
+            .map(|(idx, boid)| {
+                let neighbors = neighbor_counts.get(idx).copied().unwrap_or_default();
+                FlockTally::new(boid.pos, boid.vel, neighbors)
+            })
+            .sum()
+    }
+
+    pub fn population(&self) -> usize {
+        self.count
+    }
+
+    /// Like `polarization`
+    pub fn polarization(&self) -> f32 {
+        self.mean(self.heading_sum.norm())
+    }
+
+    pub fn mean_speed(&self) -> f32 {
+        self.mean(self.speed_sum)
+    }
+
+    /// The population standard deviation of the boids' speeds
+    pub fn speed_stddev(&self) -> f32 {
+        let mean = self.mean_speed();
+        // Rounding can take this a hair below 0 when every speed is the same
+        (self.mean(self.speed_squares) - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+
+    /// Like `flock_centroid`
+    pub fn centroid(&self) -> Vector2<f32> {
+        if self.count == 0 {
+            return Vector2::zeros();
+        }
+        self.pos_sum / self.count as f32
+    }
+
+    /// Top left and bottom right corners of the box around every boid, or
+    /// `None` with no boids
+    pub fn bounds(&self) -> Option<(Vector2<f32>, Vector2<f32>)> {
+        self.bounds
+    }
+
+    pub fn mean_neighbors(&self) -> f32 {
+        self.mean(self.neighbor_sum as f32)
+    }
+
+    pub fn max_neighbors(&self) -> usize {
+        self.max_neighbors
+    }
+
+    /// Boids left stopped dead by `StallPolicy::Error`
+    pub fn stalled(&self) -> usize {
+        self.stalled
+    }
+
+    fn mean(&self, total: f32) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        total / self.count as f32
+    }
+}
+
+impl std::ops::Add for FlockTally {
+    type Output = FlockTally;
+
+    fn add(self, other: FlockTally) -> FlockTally {
+        let bounds = match (self.bounds, other.bounds) {
+            (Some((min, max)), Some((other_min, other_max))) => {
+                Some((min.inf(&other_min), max.sup(&other_max)))
             }
-            if boid.pos.x < parameters.margin as f32 {
-                next_vel.x += parameters.turn_factor;
+            (bounds, None) | (None, bounds) => bounds,
+        };
+        FlockTally {
+            count: self.count + other.count,
+            pos_sum: self.pos_sum + other.pos_sum,
+            heading_sum: self.heading_sum + other.heading_sum,
+            speed_sum: self.speed_sum + other.speed_sum,
+            speed_squares: self.speed_squares + other.speed_squares,
+            bounds,
+            neighbor_sum: self.neighbor_sum + other.neighbor_sum,
+            max_neighbors: self.max_neighbors.max(other.max_neighbors),
+            stalled: self.stalled + other.stalled,
+        }
+    }
+}
+
+impl std::iter::Sum for FlockTally {
+    fn sum<I: Iterator<Item = FlockTally>>(iter: I) -> FlockTally {
+        iter.fold(FlockTally::default(), std::ops::Add::add)
+    }
+}
+
+/// Pixels between the points the flock's density is sampled at for
+/// `ColourMode::Kde`
+pub const KDE_SPACING: f32 = 20.0;
+
+// Boids further than this many bandwidths from a point add less than 0.0112
+// to its density, so are left out
+const KDE_CUTOFF: f32 = 3.0;
+
+/// A smooth estimate of how crowded the flock is at each of `sample_points`,
+/// the sum of a Gaussian kernel of `bandwidth` over the boids near each one.
+/// A boid right on a point adds 1 to it. Without a bandwidth above 0 every
+/// point is 0.
+pub fn compute_kde(boids: &[Boid], sample_points: &[(f32, f32)], bandwidth: f32) -> Vec<f32> {
+    if boids.is_empty() || bandwidth.is_nan() || bandwidth <= 0.0 {
+        return vec![0.0; sample_points.len()];
+    }
+    let reach = bandwidth * KDE_CUTOFF;
+    let grid = Grid::build(boids, reach);
+    let scale = -1.0 / (2.0 * bandwidth * bandwidth);
+    sample_points
+        .par_iter()
+        .map(|&(x, y)| {
+            let point = Vector2::new(x, y);
+            grid.query_radius(point, reach)
+                .map(|index| ((boids[index].pos - point).norm_squared() * scale).exp())
+                .sum()
+        })
+        .collect()
+}
+
+/// The centres of the `KDE_SPACING` squares covering a `width` x `height`
+/// world, a row at a time
+pub fn kde_sample_points(width: u32, height: u32) -> Vec<(f32, f32)> {
+    let cols = (width as f32 / KDE_SPACING).ceil() as u32;
+    let rows = (height as f32 / KDE_SPACING).ceil() as u32;
+    (0..rows)
+        .flat_map(|row| {
+            (0..cols).map(move |col| {
+                (
+                    (col as f32 + 0.5) * KDE_SPACING,
+                    (row as f32 + 0.5) * KDE_SPACING,
+                )
+            })
+        })
+        .collect()
+}
+
+/// Steers and moves every boid by one frame, turning them back from the
+/// boundary of `world`, giving the totals over the flock once it's moved
+pub fn update_boids(boids: &mut [Boid], world: &World, parameters: &Parameters) -> FlockTally {
+    steer_all(boids, world, parameters, CLASSIC_RULES, &[], &[], None).1
+}
+
+/// Like `update_boids`, with the forces from `behaviors` pushing on every
+/// boid as well as the usual rules
+pub fn update_boids_with_behaviors(
+    boids: &mut [Boid],
+    world: &World,
+    parameters: &Parameters,
+    behaviors: &[Box<dyn BoidBehavior>],
+) -> FlockTally {
+    steer_all(
+        boids,
+        world,
+        parameters,
+        CLASSIC_RULES,
+        behaviors,
+        &[],
+        None,
+    )
+    .1
+}
+
+/// Like `update_boids`, steering by `rules` in place of the classic ones
+pub fn update_boids_with_rules(
+    boids: &mut [Boid],
+    world: &World,
+    parameters: &Parameters,
+    rules: &[&dyn SteeringRule],
+) -> FlockTally {
+    steer_all(boids, world, parameters, rules, &[], &[], None).1
+}
+
+// Where a boid moves to, with its velocity and speed, the flockmates it
+// steered by, and whether it stalled
+type Steered<N> = (Vector2<f32>, Vector2<f32>, f32, N, bool);
+
+// Steers and moves every boid, giving how many flockmates each one had and
+// the totals over the moved flock. `kicks` are added by place in the flock
+// before speed limits, if any.
+fn steer_all(
+    boids: &mut [Boid],
+    world: &World,
+    parameters: &Parameters,
+    rules: &[&dyn SteeringRule],
+    behaviors: &[Box<dyn BoidBehavior>],
+    kicks: &[Vector2<f32>],
+    balancer: Option<&mut LoadBalancer>,
+) -> (Vec<usize>, FlockTally) {
+    let cells = in_span!("grid", grid_for(boids, parameters, world));
+    trace!(
+        "Steering {} boids on a {}x{} grid, {} cells occupied",
+        boids.len(),
+        cells.grid_cols,
+        cells.grid_rows,
+        cells.cells.len()
+    );
+    let grid = Grid::new(boids, cells);
+    let centroid = global_centre(boids, parameters);
+    // For rust, we'll need to gather all the changes, then apply
+    let new_boid_states: Vec<Steered<usize>> = in_span!(
+        "neighbours",
+        map_boids(boids, balancer, |boid_idx, boid| {
+            let (mut next_vel, neighbors) =
+                steer_boid(boid_idx, &grid, centroid, world, parameters, rules);
+            if !behaviors.is_empty() {
+                next_vel += total_force(behaviors, boid, world, parameters);
             }
-            if boid.pos.y < parameters.margin as f32 {
-                next_vel.y += parameters.turn_factor;
+            if let Some(kick) = kicks.get(boid_idx) {
+                next_vel += kick;
             }
+            let (next_vel, speed, stalled) = limit_speed(next_vel, boid, parameters);
+            (
+                integrate(boid.pos, next_vel, world),
+                next_vel,
+                speed,
+                neighbors,
+                stalled,
+            )
+        })
+    );
 
-            // Make sure we're within speed limits
-            let mut speed = next_vel.norm();
-            if speed > 0.0 {
-                if speed < parameters.min_speed {
-                    next_vel = next_vel.normalize() * parameters.min_speed;
-                    speed = parameters.min_speed;
-                } else if speed > parameters.max_speed {
-                    next_vel = next_vel.normalize() * parameters.max_speed;
-                    speed = parameters.max_speed;
+    // apply the changes, totting up the flock as it goes
+    let mut tally = FlockTally::default();
+    let neighbor_counts = in_span!(
+        "integrate",
+        boids
+            .iter_mut()
+            .zip(new_boid_states)
+            .map(
+                |(boid, (new_pos, new_vel, new_speed, neighbors, stalled))| {
+                    boid.remember_heading(new_vel);
+                    boid.pos = new_pos;
+                    boid.vel = new_vel;
+                    boid.current_speed = new_speed;
+                    tally = tally + FlockTally::new(new_pos, new_vel, neighbors);
+                    tally.stalled += usize::from(stalled);
+                    neighbors
                 }
-            } else if parameters.min_speed > 0.0 {
-                // Give it a nudge if stopped
-                next_vel = Vector2::new(
-                    rng.random_range(-parameters.min_speed..parameters.min_speed),
-                    rng.random_range(-parameters.min_speed..parameters.min_speed),
-                );
-                speed = parameters.min_speed;
-            }
+            )
+            .collect()
+    );
+    (neighbor_counts, tally)
+}
 
-            let mut next_pos = boid.pos + next_vel;
+// Works out `steer` for every boid in parallel, in the runs `balancer` splits
+// the flock into if there is one, telling it how long each run took. Runs
+// are handed out as they are, as `with_min_len` could only stop rayon
+// splitting them smaller, not make them uneven.
+fn map_boids<T, F>(boids: &[Boid], balancer: Option<&mut LoadBalancer>, steer: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize, &Boid) -> T + Send + Sync,
+{
+    let Some(balancer) = balancer else {
+        return boids
+            .par_iter()
+            .enumerate()
+            .map(|(boid_idx, boid)| steer(boid_idx, boid))
+            .collect();
+    };
+    let partition = balancer.next_partition(boids.len());
+    let runs: Vec<(Vec<T>, Duration)> = partition
+        .par_iter()
+        .map(|run| {
+            let started = Instant::now();
+            let steered = run
+                .clone()
+                .map(|boid_idx| steer(boid_idx, &boids[boid_idx]))
+                .collect();
+            (steered, started.elapsed())
+        })
+        .collect();
+    let (steered, times): (Vec<Vec<T>>, Vec<Duration>) = runs.into_iter().unzip();
+    balancer.record(partition, times);
+    steered.into_iter().flatten().collect()
+}
 
-            // Finally, clamp them so they're in the screen
-            if next_pos.x < 0.0 {
-                next_pos.x = 0.0;
-            } else if next_pos.x >= width as f32 {
-                next_pos.x = (width - 1) as f32;
-            }
-            if next_pos.y < 0.0 {
-                next_pos.y = 0.0;
-            } else if next_pos.y >= height as f32 {
-                next_pos.y = (height - 1) as f32;
+// Only worth the extra pass over the flock if something is going to use it
+fn global_centre(boids: &[Boid], parameters: &Parameters) -> Vector2<f32> {
+    if parameters.global_centering_factor == 0.0 {
+        return Vector2::zeros();
+    }
+    flock_centroid(boids)
+}
+
+fn cell_for(pos: Vector2<f32>, cell_w: f32, cell_h: f32) -> (u32, u32) {
+    (
+        (pos.x / cell_w).floor() as u32,
+        (pos.y / cell_h).floor() as u32,
+    )
+}
+
+// Gathers everything the steering rules need to know about a boid's
+// neighbours in one pass over the cells around it. Flockmates are only
+// gathered with cohesion or alignment on, and nothing at all is searched for
+// with separation off too.
+fn gather_neighborhood(
+    boid_idx: usize,
+    grid: &Grid,
+    centroid: Vector2<f32>,
+    parameters: &Parameters,
+) -> Neighborhood {
+    let flocking = parameters.centering_factor != 0.0 || parameters.matching_factor != 0.0;
+    if !flocking && parameters.avoid_factor == 0.0 {
+        return Neighborhood {
+            centroid,
+            ..Neighborhood::default()
+        };
+    }
+    let boids = grid.boids();
+    let boid = &boids[boid_idx];
+    let (boid_cell_x, boid_cell_y) = grid.cells().cell_at(boid.pos);
+    let near_boids = (-1..=1)
+        .flat_map(|x_offset| (-1..=1).map(move |y_offset| (x_offset, y_offset)))
+        .flat_map(|(x_offset, y_offset)| {
+            // Stepping off the top or left edge wraps around to a huge cell
+            // index, which the grid knows is out of bounds
+            grid.query_cell(
+                boid_cell_x.wrapping_add_signed(x_offset),
+                boid_cell_y.wrapping_add_signed(y_offset),
+            )
+        })
+        .filter(|otherboid_idx| **otherboid_idx != boid_idx)
+        .map(|otherboid_idx| &boids[*otherboid_idx]);
+    let mut accum = accumulate_neighbors(boid, near_boids, parameters);
+
+    // Voronoi neighbours are found separately, so the search above was only
+    // for collisions
+    if parameters.voronoi_neighbors && flocking {
+        for otherboid_idx in SpatialQuery::voronoi_cell(boid.pos, grid.cells(), boids) {
+            accum.add_flockmate(&boids[otherboid_idx]);
+        }
+    }
+    accum.neighborhood(centroid)
+}
+
+/// Running totals of what a boid found around it, before they're averaged
+/// into a `Neighborhood`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NeighborAccum {
+    /// Flockmates added so far
+    pub count: usize,
+    pub pos_sum: Vector2<f32>,
+    pub vel_sum: Vector2<f32>,
+    /// Total of the offsets from each boid within the protected range
+    pub close_offset: Vector2<f32>,
+}
+
+impl Default for NeighborAccum {
+    fn default() -> Self {
+        NeighborAccum {
+            count: 0,
+            pos_sum: Vector2::zeros(),
+            vel_sum: Vector2::zeros(),
+            close_offset: Vector2::zeros(),
+        }
+    }
+}
+
+impl NeighborAccum {
+    /// Counts `other` as a flockmate to align and cohere with
+    pub fn add_flockmate(&mut self, other: &Boid) {
+        self.pos_sum += other.pos;
+        self.vel_sum += other.vel;
+        self.count += 1;
+    }
+
+    /// The averages over the flockmates, zero without any, with the centre
+    /// of the whole flock the rules are to use
+    pub fn neighborhood(&self, centroid: Vector2<f32>) -> Neighborhood {
+        let (mut mean_pos, mut mean_vel) = (self.pos_sum, self.vel_sum);
+        if self.count > 0 {
+            let n = self.count as f32;
+            mean_pos /= n;
+            mean_vel /= n;
+        }
+        Neighborhood {
+            count: self.count,
+            mean_pos,
+            mean_vel,
+            close_offset: self.close_offset,
+            centroid,
+        }
+    }
+}
+
+/// Totals up which of `neighbors`, in order, are too close to `boid` and
+/// which are flockmates within its visible range. `neighbors` mustn't
+/// include `boid` itself.
+///
+/// Flockmates are only looked for with cohesion or alignment on and without
+/// `voronoi_neighbors`, and no more are taken once there are
+/// `max_neighbors_for_early_exit`. Past that only the protected range is
+/// checked, as missing a collision matters far more than missing a distant
+/// flockmate.
+pub fn accumulate_neighbors<'a>(
+    boid: &Boid,
+    neighbors: impl IntoIterator<Item = &'a Boid>,
+    parameters: &Parameters,
+) -> NeighborAccum {
+    let flocking = parameters.centering_factor != 0.0 || parameters.matching_factor != 0.0;
+    let protected_range_squared = parameters.protected_range * parameters.protected_range;
+    let visible_range_squared = parameters.visible_range * parameters.visible_range;
+    let mut accum = NeighborAccum::default();
+    let mut enough_neighbors = parameters.voronoi_neighbors || !flocking;
+    for otherboid in neighbors {
+        let offset = boid.pos - otherboid.pos;
+        // Only consider those within our visible box
+        let reach = if enough_neighbors {
+            parameters.protected_range
+        } else {
+            parameters.visible_range
+        };
+        if offset.x.abs() < reach && offset.y.abs() < reach {
+            let dist_sq = boid.distance_squared_to(otherboid);
+            if dist_sq < protected_range_squared {
+                accum.close_offset += offset;
+            } else if dist_sq < visible_range_squared && !enough_neighbors {
+                accum.add_flockmate(otherboid);
+                enough_neighbors = parameters
+                    .max_neighbors_for_early_exit
+                    .is_some_and(|max| accum.count >= max);
             }
+        }
+    }
+    accum
+}
+
+// Works out the velocity a boid wants next frame by `rules`, before any
+// speed limits are applied. The new velocity, and how many flockmates were
+// aligned and cohered with.
+fn steer_boid(
+    boid_idx: usize,
+    grid: &Grid,
+    centroid: Vector2<f32>,
+    world: &World,
+    parameters: &Parameters,
+    rules: &[&dyn SteeringRule],
+) -> (Vector2<f32>, usize) {
+    let neighborhood = gather_neighborhood(boid_idx, grid, centroid, parameters);
+    let boid = &grid.boids()[boid_idx];
+    (
+        rules::steer(rules, boid, &neighborhood, world, parameters),
+        neighborhood.count,
+    )
+}
 
-            (next_pos, next_vel, speed)
+/// What's done with a boid its steering leaves stopped dead, when the
+/// minimum speed says it has to move. A boid that's never moved, such as
+/// one loaded standing still, has no heading to keep and hasn't stalled, so
+/// is always nudged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StallPolicy {
+    /// A random nudge of up to the minimum speed each way, from an RNG
+    /// seeded from the boid's id and position
+    #[default]
+    RandomNudge,
+    /// Off again at the minimum speed, along the heading it stopped on
+    KeepHeading,
+    /// Left stopped, and counted by `FlockTally::stalled` as a bug
+    Error,
+}
+
+impl fmt::Display for StallPolicy {
+    /// Writes the same form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StallPolicy::RandomNudge => "random-nudge",
+            StallPolicy::KeepHeading => "keep-heading",
+            StallPolicy::Error => "error",
         })
-        .collect();
+    }
+}
+
+impl FromStr for StallPolicy {
+    type Err = String;
 
-    // apply the changes
-    for (i, boid) in boids.iter_mut().enumerate() {
-        let (new_pos, new_vel, new_speed) = new_boid_states[i];
-        boid.pos = new_pos;
-        boid.vel = new_vel;
-        boid.current_speed = new_speed;
+    /// Parses `random-nudge`, `keep-heading` or `error`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random-nudge" => Ok(StallPolicy::RandomNudge),
+            "keep-heading" => Ok(StallPolicy::KeepHeading),
+            "error" => Ok(StallPolicy::Error),
+            _ => Err(format!(
+                "Unknown stall policy {s}, expected random-nudge, keep-heading or error"
+            )),
+        }
     }
 }
 
-// Module to handle Rgb<u8> serialization/deserialization
-mod rgb_serde {
-    use image::Rgb;
-    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+// Make sure we're within speed limits, returning the new velocity and
+// speed, and whether the boid stalled. A boid heading into a speed zone is
+// held to the zone's limits instead, and one that's stopped dead is set off
+// again by `parameters.stall_policy`.
+fn limit_speed(
+    next_vel: Vector2<f32>,
+    boid: &Boid,
+    parameters: &Parameters,
+) -> (Vector2<f32>, f32, bool) {
+    let (min_speed, max_speed) = SpeedLimitZone::limits_at(boid.pos + next_vel, parameters);
+    // Only a stopped boid needs an RNG, so moving ones don't pay for one
+    if next_vel != Vector2::zeros() || min_speed <= 0.0 {
+        let (vel, speed) = clamp_speed(next_vel, min_speed, max_speed, None::<&mut SmallRng>);
+        return (vel, speed, false);
+    }
+    let heading = boid.vel.try_normalize(0.0).or(boid.last_heading);
+    match (parameters.stall_policy, heading) {
+        (StallPolicy::KeepHeading, Some(heading)) => (heading * min_speed, min_speed, false),
+        (StallPolicy::Error, Some(_)) => (Vector2::zeros(), 0.0, true),
+        // Never having moved, such as loaded standing still, isn't stalling
+        _ => {
+            let (vel, speed) =
+                clamp_speed(next_vel, min_speed, max_speed, Some(&mut nudge_rng(boid)));
+            (vel, speed, false)
+        }
+    }
+}
+
+// The RNG a stopped `boid` is nudged from, seeded from its id and where it
+// is, so the same flock is always nudged the same way without an RNG
+// having to be carried through every step
+fn nudge_rng(boid: &Boid) -> SmallRng {
+    let pos = (u64::from(boid.pos.x.to_bits()) << 32) | u64::from(boid.pos.y.to_bits());
+    SmallRng::seed_from_u64(boid.id_u64() ^ pos)
+}
 
-    // Serialize Rgb<u8> as [u8; 3]
-    pub fn serialize<S>(rgb: &Rgb<u8>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
+/// `vel` held between `min_speed` and `max_speed`, keeping its heading, and
+/// its speed. A stopped boid has no heading to keep, so is given a random
+/// nudge from `rng` and taken to be at the minimum speed, or left stopped
+/// without one.
+pub fn clamp_speed<R: Rng + ?Sized>(
+    mut vel: Vector2<f32>,
+    min_speed: f32,
+    max_speed: f32,
+    rng: Option<&mut R>,
+) -> (Vector2<f32>, f32) {
+    let mut speed = vel.norm();
+    if speed > 0.0 {
+        if speed < min_speed {
+            vel = vel.normalize() * min_speed;
+            speed = min_speed;
+        } else if speed > max_speed {
+            vel = vel.normalize() * max_speed;
+            speed = max_speed;
+        }
+    } else if min_speed > 0.0
+        && let Some(rng) = rng
     {
-        // Rgb<T> internally stores its data as a [T; N] array in field 0.
-        // For Rgb<u8>, it's [u8; 3].
-        rgb.0.serialize(serializer)
+        // Give it a nudge if stopped
+        vel = nudge(rng, min_speed);
+        speed = min_speed;
     }
+    (vel, speed)
+}
 
-    // Deserialize Rgb<u8> from [u8; 3]
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Rgb<u8>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        let arr: [u8; 3] = Deserialize::deserialize(deserializer)?;
-        Ok(Rgb(arr))
+/// Where a boid at `pos` moving at `vel` is next frame, kept inside `world`
+pub fn integrate(pos: Vector2<f32>, vel: Vector2<f32>, world: &World) -> Vector2<f32> {
+    world.clamp(pos + vel)
+}
+
+// A random velocity of up to `min_speed` along each axis
+pub(crate) fn nudge<R: Rng + ?Sized>(rng: &mut R, min_speed: f32) -> Vector2<f32> {
+    if min_speed <= 0.0 {
+        return Vector2::zeros();
+    }
+    Vector2::new(
+        rng.random_range(-min_speed..min_speed),
+        rng.random_range(-min_speed..min_speed),
+    )
+}
+
+/// Event driven alternative to `update_boids`.
+///
+/// Only boids that are "dirty" have their velocity recomputed each frame, the
+/// rest carry on in a straight line. A boid becomes dirty when it has moved
+/// more than `update_threshold` pixels since it was last steered, when it
+/// changes grid cell, or when a boid in a neighbouring cell changed velocity
+/// by more than `update_threshold` pixels per frame on the previous frame.
+/// With `update_threshold` of 0 this is exactly `update_boids`.
+#[derive(Debug, Default)]
+pub struct EventDrivenUpdate {
+    dirty: Vec<bool>,
+    last_grid_pos: Vec<(u32, u32)>,
+    last_steered_pos: Vec<Vector2<f32>>,
+    changed_cells: HashSet<(u32, u32)>,
+    neighbor_counts: Vec<usize>,
+    kicks: Vec<Vector2<f32>>,
+    balancer: Option<LoadBalancer>,
+}
+
+impl EventDrivenUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many boids had their velocity recomputed on the last update
+    pub fn dirty_count(&self) -> usize {
+        self.dirty.iter().filter(|dirty| **dirty).count()
+    }
+
+    /// Flockmates each boid aligned and cohered with when it was last
+    /// steered, by its place in the flock
+    pub fn neighbor_counts(&self) -> &[usize] {
+        &self.neighbor_counts
+    }
+
+    /// Forgets everything about the flock, so every boid is steered afresh
+    /// on the next update. Any load balancing carries on.
+    pub fn reset(&mut self) {
+        *self = EventDrivenUpdate {
+            balancer: self.balancer.take(),
+            ..EventDrivenUpdate::default()
+        };
+    }
+
+    /// Splits the flock between threads with `balancer` from the next
+    /// update on, or evenly for `None`
+    pub fn balance_load(&mut self, balancer: Option<LoadBalancer>) {
+        self.balancer = balancer;
+    }
+
+    pub fn load_balancer(&self) -> Option<&LoadBalancer> {
+        self.balancer.as_ref()
+    }
+
+    /// Adds `kicks` to the velocities of the boids at the same places in the
+    /// flock on the next update, after they're steered but before speed
+    /// limits. Boids with a kick are steered again however little they've
+    /// moved.
+    pub fn perturb(&mut self, kicks: Vec<Vector2<f32>>) {
+        self.kicks = kicks;
+    }
+
+    /// Moves the flock on by a frame, giving the totals over it once moved,
+    /// with boids that weren't steered counting the flockmates they had
+    /// when they last were
+    pub fn update(
+        &mut self,
+        boids: &mut [Boid],
+        world: &World,
+        parameters: &Parameters,
+    ) -> FlockTally {
+        self.update_with_rules(boids, world, parameters, CLASSIC_RULES)
+    }
+
+    /// `update`, steering by `rules` in place of the classic ones
+    pub fn update_with_rules(
+        &mut self,
+        boids: &mut [Boid],
+        world: &World,
+        parameters: &Parameters,
+        rules: &[&dyn SteeringRule],
+    ) -> FlockTally {
+        let kicks = std::mem::take(&mut self.kicks);
+        if parameters.update_threshold <= 0.0 {
+            let tally;
+            (self.neighbor_counts, tally) = steer_all(
+                boids,
+                world,
+                parameters,
+                rules,
+                &[],
+                &kicks,
+                self.balancer.as_mut(),
+            );
+            self.dirty = vec![true; boids.len()];
+            self.last_grid_pos.clear();
+            self.changed_cells.clear();
+            return tally;
+        }
+        let grid = Grid::new(boids, in_span!("grid", grid_for(boids, parameters, world)));
+        let centroid = global_centre(boids, parameters);
+
+        // Anything we haven't seen before (or a different flock entirely) is
+        // treated as needing a full update
+        if self.last_grid_pos.len() != boids.len() {
+            self.dirty = vec![true; boids.len()];
+            self.last_grid_pos = boids
+                .iter()
+                .map(|boid| grid.cells().cell_at(boid.pos))
+                .collect();
+            self.last_steered_pos = boids.iter().map(|boid| boid.pos).collect();
+            self.neighbor_counts = vec![0; boids.len()];
+        } else {
+            self.dirty.iter_mut().for_each(|dirty| *dirty = false);
+            let threshold_squared = parameters.update_threshold * parameters.update_threshold;
+            for (idx, boid) in boids.iter().enumerate() {
+                let cell = grid.cells().cell_at(boid.pos);
+                if cell != self.last_grid_pos[idx]
+                    || (boid.pos - self.last_steered_pos[idx]).norm_squared() > threshold_squared
+                {
+                    self.dirty[idx] = true;
+                }
+                self.last_grid_pos[idx] = cell;
+            }
+            // Propagate changes from last frame out to the neighbouring cells
+            for (cell_x, cell_y) in &self.changed_cells {
+                for x in cell_x.saturating_sub(1)..=cell_x + 1 {
+                    for y in cell_y.saturating_sub(1)..=cell_y + 1 {
+                        for idx in grid.query_cell(x, y) {
+                            self.dirty[*idx] = true;
+                        }
+                    }
+                }
+            }
+        }
+        for (dirty, kick) in self.dirty.iter_mut().zip(&kicks) {
+            if *kick != Vector2::zeros() {
+                *dirty = true;
+            }
+        }
+
+        let dirty = &self.dirty;
+        // Boids that weren't re-steered keep the count from when they were
+        let new_boid_states: Vec<Steered<Option<usize>>> = in_span!(
+            "neighbours",
+            map_boids(boids, self.balancer.as_mut(), |boid_idx, boid| {
+                if !dirty[boid_idx] {
+                    return (
+                        integrate(boid.pos, boid.vel, world),
+                        boid.vel,
+                        boid.current_speed,
+                        None,
+                        false,
+                    );
+                }
+                let (mut next_vel, neighbors) =
+                    steer_boid(boid_idx, &grid, centroid, world, parameters, rules);
+                if let Some(kick) = kicks.get(boid_idx) {
+                    next_vel += kick;
+                }
+                let (next_vel, speed, stalled) = limit_speed(next_vel, boid, parameters);
+                (
+                    integrate(boid.pos, next_vel, world),
+                    next_vel,
+                    speed,
+                    Some(neighbors),
+                    stalled,
+                )
+            })
+        );
+
+        trace!("Re-steered {} of {} boids", self.dirty_count(), boids.len());
+        let threshold_squared = parameters.update_threshold * parameters.update_threshold;
+        self.changed_cells.clear();
+        let mut tally = FlockTally::default();
+        in_span!(
+            "integrate",
+            for (i, boid) in boids.iter_mut().enumerate() {
+                let (new_pos, new_vel, new_speed, neighbors, stalled) = new_boid_states[i];
+                if let Some(neighbors) = neighbors {
+                    self.neighbor_counts[i] = neighbors;
+                }
+                if self.dirty[i] {
+                    if (new_vel - boid.vel).norm_squared() > threshold_squared {
+                        self.changed_cells.insert(self.last_grid_pos[i]);
+                    }
+                    self.last_steered_pos[i] = boid.pos;
+                }
+                boid.remember_heading(new_vel);
+                boid.pos = new_pos;
+                boid.vel = new_vel;
+                boid.current_speed = new_speed;
+                tally = tally + FlockTally::new(new_pos, new_vel, self.neighbor_counts[i]);
+                tally.stalled += usize::from(stalled);
+            }
+        );
+        tally
+    }
+}
+
+// Boids saved without a colour come back white
+fn white() -> Colour {
+    Colour::WHITE
+}
+
+// Archives Vector2<f32> as [f32; 2], since nalgebra doesn't know about
+// rkyv, and Colour as [u8; 3]
+#[cfg(feature = "rkyv")]
+mod rkyv_with {
+    use nalgebra::Vector2;
+    use rkyv::rancor::Fallible;
+    use rkyv::with::{ArchiveWith, DeserializeWith, SerializeWith};
+    use rkyv::{Archive, Archived, Deserialize, Place, Resolver, Serialize};
+
+    use crate::colour::Colour;
+
+    pub struct Vector2AsArray;
+
+    impl ArchiveWith<Vector2<f32>> for Vector2AsArray {
+        type Archived = Archived<[f32; 2]>;
+        type Resolver = Resolver<[f32; 2]>;
+
+        fn resolve_with(
+            field: &Vector2<f32>,
+            resolver: Self::Resolver,
+            out: Place<Self::Archived>,
+        ) {
+            [field.x, field.y].resolve(resolver, out);
+        }
+    }
+
+    impl<S: Fallible + ?Sized> SerializeWith<Vector2<f32>, S> for Vector2AsArray {
+        fn serialize_with(
+            field: &Vector2<f32>,
+            serializer: &mut S,
+        ) -> Result<Self::Resolver, S::Error> {
+            [field.x, field.y].serialize(serializer)
+        }
+    }
+
+    impl<D: Fallible + ?Sized> DeserializeWith<Archived<[f32; 2]>, Vector2<f32>, D> for Vector2AsArray {
+        fn deserialize_with(
+            field: &Archived<[f32; 2]>,
+            deserializer: &mut D,
+        ) -> Result<Vector2<f32>, D::Error> {
+            let [x, y]: [f32; 2] = field.deserialize(deserializer)?;
+            Ok(Vector2::new(x, y))
+        }
+    }
+
+    pub struct ColourAsArray;
+
+    impl ArchiveWith<Colour> for ColourAsArray {
+        type Archived = Archived<[u8; 3]>;
+        type Resolver = Resolver<[u8; 3]>;
+
+        fn resolve_with(field: &Colour, resolver: Self::Resolver, out: Place<Self::Archived>) {
+            field.0.resolve(resolver, out);
+        }
+    }
+
+    impl<S: Fallible + ?Sized> SerializeWith<Colour, S> for ColourAsArray {
+        fn serialize_with(field: &Colour, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+            field.0.serialize(serializer)
+        }
+    }
+
+    impl<D: Fallible + ?Sized> DeserializeWith<Archived<[u8; 3]>, Colour, D> for ColourAsArray {
+        fn deserialize_with(
+            field: &Archived<[u8; 3]>,
+            deserializer: &mut D,
+        ) -> Result<Colour, D::Error> {
+            Ok(Colour(field.deserialize(deserializer)?))
+        }
     }
 }
@@ -0,0 +1,170 @@
+//! The shape of the world boids are kept inside. By default that's the
+//! rectangle of the frame, but any shape can be given as a signed distance
+//! field drawn in a greyscale PNG.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "render")]
+use image::GrayImage;
+use log::debug;
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+/// Grey level of the edge in an SDF image, brighter is inside
+pub const SDF_EDGE: u8 = 128;
+
+/// How the edge of the world is described in `Parameters`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BoundaryMode {
+    /// Boids turn back within `margin` of the edges of the frame
+    #[default]
+    Rectangle,
+    /// Boids turn back within `margin` of the edge of the shape in a
+    /// greyscale signed distance field the same size as the world. Each grey
+    /// level is a pixel of distance, with 128 on the edge.
+    Sdf { path: PathBuf },
+}
+
+/// Raised when a boundary can't be set up for a world
+#[derive(Debug)]
+pub enum BoundaryError {
+    #[cfg(feature = "render")]
+    Image(image::ImageError),
+    /// Reading an SDF image needs the `render` feature
+    NoImageSupport,
+    SizeMismatch {
+        world: (u32, u32),
+        image: (u32, u32),
+    },
+}
+
+impl fmt::Display for BoundaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "render")]
+            BoundaryError::Image(err) => write!(f, "unable to read the SDF image: {err}"),
+            BoundaryError::NoImageSupport => {
+                write!(f, "SDF boundaries can't be read without the render feature")
+            }
+            BoundaryError::SizeMismatch { world, image } => write!(
+                f,
+                "the SDF image is {}x{} but the world is {}x{}",
+                image.0, image.1, world.0, world.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BoundaryError {}
+
+#[cfg(feature = "render")]
+impl From<image::ImageError> for BoundaryError {
+    fn from(err: image::ImageError) -> Self {
+        BoundaryError::Image(err)
+    }
+}
+
+/// A signed distance field over the world, positive inside the shape
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfBoundary {
+    pub width: u32,
+    pub height: u32,
+    /// Row major distance to the edge at each pixel
+    pub distance: Vec<f32>,
+    /// Row major unit vector pointing into the shape at each pixel, zero
+    /// where the field is flat
+    pub sdf_normal_map: Vec<Vector2<f32>>,
+}
+
+impl SdfBoundary {
+    #[cfg(feature = "render")]
+    pub fn load(path: &Path, width: u32, height: u32) -> Result<Self, BoundaryError> {
+        debug!("Loading the SDF boundary from {}", path.display());
+        let img = image::open(path)?.into_luma8();
+        if img.dimensions() != (width, height) {
+            return Err(BoundaryError::SizeMismatch {
+                world: (width, height),
+                image: img.dimensions(),
+            });
+        }
+        Ok(SdfBoundary::from_image(&img))
+    }
+
+    #[cfg(not(feature = "render"))]
+    pub fn load(path: &Path, _width: u32, _height: u32) -> Result<Self, BoundaryError> {
+        debug!("Unable to load the SDF boundary from {}", path.display());
+        Err(BoundaryError::NoImageSupport)
+    }
+
+    #[cfg(feature = "render")]
+    pub fn from_image(img: &GrayImage) -> Self {
+        let (width, height) = img.dimensions();
+        SdfBoundary::from_levels(width, height, img.as_raw())
+    }
+
+    /// The field from the row major grey levels of an SDF image
+    pub fn from_levels(width: u32, height: u32, levels: &[u8]) -> Self {
+        assert_eq!(
+            levels.len(),
+            width as usize * height as usize,
+            "a {width}x{height} field needs a level for every pixel"
+        );
+        let distance: Vec<f32> = levels
+            .iter()
+            .map(|level| *level as f32 - SDF_EDGE as f32)
+            .collect();
+        let at = |x: u32, y: u32| distance[(y * width + x) as usize];
+        // Central differences, one sided along the edges of the image
+        let sdf_normal_map = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (left, right) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (up, down) = (y.saturating_sub(1), (y + 1).min(height - 1));
+                let gradient = Vector2::new(at(right, y) - at(left, y), at(x, down) - at(x, up));
+                gradient.try_normalize(0.0).unwrap_or_else(Vector2::zeros)
+            })
+            .collect();
+        SdfBoundary {
+            width,
+            height,
+            distance,
+            sdf_normal_map,
+        }
+    }
+
+    fn index(&self, pos: Vector2<f32>) -> usize {
+        let x = (pos.x.max(0.0) as u32).min(self.width - 1);
+        let y = (pos.y.max(0.0) as u32).min(self.height - 1);
+        (y * self.width + x) as usize
+    }
+
+    /// Distance from `pos` to the edge, negative outside the shape
+    pub fn distance_at(&self, pos: Vector2<f32>) -> f32 {
+        self.distance[self.index(pos)]
+    }
+
+    pub fn normal_at(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        self.sdf_normal_map[self.index(pos)]
+    }
+}
+
+/// The boundary a simulation actually steers against, loaded from a
+/// `BoundaryMode`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Boundary {
+    #[default]
+    Rectangle,
+    Sdf(SdfBoundary),
+}
+
+impl Boundary {
+    pub fn load(mode: &BoundaryMode, width: u32, height: u32) -> Result<Self, BoundaryError> {
+        match mode {
+            BoundaryMode::Rectangle => Ok(Boundary::Rectangle),
+            BoundaryMode::Sdf { path } => {
+                Ok(Boundary::Sdf(SdfBoundary::load(path, width, height)?))
+            }
+        }
+    }
+}
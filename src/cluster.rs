@@ -0,0 +1,99 @@
+//! Sub-populations that stay together over a whole run, found by DBSCAN on
+//! where each boid was on average rather than on any single frame.
+//!
+//! ```text
+//! {"-1": [4, 9], "0": [0, 1, 2], "1": [3, 5, 6, 7, 8]}
+//! ```
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use dbscan::Classification;
+use nalgebra::Vector2;
+
+use crate::boids::{Boid, BoidId};
+use crate::error::Error;
+
+/// Cluster given to boids that aren't in any cluster
+pub const NOISE: i32 = -1;
+
+/// Keeps a running total of every boid's position, by its place in the flock
+#[derive(Debug, Clone, Default)]
+pub struct PositionAverager {
+    sums: Vec<Vector2<f64>>,
+    frames: usize,
+}
+
+impl PositionAverager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, boids: &[Boid]) {
+        self.sums.resize(boids.len(), Vector2::zeros());
+        for (sum, boid) in self.sums.iter_mut().zip(boids) {
+            *sum += boid.pos.cast::<f64>();
+        }
+        self.frames += 1;
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Each boid's mean position over the frames added
+    pub fn means(&self) -> Vec<Vector2<f32>> {
+        let frames = self.frames.max(1) as f64;
+        self.sums
+            .iter()
+            .map(|sum| (sum / frames).cast::<f32>())
+            .collect()
+    }
+}
+
+/// DBSCAN over `positions`, giving each one a cluster numbered from 0, or
+/// `NOISE`. A position with at least `min_points` within `eps` of it,
+/// counting itself, starts or grows a cluster.
+pub fn cluster_membership(positions: &[Vector2<f32>], eps: f32, min_points: usize) -> Vec<i32> {
+    let input: Vec<Vec<f32>> = positions.iter().map(|pos| vec![pos.x, pos.y]).collect();
+    dbscan::cluster(eps as f64, min_points, &input)
+        .into_iter()
+        .map(|classification| match classification {
+            Classification::Core(cluster) | Classification::Edge(cluster) => cluster as i32,
+            Classification::Noise => NOISE,
+        })
+        .collect()
+}
+
+/// The ids of the boids in each cluster, from their `membership`
+pub fn boids_by_cluster(boids: &[Boid], membership: &[i32]) -> BTreeMap<i32, Vec<BoidId>> {
+    let mut clusters: BTreeMap<i32, Vec<BoidId>> = BTreeMap::new();
+    for (boid, cluster) in boids.iter().zip(membership) {
+        clusters.entry(*cluster).or_default().push(boid.id);
+    }
+    clusters
+}
+
+/// Writes `{ "cluster_id": [boid_ids] }`
+pub fn write_clusters(path: &Path, clusters: &BTreeMap<i32, Vec<BoidId>>) -> crate::Result<()> {
+    let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+    let mut out = BufWriter::new(file);
+    serde_json::to_writer(&mut out, clusters)
+        .map_err(io::Error::from)
+        .and_then(|()| out.flush())
+        .map_err(|err| Error::io("write", path, err))
+}
+
+/// Writes `id,cluster_membership` rows, to go with a trajectory CSV
+pub fn write_membership_csv<W: Write>(
+    mut out: W,
+    boids: &[Boid],
+    membership: &[i32],
+) -> io::Result<()> {
+    writeln!(out, "id,cluster_membership")?;
+    for (boid, cluster) in boids.iter().zip(membership) {
+        writeln!(out, "{},{cluster}", boid.id)?;
+    }
+    out.flush()
+}
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::f32::consts::{PI, TAU};
+use std::str::FromStr;
+
+use colors_transform::{Color, Hsl};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::boids::{compute_kde, kde_sample_points, Boid, KDE_SPACING};
+use crate::field::compute_velocity_field;
+use crate::world::World;
+use crate::Parameters;
+
+/// A red, green and blue colour. It converts to and from `image::Rgb<u8>`
+/// with the `render` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Colour(pub [u8; 3]);
+
+impl Colour {
+    pub const BLACK: Colour = Colour([0, 0, 0]);
+    pub const WHITE: Colour = Colour([255, 255, 255]);
+}
+
+impl From<[u8; 3]> for Colour {
+    fn from(channels: [u8; 3]) -> Self {
+        Colour(channels)
+    }
+}
+
+#[cfg(feature = "render")]
+impl From<image::Rgb<u8>> for Colour {
+    fn from(rgb: image::Rgb<u8>) -> Self {
+        Colour(rgb.0)
+    }
+}
+
+#[cfg(feature = "render")]
+impl From<Colour> for image::Rgb<u8> {
+    fn from(colour: Colour) -> Self {
+        image::Rgb(colour.0)
+    }
+}
+
+/// Evenly spaced colour stops that values between 0 and 1 are mapped across
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourGradient {
+    stops: Vec<Colour>,
+}
+
+// Blue through green to red, slow to fast
+const DEFAULT_STOPS: [Colour; 4] = [
+    Colour([40, 80, 255]),
+    Colour([40, 220, 120]),
+    Colour([255, 220, 40]),
+    Colour([255, 40, 40]),
+];
+
+// Contracting through still to expanding
+const DIVERGENCE_STOPS: [Colour; 3] = [
+    Colour([0, 0, 255]),
+    Colour([0, 255, 0]),
+    Colour([255, 0, 0]),
+];
+
+impl Default for ColourGradient {
+    /// Blue through green to red, slow to fast
+    fn default() -> Self {
+        ColourGradient::new(DEFAULT_STOPS.to_vec())
+    }
+}
+
+impl ColourGradient {
+    /// Panics without at least one stop
+    pub fn new(stops: Vec<Colour>) -> Self {
+        assert!(!stops.is_empty(), "a gradient needs at least one colour");
+        ColourGradient { stops }
+    }
+
+    /// The colour `t` of the way along the gradient, clamped to its ends
+    pub fn at(&self, t: f32) -> Colour {
+        gradient_at(&self.stops, t)
+    }
+}
+
+fn gradient_at(stops: &[Colour], t: f32) -> Colour {
+    let last = stops.len() - 1;
+    let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) } * last as f32;
+    let index = (t.floor() as usize).min(last);
+    let (from, to) = (stops[index], stops[(index + 1).min(last)]);
+    let fraction = t - index as f32;
+    Colour(std::array::from_fn(|channel| {
+        let (from, to) = (from.0[channel] as f32, to.0[channel] as f32);
+        (from + (to - from) * fraction).round() as u8
+    }))
+}
+
+/// A fully saturated colour of the given hue in degrees
+pub fn hue_colour(hue: f32) -> Colour {
+    let rgb = Hsl::from(hue.rem_euclid(360.0), 100.0, 50.0).to_rgb();
+    Colour([
+        rgb.get_red().round() as u8,
+        rgb.get_green().round() as u8,
+        rgb.get_blue().round() as u8,
+    ])
+}
+
+/// The rainbow across the width of the world that boids start out with
+pub fn colour_by_width(x: f32, width: u32) -> Colour {
+    hue_colour(360.0 / width as f32 * x)
+}
+
+/// How boids are coloured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColourMode {
+    /// A rainbow across the world, by where each boid is when coloured
+    #[default]
+    InitialX,
+    /// A hue picked from each boid's id, stable however it moves
+    IdHash,
+    Random,
+    /// Slow to fast along `ColourGradient::default`, updated every frame
+    Speed,
+    /// The hue of the direction of travel, updated every frame
+    Heading,
+    /// Starts from the `IdHash` hue and turns through the rainbow by
+    /// `colour_rotation_speed` every frame
+    Rotating,
+    /// Red where the flock is spreading out, blue where it's closing in and
+    /// green where it's neither, from the divergence of the velocity field
+    /// over grid cells. Scaled by the largest divergence in each frame.
+    VelocityDivergence,
+    /// Blue where the flock is sparse to red where it's most crowded, from a
+    /// kernel density estimate of bandwidth `visible_range` sampled every
+    /// `KDE_SPACING` pixels
+    Kde,
+    /// Blue for boids flying smoothly to red for the most erratic, from how
+    /// much their heading changes varied over the last `trail_length`
+    /// frames. Scaled by the most varied in each frame.
+    TrajectoryEntropy,
+}
+
+impl FromStr for ColourMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "initial-x" => Ok(ColourMode::InitialX),
+            "id-hash" => Ok(ColourMode::IdHash),
+            "random" => Ok(ColourMode::Random),
+            "speed" => Ok(ColourMode::Speed),
+            "heading" => Ok(ColourMode::Heading),
+            "rotating" => Ok(ColourMode::Rotating),
+            "velocity-divergence" => Ok(ColourMode::VelocityDivergence),
+            "kde" => Ok(ColourMode::Kde),
+            "trajectory-entropy" => Ok(ColourMode::TrajectoryEntropy),
+            _ => Err(format!(
+                "Unknown colour mode {s}, expected initial-x, id-hash, random, speed, heading, rotating, velocity-divergence, kde or trajectory-entropy"
+            )),
+        }
+    }
+}
+
+// splitmix64's finaliser, so neighbouring ids get unrelated hues
+fn mix(id: u64) -> u64 {
+    let mut z = id.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn id_hue(boid: &Boid) -> f32 {
+    (mix(boid.id_u64()) % 360) as f32
+}
+
+impl ColourMode {
+    /// Whether the colour follows the boid's motion, so has to be worked out
+    /// again every frame and whatever colour was saved doesn't matter
+    pub fn is_dynamic(self) -> bool {
+        matches!(
+            self,
+            ColourMode::Speed
+                | ColourMode::Heading
+                | ColourMode::Rotating
+                | ColourMode::VelocityDivergence
+                | ColourMode::Kde
+                | ColourMode::TrajectoryEntropy
+        )
+    }
+
+    /// The colour `boid` should have in a world `width` wide. A boid on its
+    /// own has no flow around it, so gets the still colour with
+    /// `VelocityDivergence` and the sparsest with `Kde`, and
+    /// `TrajectoryEntropy` needs a few frames of headings to be anything but
+    /// smooth. `recolour` colours the whole flock properly.
+    pub fn colour<R: Rng + ?Sized>(
+        self,
+        boid: &Boid,
+        width: u32,
+        max_speed: f32,
+        rng: &mut R,
+    ) -> Colour {
+        match self {
+            ColourMode::InitialX => colour_by_width(boid.pos.x, width),
+            ColourMode::IdHash => hue_colour(id_hue(boid)),
+            ColourMode::Random => hue_colour(rng.random_range(0.0..360.0)),
+            ColourMode::Speed => gradient_at(&DEFAULT_STOPS, boid.vel.norm() / max_speed),
+            ColourMode::Heading => hue_colour(boid.heading().to_degrees()),
+            ColourMode::Rotating => hue_colour(id_hue(boid) + boid.hue_offset),
+            ColourMode::VelocityDivergence => divergence_colour(0.0),
+            ColourMode::Kde | ColourMode::TrajectoryEntropy => gradient_at(&DEFAULT_STOPS, 0.0),
+        }
+    }
+}
+
+/// Blue at -1 for contracting, through green at 0 to red at 1 for expanding
+pub fn divergence_colour(divergence: f32) -> Colour {
+    gradient_at(&DIVERGENCE_STOPS, (divergence + 1.0) / 2.0)
+}
+
+/// Colours each boid by the divergence of the flock's velocity field in
+/// the `cell_size` cell it's in, relative to the largest either way
+pub fn colour_by_divergence(boids: &mut [Boid], world: &World, cell_size: f32) {
+    let (width, height) = world.pixels();
+    let field = compute_velocity_field(boids, cell_size, width, height);
+    let divergence = field.divergence();
+    let max = divergence
+        .iter()
+        .fold(0.0f32, |max, cell| max.max(cell.abs()));
+    for boid in boids {
+        let cell = divergence[field.index_at(boid.pos)];
+        boid.colour = divergence_colour(if max > 0.0 { cell / max } else { 0.0 });
+    }
+}
+
+/// Colours each boid by the flock's density at the nearest of
+/// `kde_sample_points`, relative to the most crowded of them
+pub fn colour_by_density(boids: &mut [Boid], world: &World, bandwidth: f32) {
+    let (width, height) = world.pixels();
+    let density = compute_kde(boids, &kde_sample_points(width, height), bandwidth);
+    let cols = (width as f32 / KDE_SPACING).ceil() as usize;
+    let rows = (height as f32 / KDE_SPACING).ceil() as usize;
+    let max = density.iter().fold(0.0f32, |max, point| max.max(*point));
+    for boid in boids {
+        let col = ((boid.pos.x.max(0.0) / KDE_SPACING) as usize).min(cols.saturating_sub(1));
+        let row = ((boid.pos.y.max(0.0) / KDE_SPACING) as usize).min(rows.saturating_sub(1));
+        let point = density.get(row * cols + col).copied().unwrap_or(0.0);
+        boid.colour = gradient_at(&DEFAULT_STOPS, if max > 0.0 { point / max } else { 0.0 });
+    }
+}
+
+/// The variance of the turns between each of `headings` and the next, in
+/// radians, taking the short way round. 0 with fewer than two turns.
+pub fn heading_change_variance(headings: &VecDeque<f32>) -> f32 {
+    if headings.len() < 3 {
+        return 0.0;
+    }
+    let turns: Vec<f32> = headings
+        .iter()
+        .zip(headings.iter().skip(1))
+        .map(|(from, to)| (to - from + PI).rem_euclid(TAU) - PI)
+        .collect();
+    let mean = turns.iter().sum::<f32>() / turns.len() as f32;
+    turns.iter().map(|turn| (turn - mean).powi(2)).sum::<f32>() / turns.len() as f32
+}
+
+/// Adds each boid's heading to its history, keeping the last
+/// `trail_length`, then colours it by its `heading_change_variance`
+/// relative to the most varied in the flock. Called once a frame, it's the
+/// headings over the last `trail_length` frames.
+pub fn colour_by_trajectory_entropy(boids: &mut [Boid], trail_length: usize) {
+    let variances: Vec<f32> = boids
+        .iter_mut()
+        .map(|boid| {
+            let heading = boid.heading();
+            let history = &mut boid.heading_history;
+            history.push_back(heading);
+            while history.len() > trail_length {
+                history.pop_front();
+            }
+            heading_change_variance(history)
+        })
+        .collect();
+    let max = variances
+        .iter()
+        .fold(0.0f32, |max, variance| max.max(*variance));
+    for (boid, variance) in boids.iter_mut().zip(variances) {
+        boid.colour = gradient_at(&DEFAULT_STOPS, if max > 0.0 { variance / max } else { 0.0 });
+    }
+}
+
+/// Gives every boid the colour `mode` picks for it in `world`
+pub fn recolour<R: Rng + ?Sized>(
+    boids: &mut [Boid],
+    mode: ColourMode,
+    world: &World,
+    parameters: &Parameters,
+    rng: &mut R,
+) {
+    if mode == ColourMode::VelocityDivergence {
+        return colour_by_divergence(boids, world, parameters.cell_size);
+    }
+    if mode == ColourMode::Kde {
+        return colour_by_density(boids, world, parameters.visible_range);
+    }
+    if mode == ColourMode::TrajectoryEntropy {
+        return colour_by_trajectory_entropy(boids, parameters.trail_length);
+    }
+    let width = world.pixels().0;
+    for boid in boids {
+        boid.colour = mode.colour(boid, width, parameters.max_speed, rng);
+    }
+}
+
+/// Turns every boid's hue on by `speed` degrees for `ColourMode::Rotating`.
+/// With `per_boid` each turns at between half and one and a half times
+/// `speed`, from the high bits of its id's hash so it isn't tied to the hue.
+pub fn rotate_hues(boids: &mut [Boid], speed: f32, per_boid: bool) {
+    for boid in boids {
+        let speed = if per_boid {
+            let fraction = (mix(boid.id_u64()) >> 40) as f32 / (1u64 << 24) as f32;
+            speed * (0.5 + fraction)
+        } else {
+            speed
+        };
+        boid.hue_offset = (boid.hue_offset + speed).rem_euclid(360.0);
+    }
+}
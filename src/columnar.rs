@@ -0,0 +1,107 @@
+//! Boids written out as Parquet, one row per boid and one row group per
+//! frame, for querying with DuckDB or pandas. Needs the `parquet` feature.
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::data_type::{FloatType, Int32Type, Int64Type};
+use parquet::errors::{ParquetError, Result};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+
+use crate::boids::Boid;
+
+/// The columns every file has, in order
+pub const SCHEMA: &str = "
+message boids {
+    REQUIRED INT32 frame;
+    REQUIRED INT64 boid_id;
+    REQUIRED FLOAT pos_x;
+    REQUIRED FLOAT pos_y;
+    REQUIRED FLOAT vel_x;
+    REQUIRED FLOAT vel_y;
+    REQUIRED FLOAT speed;
+    REQUIRED INT32 r;
+    REQUIRED INT32 g;
+    REQUIRED INT32 b;
+}
+";
+
+/// Writes frames of boids to one Parquet file
+pub struct ParquetWriter {
+    writer: SerializedFileWriter<File>,
+}
+
+impl ParquetWriter {
+    pub fn create(path: &Path) -> Result<Self> {
+        let schema = Arc::new(parse_message_type(SCHEMA)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        let writer = SerializedFileWriter::new(File::create(path)?, schema, properties)?;
+        Ok(ParquetWriter { writer })
+    }
+
+    /// Writes every boid in `frame` as one row group
+    pub fn write_frame(&mut self, frame: usize, boids: &[Boid]) -> Result<()> {
+        let frame = i32::try_from(frame)
+            .map_err(|_| ParquetError::General(format!("frame {frame} doesn't fit an INT32")))?;
+        let floats: [Vec<f32>; 5] = [
+            boids.iter().map(|boid| boid.pos.x).collect(),
+            boids.iter().map(|boid| boid.pos.y).collect(),
+            boids.iter().map(|boid| boid.vel.x).collect(),
+            boids.iter().map(|boid| boid.vel.y).collect(),
+            boids.iter().map(|boid| boid.vel.norm()).collect(),
+        ];
+        let colours: [Vec<i32>; 3] = std::array::from_fn(|channel| {
+            boids
+                .iter()
+                .map(|boid| boid.colour.0[channel] as i32)
+                .collect()
+        });
+
+        let mut row_group = self.writer.next_row_group()?;
+        let mut column = 0;
+        while let Some(mut writer) = row_group.next_column()? {
+            match column {
+                0 => {
+                    writer.typed::<Int32Type>().write_batch(
+                        &vec![frame; boids.len()],
+                        None,
+                        None,
+                    )?;
+                }
+                1 => {
+                    let ids: Vec<i64> = boids.iter().map(|boid| boid.id as i64).collect();
+                    writer.typed::<Int64Type>().write_batch(&ids, None, None)?;
+                }
+                2..=6 => {
+                    writer
+                        .typed::<FloatType>()
+                        .write_batch(&floats[column - 2], None, None)?;
+                }
+                _ => {
+                    writer
+                        .typed::<Int32Type>()
+                        .write_batch(&colours[column - 7], None, None)?;
+                }
+            }
+            writer.close()?;
+            column += 1;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+
+    /// Writes the footer, without which the file can't be read
+    pub fn close(self) -> Result<()> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Writes `boids` to `path` as a file holding just the one frame
+pub fn write_frame_file(path: &Path, frame: usize, boids: &[Boid]) -> Result<()> {
+    let mut writer = ParquetWriter::create(path)?;
+    writer.write_frame(frame, boids)?;
+    writer.close()
+}
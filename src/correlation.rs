@@ -0,0 +1,146 @@
+//! The two point velocity correlation `C(r)`, how alike the headings of
+//! boids `r` apart are. Long range order keeps `C(r)` up at large `r`,
+//! where boids that only happen to line up with their neighbours drop off.
+//!
+//! ```text
+//! frame,r,correlation
+//! 0,2.5,0.93
+//! ```
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use nalgebra::Vector2;
+
+use crate::boids::{Boid, SpatialGrid};
+use crate::error::Error;
+use crate::parallel::*;
+
+pub const CSV_HEADER: &str = "frame,r,correlation";
+
+/// Mean dot product of unit velocities over every pair of boids, binned by
+/// how far apart the pair is
+pub struct CorrelationFunction;
+
+impl CorrelationFunction {
+    /// `(r, C(r))` for `n_bins` equal bins out to `r_max`, with `r` the
+    /// middle of the bin. Only the cells of `grid` that could hold a boid
+    /// within `r_max` are searched, and each pair is counted once. Boids that
+    /// aren't moving have no heading and are left out, and bins without any
+    /// pairs in them are 0.
+    pub fn compute(
+        boids: &[Boid],
+        grid: &SpatialGrid,
+        r_max: f32,
+        n_bins: usize,
+    ) -> Vec<(f32, f32)> {
+        if n_bins == 0 || r_max.is_nan() || r_max <= 0.0 {
+            return Vec::new();
+        }
+        let bin_width = r_max / n_bins as f32;
+        let headings: Vec<Option<Vector2<f32>>> = boids
+            .iter()
+            .map(|boid| boid.vel.try_normalize(0.0))
+            .collect();
+        let reach_x = (r_max / grid.cell_w).ceil() as i32;
+        let reach_y = (r_max / grid.cell_h).ceil() as i32;
+        let r_max_squared = r_max * r_max;
+        type Bins = (Vec<f64>, Vec<u64>);
+        let empty = || (vec![0.0f64; n_bins], vec![0u64; n_bins]);
+        let accumulate =
+            |(mut sums, mut counts): Bins, (idx, boid, heading): (usize, &Boid, Vector2<f32>)| {
+                let (cell_x, cell_y) = grid.cell_at(boid.pos);
+                for x_offset in -reach_x..=reach_x {
+                    for y_offset in -reach_y..=reach_y {
+                        let near_boids = grid.get_cell(
+                            cell_x.wrapping_add_signed(x_offset),
+                            cell_y.wrapping_add_signed(y_offset),
+                        );
+                        for &other_idx in near_boids.unwrap_or_default() {
+                            // Each pair is seen from both ends, keep one
+                            if other_idx <= idx {
+                                continue;
+                            }
+                            let Some(other_heading) = headings[other_idx] else {
+                                continue;
+                            };
+                            let dist_sq = boid.distance_squared_to(&boids[other_idx]);
+                            if dist_sq >= r_max_squared {
+                                continue;
+                            }
+                            let bin = ((dist_sq.sqrt() / bin_width) as usize).min(n_bins - 1);
+                            sums[bin] += heading.dot(&other_heading) as f64;
+                            counts[bin] += 1;
+                        }
+                    }
+                }
+                (sums, counts)
+            };
+        let moving = boids
+            .par_iter()
+            .enumerate()
+            .filter_map(|(idx, boid)| Some((idx, boid, headings[idx]?)));
+        // rayon folds each thread's share separately, then adds them up
+        #[cfg(feature = "parallel")]
+        let (sums, counts) = moving.fold(empty, accumulate).reduce(
+            empty,
+            |(mut sums, mut counts): Bins, (other_sums, other_counts)| {
+                sums.iter_mut().zip(other_sums).for_each(|(a, b)| *a += b);
+                counts
+                    .iter_mut()
+                    .zip(other_counts)
+                    .for_each(|(a, b)| *a += b);
+                (sums, counts)
+            },
+        );
+        #[cfg(not(feature = "parallel"))]
+        let (sums, counts) = moving.fold(empty(), accumulate);
+        sums.into_iter()
+            .zip(counts)
+            .enumerate()
+            .map(|(bin, (sum, count))| {
+                let r = (bin as f32 + 0.5) * bin_width;
+                let correlation = if count > 0 {
+                    (sum / count as f64) as f32
+                } else {
+                    0.0
+                };
+                (r, correlation)
+            })
+            .collect()
+    }
+}
+
+/// Appends `frame,r,correlation` rows as the correlation function is worked out
+pub struct CorrelationCsvWriter<W: Write> {
+    out: W,
+}
+
+impl CorrelationCsvWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        CorrelationCsvWriter::new(BufWriter::new(file)).map_err(|err| Error::io("write", path, err))
+    }
+}
+
+impl<W: Write> CorrelationCsvWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "{CSV_HEADER}")?;
+        Ok(CorrelationCsvWriter { out })
+    }
+
+    pub fn write_frame(&mut self, frame: usize, correlation: &[(f32, f32)]) -> io::Result<()> {
+        for (r, value) in correlation {
+            writeln!(self.out, "{frame},{r},{value}")?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
@@ -0,0 +1,107 @@
+//! The one error type for everything in the library that reads or writes a
+//! file, carrying the path it was working on so a message makes sense
+//! without the code that called it.
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::parameters::ValidationError;
+use crate::state::StateError;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("unable to {action} {}: {source}", .path.display())]
+    Io {
+        /// What was being done to `path`, such as "read" or "create"
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A file that was read but couldn't be decoded. `source` is the
+    /// decoder's own error, such as a `StateError`.
+    #[error("{}{}: {source}", .path.display(), Position::suffix(.position))]
+    Format {
+        path: PathBuf,
+        /// Where in a text file the problem is, if the decoder says
+        position: Option<Position>,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Every rule a set of parameters breaks
+    #[error("invalid parameters: {}", ValidationError::join(.0))]
+    Validation(Vec<ValidationError>),
+    /// A frame that couldn't be encoded or saved as an image
+    #[cfg(feature = "render")]
+    #[error("unable to render {}: {source}", .path.display())]
+    Render {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+}
+
+impl From<Vec<ValidationError>> for Error {
+    fn from(errors: Vec<ValidationError>) -> Self {
+        Error::Validation(errors)
+    }
+}
+
+impl Error {
+    pub fn io(action: &'static str, path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            action,
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Files `err` under what went wrong with `path`, as an `Io` error if
+    /// that's all it is
+    pub fn state(action: &'static str, path: &Path, err: StateError) -> Self {
+        if let StateError::Io(source) = err {
+            return Error::io(action, path, source);
+        }
+        let position = match &err {
+            StateError::Json(json) => Some(Position {
+                line: json.line(),
+                column: json.column(),
+            }),
+            _ => None,
+        };
+        Error::Format {
+            path: path.to_path_buf(),
+            position,
+            source: Box::new(err),
+        }
+    }
+}
+
+/// A 1 based line and column in a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position of byte `offset` into `text`
+    pub fn at(text: &str, offset: usize) -> Self {
+        let before = &text[..offset.min(text.len())];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Position {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    // `:line:column` to go after a path, or nothing
+    fn suffix(position: &Option<Position>) -> String {
+        position.map_or_else(String::new, |position| format!(":{position}"))
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
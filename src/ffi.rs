@@ -0,0 +1,263 @@
+//! A C interface to the simulation, for driving a flock from another
+//! language. `include/boids.h` declares it for C and C++, regenerated with
+//! `cbindgen --config cbindgen.toml --output include/boids.h` after any
+//! change here. Link against the library built with
+//! `cargo rustc --lib --release --features ffi --crate-type staticlib`.
+//!
+//! No function panics across the boundary, and null pointers are turned
+//! away rather than followed.
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use log::error;
+
+use crate::boids::StallPolicy;
+use crate::parameters::{ParametersBuilder, ValidationError};
+use crate::simulation::{Simulation, SimulationConfig};
+use crate::world::World;
+use crate::Parameters;
+
+/// How a call went
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoidsStatus {
+    Ok = 0,
+    /// A pointer that had to be set was null
+    ErrorNull = 1,
+    /// Something went wrong inside the library, and the simulation
+    /// shouldn't be used again
+    ErrorPanic = 2,
+}
+
+/// A flock, only ever handled through a pointer
+pub struct BoidsSim(Simulation);
+
+/// The `Parameters` that are plain numbers and flags. Those that aren't,
+/// such as the boundary shape and speed zones, are left at their defaults.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoidsParameters {
+    pub max_speed: f32,
+    pub min_speed: f32,
+    pub margin: u32,
+    pub visible_range: f32,
+    pub protected_range: f32,
+    pub avoid_factor: f32,
+    pub matching_factor: f32,
+    pub centering_factor: f32,
+    pub turn_factor: f32,
+    pub cell_size: f32,
+    pub draw_radius: i32,
+    pub update_threshold: f32,
+    pub global_centering_factor: f32,
+    pub render_smoothing: f32,
+    /// 0 for no limit
+    pub max_neighbors_for_early_exit: usize,
+    pub aspect_cells: bool,
+    pub colour_rotation_speed: f32,
+    pub per_boid_colour_rotation: bool,
+    pub voronoi_neighbors: bool,
+    pub heading_histogram_bins: usize,
+    pub draw_flock_hulls: bool,
+    /// 0 to keep no frames to rewind to
+    pub rewind_depth: usize,
+    pub perturbation_fraction: f32,
+    pub perturbation_strength: f32,
+    pub trail_length: usize,
+    /// 0 to nudge a stopped boid, 1 to send it off along its heading, 2 to
+    /// leave it stopped. Anything else is taken as 0.
+    pub stall_policy: u32,
+}
+
+impl From<&Parameters> for BoidsParameters {
+    fn from(parameters: &Parameters) -> Self {
+        BoidsParameters {
+            max_speed: parameters.max_speed,
+            min_speed: parameters.min_speed,
+            margin: parameters.margin,
+            visible_range: parameters.visible_range,
+            protected_range: parameters.protected_range,
+            avoid_factor: parameters.avoid_factor,
+            matching_factor: parameters.matching_factor,
+            centering_factor: parameters.centering_factor,
+            turn_factor: parameters.turn_factor,
+            cell_size: parameters.cell_size,
+            draw_radius: parameters.draw_radius,
+            update_threshold: parameters.update_threshold,
+            global_centering_factor: parameters.global_centering_factor,
+            render_smoothing: parameters.render_smoothing,
+            max_neighbors_for_early_exit: parameters.max_neighbors_for_early_exit.unwrap_or(0),
+            aspect_cells: parameters.aspect_cells,
+            colour_rotation_speed: parameters.colour_rotation_speed,
+            per_boid_colour_rotation: parameters.per_boid_colour_rotation,
+            voronoi_neighbors: parameters.voronoi_neighbors,
+            heading_histogram_bins: parameters.heading_histogram_bins,
+            draw_flock_hulls: parameters.draw_flock_hulls,
+            rewind_depth: parameters.rewind_depth,
+            perturbation_fraction: parameters.perturbation_fraction,
+            perturbation_strength: parameters.perturbation_strength,
+            trail_length: parameters.trail_length,
+            stall_policy: match parameters.stall_policy {
+                StallPolicy::RandomNudge => 0,
+                StallPolicy::KeepHeading => 1,
+                StallPolicy::Error => 2,
+            },
+        }
+    }
+}
+
+impl From<&BoidsParameters> for Parameters {
+    fn from(parameters: &BoidsParameters) -> Self {
+        Parameters {
+            max_speed: parameters.max_speed,
+            min_speed: parameters.min_speed,
+            margin: parameters.margin,
+            visible_range: parameters.visible_range,
+            protected_range: parameters.protected_range,
+            avoid_factor: parameters.avoid_factor,
+            matching_factor: parameters.matching_factor,
+            centering_factor: parameters.centering_factor,
+            turn_factor: parameters.turn_factor,
+            cell_size: parameters.cell_size,
+            draw_radius: parameters.draw_radius,
+            update_threshold: parameters.update_threshold,
+            global_centering_factor: parameters.global_centering_factor,
+            render_smoothing: parameters.render_smoothing,
+            max_neighbors_for_early_exit: Some(parameters.max_neighbors_for_early_exit)
+                .filter(|limit| *limit > 0),
+            aspect_cells: parameters.aspect_cells,
+            colour_rotation_speed: parameters.colour_rotation_speed,
+            per_boid_colour_rotation: parameters.per_boid_colour_rotation,
+            voronoi_neighbors: parameters.voronoi_neighbors,
+            heading_histogram_bins: parameters.heading_histogram_bins,
+            draw_flock_hulls: parameters.draw_flock_hulls,
+            rewind_depth: parameters.rewind_depth,
+            perturbation_fraction: parameters.perturbation_fraction,
+            perturbation_strength: parameters.perturbation_strength,
+            trail_length: parameters.trail_length,
+            stall_policy: match parameters.stall_policy {
+                1 => StallPolicy::KeepHeading,
+                2 => StallPolicy::Error,
+                _ => StallPolicy::RandomNudge,
+            },
+            ..Parameters::default()
+        }
+    }
+}
+
+/// Fills `out` with the default parameters, to change before passing to
+/// `boids_sim_new`
+///
+/// # Safety
+///
+/// `out` has to be null or point to memory a `BoidsParameters` can be
+/// written to.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn boids_parameters_default(out: *mut BoidsParameters) -> BoidsStatus {
+    if out.is_null() {
+        return BoidsStatus::ErrorNull;
+    }
+    // SAFETY: checked for null above, the caller promises the rest
+    unsafe { out.write(BoidsParameters::from(&Parameters::default())) };
+    BoidsStatus::Ok
+}
+
+/// `count` boids spread over a `width` x `height` world from `seed`, flying
+/// by `parameters`, or the defaults if it's null. Returns null if the world
+/// is empty or the parameters don't make sense for it. Free the flock with
+/// `boids_sim_free`.
+///
+/// # Safety
+///
+/// `parameters` has to be null or point to a `BoidsParameters`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn boids_sim_new(
+    width: u32,
+    height: u32,
+    count: usize,
+    seed: u64,
+    parameters: *const BoidsParameters,
+) -> *mut BoidsSim {
+    // SAFETY: the caller promises it's null or a `BoidsParameters`
+    let parameters = unsafe { parameters.as_ref() }.map(Parameters::from);
+    let created = catch_unwind(|| {
+        let parameters = ParametersBuilder::from(parameters.unwrap_or_default())
+            .world(width, height)
+            .build()
+            .map_err(|errors| ValidationError::join(&errors))?;
+        let config = SimulationConfig {
+            seed,
+            ..SimulationConfig::new(World::from_pixels(width, height), parameters, count)
+        };
+        Simulation::new(config).map_err(|err| err.to_string())
+    });
+    match created {
+        Ok(Ok(simulation)) => Box::into_raw(Box::new(BoidsSim(simulation))),
+        Ok(Err(err)) => {
+            error!("Unable to start a simulation: {err}");
+            ptr::null_mut()
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Moves the flock on by a frame
+///
+/// # Safety
+///
+/// `sim` has to be null or a flock from `boids_sim_new` that hasn't been
+/// freed, and not in use anywhere else during the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn boids_sim_step(sim: *mut BoidsSim) -> BoidsStatus {
+    // SAFETY: the caller promises it's null or a live flock
+    let Some(sim) = (unsafe { sim.as_mut() }) else {
+        return BoidsStatus::ErrorNull;
+    };
+    match catch_unwind(AssertUnwindSafe(|| sim.0.step())) {
+        Ok(_) => BoidsStatus::Ok,
+        Err(_) => BoidsStatus::ErrorPanic,
+    }
+}
+
+/// Writes each boid's x and y in turn into `out`, as many as fit in its
+/// `cap` floats, and returns how many boids there are, so a call with a
+/// `cap` of 0 finds the size to allocate. Returns 0 for a null `sim`.
+///
+/// # Safety
+///
+/// `sim` has to be null or a flock from `boids_sim_new` that hasn't been
+/// freed. `out` has to be null or point to `cap` floats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn boids_sim_positions(
+    sim: *const BoidsSim,
+    out: *mut f32,
+    cap: usize,
+) -> usize {
+    // SAFETY: the caller promises it's null or a live flock
+    let Some(sim) = (unsafe { sim.as_ref() }) else {
+        return 0;
+    };
+    let boids = sim.0.boids();
+    if !out.is_null() {
+        // SAFETY: not null, and the caller promises `cap` floats
+        let out = unsafe { std::slice::from_raw_parts_mut(out, cap) };
+        for (pair, boid) in out.chunks_exact_mut(2).zip(boids) {
+            pair.copy_from_slice(&[boid.pos.x, boid.pos.y]);
+        }
+    }
+    boids.len()
+}
+
+/// Frees a flock from `boids_sim_new`. Null is ignored.
+///
+/// # Safety
+///
+/// `sim` has to be null or a flock from `boids_sim_new` that hasn't
+/// already been freed, and isn't used again.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn boids_sim_free(sim: *mut BoidsSim) {
+    if !sim.is_null() {
+        // SAFETY: it came from `Box::into_raw` in `boids_sim_new`
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(sim) })));
+    }
+}
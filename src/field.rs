@@ -0,0 +1,222 @@
+//! The flock's average velocity field, and streamlines traced through it,
+//! drawn a bit like a wind map.
+#[cfg(feature = "render")]
+use image::RgbImage;
+#[cfg(feature = "render")]
+use imageproc::drawing::draw_antialiased_line_segment_mut;
+#[cfg(feature = "render")]
+use imageproc::pixelops::interpolate;
+#[cfg(feature = "render")]
+use log::trace;
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+use crate::colour::ColourGradient;
+
+/// Mean velocity of the boids in each cell of a regular grid
+#[derive(Debug, Clone, PartialEq)]
+pub struct VelocityField {
+    pub cols: u32,
+    pub rows: u32,
+    pub spacing: f32,
+    pub width: u32,
+    pub height: u32,
+    /// Row major, zero for cells without any boids
+    pub cells: Vec<Vector2<f32>>,
+    /// Row major number of boids in each cell
+    pub counts: Vec<u32>,
+}
+
+pub fn compute_velocity_field(
+    boids: &[Boid],
+    spacing: f32,
+    width: u32,
+    height: u32,
+) -> VelocityField {
+    let cols = ((width as f32 / spacing).ceil() as u32).max(1);
+    let rows = ((height as f32 / spacing).ceil() as u32).max(1);
+    let mut field = VelocityField {
+        cols,
+        rows,
+        spacing,
+        width,
+        height,
+        cells: vec![Vector2::zeros(); (cols * rows) as usize],
+        counts: vec![0; (cols * rows) as usize],
+    };
+    for boid in boids {
+        let index = field.index_at(boid.pos);
+        field.cells[index] += boid.vel;
+        field.counts[index] += 1;
+    }
+    for (cell, count) in field.cells.iter_mut().zip(&field.counts) {
+        if *count > 0 {
+            *cell /= *count as f32;
+        }
+    }
+    field
+}
+
+impl VelocityField {
+    pub fn get(&self, col: u32, row: u32) -> Vector2<f32> {
+        self.cells[(row * self.cols + col) as usize]
+    }
+
+    /// Row major index of the cell `pos` is in, the nearest one if it's
+    /// outside of the world
+    pub fn index_at(&self, pos: Vector2<f32>) -> usize {
+        let col = ((pos.x.max(0.0) / self.spacing) as u32).min(self.cols - 1);
+        let row = ((pos.y.max(0.0) / self.spacing) as u32).min(self.rows - 1);
+        (row * self.cols + col) as usize
+    }
+
+    /// Row major divergence of the field, positive where the flock is
+    /// spreading out and negative where it's closing in. Worked out from
+    /// central differences between the occupied cells either side, one sided
+    /// where only one side has boids, so the empty space around a flock
+    /// isn't mistaken for it stopping dead. Empty cells have none.
+    pub fn divergence(&self) -> Vec<f32> {
+        let occupied = |col: u32, row: u32| self.counts[(row * self.cols + col) as usize] > 0;
+        // The change in `axis` of the velocity from `before` to `after`,
+        // per pixel
+        let slope = |before: Option<(u32, u32)>,
+                     here: (u32, u32),
+                     after: Option<(u32, u32)>,
+                     axis: usize| {
+            let before = before.filter(|(col, row)| occupied(*col, *row));
+            let after = after.filter(|(col, row)| occupied(*col, *row));
+            let (from, to, cells) = match (before, after) {
+                (Some(before), Some(after)) => (before, after, 2.0),
+                (Some(before), None) => (before, here, 1.0),
+                (None, Some(after)) => (here, after, 1.0),
+                (None, None) => return 0.0,
+            };
+            (self.get(to.0, to.1)[axis] - self.get(from.0, from.1)[axis]) / (cells * self.spacing)
+        };
+        (0..self.rows)
+            .flat_map(|row| (0..self.cols).map(move |col| (col, row)))
+            .map(|(col, row)| {
+                if !occupied(col, row) {
+                    return 0.0;
+                }
+                let left = col.checked_sub(1).map(|col| (col, row));
+                let right = (col + 1 < self.cols).then_some((col + 1, row));
+                let up = row.checked_sub(1).map(|row| (col, row));
+                let down = (row + 1 < self.rows).then_some((col, row + 1));
+                slope(left, (col, row), right, 0) + slope(up, (col, row), down, 1)
+            })
+            .collect()
+    }
+
+    /// The velocity at `pos`, bilinearly interpolated between the centres of
+    /// the cells around it, or `None` outside of the world
+    pub fn sample(&self, pos: Vector2<f32>) -> Option<Vector2<f32>> {
+        if !(0.0..self.width as f32).contains(&pos.x) || !(0.0..self.height as f32).contains(&pos.y)
+        {
+            return None;
+        }
+        let x = (pos.x / self.spacing - 0.5).clamp(0.0, (self.cols - 1) as f32);
+        let y = (pos.y / self.spacing - 0.5).clamp(0.0, (self.rows - 1) as f32);
+        let (col, row) = (x.floor() as u32, y.floor() as u32);
+        let (next_col, next_row) = ((col + 1).min(self.cols - 1), (row + 1).min(self.rows - 1));
+        let (fx, fy) = (x - col as f32, y - row as f32);
+        let top = self.get(col, row).lerp(&self.get(next_col, row), fx);
+        let bottom = self
+            .get(col, next_row)
+            .lerp(&self.get(next_col, next_row), fx);
+        Some(top.lerp(&bottom, fy))
+    }
+}
+
+/// Follows the field from `start`, `step` pixels at a time, until it leaves
+/// the world, reaches still air or has taken `max_steps` steps. Each point
+/// comes with the speed of the field there.
+pub fn trace_streamline(
+    field: &VelocityField,
+    start: Vector2<f32>,
+    step: f32,
+    max_steps: usize,
+) -> Vec<(Vector2<f32>, f32)> {
+    let mut points = Vec::new();
+    let mut pos = start;
+    let Some(mut vel) = field.sample(pos) else {
+        return points;
+    };
+    points.push((pos, vel.norm()));
+    for _ in 0..max_steps {
+        if vel.norm() < 1e-3 {
+            break;
+        }
+        // Midpoint method, so curves don't spiral outwards
+        let Some(mid) = field.sample(pos + vel.normalize() * step / 2.0) else {
+            break;
+        };
+        if mid.norm() < 1e-3 {
+            break;
+        }
+        pos += mid.normalize() * step;
+        let Some(next) = field.sample(pos) else {
+            break;
+        };
+        vel = next;
+        points.push((pos, vel.norm()));
+    }
+    points
+}
+
+/// Settings for drawing streamlines over a frame
+#[derive(Debug, Clone)]
+pub struct FieldLines {
+    /// Seed points across the width of the frame
+    pub density: f32,
+    pub max_steps: usize,
+    /// Pixels per step along a streamline
+    pub step: f32,
+    pub gradient: ColourGradient,
+    /// Speed drawn with the end of the gradient
+    pub max_speed: f32,
+}
+
+impl FieldLines {
+    pub fn new(density: f32, max_speed: f32) -> Self {
+        FieldLines {
+            density,
+            max_steps: 100,
+            step: 2.0,
+            gradient: ColourGradient::default(),
+            max_speed,
+        }
+    }
+
+    /// Seed points on a regular grid `width / density` apart
+    pub fn seeds(&self, width: u32, height: u32) -> Vec<Vector2<f32>> {
+        let spacing = width as f32 / self.density;
+        let cols = (width as f32 / spacing).round() as u32;
+        let rows = (height as f32 / spacing).round() as u32;
+        (0..rows)
+            .flat_map(|row| {
+                (0..cols).map(move |col| {
+                    Vector2::new((col as f32 + 0.5) * spacing, (row as f32 + 0.5) * spacing)
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "render")]
+    pub fn draw(&self, img: &mut RgbImage, field: &VelocityField) {
+        let seeds = self.seeds(img.width(), img.height());
+        trace!("Drawing {} streamlines", seeds.len());
+        for seed in seeds {
+            let line = trace_streamline(field, seed, self.step, self.max_steps);
+            for ((from, _), (to, speed)) in line.iter().zip(line.iter().skip(1)) {
+                draw_antialiased_line_segment_mut(
+                    img,
+                    (from.x.round() as i32, from.y.round() as i32),
+                    (to.x.round() as i32, to.y.round() as i32),
+                    self.gradient.at(speed / self.max_speed).into(),
+                    interpolate,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Flocks followed from one frame to the next. Clustering numbers the
+//! groups it finds afresh every frame, so the same flock can be cluster 0
+//! on one frame and cluster 3 on the next; `FlockTracker` gives each one an
+//! id it keeps for as long as it holds together.
+use std::collections::{BTreeSet, HashMap};
+
+use crate::boids::Boid;
+use crate::cluster::NOISE;
+
+/// Flock id of a boid that isn't in any flock. Real flocks count from 1.
+pub const NO_FLOCK: u32 = 0;
+
+/// The boids in each cluster of `membership`, by their place in the flock,
+/// in cluster order. Noise isn't a flock, so is left out.
+pub fn components(membership: &[i32]) -> Vec<Vec<usize>> {
+    let clusters = membership
+        .iter()
+        .filter(|cluster| **cluster != NOISE)
+        .max()
+        .map_or(0, |max| *max as usize + 1);
+    let mut components = vec![Vec::new(); clusters];
+    for (idx, cluster) in membership.iter().enumerate() {
+        if *cluster != NOISE {
+            components[*cluster as usize].push(idx);
+        }
+    }
+    components
+}
+
+/// Gives the groups found on each frame the ids of the flocks they have the
+/// most boids in common with the frame before. Pairs are matched greedily,
+/// biggest overlap first, and each old flock carries on in at most one new
+/// one. A group with nothing to carry on from, such as one half of a split,
+/// gets the next unused id. A flock that doesn't carry on, such as one that
+/// merged into another, is tombstoned and its id is never handed out again.
+#[derive(Debug, Clone)]
+pub struct FlockTracker {
+    // Flock each boid was in on the last frame, by its place in the flock
+    previous: Vec<u32>,
+    live: BTreeSet<u32>,
+    tombstones: BTreeSet<u32>,
+    next_id: u32,
+}
+
+impl Default for FlockTracker {
+    fn default() -> Self {
+        FlockTracker {
+            previous: Vec::new(),
+            live: BTreeSet::new(),
+            tombstones: BTreeSet::new(),
+            next_id: NO_FLOCK + 1,
+        }
+    }
+}
+
+impl FlockTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes this frame's groups of boids, by their place in the flock, and
+    /// gives each boid its flock's id. There's an id for every place up to
+    /// the highest in `components`, `NO_FLOCK` for those not in a group.
+    pub fn update(&mut self, components: &[Vec<usize>]) -> Vec<u32> {
+        let mut overlaps: HashMap<(usize, u32), usize> = HashMap::new();
+        for (component, members) in components.iter().enumerate() {
+            for idx in members {
+                if let Some(&flock) = self.previous.get(*idx)
+                    && flock != NO_FLOCK
+                {
+                    *overlaps.entry((component, flock)).or_default() += 1;
+                }
+            }
+        }
+        // Biggest overlaps first, ties going to the earlier group and flock
+        // so the result doesn't depend on the order of the map
+        let mut overlaps: Vec<((usize, u32), usize)> = overlaps.into_iter().collect();
+        overlaps.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        let mut ids = vec![NO_FLOCK; components.len()];
+        let mut carried = BTreeSet::new();
+        for ((component, flock), _) in overlaps {
+            if ids[component] == NO_FLOCK && carried.insert(flock) {
+                ids[component] = flock;
+            }
+        }
+        for id in ids.iter_mut().filter(|id| **id == NO_FLOCK) {
+            *id = self.next_id;
+            self.next_id += 1;
+        }
+
+        self.tombstones.extend(self.live.difference(&carried));
+        self.live = ids.iter().copied().collect();
+        let len = components.iter().flatten().max().map_or(0, |max| max + 1);
+        self.previous = vec![NO_FLOCK; len];
+        for (members, id) in components.iter().zip(&ids) {
+            for idx in members {
+                self.previous[*idx] = *id;
+            }
+        }
+        self.previous.clone()
+    }
+
+    /// Like `update`, setting the flock id of every boid in `boids` instead
+    pub fn assign(&mut self, boids: &mut [Boid], components: &[Vec<usize>]) {
+        let ids = self.update(components);
+        for (idx, boid) in boids.iter_mut().enumerate() {
+            boid.flock_id = ids.get(idx).copied().unwrap_or(NO_FLOCK);
+        }
+    }
+
+    /// Flocks seen on the last frame
+    pub fn live(&self) -> &BTreeSet<u32> {
+        &self.live
+    }
+
+    /// Flocks that have died out or merged into others
+    pub fn tombstones(&self) -> &BTreeSet<u32> {
+        &self.tombstones
+    }
+
+    pub fn distinct_flocks(&self) -> u32 {
+        self.live.len() as u32
+    }
+}
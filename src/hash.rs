@@ -0,0 +1,70 @@
+//! Hashes of rendered frames, for telling whether a change altered what a
+//! run draws.
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use image::RgbImage;
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+use crate::error::Error;
+
+/// XXH3 of a frame's raw pixels and its size, so it doesn't depend on how
+/// the frame is encoded
+pub fn frame_hash(img: &RgbImage) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&img.width().to_le_bytes());
+    hasher.update(&img.height().to_le_bytes());
+    hasher.update(img.as_raw());
+    hasher.digest()
+}
+
+/// Writes `frame hash` lines as frames are rendered, and keeps a hash of
+/// every frame's hash in order
+pub struct FrameHashes<W: Write> {
+    out: W,
+    combined: Xxh3,
+}
+
+impl FrameHashes<BufWriter<File>> {
+    pub fn create(path: &Path) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        Ok(FrameHashes::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> FrameHashes<W> {
+    pub fn new(out: W) -> Self {
+        FrameHashes {
+            out,
+            combined: Xxh3::new(),
+        }
+    }
+
+    /// Hashes one frame, returning its hash
+    pub fn record(&mut self, frame: usize, img: &RgbImage) -> io::Result<u64> {
+        let hash = frame_hash(img);
+        self.combined.update(&hash.to_le_bytes());
+        writeln!(self.out, "{frame} {hash:016x}")?;
+        Ok(hash)
+    }
+
+    /// Hash of all the frames so far, which changes if any of them do
+    pub fn combined(&self) -> u64 {
+        self.combined.digest()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// The combined hash of a list of frame hashes, as `FrameHashes` works it out
+pub fn combine(hashes: &[u64]) -> u64 {
+    let bytes: Vec<u8> = hashes.iter().flat_map(|hash| hash.to_le_bytes()).collect();
+    xxh3_64(&bytes)
+}
@@ -0,0 +1,86 @@
+//! How the headings of the flock are spread around the circle. An aligned
+//! flock piles up in a bin or two, a disordered swarm is spread evenly.
+//!
+//! ```text
+//! frame,-2.3561945,-0.7853982,0.7853982,2.3561945
+//! 0,3,9,0,0
+//! ```
+use std::f32::consts::{PI, TAU};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::boids::Boid;
+use crate::error::Error;
+
+/// Counts of boids heading into each of a number of equal width bins over
+/// `[-pi, pi)`, with headings given by `Boid::heading`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadingHistogram {
+    pub counts: Vec<usize>,
+}
+
+impl HeadingHistogram {
+    /// Bins the heading of every boid into `bins` bins
+    pub fn compute(boids: &[Boid], bins: usize) -> Self {
+        let mut counts = vec![0; bins];
+        if bins == 0 {
+            return HeadingHistogram { counts };
+        }
+        let bin_width = TAU / bins as f32;
+        for boid in boids {
+            // pi is the same way as -pi, so wraps round into the first bin
+            let bin = ((boid.heading() + PI) / bin_width) as usize % bins;
+            counts[bin] += 1;
+        }
+        HeadingHistogram { counts }
+    }
+
+    /// The angle in the middle of each of `bins` bins
+    pub fn centres(bins: usize) -> Vec<f32> {
+        (0..bins)
+            .map(|bin| PI * ((2 * bin + 1) as f32 / bins as f32 - 1.0))
+            .collect()
+    }
+}
+
+/// Appends a `frame` row of counts per bin for each frame, under a header
+/// naming each bin by its centre angle
+pub struct HeadingHistogramCsvWriter<W: Write> {
+    out: W,
+}
+
+impl HeadingHistogramCsvWriter<BufWriter<File>> {
+    pub fn create(path: &Path, bins: usize) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        HeadingHistogramCsvWriter::new(BufWriter::new(file), bins)
+            .map_err(|err| Error::io("write", path, err))
+    }
+}
+
+impl<W: Write> HeadingHistogramCsvWriter<W> {
+    pub fn new(mut out: W, bins: usize) -> io::Result<Self> {
+        write!(out, "frame")?;
+        for centre in HeadingHistogram::centres(bins) {
+            write!(out, ",{centre}")?;
+        }
+        writeln!(out)?;
+        Ok(HeadingHistogramCsvWriter { out })
+    }
+
+    pub fn write_frame(&mut self, frame: usize, histogram: &HeadingHistogram) -> io::Result<()> {
+        write!(self.out, "{frame}")?;
+        for count in &histogram.counts {
+            write!(self.out, ",{count}")?;
+        }
+        writeln!(self.out)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
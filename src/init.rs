@@ -0,0 +1,328 @@
+//! Where new boids are placed, and which way they're sent, when a run
+//! doesn't start from a saved state.
+use std::fmt;
+use std::str::FromStr;
+
+use log::debug;
+use nalgebra::Vector2;
+use rand::Rng;
+use rand_distr::{Distribution, Normal};
+
+use crate::boids::{Boid, BoidId};
+use crate::colour::colour_by_width;
+use crate::world::World;
+use crate::Parameters;
+
+/// A pattern to spawn boids in. Positions that would fall outside of the
+/// world are clamped to its edge.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoidSpawnDistribution {
+    /// Evenly over the whole world, on whole pixels
+    Uniform,
+    /// A normal distribution around `mean`
+    Gaussian { mean: Vector2<f32>, std: f32 },
+    /// Around a circle, `spread` being the standard deviation of the distance
+    /// from its edge
+    Ring {
+        center: Vector2<f32>,
+        radius: f32,
+        spread: f32,
+    },
+    /// In the middle of the cells of a `rows` x `cols` grid over the world,
+    /// moved up to `jitter` pixels each way
+    Grid {
+        rows: usize,
+        cols: usize,
+        jitter: f32,
+    },
+    /// `per_center` boids normally distributed around each centre in turn,
+    /// going back to the first once every centre has its share
+    Cluster {
+        centers: Vec<Vector2<f32>>,
+        per_center: usize,
+        spread: f32,
+    },
+}
+
+impl fmt::Display for BoidSpawnDistribution {
+    /// Writes the same form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoidSpawnDistribution::Uniform => write!(f, "uniform"),
+            BoidSpawnDistribution::Gaussian { mean, std } => {
+                write!(f, "gaussian:{},{},{std}", mean.x, mean.y)
+            }
+            BoidSpawnDistribution::Ring {
+                center,
+                radius,
+                spread,
+            } => write!(f, "ring:{},{},{radius},{spread}", center.x, center.y),
+            BoidSpawnDistribution::Grid { rows, cols, jitter } => {
+                write!(f, "grid:{rows},{cols},{jitter}")
+            }
+            BoidSpawnDistribution::Cluster {
+                centers,
+                per_center,
+                spread,
+            } => {
+                write!(f, "cluster:{per_center},{spread}")?;
+                for center in centers {
+                    write!(f, ",{},{}", center.x, center.y)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for BoidSpawnDistribution {
+    type Err = String;
+
+    /// Parses `uniform`, `gaussian:X,Y,STD`, `ring:X,Y,RADIUS,SPREAD`,
+    /// `grid:ROWS,COLS,JITTER` or `cluster:PER_CENTER,SPREAD,X,Y[,X,Y...]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        let numbers: Vec<f32> = if args.is_empty() {
+            Vec::new()
+        } else {
+            args.split(',')
+                .map(|arg| arg.trim().parse::<f32>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Spawn distribution {s} has a value that isn't a number"))?
+        };
+        if numbers
+            .iter()
+            .any(|number| !number.is_finite() || *number < 0.0)
+        {
+            return Err(format!("Spawn distribution {s} can't have negative values"));
+        }
+        let distribution = match (name, &numbers[..]) {
+            ("uniform", []) => BoidSpawnDistribution::Uniform,
+            ("gaussian", &[x, y, std]) => BoidSpawnDistribution::Gaussian {
+                mean: Vector2::new(x, y),
+                std,
+            },
+            ("ring", &[x, y, radius, spread]) => BoidSpawnDistribution::Ring {
+                center: Vector2::new(x, y),
+                radius,
+                spread,
+            },
+            ("grid", &[rows, cols, jitter]) if rows >= 1.0 && cols >= 1.0 => {
+                BoidSpawnDistribution::Grid {
+                    rows: rows as usize,
+                    cols: cols as usize,
+                    jitter,
+                }
+            }
+            ("cluster", &[per_center, spread, ref centers @ ..])
+                if per_center >= 1.0 && !centers.is_empty() && centers.len() % 2 == 0 =>
+            {
+                BoidSpawnDistribution::Cluster {
+                    centers: centers
+                        .chunks_exact(2)
+                        .map(|center| Vector2::new(center[0], center[1]))
+                        .collect(),
+                    per_center: per_center as usize,
+                    spread,
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown spawn distribution {s}, expected uniform, gaussian:X,Y,STD, ring:X,Y,RADIUS,SPREAD, grid:ROWS,COLS,JITTER or cluster:PER_CENTER,SPREAD,X,Y[,X,Y...]"
+                ));
+            }
+        };
+        Ok(distribution)
+    }
+}
+
+impl BoidSpawnDistribution {
+    /// The point the `index`th boid is spawned around: the middle of a ring
+    /// or gaussian, the boid's cluster, or else the middle of the world
+    fn center(&self, index: usize, world: &World) -> Vector2<f32> {
+        match self {
+            BoidSpawnDistribution::Gaussian { mean, .. } => *mean,
+            BoidSpawnDistribution::Ring { center, .. } => *center,
+            BoidSpawnDistribution::Cluster {
+                centers,
+                per_center,
+                ..
+            } => centers[index / per_center % centers.len()],
+            BoidSpawnDistribution::Uniform | BoidSpawnDistribution::Grid { .. } => {
+                Vector2::new(world.width / 2.0, world.height / 2.0)
+            }
+        }
+    }
+
+    fn position<R: Rng + ?Sized>(&self, index: usize, rng: &mut R, world: &World) -> Vector2<f32> {
+        let normal = |std: f32| Normal::new(0.0, std).expect("std was checked when parsing");
+        match self {
+            BoidSpawnDistribution::Uniform => {
+                // Whole pixels, so the same seed spawns the same flock
+                let (width, height) = world.pixels();
+                Vector2::new(
+                    rng.random_range(0..width) as f32,
+                    rng.random_range(0..height) as f32,
+                )
+            }
+            BoidSpawnDistribution::Gaussian { mean, std } => {
+                let normal = normal(*std);
+                mean + Vector2::new(normal.sample(rng), normal.sample(rng))
+            }
+            BoidSpawnDistribution::Ring {
+                center,
+                radius,
+                spread,
+            } => {
+                let angle = rng.random_range(0.0..std::f32::consts::TAU);
+                let distance = radius + normal(*spread).sample(rng);
+                center + Vector2::new(angle.cos(), angle.sin()) * distance
+            }
+            BoidSpawnDistribution::Grid { rows, cols, jitter } => {
+                let cell = index % (rows * cols);
+                let size = Vector2::new(world.width / *cols as f32, world.height / *rows as f32);
+                let middle = Vector2::new(
+                    ((cell % cols) as f32 + 0.5) * size.x,
+                    ((cell / cols) as f32 + 0.5) * size.y,
+                );
+                middle
+                    + Vector2::new(
+                        rng.random_range(-jitter..=*jitter),
+                        rng.random_range(-jitter..=*jitter),
+                    )
+            }
+            BoidSpawnDistribution::Cluster {
+                centers,
+                per_center,
+                spread,
+            } => {
+                let center = centers[index / per_center % centers.len()];
+                let normal = normal(*spread);
+                center + Vector2::new(normal.sample(rng), normal.sample(rng))
+            }
+        }
+    }
+}
+
+/// How new boids are set moving, relative to the point their distribution
+/// spawns them around
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BoidSpawnVelocity {
+    /// Within half of `max_speed` either way
+    #[default]
+    Random,
+    /// Straight at the centre
+    InwardNormal { speed: f32 },
+    /// Straight away from the centre
+    OutwardNormal { speed: f32 },
+    /// Around the centre, clockwise or not as drawn on screen
+    Tangential { speed: f32, clockwise: bool },
+}
+
+impl fmt::Display for BoidSpawnVelocity {
+    /// Writes the same form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoidSpawnVelocity::Random => write!(f, "random"),
+            BoidSpawnVelocity::InwardNormal { speed } => write!(f, "inward:{speed}"),
+            BoidSpawnVelocity::OutwardNormal { speed } => write!(f, "outward:{speed}"),
+            BoidSpawnVelocity::Tangential {
+                speed,
+                clockwise: true,
+            } => write!(f, "clockwise:{speed}"),
+            BoidSpawnVelocity::Tangential {
+                speed,
+                clockwise: false,
+            } => write!(f, "anticlockwise:{speed}"),
+        }
+    }
+}
+
+impl FromStr for BoidSpawnVelocity {
+    type Err = String;
+
+    /// Parses `random`, `inward:SPEED`, `outward:SPEED`, `clockwise:SPEED`
+    /// or `anticlockwise:SPEED`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let speed = |text: &str| match text.trim().parse::<f32>() {
+            Ok(speed) if speed.is_finite() && speed >= 0.0 => Ok(speed),
+            _ => Err(format!("Spawn velocity {s} needs a speed of 0 or more")),
+        };
+        match s.split_once(':') {
+            None if s == "random" => Ok(BoidSpawnVelocity::Random),
+            Some(("inward", text)) => Ok(BoidSpawnVelocity::InwardNormal { speed: speed(text)? }),
+            Some(("outward", text)) => Ok(BoidSpawnVelocity::OutwardNormal { speed: speed(text)? }),
+            Some((direction @ ("clockwise" | "anticlockwise"), text)) => {
+                Ok(BoidSpawnVelocity::Tangential {
+                    speed: speed(text)?,
+                    clockwise: direction == "clockwise",
+                })
+            }
+            _ => Err(format!(
+                "Unknown spawn velocity {s}, expected random, inward:SPEED, outward:SPEED, clockwise:SPEED or anticlockwise:SPEED"
+            )),
+        }
+    }
+}
+
+impl BoidSpawnVelocity {
+    fn velocity<R: Rng + ?Sized>(
+        &self,
+        pos: Vector2<f32>,
+        center: Vector2<f32>,
+        rng: &mut R,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        // Boids spawned right on the centre head along x
+        let outward = (pos - center)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector2::x);
+        match *self {
+            BoidSpawnVelocity::Random => {
+                let half_speed = parameters.max_speed / 2.0;
+                Vector2::new(
+                    rng.random_range(-half_speed..half_speed),
+                    rng.random_range(-half_speed..half_speed),
+                )
+            }
+            BoidSpawnVelocity::InwardNormal { speed } => -outward * speed,
+            BoidSpawnVelocity::OutwardNormal { speed } => outward * speed,
+            // y grows down the screen, so this turn is clockwise as drawn
+            BoidSpawnVelocity::Tangential { speed, clockwise } => {
+                let tangent = Vector2::new(-outward.y, outward.x) * speed;
+                if clockwise {
+                    tangent
+                } else {
+                    -tangent
+                }
+            }
+        }
+    }
+}
+
+/// Spawns `count` boids numbered from 0, placed by `distribution` in
+/// `world` and set moving by `velocity`, with colours a rainbow across the
+/// world.
+pub fn spawn_boids<R: Rng + ?Sized>(
+    count: usize,
+    distribution: &BoidSpawnDistribution,
+    velocity: BoidSpawnVelocity,
+    rng: &mut R,
+    parameters: &Parameters,
+    world: &World,
+) -> Vec<Boid> {
+    debug!("Spawning {count} boids, {distribution}, moving {velocity}");
+    (0..count)
+        .map(|id| {
+            let pos = world.clamp(distribution.position(id, rng, world));
+            let center = distribution.center(id, world);
+            let vel = velocity.velocity(pos, center, rng, parameters);
+            Boid::new(
+                id as BoidId,
+                pos,
+                vel,
+                colour_by_width(pos.x, world.pixels().0),
+            )
+        })
+        .collect()
+}
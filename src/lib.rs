@@ -1,16 +1,453 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::boids::StallPolicy;
+use crate::boundary::BoundaryMode;
+use crate::schedule::RandomSeedSchedule;
+use crate::zone::SpeedLimitZone;
+
+pub mod balance;
+pub mod behavior;
 pub mod boids;
+pub mod boundary;
+pub mod cluster;
+pub mod colour;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod correlation;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod field;
+pub mod flock;
+#[cfg(feature = "render")]
+pub mod hash;
+pub mod heading;
+pub mod init;
+pub mod manifest;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "render")]
+pub mod overlay;
+pub mod pacing;
+mod parallel;
+pub mod parameters;
+pub mod prelude;
+pub mod quality;
+pub mod query;
+#[cfg(feature = "render")]
+pub mod render;
+pub mod replay;
+pub mod rules;
+pub mod schedule;
+pub mod simulation;
+pub mod smoothing;
+pub mod state;
+pub mod stop;
+pub mod summary;
+pub mod sweep;
+pub mod sys;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod trace;
+pub mod trajectory;
+pub mod transform;
+pub mod turbulence;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod world;
+pub mod zone;
+
+pub use error::{Error, Result};
 
-#[derive(Debug, Clone, Copy)]
+// What nearly everything using the crate needs, so it can be had without
+// knowing which module each lives in, or writing `boids::boids`. The rest
+// stays in its module.
+pub use boids::{update_boids, Boid, BoidId, FlockTally, SpatialGrid};
+pub use simulation::{FrameStats, Simulation, SimulationConfig, SimulationError};
+pub use world::World;
+
+/// How boids fly. Any left out when read, such as by a file from before
+/// they were added, are the defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Parameters {
     pub max_speed: f32,
     pub min_speed: f32,
     pub margin: u32,
     pub visible_range: f32,
     pub protected_range: f32,
+    /// How hard boids steer apart inside the protected range, 0 to turn
+    /// separation off
     pub avoid_factor: f32,
+    /// How hard boids match their flockmates' velocity, 0 to turn alignment
+    /// off
     pub matching_factor: f32,
+    /// How hard boids steer towards their flockmates, 0 to turn cohesion
+    /// off. With alignment off too, flockmates aren't even looked for.
     pub centering_factor: f32,
+    /// How hard boids turn back from within `margin` of the edge, 0 to turn
+    /// edge turning off and leave them to bump along it
     pub turn_factor: f32,
     pub cell_size: f32,
     pub draw_radius: i32,
+    pub update_threshold: f32,
+    pub global_centering_factor: f32,
+    pub render_smoothing: f32,
+    /// Stop gathering neighbours for alignment and cohesion once a boid has
+    /// this many, trading accuracy for speed in dense flocks. Boids inside
+    /// the protected range are always all avoided.
+    pub max_neighbors_for_early_exit: Option<usize>,
+    /// Stretch grid cells along the world's longer side so there are as many
+    /// across as down, rather than using `cell_size` squares
+    pub aspect_cells: bool,
+    /// The shape boids turn back from within `margin` of its edge
+    pub boundary: BoundaryMode,
+    /// Degrees the hue of each boid turns through every frame with
+    /// `ColourMode::Rotating`, 0 to keep them still
+    pub colour_rotation_speed: f32,
+    /// Turn each boid's hue at its own speed, between half and one and a half
+    /// times `colour_rotation_speed`, picked from its id
+    pub per_boid_colour_rotation: bool,
+    /// Align and cohere with the boids whose Voronoi cells touch each boid's
+    /// own, however far away, rather than everything in `visible_range`.
+    /// Boids inside the protected range are still avoided.
+    pub voronoi_neighbors: bool,
+    /// Equal width bins over `[-pi, pi)` the headings of the flock are
+    /// counted into each frame, 0 to not count them
+    pub heading_histogram_bins: usize,
+    /// Frames every boid is given a random nudge on, from a seed of its own
+    pub seed_schedule: RandomSeedSchedule,
+    /// Outline each flock found by clustering the boids every frame
+    pub draw_flock_hulls: bool,
+    /// Regions with speed limits of their own, the first a boid is heading
+    /// into wins where they overlap
+    pub speed_zones: Vec<SpeedLimitZone>,
+    /// Frames of the flock `SimulationState` keeps to rewind to, 0 to keep
+    /// none. Each one is a full copy of the flock.
+    pub rewind_depth: usize,
+    /// Share of the flock given a random kick every frame, 0.01 for 1% of
+    /// boids, 0 for none
+    pub perturbation_fraction: f32,
+    /// Pixels per frame each kick adds to a boid's velocity, before speed
+    /// limits
+    pub perturbation_strength: f32,
+    /// Frames of heading each boid keeps for `ColourMode::TrajectoryEntropy`
+    pub trail_length: usize,
+    /// What's done with a boid its steering leaves stopped dead
+    pub stall_policy: StallPolicy,
+}
+
+impl Default for Parameters {
+    /// Loose flocks that form and break up across a 1920x1080 world
+    fn default() -> Self {
+        Parameters {
+            max_speed: 3.0,
+            min_speed: 0.5,
+            margin: 10,
+            visible_range: 20.0,
+            protected_range: 2.0,
+            avoid_factor: 0.10,
+            matching_factor: 0.05,
+            centering_factor: 0.0005,
+            turn_factor: 0.2,
+            cell_size: 22.0,
+            draw_radius: 2,
+            update_threshold: 0.0,
+            global_centering_factor: 0.0,
+            render_smoothing: 0.0,
+            max_neighbors_for_early_exit: None,
+            aspect_cells: false,
+            boundary: BoundaryMode::Rectangle,
+            colour_rotation_speed: 0.0,
+            per_boid_colour_rotation: false,
+            voronoi_neighbors: false,
+            heading_histogram_bins: 0,
+            seed_schedule: RandomSeedSchedule::default(),
+            draw_flock_hulls: false,
+            speed_zones: Vec::new(),
+            rewind_depth: 0,
+            perturbation_fraction: 0.0,
+            perturbation_strength: 0.0,
+            trail_length: 10,
+            stall_policy: StallPolicy::RandomNudge,
+        }
+    }
+}
+
+/// Names `Parameters::preset` knows
+pub const PRESETS: [&str; 8] = [
+    "default",
+    "dense",
+    "sparse",
+    "lazy-drift",
+    "murmuration",
+    "swarm",
+    "schooling",
+    "chaos",
+];
+
+impl Parameters {
+    /// Short sighted boids packed into tight, fast turning schools
+    pub fn dense() -> Self {
+        Parameters {
+            max_speed: 2.5,
+            min_speed: 1.0,
+            visible_range: 12.0,
+            protected_range: 4.0,
+            avoid_factor: 0.15,
+            matching_factor: 0.08,
+            centering_factor: 0.002,
+            cell_size: 13.2,
+            ..Parameters::default()
+        }
+    }
+
+    /// Far sighted, quick boids spread out in a few wide, loose flocks
+    pub fn sparse() -> Self {
+        Parameters {
+            max_speed: 4.0,
+            visible_range: 40.0,
+            protected_range: 6.0,
+            avoid_factor: 0.05,
+            matching_factor: 0.03,
+            centering_factor: 0.0002,
+            cell_size: 44.0,
+            ..Parameters::default()
+        }
+    }
+
+    /// Slow boids that barely steer, drifting in gently swirling sheets
+    pub fn lazy_drift() -> Self {
+        Parameters {
+            max_speed: 1.0,
+            min_speed: 0.2,
+            visible_range: 25.0,
+            avoid_factor: 0.05,
+            matching_factor: 0.02,
+            centering_factor: 0.0001,
+            turn_factor: 0.05,
+            cell_size: 27.5,
+            ..Parameters::default()
+        }
+    }
+
+    /// Fast boids that match their flockmates closely, sweeping round in
+    /// one tight, rippling cloud
+    pub fn murmuration() -> Self {
+        Parameters {
+            max_speed: 5.0,
+            min_speed: 2.5,
+            visible_range: 25.0,
+            protected_range: 3.0,
+            avoid_factor: 0.08,
+            matching_factor: 0.15,
+            centering_factor: 0.001,
+            turn_factor: 0.3,
+            cell_size: 27.5,
+            ..Parameters::default()
+        }
+    }
+
+    /// Boids that barely align but crowd in on each other, milling about
+    /// in dense balls
+    pub fn swarm() -> Self {
+        Parameters {
+            min_speed: 1.0,
+            visible_range: 30.0,
+            protected_range: 4.0,
+            avoid_factor: 0.12,
+            matching_factor: 0.005,
+            centering_factor: 0.01,
+            cell_size: 33.0,
+            ..Parameters::default()
+        }
+    }
+
+    /// Boids kept near one speed and strongly aligned, settling into long
+    /// parallel lanes
+    pub fn schooling() -> Self {
+        Parameters {
+            min_speed: 2.5,
+            protected_range: 3.0,
+            avoid_factor: 0.05,
+            matching_factor: 0.2,
+            centering_factor: 0.0002,
+            ..Parameters::default()
+        }
+    }
+
+    /// Weakly steered boids kicked about at random, never settling
+    pub fn chaos() -> Self {
+        Parameters {
+            max_speed: 4.0,
+            avoid_factor: 0.2,
+            matching_factor: 0.01,
+            perturbation_fraction: 0.3,
+            perturbation_strength: 1.5,
+            ..Parameters::default()
+        }
+    }
+
+    /// A line saying what one of the `PRESETS` looks like, for listing them
+    pub fn preset_description(name: &str) -> Option<&'static str> {
+        match name {
+            "default" => Some("a balanced flock, for a starting point"),
+            "dense" => Some("short sighted boids in tight, fast turning schools"),
+            "sparse" => Some("far sighted, quick boids in a few wide, loose flocks"),
+            "lazy-drift" => Some("slow boids drifting in gently swirling sheets"),
+            "murmuration" => Some("fast, closely matched boids in one tight, rippling cloud"),
+            "swarm" => Some("weakly aligned boids milling about in dense balls"),
+            "schooling" => Some("strongly aligned boids settling into parallel lanes"),
+            "chaos" => Some("weakly steered boids kicked about at random"),
+            _ => None,
+        }
+    }
+
+    /// One of the `PRESETS` by name
+    pub fn preset(name: &str) -> Result<Self, String> {
+        match name {
+            "default" => Ok(Parameters::default()),
+            "dense" => Ok(Parameters::dense()),
+            "sparse" => Ok(Parameters::sparse()),
+            "lazy-drift" => Ok(Parameters::lazy_drift()),
+            "murmuration" => Ok(Parameters::murmuration()),
+            "swarm" => Ok(Parameters::swarm()),
+            "schooling" => Ok(Parameters::schooling()),
+            "chaos" => Ok(Parameters::chaos()),
+            _ => Err(format!(
+                "Unknown preset {name}, expected one of {}",
+                PRESETS.join(", ")
+            )),
+        }
+    }
+
+    /// The cell size for a grid where the 3x3 neighbourhood around a boid
+    /// just covers everything it can see, `visible_range * factor`. Factors
+    /// below 1 would make the search miss visible boids, so are rejected.
+    pub fn auto_cell_size(&self, factor: f32) -> Result<f32, String> {
+        let cell_size = self.visible_range * factor;
+        if cell_size.is_nan() || cell_size < self.visible_range {
+            return Err(format!(
+                "a cell size of {cell_size} is smaller than the visible range of {}, the auto cell factor must be at least 1",
+                self.visible_range
+            ));
+        }
+        Ok(cell_size)
+    }
+
+    /// Width and height of the spatial grid's cells in a `width` x `height`
+    /// world. With `aspect_cells` the shorter side is still `cell_size`, so
+    /// the neighbourhood search covers at least as much as a square cell.
+    pub fn cell_dimensions(&self, width: u32, height: u32) -> (f32, f32) {
+        if !self.aspect_cells || width == 0 || height == 0 {
+            return (self.cell_size, self.cell_size);
+        }
+        let aspect = width as f32 / height as f32;
+        if aspect >= 1.0 {
+            (self.cell_size * aspect, self.cell_size)
+        } else {
+            (self.cell_size, self.cell_size / aspect)
+        }
+    }
+}
+
+/// The numeric parameters that differ between two sets, found by `diff`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParameterDiff {
+    /// `(field, value in self, value in other)` for every field that differs
+    pub changed_fields: Vec<(String, f32, f32)>,
+}
+
+impl ParameterDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changed_fields.is_empty()
+    }
+
+    /// One `field: ours -> theirs` line per change
+    pub fn summary(&self) -> String {
+        if self.is_empty() {
+            return String::from("No parameters differ");
+        }
+        self.changed_fields
+            .iter()
+            .map(|(field, ours, theirs)| format!("{field}: {ours} -> {theirs}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl fmt::Display for ParameterDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.summary())
+    }
+}
+
+// Lists every field as an f32 for `diff`. The destructuring won't compile
+// unless every field of `Parameters` is named, so new ones can't be missed.
+// Fields that aren't numbers at all are named last and left out.
+macro_rules! numeric_fields {
+    ($parameters:expr, $($field:ident),* ; $($optional:ident),* ; $($flag:ident),* ; $($other:ident),*) => {{
+        let Parameters { $($field,)* $($optional,)* $($flag,)* $($other: _,)* } = $parameters;
+        [
+            $((stringify!($field), $field as f32),)*
+            $((stringify!($flag), u8::from($flag) as f32),)*
+            // No limit at all is as good as an infinite one
+            $((stringify!($optional), $optional.map_or(f32::INFINITY, |value| value as f32)),)*
+        ]
+    }};
+}
+
+impl Parameters {
+    /// Every field with a value more than `f32::EPSILON` away from the one
+    /// in `other`. Whole number fields are compared as `f32` too, switches
+    /// as 0 or 1, and a missing `max_neighbors_for_early_exit` as infinity.
+    /// The boundary, seed schedule, speed zones and stall policy aren't
+    /// single numbers, so aren't compared.
+    pub fn diff(&self, other: &Parameters) -> ParameterDiff {
+        fn fields(parameters: &Parameters) -> [(&'static str, f32); 25] {
+            numeric_fields!(
+                *parameters,
+                max_speed,
+                min_speed,
+                margin,
+                visible_range,
+                protected_range,
+                avoid_factor,
+                matching_factor,
+                centering_factor,
+                turn_factor,
+                cell_size,
+                draw_radius,
+                update_threshold,
+                global_centering_factor,
+                render_smoothing,
+                colour_rotation_speed,
+                heading_histogram_bins,
+                rewind_depth,
+                perturbation_fraction,
+                perturbation_strength,
+                trail_length;
+                max_neighbors_for_early_exit;
+                aspect_cells,
+                per_boid_colour_rotation,
+                voronoi_neighbors,
+                draw_flock_hulls;
+                boundary,
+                seed_schedule,
+                speed_zones,
+                stall_policy
+            )
+        }
+        let changed_fields = fields(self)
+            .into_iter()
+            .zip(fields(other))
+            .filter(|((_, ours), (_, theirs))| {
+                ours != theirs && (ours - theirs).abs() > f32::EPSILON
+            })
+            .map(|((field, ours), (_, theirs))| (field.to_string(), ours, theirs))
+            .collect();
+        ParameterDiff { changed_fields }
+    }
 }
@@ -1,58 +1,995 @@
 use std::fs;
-use std::path::Path;
+use std::io::{self, IsTerminal, Read, Write};
+use std::iter;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use argh::FromArgs;
-use colors_transform::{Color, Hsl};
-use image::{Rgb, RgbImage};
-use indicatif::{ProgressBar, ProgressStyle};
+use image::ImageFormat;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif_log_bridge::LogWrapper;
+use log::{debug, error, info, warn, Level, LevelFilter};
 use nalgebra::Vector2;
 use rand::prelude::*;
 
-use boids::boids::{update_boids, Boid};
-use boids::Parameters;
+use boids::boids::{angular_momentum, mean_speed, polarization, StallPolicy};
+use boids::boundary::BoundaryMode;
+use boids::cluster::{self, PositionAverager};
+use boids::colour::{colour_by_width, ColourMode};
+#[cfg(feature = "parquet")]
+use boids::columnar::{self, ParquetWriter};
+use boids::correlation::{CorrelationCsvWriter, CorrelationFunction};
+use boids::field::{compute_velocity_field, FieldLines};
+use boids::hash::FrameHashes;
+use boids::heading::{HeadingHistogram, HeadingHistogramCsvWriter};
+use boids::init::{BoidSpawnDistribution, BoidSpawnVelocity};
+use boids::manifest::Manifest;
+#[cfg(feature = "metrics")]
+use boids::metrics::{self, Metrics};
+use boids::overlay::{
+    draw_speed_zones, FlockConvexHull, NeighborCountMap, HULL_ALPHA, SPEED_ZONE_ALPHA,
+};
+use boids::pacing::{FramePacer, Pace};
+use boids::parameters::{ParametersBuilder, ValidationError};
+use boids::quality::QualityWeights;
+use boids::render::Renderer;
+use boids::replay::{CsvTrajectoryWriter, ReplayReader};
+use boids::schedule::{RandomSeedSchedule, SeedEvent};
+use boids::smoothing::TemporalSmoothing;
+use boids::state::{self, Encoding, Format, Loaded, Metadata, SaveFile};
+use boids::stop::{StopCondition, StopWhen};
+use boids::summary::{summarize, RunRecorder, Stage};
+use boids::sweep::{self, Combination, SweepAxis, SweepCsvWriter, SweepResult};
+use boids::sys;
+#[cfg(feature = "trace")]
+use boids::trace;
+use boids::trajectory::{self, Interpolation, TrajectoryReader, TrajectoryWriter};
+use boids::transform::{self, Transform};
+use boids::zone::SpeedLimitZone;
+use boids::{
+    in_span, Boid, Error, FrameStats, Parameters, Simulation, SimulationConfig, World, PRESETS,
+};
+
+// argh can't flatten one struct of flags into another, so the groups of
+// flags several subcommands share are spliced into each by this, along with
+// a method reading each group back out as one value
+macro_rules! shared_flags {
+    (
+        $(#[$meta:meta])*
+        struct $name:ident uses [$($group:ident),*] { $($field:tt)* }
+    ) => {
+        shared_flags!(@group [$(#[$meta])*] $name [$($group)*] [] [$($field)*]);
+    };
+    (@group $meta:tt $name:ident [world $($group:ident)*] [$($shared:tt)*] $fields:tt) => {
+        shared_flags!(@group $meta $name [$($group)*] [$($shared)*
+            #[argh(
+                option,
+                description = "width of image, defaults 1920",
+                default = "1920"
+            )]
+            width: u32,
+            #[argh(
+                option,
+                description = "height of image, defaults 1080",
+                default = "1080"
+            )]
+            height: u32,
+        ] $fields);
+        impl $name {
+            fn world_flags(&self) -> WorldFlags {
+                WorldFlags {
+                    width: self.width,
+                    height: self.height,
+                }
+            }
+        }
+    };
+    (@group $meta:tt $name:ident [flock $($group:ident)*] [$($shared:tt)*] $fields:tt) => {
+        shared_flags!(@group $meta $name [$($group)*] [$($shared)*
+            #[argh(option, description = "boids to simulate", default = "10000")]
+            boids: usize,
+            #[argh(option, description = "seed for random choices, defaults to random")]
+            seed: Option<u64>,
+        ] $fields);
+        impl $name {
+            fn flock_flags(&self) -> FlockFlags {
+                FlockFlags {
+                    boids: self.boids,
+                    seed: self.seed,
+                }
+            }
+        }
+    };
+    (@group $meta:tt $name:ident [output $($group:ident)*] [$($shared:tt)*] $fields:tt) => {
+        shared_flags!(@group $meta $name [$($group)*] [$($shared)*
+            #[argh(
+                option,
+                description = "directory for images",
+                from_str_fn(valid_directory)
+            )]
+            dir: Option<String>,
+        ] $fields);
+        impl $name {
+            fn output_flags(&self) -> OutputFlags {
+                OutputFlags {
+                    dir: self.dir.clone(),
+                }
+            }
+        }
+    };
+    (@group [$($meta:tt)*] $name:ident [] [$($shared:tt)*] [$($field:tt)*]) => {
+        $($meta)*
+        struct $name {
+            $($shared)*
+            $($field)*
+        }
+    };
+}
+
+/// The size of the world to simulate
+#[derive(Debug, Clone, Copy)]
+struct WorldFlags {
+    width: u32,
+    height: u32,
+}
+
+impl WorldFlags {
+    fn world(self) -> World {
+        World::from_pixels(self.width, self.height)
+    }
+}
+
+/// How many boids to spawn, and what to seed them from
+#[derive(Debug, Clone, Copy)]
+struct FlockFlags {
+    boids: usize,
+    seed: Option<u64>,
+}
+
+impl FlockFlags {
+    /// Always a seed, so it can be saved and the run repeated
+    fn seed(self) -> u64 {
+        self.seed.unwrap_or_else(|| rand::rng().random())
+    }
+}
+
+/// Where images are written
+#[derive(Debug, Clone)]
+struct OutputFlags {
+    dir: Option<String>,
+}
+
+impl OutputFlags {
+    fn dir(self) -> String {
+        self.dir.unwrap_or_else(|| {
+            error!("Required options not provided:\n    --dir");
+            process::exit(1);
+        })
+    }
+}
 
 #[derive(Debug, FromArgs)]
-#[argh(help_triggers("-h", "--help", "help"), description = "Boids simulator")]
+#[argh(
+    help_triggers("-h", "--help", "help"),
+    description = "Boids simulator, running a flock when no subcommand is given"
+)]
 struct Flags {
+    #[argh(switch, short = 'q', description = "only print warnings and errors")]
+    quiet: bool,
     #[argh(
-        option,
-        description = "width of image, defaults 1920",
-        default = "1920"
+        switch,
+        short = 'v',
+        description = "print more detail, -vv for everything"
     )]
-    width: u32,
+    verbose: u8,
+    #[argh(subcommand)]
+    command: Command,
+}
+
+shared_flags! {
+    #[derive(Debug, FromArgs)]
     #[argh(
-        option,
-        description = "height of image, defaults 1080",
-        default = "1080"
+        subcommand,
+        name = "run",
+        description = "simulate a flock and draw its frames, what's run when no subcommand is given"
     )]
-    height: u32,
+    struct RunArgs uses [world, flock, output] {
+        #[argh(
+            option,
+            description = "frames to simulate, each drawn once it's stepped to, so exactly this many images numbered from 1",
+            default = "1000"
+        )]
+        frames: usize,
+        #[argh(
+            switch,
+            description = "also draw the starting state, before any step, as frame 0, for one more image than --frames"
+        )]
+        include_initial_frame: bool,
+        #[argh(
+            option,
+            description = "first frame to render, earlier frames are simulated without drawing, starting from a checkpoint in --dir when there is one"
+        )]
+        frame_start: Option<usize>,
+        #[argh(
+            option,
+            description = "stop before rendering this frame, at most one past --frames"
+        )]
+        frame_end: Option<usize>,
+        #[argh(
+            option,
+            description = "uniform, gaussian:X,Y,STD, ring:X,Y,RADIUS,SPREAD, grid:ROWS,COLS,JITTER or cluster:PER_CENTER,SPREAD,X,Y[,X,Y...], defaults uniform",
+            default = "BoidSpawnDistribution::Uniform"
+        )]
+        spawn_distribution: BoidSpawnDistribution,
+        #[argh(
+            option,
+            description = "random, inward:SPEED, outward:SPEED, clockwise:SPEED or anticlockwise:SPEED, relative to the spawn distribution's centre, defaults random",
+            default = "BoidSpawnVelocity::Random"
+        )]
+        spawn_velocity: BoidSpawnVelocity,
+        #[argh(option, description = "file to save starting boids to, - for stdout")]
+        save_file: Option<String>,
+        #[argh(
+            option,
+            description = "json, bin, ron or rkyv, optionally ending .zst, for states on stdin or stdout, defaults json",
+            default = "Encoding { format: Format::Json, compressed: false }"
+        )]
+        stdio_format: Encoding,
+        #[argh(
+            option,
+            description = "file to load starting boids from",
+            from_str_fn(valid_file)
+        )]
+        load_file: Option<String>,
+        #[argh(
+            option,
+            description = "initial-x, id-hash, random, speed, heading, rotating, velocity-divergence, kde or trajectory-entropy, defaults initial-x",
+            default = "ColourMode::InitialX"
+        )]
+        colour_mode: ColourMode,
+        #[argh(
+            switch,
+            description = "recolour loaded boids with --colour-mode, speed, heading, rotating, velocity-divergence, kde and trajectory-entropy always are"
+        )]
+        recolor: bool,
+        #[argh(
+            option,
+            description = "degrees each boid's hue turns per frame with --colour-mode rotating, defaults 0"
+        )]
+        colour_rotation_speed: Option<f32>,
+        #[argh(
+            switch,
+            description = "turn each boid's hue at its own speed around --colour-rotation-speed"
+        )]
+        per_boid_colour_rotation: bool,
+        #[argh(option, description = "note to keep with the --save-file state")]
+        note: Option<String>,
+        #[argh(
+            switch,
+            description = "rescale a loaded state saved at a different size to --width and --height"
+        )]
+        load_rescale: bool,
+        #[argh(
+            switch,
+            description = "refuse to load a state with fields this version doesn't know"
+        )]
+        strict_load: bool,
+        #[argh(
+            option,
+            description = "scale velocities too with --load-rescale, defaults true",
+            default = "true"
+        )]
+        rescale_velocities: bool,
+        #[argh(
+            option,
+            description = "fliph, flipv, rot90, rot180 or rotate:DEGREES applied to a loaded state, can be repeated"
+        )]
+        load_transform: Vec<Transform>,
+        #[argh(
+            switch,
+            description = "clamp boids --load-transform moves out of the world instead of failing"
+        )]
+        load_transform_clamp: bool,
+        #[argh(option, description = "keep a random sample of this many loaded boids")]
+        load_sample: Option<usize>,
+        #[argh(option, description = "keep a random fraction of the loaded boids")]
+        load_sample_fraction: Option<f32>,
+        #[argh(
+            option,
+            description = "keep loaded boids inside the X,Y,WIDTH,HEIGHT rectangle",
+            from_str_fn(parse_region)
+        )]
+        load_region: Option<[f32; 4]>,
+        #[argh(
+            option,
+            description = "flocking to start from, one of --list-presets, defaults default",
+            from_str_fn(Parameters::preset)
+        )]
+        preset: Option<Parameters>,
+        #[argh(
+            switch,
+            description = "list the presets and what each looks like, then exit"
+        )]
+        list_presets: bool,
+        #[argh(
+            option,
+            description = "TOML parameters to start from, over --preset and under any flags given",
+            from_str_fn(valid_file)
+        )]
+        params: Option<String>,
+        #[argh(
+            option,
+            description = "fastest a boid flies, in pixels per frame, defaults 3"
+        )]
+        max_speed: Option<f32>,
+        #[argh(
+            option,
+            description = "slowest a boid flies, in pixels per frame, defaults 0.5"
+        )]
+        min_speed: Option<f32>,
+        #[argh(
+            option,
+            description = "pixels from the edge boids start turning back at, defaults 10"
+        )]
+        margin: Option<u32>,
+        #[argh(option, description = "how far boids see flockmates, defaults 20")]
+        visible_range: Option<f32>,
+        #[argh(
+            option,
+            description = "how close boids get before steering apart, defaults 2"
+        )]
+        protected_range: Option<f32>,
+        #[argh(
+            option,
+            description = "how hard boids steer apart inside the protected range, defaults 0.1"
+        )]
+        avoid_factor: Option<f32>,
+        #[argh(
+            option,
+            description = "how hard boids match their flockmates' velocity, defaults 0.05"
+        )]
+        matching_factor: Option<f32>,
+        #[argh(
+            option,
+            description = "how hard boids steer towards their flockmates, defaults 0.0005"
+        )]
+        centering_factor: Option<f32>,
+        #[argh(
+            option,
+            description = "how hard boids turn back from the edge, defaults 0.2"
+        )]
+        turn_factor: Option<f32>,
+        #[argh(
+            option,
+            description = "radius boids are drawn with, in pixels, defaults 2"
+        )]
+        draw_radius: Option<i32>,
+        #[argh(
+            option,
+            description = "only re-steer boids that moved this many pixels, defaults 0 (always)"
+        )]
+        update_threshold: Option<f32>,
+        #[argh(
+            option,
+            description = "pull towards the whole flock's centre of mass, defaults 0 (off)"
+        )]
+        global_centering_factor: Option<f32>,
+        #[argh(
+            option,
+            description = "share of the flock given a random kick every frame, e.g. 0.01 for 1%, defaults 0 (off)"
+        )]
+        perturbation_fraction: Option<f32>,
+        #[argh(
+            option,
+            description = "pixels per frame each random kick adds to a boid's velocity, defaults 0"
+        )]
+        perturbation_strength: Option<f32>,
+        #[argh(
+            option,
+            description = "frames of heading trajectory-entropy colours boids by, defaults 10"
+        )]
+        trail_length: Option<usize>,
+        #[argh(
+            option,
+            description = "what's done with a boid stopped dead: random-nudge (the default), keep-heading, or error to stop the run"
+        )]
+        stall_policy: Option<StallPolicy>,
+        #[argh(
+            option,
+            description = "how quickly drawn positions catch up with the boids, 0 (off) to 1"
+        )]
+        render_smoothing: Option<f32>,
+        #[argh(
+            option,
+            description = "stop looking for flockmates to align with once this many are found"
+        )]
+        max_neighbors_for_early_exit: Option<usize>,
+        #[argh(option, description = "spatial grid cell size, defaults 22")]
+        cell_size: Option<f32>,
+        #[argh(
+            switch,
+            description = "size grid cells from the visible range, unless --cell-size is given"
+        )]
+        auto_cell_size: bool,
+        #[argh(
+            option,
+            description = "multiple of the visible range used by --auto-cell-size, defaults 1.1",
+            default = "1.1"
+        )]
+        auto_cell_factor: f32,
+        #[argh(
+            switch,
+            description = "stretch grid cells to the world's aspect ratio, keeping the shorter side the cell size"
+        )]
+        aspect_cells: bool,
+        #[argh(
+            switch,
+            description = "align and cohere with Voronoi neighbours instead of everything in the visible range"
+        )]
+        voronoi_neighbors: bool,
+        #[argh(switch, description = "turn off steering towards flockmates")]
+        no_cohesion: bool,
+        #[argh(switch, description = "turn off matching flockmates' velocity")]
+        no_alignment: bool,
+        #[argh(
+            switch,
+            description = "turn off steering apart inside the protected range"
+        )]
+        no_separation: bool,
+        #[argh(
+            switch,
+            description = "turn off turning back from the edge of the world"
+        )]
+        no_edge_turning: bool,
+        #[argh(
+            switch,
+            description = "outline each flock, found every frame with --cluster-eps and --cluster-min-points"
+        )]
+        draw_flock_hulls: bool,
+        #[argh(
+            option,
+            description = "frame:seed to nudge every boid at, from its own RNG seeded from the seed and its place in the flock, repeatable"
+        )]
+        seed_event: Vec<SeedEvent>,
+        #[argh(
+            option,
+            description = "x0,y0,x1,y1,max_speed[,min_speed] of a region boids are held to its own speed limits in, shaded teal if slower and red if faster, repeatable"
+        )]
+        speed_zone: Vec<SpeedLimitZone>,
+        #[argh(
+            option,
+            description = "greyscale PNG the size of the world whose bright parts boids stay inside, 128 on the edge",
+            from_str_fn(valid_file)
+        )]
+        boundary_sdf: Option<String>,
+        #[argh(
+            option,
+            description = "TOML parameters to print the differences from at startup",
+            from_str_fn(valid_file)
+        )]
+        params_compare: Option<String>,
+        #[argh(
+            switch,
+            description = "print spatial grid occupancy, to help with tuning the cell size"
+        )]
+        print_grid_stats: bool,
+        #[argh(
+            option,
+            description = "frames between grid stats, defaults 100",
+            default = "100"
+        )]
+        grid_stats_interval: usize,
+        #[argh(
+            option,
+            description = "draw streamlines of the flock's velocity, seeded this many across the width"
+        )]
+        field_lines: Option<f32>,
+        #[argh(
+            option,
+            description = "paint grid cells by how many flockmates their boids have, blended in at this alpha from 0 to 1"
+        )]
+        neighbor_count_overlay: Option<f32>,
+        #[argh(option, description = "CSV file to record every frame's boids to")]
+        trajectory_csv: Option<String>,
+        #[argh(
+            option,
+            description = "directory to write every frame's boids to as Parquet, needs the parquet feature",
+            from_str_fn(valid_directory)
+        )]
+        parquet_output: Option<String>,
+        #[argh(
+            option,
+            description = "single file to write every frame's boids to as Parquet, needs the parquet feature"
+        )]
+        parquet_all_frames: Option<String>,
+        #[argh(
+            option,
+            description = "MB of memory to stop recording trajectories over, and to stop the run at 1.5 times"
+        )]
+        max_memory: Option<u64>,
+        #[argh(
+            option,
+            description = "end the run early once e.g. \"polarization>0.98 for 200\" holds, from polarization, angular-momentum, mean-speed, population or quality-score, can be repeated"
+        )]
+        stop_when: Vec<StopCondition>,
+        #[argh(
+            option,
+            default = "QualityWeights::default()",
+            description = "weights of polarization, connectedness and smoothness in the quality score shown as Q, then optionally the mean jerk that counts as not smooth at all (default 0.4,0.3,0.3,1)"
+        )]
+        quality_weights: QualityWeights,
+        #[argh(
+            switch,
+            description = "split boids between threads by how long each part took to steer last frame, shown as the imbalance"
+        )]
+        balance_load: bool,
+        #[argh(
+            option,
+            description = "address such as 0.0.0.0:9100 to serve Prometheus metrics on, needs the metrics feature"
+        )]
+        metrics_addr: Option<String>,
+        #[argh(
+            option,
+            description = "file to write a Chrome trace of where each frame's time goes to, needs the trace feature"
+        )]
+        trace_out: Option<String>,
+        #[argh(
+            option,
+            description = "JSON file to write clusters of boids that stayed together to, from their mean positions"
+        )]
+        cluster_output: Option<String>,
+        #[argh(
+            option,
+            description = "how close boids have to be to cluster, defaults to the visible range"
+        )]
+        cluster_eps: Option<f32>,
+        #[argh(
+            option,
+            description = "boids needed within --cluster-eps to start a cluster, defaults 5",
+            default = "5"
+        )]
+        cluster_min_points: usize,
+        #[argh(option, description = "JSON file to write the end of run summary to")]
+        summary_file: Option<String>,
+        #[argh(
+            option,
+            description = "JSON file to list every frame written in, with the parameters and its stats"
+        )]
+        output_manifest: Option<String>,
+        #[argh(
+            switch,
+            description = "rewrite --output-manifest after every frame, not just at the end"
+        )]
+        live_manifest: bool,
+        #[argh(
+            switch,
+            description = "hash every frame's pixels into frame_hashes.txt in --dir"
+        )]
+        hash_frames: bool,
+        #[argh(
+            option,
+            description = "run no faster than this many frames a second, rather than as fast as it can"
+        )]
+        target_fps: Option<u32>,
+        #[argh(
+            switch,
+            description = "skip drawing frames, but not simulating them, while behind --target-fps"
+        )]
+        allow_frame_drop: bool,
+        #[argh(
+            option,
+            description = "CSV file to write the velocity correlation against distance to"
+        )]
+        correlation_function_csv: Option<String>,
+        #[argh(
+            option,
+            description = "frames between correlation functions, defaults 100",
+            default = "100"
+        )]
+        correlation_interval: usize,
+        #[argh(
+            option,
+            description = "furthest apart boids are correlated, defaults 10 times the visible range"
+        )]
+        correlation_r_max: Option<f32>,
+        #[argh(
+            option,
+            description = "distance bins in the correlation function, defaults 50",
+            default = "50"
+        )]
+        correlation_bins: usize,
+        #[argh(
+            option,
+            description = "CSV file to write each frame's count of boids heading each way to"
+        )]
+        heading_histogram_csv: Option<String>,
+        #[argh(
+            option,
+            description = "bins around the circle headings are counted in, needed for --heading-histogram-csv"
+        )]
+        heading_histogram_bins: Option<usize>,
+        #[argh(option, description = "file to record every frame's boids to")]
+        trajectory_out: Option<String>,
+        #[argh(
+            option,
+            description = "subdivisions of a pixel positions are recorded to, defaults 16",
+            default = "16"
+        )]
+        trajectory_precision: u32,
+        #[argh(
+            option,
+            description = "frames between full keyframes in the trajectory, defaults 100",
+            default = "100"
+        )]
+        trajectory_keyframe_interval: u32,
+    }
+}
+
+// Parsed once, and argh can't take the run flags boxed
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Run(RunArgs),
+    Bench(BenchArgs),
+    Sweep(SweepArgs),
+    Convert(ConvertArgs),
+    Merge(MergeArgs),
+    Replay(ReplayArgs),
+    Info(InfoArgs),
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "info",
+    description = "print what a state file holds and how it was made"
+)]
+struct InfoArgs {
+    #[argh(positional, from_str_fn(valid_file))]
+    input: String,
+}
+
+shared_flags! {
+    #[derive(Debug, FromArgs)]
+    #[argh(
+        subcommand,
+        name = "sweep",
+        description = "run every combination of some parameters' values, each into its own directory"
+    )]
+    struct SweepArgs uses [world, flock, output] {
+        #[argh(
+            option,
+            description = "FIELD=VALUE[,VALUE...] or FIELD=START..END:COUNT values of a parameter to run, can be repeated"
+        )]
+        vary: Vec<SweepAxis>,
+        #[argh(option, description = "frames to run each for, defaults 1000", default = "1000")]
+        frames: usize,
+        #[argh(
+            option,
+            description = "flocking to sweep from, one of run --list-presets, defaults default",
+            from_str_fn(Parameters::preset)
+        )]
+        preset: Option<Parameters>,
+    }
+}
+
+shared_flags! {
+    #[derive(Debug, FromArgs)]
+    #[argh(
+        subcommand,
+        name = "bench",
+        description = "time a flock's steps without drawing them"
+    )]
+    struct BenchArgs uses [world, flock] {
+        #[argh(option, description = "frames to time, defaults 100", default = "100")]
+        frames: usize,
+        #[argh(
+            option,
+            description = "flocking to time, one of run --list-presets, defaults default",
+            from_str_fn(Parameters::preset)
+        )]
+        preset: Option<Parameters>,
+    }
+}
+
+#[derive(Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "convert",
+    description = "convert a state file between json, bincode and ron, optionally zstd compressed"
+)]
+struct ConvertArgs {
+    #[argh(positional, from_str_fn(valid_file))]
+    input: String,
+    #[argh(positional)]
+    output: String,
+    #[argh(
+        switch,
+        description = "recompute colours from the position in the world"
+    )]
+    recolor: bool,
+    #[argh(switch, description = "renumber boid ids from 0")]
+    reseed_ids: bool,
     #[argh(
         option,
-        description = "directory for images",
-        from_str_fn(valid_directory)
+        description = "WIDTHxHEIGHT of the world, for old saves that didn't record it",
+        from_str_fn(parse_size)
     )]
-    dir: String,
-    #[argh(option, description = "frames to simulate", default = "1000")]
-    frames: usize,
-    #[argh(option, description = "boids to simulate", default = "10000")]
-    boids: usize,
-    #[argh(option, description = "file to save starting boids to")]
-    save_file: Option<String>,
+    world: Option<(u32, u32)>,
     #[argh(
         option,
-        description = "file to load starting boids from",
-        from_str_fn(valid_file)
+        description = "rescale positions to a WIDTHxHEIGHT world",
+        from_str_fn(parse_size)
     )]
-    load_file: Option<String>,
+    scale_to: Option<(u32, u32)>,
 }
 
 fn valid_file(file: &str) -> Result<String, String> {
-    if Path::new(file).is_file() {
+    if file == STDIO || Path::new(file).is_file() {
         return Ok(String::from(file));
     }
     Err(String::from("Source file for boids valid"))
 }
 
+/// Given instead of a path to read a state from stdin or write it to stdout
+const STDIO: &str = "-";
+
+fn load_state(source: &str, encoding: Encoding) -> boids::Result<Loaded> {
+    if source != STDIO {
+        return state::load_tolerant(Path::new(source));
+    }
+    let stdin = Path::new("stdin");
+    let mut bytes = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut bytes)
+        .map_err(|err| Error::io("read", stdin, err))?;
+    state::from_bytes_tolerant(&bytes, encoding.format)
+        .map_err(|err| Error::state("read", stdin, err))
+}
+
+fn save_state(target: &str, save: &SaveFile, encoding: Encoding) -> boids::Result<()> {
+    if target != STDIO {
+        return state::save(Path::new(target), save);
+    }
+    let stdout_path = Path::new("stdout");
+    let bytes =
+        state::to_bytes(save, encoding).map_err(|err| Error::state("write", stdout_path, err))?;
+    let mut stdout = io::stdout().lock();
+    let binary = encoding.compressed || !matches!(encoding.format, Format::Json | Format::Ron);
+    if binary && stdout.is_terminal() {
+        warn!("writing a binary state to a terminal");
+    }
+    stdout
+        .write_all(&bytes)
+        .and_then(|()| stdout.flush())
+        .map_err(|err| Error::io("write", stdout_path, err))
+}
+
+/// Exit code for a file that couldn't be decoded, EX_DATAERR in sysexits.h
+const DATA_ERROR_EXIT_CODE: i32 = 65;
+/// Exit code for a file that couldn't be read or written, EX_IOERR in
+/// sysexits.h
+const IO_ERROR_EXIT_CODE: i32 = 74;
+
+// Errors from the library already say which file they're about
+fn exit_with(err: Error) -> ! {
+    error!("{err}");
+    process::exit(match err {
+        Error::Io { .. } | Error::Render { .. } => IO_ERROR_EXIT_CODE,
+        Error::Format { .. } => DATA_ERROR_EXIT_CODE,
+        Error::Validation(_) => 1,
+    })
+}
+
+/// Logs every rule the parameters break, each on its own line, and exits
+fn exit_invalid(errors: &[ValidationError]) -> ! {
+    for err in errors {
+        error!("Invalid parameters: {err}");
+    }
+    process::exit(1);
+}
+
+/// Names of the subcommands, and of asking for help, any of which may come
+/// first on the command line
+const SUBCOMMANDS: [&str; 10] = [
+    "run", "bench", "sweep", "convert", "merge", "replay", "info", "help", "-h", "--help",
+];
+
+// argh wants each switch on its own, so -vv is split into -v -v first. The
+// logging switches then go before the subcommand, wherever they were given,
+// and `run` is put in when there's no subcommand, so command lines from
+// before there were subcommands still work.
+fn parse_flags() -> Flags {
+    let mut args = std::env::args();
+    let path = args.next().unwrap_or_default();
+    let command = Path::new(&path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&path);
+    let rest: Vec<String> = args
+        .flat_map(|arg| match arg.strip_prefix('-') {
+            Some(count) if count.len() > 1 && count.chars().all(|c| c == 'v') => {
+                vec![String::from("-v"); count.len()]
+            }
+            _ => vec![arg],
+        })
+        .collect();
+    let (mut logging, rest): (Vec<String>, Vec<String>) = rest
+        .into_iter()
+        .partition(|arg| matches!(arg.as_str(), "-q" | "--quiet" | "-v" | "--verbose"));
+    if !rest
+        .first()
+        .is_some_and(|first| SUBCOMMANDS.contains(&first.as_str()))
+    {
+        logging.push(String::from("run"));
+    }
+    let rest: Vec<&str> = logging.iter().chain(&rest).map(String::as_str).collect();
+    Flags::from_args(&[command], &rest).unwrap_or_else(|early_exit| match early_exit.status {
+        Ok(()) => {
+            println!("{}", early_exit.output);
+            process::exit(0);
+        }
+        Err(()) => {
+            eprintln!(
+                "{}\nRun {command} --help for more information.",
+                early_exit.output
+            );
+            process::exit(1);
+        }
+    })
+}
+
+/// Sends everything logged to stderr, around the progress bars added to
+/// the returned `MultiProgress`. `RUST_LOG` overrides the level from the
+/// flags.
+fn init_logging(quiet: bool, verbose: u8) -> MultiProgress {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let logger = env_logger::Builder::new()
+        .filter_level(level)
+        .parse_env("RUST_LOG")
+        .format(|out, record| match record.level() {
+            Level::Error | Level::Info => writeln!(out, "{}", record.args()),
+            Level::Warn => writeln!(out, "Warning: {}", record.args()),
+            Level::Debug | Level::Trace => writeln!(
+                out,
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+        })
+        .build();
+    let max_level = logger.filter();
+    let multi = MultiProgress::new();
+    if LogWrapper::new(multi.clone(), logger).try_init().is_ok() {
+        log::set_max_level(max_level);
+    }
+    multi
+}
+
+/// Exit code for a run stopped by Ctrl-C, as a shell reports for SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// The first Ctrl-C sets the returned flag, so the frame being drawn can be
+// finished and a checkpoint written. A second one quits straight away.
+fn interrupt_flag() -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    let installed = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::Relaxed) {
+            info!("Interrupted again, quitting without a checkpoint");
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+        info!("Interrupted, finishing the frame and writing a checkpoint, Ctrl-C again to quit");
+    });
+    if let Err(err) = installed {
+        warn!("unable to handle Ctrl-C, interrupting will lose the run: {err}");
+    }
+    interrupted
+}
+
+const FRAME_STAGES: [Stage; 4] = [Stage::Simulate, Stage::Rasterize, Stage::Encode, Stage::Io];
+
+// How often --max-memory is checked, and how far over it a run is stopped
+const MEMORY_CHECK_INTERVAL: usize = 100;
+const HARD_MEMORY_FACTOR: f64 = 1.5;
+/// Exit code for a run stopped for using too much memory
+const OVER_MEMORY_EXIT_CODE: i32 = 2;
+
+/// Frames --allow-frame-drop skips drawing in a row before drawing one
+/// anyway, so a run too slow to simulate at --target-fps still shows
+/// something
+const MAX_DROPPED_IN_A_ROW: usize = 10;
+
+/// Flags set by SIGUSR1 and SIGUSR2, cleared once the frame loop has seen
+/// them. The handlers only store to these, so stay async signal safe.
+/// Neither signal exists off Unix, where the flags are never set.
+struct UserSignals {
+    /// Write the current state out at the next frame boundary
+    dump: Arc<AtomicBool>,
+    /// Turn per frame stats on or off
+    toggle_verbose: Arc<AtomicBool>,
+}
+
+impl UserSignals {
+    fn install() -> Self {
+        let signals = UserSignals {
+            dump: Arc::new(AtomicBool::new(false)),
+            toggle_verbose: Arc::new(AtomicBool::new(false)),
+        };
+        #[cfg(unix)]
+        {
+            use signal_hook::consts::{SIGUSR1, SIGUSR2};
+            for (signal, flag) in [(SIGUSR1, &signals.dump), (SIGUSR2, &signals.toggle_verbose)] {
+                if let Err(err) = signal_hook::flag::register(signal, flag.clone()) {
+                    warn!("unable to handle signal {signal}: {err}");
+                }
+            }
+        }
+        signals
+    }
+}
+
+// The parameters saved with a loaded flock, with any the command line
+// changes from their defaults taken from it instead
+fn restore_parameters(
+    source: &str,
+    saved: &Parameters,
+    parameters: &Parameters,
+    (width, height): (u32, u32),
+) -> Parameters {
+    let restored = parameters.overriding(saved, &Parameters::default());
+    let overridden = saved.diff(&restored);
+    if overridden.is_empty() {
+        info!("Using the parameters saved with {source}");
+    } else {
+        info!("Using the parameters saved with {source}, except:");
+        for line in overridden.summary().lines() {
+            info!("  {line}");
+        }
+    }
+    ParametersBuilder::from(restored)
+        .world(width, height)
+        .build()
+        .unwrap_or_else(|errors| exit_invalid(&errors))
+}
+
+/// Fast forwarding further than this without a checkpoint gets a warning
+const FAST_FORWARD_WARNING: usize = 10_000;
+
+// The latest checkpoint or SIGUSR1 dump in dir from this seed that was
+// written at or before frame, which a sharded run can resume from
+fn find_checkpoint(dir: &str, frame: usize, seed: u64) -> Option<(usize, SaveFile)> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            if name != "checkpoint.json"
+                && !(name.starts_with("state_frame_") && name.ends_with(".json"))
+            {
+                return None;
+            }
+            let save = state::load(&path).ok()?;
+            let saved_frame = save.metadata.frame? as usize;
+            (save.metadata.seed == Some(seed) && saved_frame <= frame)
+                .then_some((saved_frame, save))
+        })
+        .max_by_key(|(saved_frame, _)| *saved_frame)
+}
+
 fn valid_directory(dir: &str) -> Result<String, String> {
     if Path::new(dir).is_dir() {
         return Ok(String::from(dir));
@@ -60,100 +997,1394 @@ fn valid_directory(dir: &str) -> Result<String, String> {
     Err(String::from("Target directory not valid"))
 }
 
-fn get_colour_by_width(x: f32, width: u32) -> Rgb<u8> {
-    let width = width as f32;
-    let h_per = 360.0 / width;
-    let hsl = Hsl::from(h_per * x, 100.0, 50.0);
-    let rgb = hsl.to_rgb();
-    Rgb([
-        rgb.get_red().round() as u8,
-        rgb.get_green().round() as u8,
-        rgb.get_blue().round() as u8,
-    ])
+#[derive(Debug, FromArgs)]
+#[argh(
+    subcommand,
+    name = "merge",
+    description = "combine several state files into one flock"
+)]
+struct MergeArgs {
+    #[argh(positional, from_str_fn(valid_file))]
+    inputs: Vec<String>,
+    #[argh(
+        option,
+        short = 'o',
+        description = "file to write the combined state to"
+    )]
+    output: String,
+    #[argh(
+        option,
+        description = "WIDTHxHEIGHT of the combined world, defaults to the largest input",
+        from_str_fn(parse_size)
+    )]
+    world: Option<(u32, u32)>,
+    #[argh(
+        option,
+        description = "offset X,Y to move each input by, given once per input in order",
+        from_str_fn(parse_offset)
+    )]
+    offset: Vec<Vector2<f32>>,
+    #[argh(switch, description = "clamp boids that land outside the world")]
+    clamp: bool,
+    #[argh(switch, description = "give every input its own colour")]
+    colour_per_file: bool,
+    #[argh(option, description = "CSV file to record old to new boid ids in")]
+    id_map: Option<String>,
+}
+
+shared_flags! {
+    #[derive(Debug, FromArgs)]
+    #[argh(
+        subcommand,
+        name = "replay",
+        description = "render the frames of a recorded trajectory, or a trajectory CSV"
+    )]
+    struct ReplayArgs uses [output] {
+        #[argh(positional, from_str_fn(valid_file))]
+        input: String,
+        #[argh(
+            option,
+            description = "WIDTHxHEIGHT of the world, needed to replay a CSV",
+            from_str_fn(parse_size)
+        )]
+        world: Option<(u32, u32)>,
+        #[argh(
+            option,
+            description = "first frame to render, defaults 0",
+            default = "0"
+        )]
+        start: usize,
+        #[argh(
+            option,
+            description = "radius boids are drawn with, defaults 2",
+            default = "2"
+        )]
+        draw_radius: i32,
+        #[argh(
+            option,
+            description = "rendered frames per recorded frame, for a higher frame rate, defaults 1",
+            default = "1"
+        )]
+        interpolate: usize,
+        #[argh(
+            option,
+            description = "linear or hermite, how --interpolate fills in between frames, defaults hermite",
+            default = "Interpolation::Hermite"
+        )]
+        interpolation: Interpolation,
+    }
+}
+
+fn parse_offset(offset: &str) -> Result<Vector2<f32>, String> {
+    let parsed = offset
+        .split_once(',')
+        .map(|(x, y)| (x.trim().parse(), y.trim().parse()));
+    match parsed {
+        Some((Ok(x), Ok(y))) => Ok(Vector2::new(x, y)),
+        _ => Err(String::from("Offset must be in the form X,Y")),
+    }
+}
+
+fn parse_region(region: &str) -> Result<[f32; 4], String> {
+    let parts: Vec<f32> = region
+        .split(',')
+        .map(|part| part.trim().parse::<f32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| String::from("Region must be in the form X,Y,WIDTH,HEIGHT"))?;
+    match parts[..] {
+        [x, y, width, height] if width > 0.0 && height > 0.0 => Ok([x, y, width, height]),
+        _ => Err(String::from("Region must be in the form X,Y,WIDTH,HEIGHT")),
+    }
+}
+
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size
+        .split_once('x')
+        .ok_or_else(|| String::from("Size must be in the form WIDTHxHEIGHT"))?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(String::from("Size must be in the form WIDTHxHEIGHT")),
+    }
+}
+
+type Frames = Box<dyn Iterator<Item = io::Result<(usize, Vec<Boid>)>>>;
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "csv")
+}
+
+// CSV files don't record the world size, so need `--world`
+fn open_replay(args: &ReplayArgs) -> boids::Result<(u32, u32, Frames)> {
+    let path = Path::new(&args.input);
+    if let Some((width, height)) = args.world.filter(|_| is_csv(path)) {
+        let mut reader = ReplayReader::new(path)?;
+        let start = args.start;
+        let frames = iter::from_fn(move || {
+            reader
+                .next_frame()
+                .map(|boids| boids.map(|boids| (reader.frame().unwrap(), boids)))
+        })
+        .filter(move |frame| !matches!(frame, Ok((frame, _)) if *frame < start));
+        return Ok((width, height, Box::new(frames)));
+    }
+    let mut reader = TrajectoryReader::open(path)?;
+    reader
+        .seek(args.start)
+        .map_err(|err| Error::io("read", path, err))?;
+    let header = reader.header();
+    let frames = iter::from_fn(move || {
+        reader
+            .next_frame()
+            .map(|boids| boids.map(|boids| (reader.position() - 1, boids)))
+    });
+    Ok((header.width, header.height, Box::new(frames)))
+}
+
+fn replay(args: ReplayArgs, multi: &MultiProgress) {
+    let dir = args.output_flags().dir();
+    if args.interpolate == 0 {
+        error!("--interpolate must be at least 1");
+        process::exit(1);
+    }
+    if is_csv(Path::new(&args.input)) && args.world.is_none() {
+        error!(
+            "{} is a CSV, which doesn't record the world size, use --world",
+            args.input
+        );
+        process::exit(1);
+    }
+    let (width, height, frames) = open_replay(&args).unwrap_or_else(|err| exit_with(err));
+    let size = Vector2::new(width as f32, height as f32);
+    let render = |boids: &[Boid], frame: usize| {
+        // Hermite curves can overshoot the edge of the world a little, keep
+        // them in the same way the simulation does
+        let positions: Vec<Vector2<f32>> = boids
+            .iter()
+            .map(|boid| {
+                boid.pos.zip_map(&size, |pos, size| match pos {
+                    _ if pos < 0.0 => 0.0,
+                    _ if pos >= size => size - 1.0,
+                    _ => pos,
+                })
+            })
+            .collect();
+        let mut renderer = Renderer::new(width, height, args.draw_radius);
+        renderer.render_at(boids, &positions);
+        renderer
+            .save(format!("{dir}/frames_{frame:0>8}.png"))
+            .unwrap_or_else(|err| exit_with(err));
+    };
+    let pbar = multi.add(ProgressBar::no_length());
+    let mut previous: Option<(usize, Vec<Boid>)> = None;
+    for next in frames {
+        let (next_frame, boids) = next.unwrap_or_else(|err| {
+            error!("Unable to read {}: {err}", args.input);
+            process::exit(1);
+        });
+        // Everything from the previous frame up to, but not including, this one
+        if let Some((frame, from)) = &previous {
+            for step in 0..args.interpolate {
+                let t = step as f32 / args.interpolate as f32;
+                let between = trajectory::interpolate(from, &boids, t, args.interpolation);
+                render(&between, frame * args.interpolate + step);
+                pbar.inc(1);
+            }
+        }
+        previous = Some((next_frame, boids));
+    }
+    if let Some((frame, boids)) = previous {
+        render(&boids, frame * args.interpolate);
+        pbar.inc(1);
+    }
+    pbar.finish();
+}
+
+fn describe(metadata: &Metadata) -> Vec<String> {
+    let fields = [
+        ("Seed", metadata.seed.map(|seed| seed.to_string())),
+        ("Spawn", metadata.spawn.clone()),
+        (
+            "Written by",
+            metadata
+                .crate_version
+                .as_ref()
+                .map(|v| format!("boids {v}")),
+        ),
+        ("Created", metadata.created.clone()),
+        ("Note", metadata.note.clone()),
+        ("Frame", metadata.frame.map(|frame| frame.to_string())),
+        (
+            "Parameters",
+            metadata.parameters.as_ref().map(|parameters| {
+                let changed = Parameters::default().diff(parameters).changed_fields.len();
+                format!("saved, {changed} changed from the defaults")
+            }),
+        ),
+    ];
+    fields
+        .into_iter()
+        .filter_map(|(name, value)| Some(format!("{name}: {}", value?)))
+        .collect()
+}
+
+fn info(args: InfoArgs) {
+    let save = state::load(Path::new(&args.input)).unwrap_or_else(|err| exit_with(err));
+    println!("{}", args.input);
+    println!("Version: {}", save.version);
+    match save.world_size {
+        Some((width, height)) => println!("World: {width}x{height}"),
+        None => println!("World: not recorded"),
+    }
+    println!("Boids: {}", save.boids.len());
+    println!("Flock: {}", summarize(&save.boids));
+    for line in describe(&save.metadata) {
+        println!("{line}");
+    }
+}
+
+fn bench(args: BenchArgs) {
+    let world = args.world_flags();
+    let flock = args.flock_flags();
+    let parameters = ParametersBuilder::from(args.preset.clone().unwrap_or_default())
+        .world(world.width, world.height)
+        .build()
+        .unwrap_or_else(|errors| exit_invalid(&errors));
+    let config = SimulationConfig {
+        seed: flock.seed(),
+        ..SimulationConfig::new(world.world(), parameters, flock.boids)
+    };
+    let mut simulation = Simulation::new(config).unwrap_or_else(|err| {
+        error!("Unable to start the simulation: {err}");
+        process::exit(1);
+    });
+    let mut times = Vec::with_capacity(args.frames);
+    for _ in 0..args.frames {
+        let started = Instant::now();
+        simulation.step();
+        times.push(started.elapsed());
+    }
+    let (Some(fastest), Some(slowest)) = (times.iter().min(), times.iter().max()) else {
+        println!("No frames timed");
+        return;
+    };
+    let total: Duration = times.iter().sum();
+    let mean = total / times.len() as u32;
+    println!(
+        "{} frames of {} boids in {total:.2?}",
+        times.len(),
+        flock.boids
+    );
+    println!("Per frame: mean {mean:.2?}, fastest {fastest:.2?}, slowest {slowest:.2?}");
+    println!(
+        "Frames per second: {:.1}",
+        times.len() as f64 / total.as_secs_f64()
+    );
+}
+
+fn sweep(args: SweepArgs) {
+    let dir = args.output_flags().dir();
+    let world = args.world_flags();
+    let flock = args.flock_flags();
+    // The same seed for every run, so only the parameters differ
+    let seed = flock.seed();
+    for (i, axis) in args.vary.iter().enumerate() {
+        if args.vary[..i].iter().any(|other| other.field == axis.field) {
+            error!("{} is given more than one --vary", axis.field);
+            process::exit(1);
+        }
+    }
+    // Every run is checked before any are started
+    let baseline = args.preset.clone().unwrap_or_default();
+    let runs: Vec<(Combination, Parameters)> = sweep::combinations(&args.vary)
+        .into_iter()
+        .map(|combination| {
+            let parameters = sweep::apply(&baseline, &combination)
+                .and_then(|parameters| {
+                    ParametersBuilder::from(parameters)
+                        .world(world.width, world.height)
+                        .build()
+                        .map_err(|errors| ValidationError::join(&errors))
+                })
+                .unwrap_or_else(|err| {
+                    error!(
+                        "Invalid parameters for {}: {err}",
+                        sweep::run_name(&combination)
+                    );
+                    process::exit(1);
+                });
+            (combination, parameters)
+        })
+        .collect();
+    info!("Sweeping {} runs with seed {seed}", runs.len());
+    let csv_path = PathBuf::from(format!("{dir}/sweep.csv"));
+    let mut csv =
+        SweepCsvWriter::create(&csv_path, &args.vary).unwrap_or_else(|err| exit_with(err));
+    for (combination, parameters) in runs {
+        let name = sweep::run_name(&combination);
+        let run_dir = PathBuf::from(format!("{dir}/{name}"));
+        fs::create_dir_all(&run_dir).unwrap_or_else(|err| {
+            exit_with(Error::io("create", &run_dir, err));
+        });
+        info!("Running {name}");
+        let config = SimulationConfig {
+            seed,
+            ..SimulationConfig::new(world.world(), parameters.clone(), flock.boids)
+        };
+        let mut simulation = Simulation::new(config).unwrap_or_else(|err| {
+            error!("Unable to start the simulation: {err}");
+            process::exit(1);
+        });
+        simulation.run_for(args.frames);
+        let boids = simulation.boids();
+        let result = SweepResult {
+            polarization: polarization(boids),
+            angular_momentum: angular_momentum(boids),
+            mean_speed: mean_speed(boids),
+        };
+        let mut save = SaveFile::new(world.width, world.height, boids.to_vec());
+        save.metadata.seed = Some(seed);
+        save.metadata.frame = Some(args.frames as u64);
+        save.metadata.parameters = Some(parameters.clone());
+        state::save(&run_dir.join("final.json"), &save).unwrap_or_else(|err| exit_with(err));
+        let mut renderer = Renderer::new(world.width, world.height, parameters.draw_radius);
+        renderer.render(boids);
+        renderer
+            .save(run_dir.join("final.png"))
+            .unwrap_or_else(|err| exit_with(err));
+        csv.write_run(&combination, &result)
+            .and_then(|()| csv.flush())
+            .unwrap_or_else(|err| exit_with(Error::io("write", &csv_path, err)));
+    }
+}
+
+fn convert(args: ConvertArgs) {
+    let mut save = state::load(Path::new(&args.input)).unwrap_or_else(|err| exit_with(err));
+    if save.world_size.is_none() {
+        save.world_size = args.world;
+    }
+    if let Some((width, height)) = args.scale_to
+        && let Err(err) = save.scale_to(width, height, false)
+    {
+        error!("Unable to rescale {}: {err}", args.input);
+        process::exit(1);
+    }
+    if args.reseed_ids {
+        save.reseed_ids();
+    }
+    if args.recolor {
+        let Some((width, _)) = save.world_size else {
+            error!(
+                "Unable to recolor {}: no world size recorded, use --world",
+                args.input
+            );
+            process::exit(1);
+        };
+        for boid in &mut save.boids {
+            boid.colour = colour_by_width(boid.pos.x, width);
+        }
+    }
+    if let Err(err) = state::save(Path::new(&args.output), &save) {
+        exit_with(err);
+    }
+    info!(
+        "Converted {} boids from {} to {}",
+        save.boids.len(),
+        args.input,
+        args.output
+    );
+}
+
+fn merge(args: MergeArgs) {
+    if args.inputs.is_empty() {
+        error!("No state files given to merge");
+        process::exit(1);
+    }
+    if args.offset.len() > args.inputs.len() {
+        error!("More offsets given than there are inputs");
+        process::exit(1);
+    }
+    let mut sources = Vec::new();
+    let (mut width, mut height) = (0, 0);
+    for (index, input) in args.inputs.iter().enumerate() {
+        let save = state::load(Path::new(input)).unwrap_or_else(|err| exit_with(err));
+        if let Some((save_width, save_height)) = save.world_size {
+            width = width.max(save_width);
+            height = height.max(save_height);
+        }
+        let offset = args.offset.get(index).copied().unwrap_or_default();
+        sources.push((save, offset));
+    }
+    let (width, height) = match args.world {
+        Some(world) => world,
+        None if width > 0 && height > 0 => (width, height),
+        None => {
+            error!("None of the inputs record their world size, use --world");
+            process::exit(1);
+        }
+    };
+    let (mut save, mapping) =
+        state::merge(sources, width, height, args.clamp).unwrap_or_else(|err| {
+            error!("Unable to merge: {err}, use --clamp or a larger --world");
+            process::exit(1);
+        });
+    save.metadata.spawn = Some(format!("merged from {}", args.inputs.join(", ")));
+    if args.colour_per_file {
+        for (boid, id) in save.boids.iter_mut().zip(&mapping) {
+            boid.colour = colour_by_width(id.source as f32, args.inputs.len() as u32);
+        }
+    }
+    if let Some(id_map) = &args.id_map {
+        let mut csv = String::from("source,old_id,new_id\n");
+        for id in &mapping {
+            csv.push_str(&format!(
+                "{},{},{}\n",
+                args.inputs[id.source], id.old_id, id.new_id
+            ));
+        }
+        fs::write(id_map, csv).unwrap_or_else(|err| {
+            error!("Unable to write {id_map}: {err}");
+            process::exit(1);
+        });
+    }
+    if let Err(err) = state::save(Path::new(&args.output), &save) {
+        exit_with(err);
+    }
+    info!(
+        "Merged {} boids from {} files into a {width}x{height} world in {}",
+        save.boids.len(),
+        args.inputs.len(),
+        args.output
+    );
+}
+
+// The parameters asked for on the command line, each layer over the one
+// before: the defaults, --preset, --params, then any flags given
+fn command_line_parameters(args: &RunArgs) -> Parameters {
+    let preset = args.preset.clone().unwrap_or_default();
+    let mut parameters = match &args.params {
+        Some(path) => Parameters::load(Path::new(path))
+            .unwrap_or_else(|err| exit_with(err))
+            .overriding(&preset, &Parameters::default()),
+        None => preset,
+    };
+    macro_rules! flagged {
+        ($($field:ident),* $(,)?) => {
+            $(
+                if let Some(value) = args.$field {
+                    parameters.$field = value;
+                }
+            )*
+        };
+    }
+    flagged!(
+        max_speed,
+        min_speed,
+        margin,
+        visible_range,
+        protected_range,
+        avoid_factor,
+        matching_factor,
+        centering_factor,
+        turn_factor,
+        draw_radius,
+        update_threshold,
+        global_centering_factor,
+        render_smoothing,
+        colour_rotation_speed,
+        heading_histogram_bins,
+        perturbation_fraction,
+        perturbation_strength,
+        trail_length,
+        stall_policy,
+    );
+    if args.max_neighbors_for_early_exit.is_some() {
+        parameters.max_neighbors_for_early_exit = args.max_neighbors_for_early_exit;
+    }
+    parameters.aspect_cells |= args.aspect_cells;
+    parameters.per_boid_colour_rotation |= args.per_boid_colour_rotation;
+    parameters.voronoi_neighbors |= args.voronoi_neighbors;
+    parameters.draw_flock_hulls |= args.draw_flock_hulls;
+    if let Some(path) = &args.boundary_sdf {
+        parameters.boundary = BoundaryMode::Sdf { path: path.into() };
+    }
+    if !args.seed_event.is_empty() {
+        parameters.seed_schedule = RandomSeedSchedule::new(args.seed_event.clone());
+    }
+    parameters
 }
 
 fn main() {
-    let args: Flags = argh::from_env();
-
-    let parameters: Parameters = Parameters {
-        max_speed: 3.0,
-        min_speed: 0.5,
-        margin: 10,
-        visible_range: 20.0,
-        protected_range: 2.0,
-        avoid_factor: 0.10,
-        matching_factor: 0.05,
-        centering_factor: 0.0005,
-        turn_factor: 0.2,
-        cell_size: 22.0,
-        draw_radius: 2,
+    let flags = parse_flags();
+    let multi = init_logging(flags.quiet, flags.verbose);
+    match flags.command {
+        Command::Run(args) => run(args, &multi),
+        Command::Bench(args) => bench(args),
+        Command::Sweep(args) => sweep(args),
+        Command::Convert(args) => convert(args),
+        Command::Merge(args) => merge(args),
+        Command::Replay(args) => replay(args, &multi),
+        Command::Info(args) => info(args),
+    }
+}
+
+fn run(args: RunArgs, multi: &MultiProgress) {
+    if args.list_presets {
+        for name in PRESETS {
+            let description = Parameters::preset_description(name).unwrap_or_default();
+            println!("{name:<12} {description}");
+        }
+        return;
+    }
+    let mut recorder = RunRecorder::new();
+    let dir = args.output_flags().dir();
+
+    let mut parameters = command_line_parameters(&args);
+    // Rules are turned off by zeroing their factor
+    let disabled = [
+        (args.no_cohesion, &mut parameters.centering_factor),
+        (args.no_alignment, &mut parameters.matching_factor),
+        (args.no_separation, &mut parameters.avoid_factor),
+        (args.no_edge_turning, &mut parameters.turn_factor),
+    ];
+    for (disabled, factor) in disabled {
+        if disabled {
+            *factor = 0.0;
+        }
+    }
+    if !args.speed_zone.is_empty() {
+        parameters.speed_zones = args.speed_zone.clone();
+    }
+    if let Some(cell_size) = args.cell_size {
+        parameters.cell_size = cell_size;
+    } else if args.auto_cell_size {
+        parameters.cell_size = parameters
+            .auto_cell_size(args.auto_cell_factor)
+            .unwrap_or_else(|err| {
+                error!("Unable to pick a cell size: {err}");
+                process::exit(1);
+            });
+        info!("Using a cell size of {}", parameters.cell_size);
+    }
+    let parameters = ParametersBuilder::from(parameters)
+        .world(args.width, args.height)
+        .build()
+        .unwrap_or_else(|errors| exit_invalid(&errors));
+    // Read before anything is started, as it may bring its own parameters
+    let loaded = args.load_file.as_ref().map(|source| {
+        info!("Loading starting state from {source}");
+        let loaded = load_state(source, args.stdio_format).unwrap_or_else(|err| exit_with(err));
+        let save = if args.strict_load {
+            loaded.strict().unwrap_or_else(|err| {
+                error!("Unable to load {source}: {err}");
+                process::exit(1);
+            })
+        } else {
+            if !loaded.unknown_fields.is_empty() {
+                warn!(
+                    "ignoring fields in {source} this version doesn't know about: {}",
+                    loaded.unknown_fields.join(", ")
+                );
+            }
+            loaded.state
+        };
+        for line in describe(&save.metadata) {
+            info!("  {line}");
+        }
+        (source.clone(), save)
+    });
+    let parameters = match &loaded {
+        Some((source, save)) => match &save.metadata.parameters {
+            Some(saved) => {
+                restore_parameters(source, saved, &parameters, (args.width, args.height))
+            }
+            None => parameters,
+        },
+        None => parameters,
     };
-    let mut rng = rand::rng();
-    let mut boids: Vec<Boid>;
-    if let Some(source) = args.load_file {
-        println!("Loading starting state from {source}");
-        let data = fs::read_to_string(source).expect("Unable to read source file");
-        boids = serde_json::from_str(&data).unwrap();
+    info!(
+        "Parameters: max_speed {}, min_speed {}, margin {}, visible_range {}, \
+         protected_range {}, avoid_factor {}, matching_factor {}, centering_factor {}, \
+         turn_factor {}, cell_size {}, draw_radius {}",
+        parameters.max_speed,
+        parameters.min_speed,
+        parameters.margin,
+        parameters.visible_range,
+        parameters.protected_range,
+        parameters.avoid_factor,
+        parameters.matching_factor,
+        parameters.centering_factor,
+        parameters.turn_factor,
+        parameters.cell_size,
+        parameters.draw_radius,
+    );
+    if let Some(other) = &args.params_compare {
+        let compared = Parameters::load(Path::new(other)).unwrap_or_else(|err| exit_with(err));
+        info!("Compared with {other}:");
+        for line in parameters.diff(&compared).summary().lines() {
+            info!("  {line}");
+        }
+    }
+    let seed = args.flock_flags().seed();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let world = args.world_flags().world();
+    let config = SimulationConfig {
+        world: world.clone(),
+        parameters: parameters.clone(),
+        boids: args.boids,
+        spawn: args.spawn_distribution.clone(),
+        spawn_velocity: args.spawn_velocity,
+        seed,
+        colour_mode: args.colour_mode,
+    };
+    let quality_weights = args.quality_weights;
+    let balance_load = args.balance_load;
+    let start = |boids: Option<Vec<Boid>>, frame: usize| {
+        let mut sim = match boids {
+            Some(boids) => Simulation::from_boids(config.clone(), boids, frame),
+            None => Simulation::new(config.clone()),
+        }
+        .unwrap_or_else(|err| {
+            error!("Unable to start the simulation: {err}");
+            process::exit(1);
+        });
+        sim.set_quality_weights(quality_weights);
+        sim.balance_load(balance_load);
+        sim
+    };
+    let mut sim;
+    let spawn;
+    if let Some((source, mut save)) = loaded {
+        spawn = format!("loaded from {source}");
+        if let Some((width, height)) = save.world_size
+            && (width, height) != (args.width, args.height)
+        {
+            if !args.load_rescale {
+                error!(
+                    "{source} was saved from a {width}x{height} world but this one is {}x{}, use --load-rescale to fit it",
+                    args.width, args.height
+                );
+                process::exit(1);
+            }
+            let scale_x = args.width as f32 / width as f32;
+            let scale_y = args.height as f32 / height as f32;
+            if (scale_x - scale_y).abs() > 0.01 {
+                warn!(
+                    "rescaling by {scale_x:.3}x{scale_y:.3} is not uniform, expect the flocking to be distorted"
+                );
+            }
+            save.scale_to(args.width, args.height, args.rescale_velocities)
+                .expect("World size was just checked");
+            info!(
+                "Rescaled from {width}x{height} to {}x{}",
+                args.width, args.height
+            );
+        }
+        for transform in &args.load_transform {
+            if let Err(err) = transform::apply(
+                &mut save.boids,
+                *transform,
+                &world,
+                args.load_transform_clamp,
+            ) {
+                error!(
+                    "Unable to apply {transform:?}: {err}, use --load-transform-clamp to keep them in"
+                );
+                process::exit(1);
+            }
+        }
+        if let Some([x, y, width, height]) = args.load_region {
+            save.retain_region(x, y, width, height);
+            info!("Kept {} boids inside the load region", save.boids.len());
+        }
+        let sample = match (args.load_sample, args.load_sample_fraction) {
+            (Some(_), Some(_)) => {
+                error!("Only one of --load-sample and --load-sample-fraction can be used");
+                process::exit(1);
+            }
+            (Some(count), None) => Some(count),
+            (None, Some(fraction)) => {
+                Some((save.boids.len() as f32 * fraction.clamp(0.0, 1.0)).round() as usize)
+            }
+            (None, None) => None,
+        };
+        if let Some(count) = sample {
+            if count > save.boids.len() {
+                warn!(
+                    "asked to sample {count} boids but only {} were loaded, keeping them all",
+                    save.boids.len()
+                );
+            }
+            save.sample(count, &mut rng);
+        }
+        // Modes that follow the motion are always recoloured
+        sim = start(Some(save.boids), 0);
+        if args.recolor && !args.colour_mode.is_dynamic() {
+            sim.recolour();
+        }
     } else {
-        boids = (0..args.boids)
-            .map(|id| {
-                let x = rng.random_range(0..args.width) as f32;
-                Boid::new(
-                    id,
-                    Vector2::new(x, rng.random_range(0..args.height) as f32),
-                    Vector2::new(
-                        rng.random_range(-parameters.max_speed / 2.0..parameters.max_speed / 2.0),
-                        rng.random_range(-parameters.max_speed / 2.0..parameters.max_speed / 2.0),
-                    ),
-                    0.0,
-                    get_colour_by_width(x, args.width),
-                )
-            })
-            .collect();
+        spawn = format!(
+            "{}, {} boids moving {}",
+            args.spawn_distribution, args.boids, args.spawn_velocity
+        );
+        sim = start(None, 0);
     }
+    info!("Starting with {}", summarize(sim.boids()));
     if let Some(target) = args.save_file {
-        println!("Saving starting state to {target}");
-        let data = serde_json::to_string(&boids).unwrap();
-        fs::write(target, data).expect("Unable to write file");
+        info!("Saving starting state to {target}");
+        let mut save = SaveFile::new(args.width, args.height, sim.boids().to_vec());
+        save.metadata.seed = Some(seed);
+        save.metadata.spawn = Some(spawn.clone());
+        save.metadata.note = args.note.clone();
+        save.metadata.parameters = Some(parameters.clone());
+        recorder
+            .time(Stage::Io, || save_state(&target, &save, args.stdio_format))
+            .unwrap_or_else(|err| exit_with(err));
+        if target != STDIO {
+            recorder.artifact(target);
+        }
+    }
+    // Frame N is the flock after N steps, so frame 0 is the starting state
+    let frame_start = args
+        .frame_start
+        .unwrap_or(if args.include_initial_frame { 0 } else { 1 });
+    let frame_end = args.frame_end.unwrap_or(args.frames + 1);
+    if (args.frame_start.is_some() || args.frame_end.is_some())
+        && !(frame_start < frame_end && frame_end <= args.frames + 1)
+    {
+        error!(
+            "--frame-start must be before --frame-end, which can't be more than one past --frames"
+        );
+        process::exit(1);
+    }
+    // Only a range asked for resumes, a leftover checkpoint never replaces
+    // the flock of a plain run
+    if args.frame_start.is_some() && frame_start > 0 {
+        match find_checkpoint(&dir, frame_start, seed) {
+            Some((saved_frame, save)) => {
+                info!("Resuming from the checkpoint at frame {saved_frame}");
+                if save.boids.len() != sim.boids().len() {
+                    warn!(
+                        "the checkpoint has {} boids, not {}",
+                        save.boids.len(),
+                        sim.boids().len()
+                    );
+                }
+                sim = start(Some(save.boids), saved_frame);
+            }
+            None if frame_start > FAST_FORWARD_WARNING => warn!(
+                "no checkpoint in {dir}, simulating {frame_start} frames that won't be rendered"
+            ),
+            None => {}
+        }
     }
-    let mut running = true;
-    let mut frame = 0;
-    let pbar = ProgressBar::new(args.frames as u64);
+    match args.target_fps {
+        Some(0) => {
+            error!("--target-fps must be greater than 0");
+            process::exit(1);
+        }
+        None if args.allow_frame_drop => {
+            error!("--allow-frame-drop needs --target-fps");
+            process::exit(1);
+        }
+        _ => {}
+    }
+    let mut running = frame_start < frame_end;
+    let pbar = multi.add(ProgressBar::new(
+        frame_end.saturating_sub(frame_start) as u64
+    ));
     pbar.set_style(
         ProgressStyle::with_template(
             "[{elapsed_precise}/{eta_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
         )
         .unwrap(),
     );
+    let mut smoothing = TemporalSmoothing::new();
+    let mut renderer = Renderer::new(args.width, args.height, parameters.draw_radius);
+    let field_lines = args.field_lines.map(|density| {
+        if density.is_nan() || density <= 0.0 {
+            error!("--field-lines must be greater than 0");
+            process::exit(1);
+        }
+        FieldLines::new(density, parameters.max_speed)
+    });
+    let mut neighbor_count_map = args.neighbor_count_overlay.map(|alpha| {
+        if !(0.0..=1.0).contains(&alpha) {
+            error!("--neighbor-count-overlay must be between 0 and 1");
+            process::exit(1);
+        }
+        NeighborCountMap::new(alpha)
+    });
+    recorder.artifact(dir.clone());
+    for path in args
+        .trajectory_out
+        .iter()
+        .chain(&args.trajectory_csv)
+        .chain(&args.correlation_function_csv)
+        .chain(&args.heading_histogram_csv)
+        .chain(&args.cluster_output)
+    {
+        recorder.artifact(path.clone());
+    }
+    // Memberships go next to the trajectory CSV, which can't have them as
+    // they're only known once the run is over
+    let membership_csv = args
+        .trajectory_csv
+        .as_ref()
+        .filter(|_| args.cluster_output.is_some())
+        .map(|path| {
+            let path = Path::new(path);
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{stem}_clusters.csv"))
+                .to_string_lossy()
+                .into_owned()
+        });
+    let mut positions = args.cluster_output.is_some().then(PositionAverager::new);
+    let cluster_eps = args.cluster_eps.unwrap_or(parameters.visible_range);
+    let mut trajectory = args.trajectory_out.as_ref().map(|path| {
+        TrajectoryWriter::create(
+            Path::new(path),
+            args.trajectory_precision,
+            args.trajectory_keyframe_interval,
+            args.width,
+            args.height,
+        )
+        .unwrap_or_else(|err| exit_with(err))
+    });
+    let mut trajectory_csv = args.trajectory_csv.as_ref().map(|path| {
+        CsvTrajectoryWriter::create(Path::new(path)).unwrap_or_else(|err| exit_with(err))
+    });
+    let correlation_r_max = args
+        .correlation_r_max
+        .unwrap_or(parameters.visible_range * 10.0);
+    let mut correlation_csv = args.correlation_function_csv.as_ref().map(|path| {
+        CorrelationCsvWriter::create(Path::new(path)).unwrap_or_else(|err| exit_with(err))
+    });
+    if args.heading_histogram_csv.is_some() && parameters.heading_histogram_bins == 0 {
+        error!("--heading-histogram-csv needs --heading-histogram-bins");
+        process::exit(1);
+    }
+    let mut heading_csv = args.heading_histogram_csv.as_ref().map(|path| {
+        HeadingHistogramCsvWriter::create(Path::new(path), parameters.heading_histogram_bins)
+            .unwrap_or_else(|err| exit_with(err))
+    });
+    #[cfg(not(feature = "parquet"))]
+    if args.parquet_output.is_some() || args.parquet_all_frames.is_some() {
+        error!("Parquet output needs boids built with the parquet feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "parquet")]
+    let mut parquet_all_frames = args.parquet_all_frames.as_ref().map(|path| {
+        let writer = ParquetWriter::create(Path::new(path)).unwrap_or_else(|err| {
+            error!("Unable to create {path}: {err}");
+            process::exit(1);
+        });
+        recorder.artifact(path.clone());
+        writer
+    });
+    #[cfg(feature = "parquet")]
+    if let Some(parquet_output) = &args.parquet_output {
+        recorder.artifact(parquet_output.clone());
+    }
+    let frame_hashes_path = format!("{dir}/frame_hashes.txt");
+    let mut frame_hashes = args.hash_frames.then(|| {
+        let hashes =
+            FrameHashes::create(Path::new(&frame_hashes_path)).unwrap_or_else(|err| exit_with(err));
+        recorder.artifact(frame_hashes_path.clone());
+        hashes
+    });
+    let mut manifest = args.output_manifest.as_ref().map(|path| {
+        recorder.artifact(path.clone());
+        Manifest::new(parameters.clone())
+    });
+    let snapshot = |boids: &[Boid], frame: usize| {
+        let mut save = SaveFile::new(args.width, args.height, boids.to_vec());
+        save.metadata.seed = Some(seed);
+        save.metadata.spawn = Some(spawn.clone());
+        save.metadata.note = args.note.clone();
+        save.metadata.frame = Some(frame as u64);
+        save.metadata.parameters = Some(parameters.clone());
+        save
+    };
+    #[cfg(not(feature = "metrics"))]
+    if args.metrics_addr.is_some() {
+        error!("--metrics-addr needs boids built with the metrics feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "metrics")]
+    let metrics = args.metrics_addr.as_ref().map(|addr| {
+        let metrics = Arc::new(Metrics::new());
+        let local = metrics::serve(addr, metrics.clone()).unwrap_or_else(|err| {
+            error!("Unable to serve metrics on {addr}: {err}");
+            process::exit(1);
+        });
+        info!("Serving metrics on http://{local}/metrics");
+        metrics
+    });
+    #[cfg(not(feature = "trace"))]
+    if args.trace_out.is_some() {
+        error!("--trace-out needs boids built with the trace feature");
+        process::exit(1);
+    }
+    #[cfg(feature = "trace")]
+    let trace_guard = args.trace_out.as_ref().map(|path| {
+        trace::install(Path::new(path)).unwrap_or_else(|err| {
+            error!("Unable to trace to {path}: {err}");
+            process::exit(1);
+        })
+    });
+    let interrupted = interrupt_flag();
+    let flag = interrupted.clone();
+    sim.add_observer(move |_: usize, _: &[Boid], _: &FrameStats| {
+        if flag.load(Ordering::Relaxed) {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    sim.add_observer(StopWhen::new(args.stop_when.clone()));
+    let signals = UserSignals::install();
+    let mut frame_stats = false;
+    let mut memory_warned = false;
+    let mut over_memory = false;
+    let mut stalled = false;
+    if args.max_memory.is_some() && sys::memory_usage_bytes().is_none() {
+        warn!("memory use can't be measured here, --max-memory does nothing");
+    }
+    let mut pacer = None;
+    let mut dropped_in_a_row = 0;
+    // Starting on a frame that's drawn, it's drawn as it is before stepping
+    let mut unstepped = sim.frame() == frame_start;
     while running {
-        let mut img = RgbImage::new(args.width, args.height);
-        update_boids(&mut boids, args.height, args.width, parameters);
-        for boid in &boids {
-            // Rather than a single pixel, going to create a circle
-            let boid_x_int = boid.pos.x.round() as i32;
-            let boid_y_int = boid.pos.y.round() as i32;
-            for dy_offset in -parameters.draw_radius..=parameters.draw_radius {
-                for dx_offset in -parameters.draw_radius..=parameters.draw_radius {
-                    if (dx_offset * dx_offset + dy_offset * dy_offset)
-                        <= (parameters.draw_radius * parameters.draw_radius)
-                    {
-                        let px = boid_x_int + dx_offset;
-                        let py = boid_y_int + dy_offset;
-                        if px >= 0 && px < args.width as i32 && py >= 0 && py < args.height as i32 {
-                            img.put_pixel(px as u32, py as u32, boid.colour);
-                        }
+        let frame = sim.frame();
+        if signals.dump.swap(false, Ordering::Relaxed) {
+            let path = format!("{dir}/state_frame_{frame}.json");
+            match recorder.time(Stage::Io, || {
+                state::save(Path::new(&path), &snapshot(sim.boids(), frame))
+            }) {
+                Ok(()) => {
+                    info!("Dumped state at frame {frame} to {path}");
+                    recorder.artifact(path);
+                }
+                Err(err) => error!("Unable to dump state to {path}: {err}"),
+            }
+        }
+        if let Some(max_memory) = args.max_memory
+            && frame.is_multiple_of(MEMORY_CHECK_INTERVAL)
+            && let Some(usage) = sys::memory_usage_bytes()
+        {
+            let limit = max_memory as f64 * 1024.0 * 1024.0;
+            let usage_mb = usage / (1024 * 1024);
+            if usage as f64 > limit * HARD_MEMORY_FACTOR {
+                error!(
+                    "Using {usage_mb}MB, more than {HARD_MEMORY_FACTOR} times --max-memory, stopping"
+                );
+                over_memory = true;
+                break;
+            }
+            if usage as f64 > limit && !memory_warned {
+                memory_warned = true;
+                warn!("using {usage_mb}MB, over --max-memory, no longer recording trajectories");
+                // Already giving up on them, so a failed write is only logged
+                // rather than ending the run too
+                let failed =
+                    |path: &str, err| error!("{}", Error::io("write", Path::new(path), err));
+                if let (Some(mut trajectory), Some(path)) =
+                    (trajectory.take(), &args.trajectory_out)
+                    && let Err(err) = trajectory.flush()
+                {
+                    failed(path, err);
+                }
+                if let (Some(mut trajectory_csv), Some(path)) =
+                    (trajectory_csv.take(), &args.trajectory_csv)
+                    && let Err(err) = trajectory_csv.flush()
+                {
+                    failed(path, err);
+                }
+                #[cfg(feature = "parquet")]
+                if let (Some(writer), Some(path)) =
+                    (parquet_all_frames.take(), &args.parquet_all_frames)
+                    && let Err(err) = writer.close()
+                {
+                    failed(path, err.into());
+                }
+            }
+        }
+        if signals.toggle_verbose.swap(false, Ordering::Relaxed) {
+            frame_stats = !frame_stats;
+            info!("Per frame stats {}", if frame_stats { "on" } else { "off" });
+        }
+        if frame_stats || (args.print_grid_stats && frame % args.grid_stats_interval.max(1) == 0) {
+            let grid = sim.grid();
+            let busiest = grid.cell_counts().first().map(|(_, cell)| *cell);
+            let message = format!(
+                "Frame {frame}: grid {}x{}, {:.1}% of cells occupied, {:.2} boids per occupied cell, at most {} in {:?}",
+                grid.grid_cols,
+                grid.grid_rows,
+                grid.occupied_fraction() * 100.0,
+                grid.mean_occupancy(),
+                grid.max_occupancy(),
+                busiest.unwrap_or_default(),
+            );
+            info!("{message}");
+        }
+        let frame_started = Instant::now();
+        let stages_before = FRAME_STAGES.map(|stage| recorder.stage_time(stage));
+        #[cfg(feature = "trace")]
+        let _frame_span = trace::tracing::info_span!("frame", frame).entered();
+        let stats = if unstepped {
+            unstepped = false;
+            sim.current_stats()
+        } else {
+            in_span!("simulate", sim.step())
+        };
+        // Everything from here on is of the frame just stepped to
+        let frame = sim.frame();
+        let stats = FrameStats { frame, ..stats };
+        let stop_reason = sim.stopped().map(String::from);
+        if stop_reason.is_some() {
+            running = false;
+        }
+        let boids = sim.boids();
+        smoothing.update(boids, parameters.render_smoothing);
+        if frame < frame_start {
+            // Catching up to the shard, nothing is drawn or recorded
+            continue;
+        }
+        if pacer.is_none()
+            && let Some(fps) = args.target_fps
+        {
+            // Paced from the first frame drawn, not counting any catching up
+            pacer = Some(FramePacer::new(fps, frame_started));
+        }
+        let mut stage_started = Instant::now();
+        recorder.add(Stage::Simulate, frame_started.elapsed());
+        if let Some(trajectory) = &mut trajectory
+            && let Err(err) = trajectory.write_frame(boids)
+        {
+            error!("Unable to record trajectory: {err}");
+            process::exit(1);
+        }
+        if let Some(trajectory_csv) = &mut trajectory_csv
+            && let Err(err) = trajectory_csv.write_frame(boids)
+        {
+            error!("Unable to record trajectory CSV: {err}");
+            process::exit(1);
+        }
+        if let Some(positions) = &mut positions {
+            positions.add(boids);
+        }
+        if let Some(correlation_csv) = &mut correlation_csv
+            && frame.is_multiple_of(args.correlation_interval.max(1))
+        {
+            let correlation = CorrelationFunction::compute(
+                boids,
+                sim.grid(),
+                correlation_r_max,
+                args.correlation_bins,
+            );
+            if let Err(err) = correlation_csv.write_frame(frame, &correlation) {
+                error!("Unable to record correlation function: {err}");
+                process::exit(1);
+            }
+        }
+        if let Some(heading_csv) = &mut heading_csv {
+            let histogram = HeadingHistogram::compute(boids, parameters.heading_histogram_bins);
+            if let Err(err) = heading_csv.write_frame(frame, &histogram) {
+                error!("Unable to record heading histogram: {err}");
+                process::exit(1);
+            }
+        }
+        #[cfg(feature = "parquet")]
+        {
+            let written = parquet_all_frames
+                .as_mut()
+                .map_or(Ok(()), |writer| writer.write_frame(frame, boids))
+                .and_then(|_| match &args.parquet_output {
+                    Some(parquet_output) => columnar::write_frame_file(
+                        Path::new(&format!("{parquet_output}/frame_{frame:0>8}.parquet")),
+                        frame,
+                        boids,
+                    ),
+                    None => Ok(()),
+                });
+            if let Err(err) = written {
+                error!("Unable to write Parquet: {err}");
+                process::exit(1);
+            }
+        }
+        recorder.add(Stage::Io, stage_started.elapsed());
+        // Drawing is what's dropped, the flock is still simulated so it
+        // moves the same however far behind the run gets
+        let dropping = args.allow_frame_drop
+            && dropped_in_a_row < MAX_DROPPED_IN_A_ROW
+            && pacer
+                .as_ref()
+                .is_some_and(|pacer| pacer.behind(Instant::now()));
+        if dropping {
+            dropped_in_a_row += 1;
+            recorder.dropped();
+        } else {
+            dropped_in_a_row = 0;
+            stage_started = Instant::now();
+            let img = in_span!("rasterize", {
+                renderer.clear();
+                draw_speed_zones(
+                    renderer.image_mut(),
+                    &parameters.speed_zones,
+                    parameters.max_speed,
+                    SPEED_ZONE_ALPHA,
+                );
+                if let Some(field_lines) = &field_lines {
+                    let field = compute_velocity_field(
+                        boids,
+                        parameters.cell_size,
+                        args.width,
+                        args.height,
+                    );
+                    field_lines.draw(renderer.image_mut(), &field);
+                }
+                renderer.draw_at(boids, smoothing.positions());
+                if let Some(neighbor_count_map) = &mut neighbor_count_map {
+                    neighbor_count_map.draw(
+                        renderer.image_mut(),
+                        sim.grid(),
+                        sim.neighbor_counts(),
+                    );
+                }
+                if parameters.draw_flock_hulls {
+                    let positions = smoothing.positions();
+                    let membership = cluster::cluster_membership(
+                        positions,
+                        cluster_eps,
+                        args.cluster_min_points,
+                    );
+                    let drawn: Vec<Boid> = boids
+                        .iter()
+                        .zip(positions)
+                        .map(|(boid, pos)| {
+                            let mut boid = boid.clone();
+                            boid.pos = *pos;
+                            boid
+                        })
+                        .collect();
+                    for hull in FlockConvexHull::from_membership(&drawn, &membership) {
+                        hull.draw(renderer.image_mut(), HULL_ALPHA);
                     }
                 }
+                renderer.image()
+            });
+            if let Some(frame_hashes) = &mut frame_hashes
+                && let Err(err) = frame_hashes.record(frame, img)
+            {
+                error!("Unable to record frame hash: {err}");
+                process::exit(1);
+            }
+            recorder.add(Stage::Rasterize, stage_started.elapsed());
+            let file_name = format!("frames_{frame:0>8}.png");
+            let path = PathBuf::from(format!("{dir}/{file_name}"));
+            let png = recorder.time(Stage::Encode, || {
+                in_span!("encode", {
+                    let mut png = io::Cursor::new(Vec::new());
+                    img.write_to(&mut png, ImageFormat::Png)
+                        .unwrap_or_else(|source| {
+                            exit_with(Error::Render {
+                                path: path.clone(),
+                                source,
+                            })
+                        });
+                    png.into_inner()
+                })
+            });
+            recorder
+                .time(Stage::Io, || in_span!("write", fs::write(&path, &png)))
+                .unwrap_or_else(|err| exit_with(Error::io("write", &path, err)));
+            recorder.wrote(png.len() as u64);
+            if let Some(manifest) = &mut manifest {
+                manifest.frame(file_name, &stats);
+                if args.live_manifest
+                    && let Some(path) = &args.output_manifest
+                    && let Err(err) = recorder.time(Stage::Io, || manifest.save(Path::new(path)))
+                {
+                    error!("Unable to update manifest: {err}");
+                }
+            }
+            recorder.frame(frame_started.elapsed());
+        }
+        if log::log_enabled!(Level::Debug) {
+            let [simulate, rasterize, encode, io] = FRAME_STAGES
+                .map(|stage| recorder.stage_time(stage) - stages_before[stage as usize]);
+            debug!(
+                "Frame {frame} in {:.2?}: simulate {simulate:.2?}, rasterize {rasterize:.2?}, encode {encode:.2?}, io {io:.2?}, re-steered {} of {} boids",
+                frame_started.elapsed(),
+                stats.re_steered,
+                stats.population
+            );
+        }
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &metrics {
+            metrics.frame(frame, stats.population, stats.polarization);
+            metrics.set_quality_score(stats.quality_score);
+            // Frames are encoded as they're drawn, so nothing ever waits
+            metrics.set_encode_queue_depth(0);
+            metrics.set_bytes_written(recorder.bytes_written());
+            for stage in FRAME_STAGES {
+                metrics.set_stage_time(stage, recorder.stage_time(stage));
             }
-            img.put_pixel(boid.pos.x as u32, boid.pos.y as u32, boid.colour);
         }
-        img.save(format!("{}/frames_{:0>8}.png", args.dir, frame))
-            .unwrap();
 
-        frame += 1;
+        let mut message = format!("Q={:.3}", stats.quality_score);
+        if args.balance_load {
+            message.push_str(&format!(" imbalance={:.2}", stats.load_imbalance));
+        }
+        if let Some(pacer) = &mut pacer {
+            let now = Instant::now();
+            match pacer.finish(now) {
+                Pace::Early(wait) => thread::sleep(wait),
+                Pace::Late(late) if pacer.overruns() == 1 => warn!(
+                    "frame {frame} finished {late:.2?} late for --target-fps, later ones are only logged with -v"
+                ),
+                Pace::Late(late) => debug!("Frame {frame} finished {late:.2?} late"),
+            }
+            message.push_str(&format!(" fps={:.1}", pacer.achieved_fps(Instant::now())));
+        }
+        pbar.set_message(message);
         pbar.inc(1);
-        if frame > args.frames {
+        if stats.stalled > 0 {
+            // Taken as a bug, so reported even on the last frame
+            error!(
+                "Stopping at frame {}: {}",
+                sim.frame(),
+                stop_reason.as_deref().unwrap_or_default()
+            );
+            stalled = true;
+            running = false;
+        } else if frame >= args.frames || frame + 1 >= frame_end {
             running = false;
+        } else if let Some(reason) = stop_reason
+            && !interrupted.load(Ordering::Relaxed)
+        {
+            info!("Stopping at frame {}: {reason}", sim.frame());
+            recorder.stopped(reason);
+        }
+    }
+    if let (Some(trajectory), Some(path)) = (&mut trajectory, &args.trajectory_out) {
+        trajectory
+            .flush()
+            .unwrap_or_else(|err| exit_with(Error::io("write", Path::new(path), err)));
+    }
+    if let (Some(trajectory_csv), Some(path)) = (&mut trajectory_csv, &args.trajectory_csv) {
+        trajectory_csv
+            .flush()
+            .unwrap_or_else(|err| exit_with(Error::io("write", Path::new(path), err)));
+    }
+    if let (Some(correlation_csv), Some(path)) =
+        (&mut correlation_csv, &args.correlation_function_csv)
+    {
+        correlation_csv
+            .flush()
+            .unwrap_or_else(|err| exit_with(Error::io("write", Path::new(path), err)));
+    }
+    if let (Some(heading_csv), Some(path)) = (&mut heading_csv, &args.heading_histogram_csv) {
+        heading_csv
+            .flush()
+            .unwrap_or_else(|err| exit_with(Error::io("write", Path::new(path), err)));
+    }
+    #[cfg(feature = "parquet")]
+    if let (Some(writer), Some(path)) = (parquet_all_frames, &args.parquet_all_frames) {
+        writer
+            .close()
+            .unwrap_or_else(|err| exit_with(Error::io("write", Path::new(path), err.into())));
+    }
+    if let Some(frame_hashes) = &mut frame_hashes {
+        frame_hashes.flush().unwrap_or_else(|err| {
+            exit_with(Error::io("write", Path::new(&frame_hashes_path), err))
+        });
+    }
+    if let (Some(path), Some(positions)) = (&args.cluster_output, &positions) {
+        let membership =
+            cluster::cluster_membership(&positions.means(), cluster_eps, args.cluster_min_points);
+        let clusters = cluster::boids_by_cluster(sim.boids(), &membership);
+        let written = recorder.time(Stage::Io, || {
+            cluster::write_clusters(Path::new(path), &clusters)?;
+            match &membership_csv {
+                Some(csv) => {
+                    let csv = Path::new(csv);
+                    fs::File::create(csv)
+                        .and_then(|file| {
+                            cluster::write_membership_csv(
+                                io::BufWriter::new(file),
+                                sim.boids(),
+                                &membership,
+                            )
+                        })
+                        .map_err(|err| Error::io("write", csv, err))
+                }
+                None => Ok(()),
+            }
+        });
+        if let Err(err) = written {
+            exit_with(err);
+        }
+        let found = clusters.keys().filter(|id| **id != cluster::NOISE).count();
+        info!("Found {found} clusters over {} frames", positions.frames());
+        if let Some(csv) = membership_csv {
+            recorder.artifact(csv);
+        }
+    }
+    let interrupted = interrupted.load(Ordering::Relaxed);
+    if interrupted {
+        let path = format!("{dir}/checkpoint.json");
+        match state::save(Path::new(&path), &snapshot(sim.boids(), sim.frame())) {
+            Ok(()) => recorder.artifact(path),
+            Err(err) => error!("Unable to write checkpoint {path}: {err}"),
         }
+        pbar.abandon_with_message(format!("interrupted at frame {}", sim.frame()));
+        recorder.stopped("interrupted");
+    } else if over_memory {
+        pbar.abandon_with_message(format!("stopped at frame {}, out of memory", sim.frame()));
+        recorder.stopped("over --max-memory");
+    } else if stalled {
+        pbar.abandon_with_message(format!("stopped at frame {}, a boid stalled", sim.frame()));
+        recorder.stopped("a boid stalled under --stall-policy error");
+    } else {
+        pbar.finish();
+    }
+    if let Some(pacer) = &pacer {
+        recorder.paced(pacer.achieved_fps(Instant::now()), pacer.overruns());
+    }
+    let summary = recorder.finish(sim.boids());
+    for line in summary.lines() {
+        info!("{line}");
+    }
+    if let Some(frame_hashes) = &frame_hashes {
+        info!("Combined frame hash: {:016x}", frame_hashes.combined());
+    }
+    if let (Some(path), Some(manifest)) = (&args.output_manifest, &manifest)
+        && let Err(err) = manifest.save(Path::new(path))
+    {
+        exit_with(err);
+    }
+    if let Some(path) = args.summary_file {
+        let written = fs::File::create(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| {
+                serde_json::to_writer_pretty(file, &summary).map_err(|err| err.to_string())
+            });
+        if let Err(err) = written {
+            error!("Unable to write {path}: {err}");
+            process::exit(1);
+        }
+    }
+    // Exiting skips destructors, and the trace isn't written out until then
+    #[cfg(feature = "trace")]
+    drop(trace_guard);
+    if interrupted {
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if over_memory {
+        process::exit(OVER_MEMORY_EXIT_CODE);
+    }
+    if stalled {
+        process::exit(1);
     }
 }
@@ -0,0 +1,105 @@
+//! A JSON index of the frames a run drew, so tools reading the output
+//! directory can tell which frame each file is and what the flock was doing
+//! in it without parsing file names.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Position};
+use crate::simulation::FrameStats;
+use crate::state::utc_timestamp;
+use crate::Parameters;
+
+/// The parameters of a run, and every frame it's written so far
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub parameters: Parameters,
+    pub frames: Vec<ManifestFrame>,
+}
+
+/// One frame written by a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestFrame {
+    pub frame: usize,
+    /// Where the frame was written, relative to the output directory
+    pub path: String,
+    /// When it was written, as an ISO 8601 UTC timestamp
+    pub written: String,
+    pub metrics: FrameMetrics,
+}
+
+/// The parts of `FrameStats` that describe the flock rather than the run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FrameMetrics {
+    pub population: usize,
+    pub polarization: f32,
+    pub mean_speed: f32,
+    pub speed_stddev: f32,
+    pub centroid: Vector2<f32>,
+    pub mean_neighbors: f32,
+    pub quality_score: f32,
+}
+
+impl From<&FrameStats> for FrameMetrics {
+    fn from(stats: &FrameStats) -> Self {
+        FrameMetrics {
+            population: stats.population,
+            polarization: stats.polarization,
+            mean_speed: stats.mean_speed,
+            speed_stddev: stats.speed_stddev,
+            centroid: stats.centroid,
+            mean_neighbors: stats.mean_neighbors,
+            quality_score: stats.quality_score,
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new(parameters: Parameters) -> Self {
+        Manifest {
+            parameters,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Records `path` as the file the step `stats` describes was drawn to,
+    /// just now
+    pub fn frame(&mut self, path: impl Into<String>, stats: &FrameStats) {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.frames.push(ManifestFrame {
+            frame: stats.frame,
+            path: path.into(),
+            written: utc_timestamp(seconds),
+            metrics: FrameMetrics::from(stats),
+        });
+    }
+
+    /// Writes the manifest to `path` by way of a `.tmp` file next to it, so
+    /// anything reading it sees the old manifest or the new one, never half
+    /// of one
+    pub fn save(&self, path: &Path) -> crate::Result<()> {
+        let mut tmp = PathBuf::from(path).into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let json = serde_json::to_vec_pretty(self).expect("manifests always serialize");
+        fs::write(&tmp, json).map_err(|err| Error::io("write", &tmp, err))?;
+        fs::rename(&tmp, path).map_err(|err| Error::io("write", path, err))
+    }
+
+    pub fn load(path: &Path) -> crate::Result<Manifest> {
+        let json = fs::read(path).map_err(|err| Error::io("read", path, err))?;
+        serde_json::from_slice(&json).map_err(|err| Error::Format {
+            path: path.to_path_buf(),
+            position: Some(Position {
+                line: err.line(),
+                column: err.column(),
+            }),
+            source: Box::new(err),
+        })
+    }
+}
@@ -0,0 +1,211 @@
+//! Live numbers about a run, served over HTTP in the Prometheus text format
+//! so long runs can be watched from Grafana or similar.
+//!
+//! The frame loop stores into atomics and the server thread only loads
+//! them, so neither ever waits on the other.
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::summary::Stage;
+
+const STAGES: [(Stage, &str); 4] = [
+    (Stage::Simulate, "simulate"),
+    (Stage::Rasterize, "rasterize"),
+    (Stage::Encode, "encode"),
+    (Stage::Io, "io"),
+];
+
+/// The latest values of everything served, shared between the frame loop
+/// and the server
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    frame: AtomicU64,
+    frames_rendered: AtomicU64,
+    population: AtomicU64,
+    // f32 and f64 values are kept as their bits
+    polarization: AtomicU32,
+    quality_score: AtomicU32,
+    encode_queue_depth: AtomicU64,
+    bytes_written: AtomicU64,
+    stage_seconds: [AtomicU64; 4],
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    /// Starts the clock frames per second are measured against
+    pub fn new() -> Self {
+        Metrics {
+            started: Instant::now(),
+            frame: AtomicU64::new(0),
+            frames_rendered: AtomicU64::new(0),
+            population: AtomicU64::new(0),
+            polarization: AtomicU32::new(0),
+            quality_score: AtomicU32::new(0),
+            encode_queue_depth: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            stage_seconds: Default::default(),
+        }
+    }
+
+    /// Records a finished frame and the state of the flock after it
+    pub fn frame(&self, frame: usize, population: usize, polarization: f32) {
+        self.frame.store(frame as u64, Ordering::Relaxed);
+        self.frames_rendered.fetch_add(1, Ordering::Relaxed);
+        self.population.store(population as u64, Ordering::Relaxed);
+        self.polarization
+            .store(polarization.to_bits(), Ordering::Relaxed);
+    }
+
+    /// The flock's `FlockingQualityScore` after the latest frame
+    pub fn set_quality_score(&self, score: f32) {
+        self.quality_score.store(score.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Frames waiting to be turned into PNGs
+    pub fn set_encode_queue_depth(&self, depth: usize) {
+        self.encode_queue_depth
+            .store(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Total bytes written so far
+    pub fn set_bytes_written(&self, bytes: u64) {
+        self.bytes_written.store(bytes, Ordering::Relaxed);
+    }
+
+    /// Total time spent in `stage` so far
+    pub fn set_stage_time(&self, stage: Stage, total: Duration) {
+        self.stage_seconds[stage as usize].store(total.as_secs_f64().to_bits(), Ordering::Relaxed);
+    }
+
+    /// Frames rendered per second since the run started
+    pub fn frames_per_second(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed == 0.0 {
+            return 0.0;
+        }
+        self.frames_rendered.load(Ordering::Relaxed) as f64 / elapsed
+    }
+
+    /// Everything in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            out.push_str(&format!(
+                "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"
+            ));
+        };
+        let load = |value: &AtomicU64| value.load(Ordering::Relaxed).to_string();
+        metric(
+            "boids_frame",
+            "gauge",
+            "The frame the run is on",
+            load(&self.frame),
+        );
+        metric(
+            "boids_frames_rendered_total",
+            "counter",
+            "Frames drawn and written so far",
+            load(&self.frames_rendered),
+        );
+        metric(
+            "boids_frames_per_second",
+            "gauge",
+            "Frames drawn per second since the run started",
+            self.frames_per_second().to_string(),
+        );
+        metric(
+            "boids_population",
+            "gauge",
+            "Boids in the flock",
+            load(&self.population),
+        );
+        metric(
+            "boids_polarization",
+            "gauge",
+            "How much the flock is heading the same way, 0 to 1",
+            f32::from_bits(self.polarization.load(Ordering::Relaxed)).to_string(),
+        );
+        metric(
+            "boids_quality_score",
+            "gauge",
+            "Polarization, connectedness and smoothness weighed into one number",
+            f32::from_bits(self.quality_score.load(Ordering::Relaxed)).to_string(),
+        );
+        metric(
+            "boids_encode_queue_depth",
+            "gauge",
+            "Frames waiting to be encoded",
+            load(&self.encode_queue_depth),
+        );
+        metric(
+            "boids_bytes_written_total",
+            "counter",
+            "Bytes of frames written",
+            load(&self.bytes_written),
+        );
+        out.push_str("# HELP boids_stage_seconds_total Time spent in each part of a frame\n");
+        out.push_str("# TYPE boids_stage_seconds_total counter\n");
+        for (stage, name) in STAGES {
+            let seconds =
+                f64::from_bits(self.stage_seconds[stage as usize].load(Ordering::Relaxed));
+            out.push_str(&format!(
+                "boids_stage_seconds_total{{stage=\"{name}\"}} {seconds}\n"
+            ));
+        }
+        out
+    }
+}
+
+/// Serves `metrics` at `/metrics` on `addr` from a thread of its own,
+/// returning the address it's listening on, which tells the port when
+/// `addr` asks for any free one
+pub fn serve(addr: impl ToSocketAddrs, metrics: Arc<Metrics>) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    thread::Builder::new()
+        .name(String::from("metrics"))
+        .spawn(move || {
+            // One bad client shouldn't stop the others being served
+            for stream in listener.incoming().flatten() {
+                let _ = respond(stream, &metrics);
+            }
+        })?;
+    Ok(local)
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(&stream);
+    reader.read_line(&mut request_line)?;
+    // The headers aren't needed, but are read so the client sees a response
+    // rather than a reset connection
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", metrics.render()),
+        _ => (
+            "404 Not Found",
+            String::from("Metrics are served at /metrics\n"),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
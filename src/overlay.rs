@@ -0,0 +1,214 @@
+//! Maps of the flock painted over the top of a frame once the boids are
+//! drawn, blended with what's under them.
+use std::collections::{BTreeMap, VecDeque};
+
+use image::{Rgb, RgbImage};
+use imageproc::drawing::draw_antialiased_line_segment_mut;
+use imageproc::pixelops::interpolate;
+use nalgebra::Vector2;
+
+use crate::boids::{Boid, SpatialGrid};
+use crate::cluster::NOISE;
+use crate::colour::ColourGradient;
+use crate::zone::SpeedLimitZone;
+
+/// Frames the brightest colour is kept for, so the scale doesn't jump about
+/// with every frame
+pub const ROLLING_FRAMES: usize = 10;
+
+/// Mixes `alpha` of `colour` into `pixel`
+pub fn blend(pixel: &mut Rgb<u8>, colour: Rgb<u8>, alpha: f32) {
+    let alpha = alpha.clamp(0.0, 1.0);
+    for (under, over) in pixel.0.iter_mut().zip(colour.0) {
+        *under = (*under as f32 * (1.0 - alpha) + over as f32 * alpha).round() as u8;
+    }
+}
+
+/// Paints each occupied grid cell by the mean number of flockmates its
+/// boids have, from cold where there are few to hot where there are the
+/// most seen over the last `ROLLING_FRAMES` frames
+#[derive(Debug, Clone)]
+pub struct NeighborCountMap {
+    pub alpha: f32,
+    pub gradient: ColourGradient,
+    recent_max: VecDeque<f32>,
+}
+
+impl NeighborCountMap {
+    pub fn new(alpha: f32) -> Self {
+        NeighborCountMap {
+            alpha,
+            gradient: ColourGradient::default(),
+            recent_max: VecDeque::with_capacity(ROLLING_FRAMES),
+        }
+    }
+
+    /// Mean of `neighbor_counts`, by place in the flock, over the boids in
+    /// each occupied cell of `grid`
+    pub fn cell_means(grid: &SpatialGrid, neighbor_counts: &[usize]) -> Vec<((u32, u32), f32)> {
+        grid.cells
+            .iter()
+            .filter(|(_, boids)| !boids.is_empty())
+            .map(|(cell, boids)| {
+                let total: usize = boids
+                    .iter()
+                    .map(|idx| neighbor_counts.get(*idx).copied().unwrap_or_default())
+                    .sum();
+                (*cell, total as f32 / boids.len() as f32)
+            })
+            .collect()
+    }
+
+    /// The highest cell mean over the last `ROLLING_FRAMES` frames drawn
+    pub fn rolling_max(&self) -> f32 {
+        self.recent_max.iter().copied().fold(0.0, f32::max)
+    }
+
+    pub fn draw(&mut self, img: &mut RgbImage, grid: &SpatialGrid, neighbor_counts: &[usize]) {
+        let means = NeighborCountMap::cell_means(grid, neighbor_counts);
+        if self.recent_max.len() == ROLLING_FRAMES {
+            self.recent_max.pop_front();
+        }
+        self.recent_max
+            .push_back(means.iter().map(|(_, mean)| *mean).fold(0.0, f32::max));
+        let max = self.rolling_max();
+        let (width, height) = img.dimensions();
+        for ((cell_x, cell_y), mean) in means {
+            let colour = self.gradient.at(if max > 0.0 { mean / max } else { 0.0 });
+            let x_range = (cell_x as f32 * grid.cell_w) as u32
+                ..(((cell_x + 1) as f32 * grid.cell_w) as u32).min(width);
+            let y_range = (cell_y as f32 * grid.cell_h) as u32
+                ..(((cell_y + 1) as f32 * grid.cell_h) as u32).min(height);
+            for y in y_range {
+                for x in x_range.clone() {
+                    blend(img.get_pixel_mut(x, y), colour.into(), self.alpha);
+                }
+            }
+        }
+    }
+}
+
+/// How much of a flock's colour its hull is drawn with
+pub const HULL_ALPHA: f32 = 0.5;
+
+/// The smallest convex polygon around a flock, drawn as an outline in the
+/// flock's mean colour
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlockConvexHull {
+    pub cluster: i32,
+    /// Corners, anticlockwise on screen
+    pub hull: Vec<Vector2<f32>>,
+    pub colour: Rgb<u8>,
+}
+
+impl FlockConvexHull {
+    /// A hull for every cluster in `membership` of at least 3 boids. Noise
+    /// isn't a flock, so has no hull.
+    pub fn from_membership(boids: &[Boid], membership: &[i32]) -> Vec<Self> {
+        let mut flocks: BTreeMap<i32, Vec<&Boid>> = BTreeMap::new();
+        for (boid, cluster) in boids.iter().zip(membership) {
+            if *cluster != NOISE {
+                flocks.entry(*cluster).or_default().push(boid);
+            }
+        }
+        flocks
+            .into_iter()
+            .filter(|(_, flock)| flock.len() >= 3)
+            .map(|(cluster, flock)| {
+                let positions: Vec<Vector2<f32>> = flock.iter().map(|boid| boid.pos).collect();
+                let colour = Rgb(std::array::from_fn(|channel| {
+                    let total: u32 = flock.iter().map(|boid| boid.colour.0[channel] as u32).sum();
+                    (total / flock.len() as u32) as u8
+                }));
+                FlockConvexHull {
+                    cluster,
+                    hull: convex_hull(&positions),
+                    colour,
+                }
+            })
+            .collect()
+    }
+
+    /// Area inside the hull. A sudden change from one frame to the next is
+    /// a flock splitting or merging.
+    pub fn area(&self) -> f32 {
+        let edges = self.hull.iter().zip(self.hull.iter().cycle().skip(1));
+        edges.map(|(a, b)| a.x * b.y - b.x * a.y).sum::<f32>().abs() / 2.0
+    }
+
+    /// Outlines the hull, `alpha` of the way from the frame to the colour
+    pub fn draw(&self, img: &mut RgbImage, alpha: f32) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        let corner = |point: &Vector2<f32>| (point.x.round() as i32, point.y.round() as i32);
+        for (from, to) in self.hull.iter().zip(self.hull.iter().cycle().skip(1)) {
+            draw_antialiased_line_segment_mut(
+                img,
+                corner(from),
+                corner(to),
+                self.colour,
+                |line, under, weight| interpolate(line, under, weight * alpha),
+            );
+        }
+    }
+}
+
+/// Andrew's monotone chain over `points`, giving the corners anticlockwise
+/// on screen from the leftmost. Points on an edge aren't corners, and
+/// fewer than 3 points that aren't all in a line give a line or a point.
+pub fn convex_hull(points: &[Vector2<f32>]) -> Vec<Vector2<f32>> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+    // Above zero when o -> a -> b turns clockwise on screen, as y grows down
+    let cross = |o: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+    let mut hull: Vec<Vector2<f32>> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) >= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point of each pass starts the other
+        hull.pop();
+    }
+    hull
+}
+
+/// How much of its colour a speed zone is drawn with
+pub const SPEED_ZONE_ALPHA: f32 = 0.25;
+/// Zones that slow boids down
+pub const SLOW_ZONE_COLOUR: Rgb<u8> = Rgb([0, 128, 128]);
+/// Zones that let boids go faster than `max_speed`
+pub const BOOST_ZONE_COLOUR: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// Fills each of `zones` with `alpha` of teal, or red if it lets boids go
+/// faster than `max_speed`. The parts outside `img` are left off.
+pub fn draw_speed_zones(img: &mut RgbImage, zones: &[SpeedLimitZone], max_speed: f32, alpha: f32) {
+    let (width, height) = img.dimensions();
+    for zone in zones {
+        let colour = if zone.is_boost(max_speed) {
+            BOOST_ZONE_COLOUR
+        } else {
+            SLOW_ZONE_COLOUR
+        };
+        // Pixels whose top left corner is inside, as with `contains`
+        let (x0, y0, x1, y1) = zone.rect;
+        let span = |from: f32, to: f32, side: u32| {
+            (from.max(0.0).ceil() as u32).min(side)..(to.max(0.0).ceil() as u32).min(side)
+        };
+        for y in span(y0, y1, height) {
+            for x in span(x0, x1, width) {
+                blend(img.get_pixel_mut(x, y), colour, alpha);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Holding a run to a steady number of frames a second of wall clock time,
+//! rather than letting it go as fast as it can. Every frame is due a whole
+//! number of frame times after the run started, so time lost to sleeping a
+//! little too long or to a slow frame is made up over the frames after it,
+//! rather than adding up.
+use std::time::{Duration, Instant};
+
+/// How a frame finished against when it was due
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    /// Finished early, with this long to wait until it's due
+    Early(Duration),
+    /// Finished this long after it was due
+    Late(Duration),
+}
+
+/// Keeps track of when each frame of a run is due
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    started: Instant,
+    interval: Duration,
+    frames: u32,
+    overruns: usize,
+}
+
+impl FramePacer {
+    /// Frames due `fps` times a second from `started`. Every frame is due
+    /// as soon as it starts with an `fps` of 0.
+    pub fn new(fps: u32, started: Instant) -> Self {
+        let interval = match fps {
+            0 => Duration::ZERO,
+            fps => Duration::from_secs(1) / fps,
+        };
+        FramePacer {
+            started,
+            interval,
+            frames: 0,
+            overruns: 0,
+        }
+    }
+
+    /// When the frame being worked on is due to be finished
+    pub fn due(&self) -> Instant {
+        self.started + self.interval * (self.frames + 1)
+    }
+
+    /// Whether the frame being worked on is already late at `now`, so
+    /// anything left to do for it puts the run further behind
+    pub fn behind(&self, now: Instant) -> bool {
+        now > self.due()
+    }
+
+    /// Counts the frame being worked on as finished at `now`
+    pub fn finish(&mut self, now: Instant) -> Pace {
+        let due = self.due();
+        self.frames += 1;
+        if now > due {
+            self.overruns += 1;
+            Pace::Late(now - due)
+        } else {
+            Pace::Early(due - now)
+        }
+    }
+
+    /// Frames finished so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Frames finished after they were due so far
+    pub fn overruns(&self) -> usize {
+        self.overruns
+    }
+
+    /// Frames finished a second from the start to `now`
+    pub fn achieved_fps(&self, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.started).as_secs_f64();
+        if elapsed > 0.0 {
+            self.frames as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! rayon's parallel iterators with the `parallel` feature, and plain
+//! iterators in their place without it, so the flock is stepped by the same
+//! code either way.
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) trait SerialSlice<T> {
+    fn par_iter(&self) -> std::slice::Iter<'_, T>;
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<T> SerialSlice<T> for [T] {
+    fn par_iter(&self) -> std::slice::Iter<'_, T> {
+        self.iter()
+    }
+}
+
+#[cfg(feature = "parallel")]
+pub(crate) use rayon::current_num_threads;
+
+#[cfg(not(feature = "parallel"))]
+pub(crate) fn current_num_threads() -> usize {
+    1
+}
@@ -0,0 +1,396 @@
+//! Building `Parameters` a field at a time, and the checks every set has to
+//! pass before it's run, however it was made.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use log::warn;
+
+use crate::boids::StallPolicy;
+use crate::boundary::BoundaryMode;
+use crate::error::{Error, Position};
+use crate::schedule::RandomSeedSchedule;
+use crate::zone::SpeedLimitZone;
+use crate::{Parameters, World};
+
+/// A way a set of parameters can't be run
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// A speed, range or factor that's negative, infinite or NaN
+    Invalid {
+        field: &'static str,
+        value: f32,
+    },
+    SpeedRange {
+        min_speed: f32,
+        max_speed: f32,
+    },
+    ProtectedRange {
+        protected_range: f32,
+        visible_range: f32,
+    },
+    CellSize(f32),
+    /// Margins on opposite sides that meet or cross, leaving nowhere for
+    /// boids to fly without being turned
+    Margin {
+        margin: u32,
+        width: u32,
+        height: u32,
+    },
+    /// A turn so strong a single frame of it is more than the top speed,
+    /// throwing boids back off the edge rather than turning them
+    TurnFactor {
+        turn_factor: f32,
+        max_speed: f32,
+    },
+}
+
+impl ValidationError {
+    /// All of `errors` in one line, as a run that breaks several rules
+    /// reports them
+    pub fn join(errors: &[ValidationError]) -> String {
+        errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Invalid { field, value } => {
+                write!(f, "{field} must be a finite number of at least 0, not {value}")
+            }
+            ValidationError::SpeedRange {
+                min_speed,
+                max_speed,
+            } => write!(
+                f,
+                "the min speed of {min_speed} is more than the max speed of {max_speed}, lower it to at most {max_speed}"
+            ),
+            ValidationError::ProtectedRange {
+                protected_range,
+                visible_range,
+            } => write!(
+                f,
+                "the protected range of {protected_range} is more than the visible range of {visible_range}, lower it to at most {visible_range}"
+            ),
+            ValidationError::CellSize(cell_size) => write!(
+                f,
+                "the cell size must be more than 0, not {cell_size}, set it to the visible range"
+            ),
+            ValidationError::Margin {
+                margin,
+                width,
+                height,
+            } => write!(
+                f,
+                "a margin of {margin} leaves no room in the middle of a {width}x{height} world, lower it to at most {}",
+                width.min(height).saturating_sub(1) / 2
+            ),
+            ValidationError::TurnFactor {
+                turn_factor,
+                max_speed,
+            } => write!(
+                f,
+                "the turn factor of {turn_factor} is more than the max speed of {max_speed}, lower it to at most {max_speed}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Parameters {
+    /// Starts from the default parameters
+    pub fn builder() -> ParametersBuilder {
+        ParametersBuilder::default()
+    }
+
+    /// Checks everything that has to hold for these to be run, giving every
+    /// problem found rather than just the first. The margin is only checked
+    /// against a `world`, if one is given. Rules between two fields skip any
+    /// field that's already invalid on its own, so each mistake is only
+    /// reported once.
+    pub fn validate(&self, world: Option<&World>) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let finite = [
+            ("max_speed", self.max_speed),
+            ("min_speed", self.min_speed),
+            ("visible_range", self.visible_range),
+            ("protected_range", self.protected_range),
+            ("avoid_factor", self.avoid_factor),
+            ("matching_factor", self.matching_factor),
+            ("centering_factor", self.centering_factor),
+            ("turn_factor", self.turn_factor),
+            ("global_centering_factor", self.global_centering_factor),
+            ("perturbation_fraction", self.perturbation_fraction),
+            ("perturbation_strength", self.perturbation_strength),
+        ];
+        for (field, value) in finite {
+            if !valid(value) {
+                errors.push(ValidationError::Invalid { field, value });
+            }
+        }
+        if valid(self.min_speed) && valid(self.max_speed) && self.min_speed > self.max_speed {
+            errors.push(ValidationError::SpeedRange {
+                min_speed: self.min_speed,
+                max_speed: self.max_speed,
+            });
+        }
+        for zone in &self.speed_zones {
+            let speeds = [
+                ("speed_zones.max_speed", Some(zone.max_speed)),
+                ("speed_zones.min_speed", zone.min_speed),
+            ];
+            for (field, value) in speeds {
+                if let Some(value) = value
+                    && !valid(value)
+                {
+                    errors.push(ValidationError::Invalid { field, value });
+                }
+            }
+            if let Some(min_speed) = zone.min_speed
+                && valid(min_speed)
+                && valid(zone.max_speed)
+                && min_speed > zone.max_speed
+            {
+                errors.push(ValidationError::SpeedRange {
+                    min_speed,
+                    max_speed: zone.max_speed,
+                });
+            }
+        }
+        if valid(self.protected_range)
+            && valid(self.visible_range)
+            && self.protected_range > self.visible_range
+        {
+            errors.push(ValidationError::ProtectedRange {
+                protected_range: self.protected_range,
+                visible_range: self.visible_range,
+            });
+        }
+        if valid(self.turn_factor) && valid(self.max_speed) && self.turn_factor > self.max_speed {
+            errors.push(ValidationError::TurnFactor {
+                turn_factor: self.turn_factor,
+                max_speed: self.max_speed,
+            });
+        }
+        if !(self.cell_size > 0.0 && self.cell_size.is_finite()) {
+            errors.push(ValidationError::CellSize(self.cell_size));
+        }
+        if let Some(world) = world
+            && world.margins_meet(self.margin)
+        {
+            let (width, height) = world.pixels();
+            errors.push(ValidationError::Margin {
+                margin: self.margin,
+                width,
+                height,
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Reads parameters from a TOML file, checking them as `build` would.
+    /// Any left out are the defaults, but ones that aren't parameters at
+    /// all, likely misspelt, are refused.
+    pub fn load(path: &Path) -> crate::Result<Parameters> {
+        let text = fs::read_to_string(path).map_err(|err| Error::io("read", path, err))?;
+        let invalid = |err: toml::de::Error| Error::Format {
+            path: path.to_path_buf(),
+            position: err.span().map(|span| Position::at(&text, span.start)),
+            source: Box::new(err),
+        };
+        let mut unknown = Vec::new();
+        let parameters: Parameters = serde_ignored::deserialize(
+            toml::Deserializer::parse(&text).map_err(invalid)?,
+            |field| unknown.push(field.to_string()),
+        )
+        .map_err(invalid)?;
+        if !unknown.is_empty() {
+            return Err(Error::Format {
+                path: path.to_path_buf(),
+                position: None,
+                source: format!("unknown parameters {}", unknown.join(", ")).into(),
+            });
+        }
+        Ok(ParametersBuilder::from(parameters).build()?)
+    }
+
+    /// `saved` with every field these change from `baseline` taken from
+    /// these instead, such as parameters loaded with a flock under those
+    /// given on the command line, with the command line's defaults as the
+    /// baseline
+    pub fn overriding(&self, saved: &Parameters, baseline: &Parameters) -> Parameters {
+        let fields = |parameters: &Parameters| match serde_json::to_value(parameters) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            _ => unreachable!("parameters serialize to an object"),
+        };
+        let baseline = fields(baseline);
+        let mut merged = fields(saved);
+        for (name, value) in fields(self) {
+            if baseline.get(&name) != Some(&value) {
+                merged.insert(name, value);
+            }
+        }
+        serde_json::from_value(serde_json::Value::Object(merged))
+            .expect("parameters read back what they write")
+    }
+
+    /// These parameters with the number `field` set to `value`. Whole number
+    /// fields need a whole `value`, switches 0 or 1, and the boundary, seed
+    /// schedule, speed zones and stall policy aren't numbers so can't be set
+    /// this way.
+    pub fn with_field(&self, field: &str, value: f32) -> Result<Parameters, String> {
+        let serde_json::Value::Object(mut fields) =
+            serde_json::to_value(self).expect("parameters always serialize")
+        else {
+            unreachable!("parameters serialize to an object")
+        };
+        let whole = value.fract() == 0.0;
+        let new_value = match fields.get(field) {
+            None => return Err(format!("there's no parameter named {field}")),
+            Some(serde_json::Value::Bool(_)) if value == 0.0 || value == 1.0 => {
+                serde_json::Value::Bool(value == 1.0)
+            }
+            Some(serde_json::Value::Bool(_)) => {
+                return Err(format!("{field} is a switch, so 0 or 1, not {value}"));
+            }
+            Some(serde_json::Value::Number(number)) if number.is_f64() => {
+                serde_json::Value::from(f64::from(value))
+            }
+            // Unset is only ever a whole number left out
+            Some(serde_json::Value::Number(_) | serde_json::Value::Null) if whole => {
+                serde_json::Value::from(value as i64)
+            }
+            Some(serde_json::Value::Number(_) | serde_json::Value::Null) => {
+                return Err(format!("{field} is a whole number, not {value}"));
+            }
+            Some(_) => return Err(format!("{field} isn't a number")),
+        };
+        fields.insert(field.to_string(), new_value);
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|err| format!("{field} can't be {value}: {err}"))
+    }
+
+    /// Whether the 3x3 cells searched around a boid can fall short of its
+    /// visible range, so some boids it should see are missed. That's
+    /// allowed, as it can be a worthwhile trade for speed.
+    pub fn misses_visible_boids(&self) -> bool {
+        self.cell_size < self.visible_range
+    }
+
+    /// Whether flocks flying by these and `other` can share a grid: the
+    /// cell sizes match, and every boid of either sees no further than a
+    /// cell, so the 3x3 cells searched around it cover its visible range.
+    /// Both are checked to within `tolerance` of the larger value, so 0.01
+    /// allows 1% either way. The size of the world isn't part of the
+    /// parameters, so has to be checked separately.
+    pub fn compatible_with(&self, other: &Parameters, tolerance: f32) -> bool {
+        let cell_size = self.cell_size.min(other.cell_size);
+        let slack = self.cell_size.max(other.cell_size) * tolerance;
+        (self.cell_size - other.cell_size).abs() <= slack
+            && self.visible_range.max(other.visible_range) <= cell_size + slack
+    }
+}
+
+// A speed, range or factor that can be run at all
+fn valid(value: f32) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+/// Sets `Parameters` a field at a time, checking them together at the end
+///
+/// ```
+/// let parameters = boids::Parameters::builder()
+///     .visible_range(30.0)
+///     .protected_range(4.0)
+///     .cell_size(33.0)
+///     .build()?;
+/// # Ok::<(), Vec<boids::parameters::ValidationError>>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ParametersBuilder {
+    parameters: Parameters,
+    world: Option<World>,
+}
+
+impl From<Parameters> for ParametersBuilder {
+    fn from(parameters: Parameters) -> Self {
+        ParametersBuilder {
+            parameters,
+            world: None,
+        }
+    }
+}
+
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Sets `", stringify!($field), "`")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.parameters.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+impl ParametersBuilder {
+    setters!(
+        max_speed: f32,
+        min_speed: f32,
+        margin: u32,
+        visible_range: f32,
+        protected_range: f32,
+        avoid_factor: f32,
+        matching_factor: f32,
+        centering_factor: f32,
+        turn_factor: f32,
+        cell_size: f32,
+        draw_radius: i32,
+        update_threshold: f32,
+        global_centering_factor: f32,
+        render_smoothing: f32,
+        max_neighbors_for_early_exit: Option<usize>,
+        aspect_cells: bool,
+        boundary: BoundaryMode,
+        colour_rotation_speed: f32,
+        per_boid_colour_rotation: bool,
+        voronoi_neighbors: bool,
+        heading_histogram_bins: usize,
+        seed_schedule: RandomSeedSchedule,
+        draw_flock_hulls: bool,
+        speed_zones: Vec<SpeedLimitZone>,
+        rewind_depth: usize,
+        perturbation_fraction: f32,
+        perturbation_strength: f32,
+        trail_length: usize,
+        stall_policy: StallPolicy,
+    );
+
+    /// Checks the margin against a `width` x `height` world too
+    pub fn world(mut self, width: u32, height: u32) -> Self {
+        self.world = Some(World::from_pixels(width, height));
+        self
+    }
+
+    /// The parameters, if they pass `Parameters::validate`
+    pub fn build(self) -> Result<Parameters, Vec<ValidationError>> {
+        self.parameters.validate(self.world.as_ref())?;
+        if self.parameters.misses_visible_boids() {
+            warn!(
+                "A cell size of {} is smaller than the visible range of {}, so some visible boids will be missed",
+                self.parameters.cell_size, self.parameters.visible_range
+            );
+        }
+        Ok(self.parameters)
+    }
+}
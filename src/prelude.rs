@@ -0,0 +1,39 @@
+//! The names most code running a flock needs, in one `use`:
+//!
+//! ```
+//! use boids::prelude::*;
+//!
+//! let parameters = Parameters::builder()
+//!     .max_speed(4.0)
+//!     .visible_range(30.0)
+//!     .cell_size(30.0)
+//!     .world(400, 300)
+//!     .build()
+//!     .map_err(boids::Error::from)?;
+//! let config = SimulationConfig {
+//!     seed: 1,
+//!     colour_mode: ColourMode::Speed,
+//!     ..SimulationConfig::new(World::from_pixels(400, 300), parameters, 50)
+//! };
+//! let mut sim = Simulation::new(config)?;
+//! let stats: FrameStats = sim.run_for(10).expect("ten frames were run");
+//! assert_eq!(stats.frame, 9);
+//! assert_eq!(stats.population, 50);
+//! assert!(stats.mean_speed <= 4.0 * 1.01);
+//! let first: &Boid = &sim.boids()[0];
+//! assert!(sim.world().contains(first.pos));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! The grid, the save file layouts and the parts of steering are left out,
+//! as only code reaching into the simulation needs them. So is
+//! `boids::Result`, which would shadow the standard one.
+pub use crate::boundary::BoundaryMode;
+pub use crate::colour::{Colour, ColourMode};
+pub use crate::parameters::ParametersBuilder;
+#[cfg(feature = "render")]
+pub use crate::render::Renderer;
+pub use crate::{
+    Boid, BoidId, Error, FrameStats, Parameters, Simulation, SimulationConfig, SimulationError,
+    World,
+};
@@ -0,0 +1,163 @@
+//! A single number for how well a flock is flocking, for watching a run or
+//! comparing parameters without weighing up polarization, neighbour counts
+//! and how smoothly boids turn separately.
+//!
+//! ```text
+//! Q = polarization weight * polarization
+//!   + connectedness weight * (1 - mean isolation)
+//!   + smoothness weight * (1 - mean jerk / max jerk)
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use nalgebra::Vector2;
+
+use crate::boids::{polarization, Boid};
+
+/// How much each part counts towards the score. With weights adding up to
+/// 1, as the defaults do, the score runs from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityWeights {
+    pub polarization: f32,
+    pub connectedness: f32,
+    pub smoothness: f32,
+    /// Mean jerk, in pixels per frame cubed, at or above which the flock
+    /// counts as not turning smoothly at all
+    pub max_jerk: f32,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        QualityWeights {
+            polarization: 0.4,
+            connectedness: 0.3,
+            smoothness: 0.3,
+            max_jerk: 1.0,
+        }
+    }
+}
+
+impl fmt::Display for QualityWeights {
+    /// Writes the same form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.polarization, self.connectedness, self.smoothness, self.max_jerk
+        )
+    }
+}
+
+impl FromStr for QualityWeights {
+    type Err = String;
+
+    /// Parses `POLARIZATION,CONNECTEDNESS,SMOOTHNESS[,MAX_JERK]`, e.g.
+    /// `0.5,0.5,0,1`, keeping the default max jerk if it's left off
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid quality weights {s}: {reason}");
+        let values = s
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite() && *value >= 0.0)
+                    .ok_or_else(|| invalid("each one has to be a number, 0 or more"))
+            })
+            .collect::<Result<Vec<f32>, String>>()?;
+        let (weights, max_jerk) = match values[..] {
+            [polarization, connectedness, smoothness] => (
+                [polarization, connectedness, smoothness],
+                QualityWeights::default().max_jerk,
+            ),
+            [polarization, connectedness, smoothness, max_jerk] => {
+                ([polarization, connectedness, smoothness], max_jerk)
+            }
+            _ => return Err(invalid("expected three weights and an optional max jerk")),
+        };
+        if max_jerk == 0.0 {
+            return Err(invalid("the max jerk has to be more than 0"));
+        }
+        let [polarization, connectedness, smoothness] = weights;
+        Ok(QualityWeights {
+            polarization,
+            connectedness,
+            smoothness,
+            max_jerk,
+        })
+    }
+}
+
+/// Mean over the flock of `exp(-neighbours)`, 1 when no boid has a
+/// flockmate and falling towards 0 as they all gain some. 0 for no boids.
+pub fn mean_isolation(neighbor_counts: &[usize]) -> f32 {
+    if neighbor_counts.is_empty() {
+        return 0.0;
+    }
+    neighbor_counts
+        .iter()
+        .map(|count| (-(*count as f32)).exp())
+        .sum::<f32>()
+        / neighbor_counts.len() as f32
+}
+
+/// Scores a flock frame by frame. The jerk, how much each boid's
+/// acceleration changed since the frame before, needs the velocities of the
+/// last two frames, so it counts as 0 until there have been three. Boids are
+/// followed by their place in the flock, and the history starts again if
+/// the population changes.
+#[derive(Debug, Clone, Default)]
+pub struct FlockingQualityScore {
+    pub weights: QualityWeights,
+    // Velocity and, once known, acceleration of each boid on the last frame
+    velocities: Vec<Vector2<f32>>,
+    accelerations: Option<Vec<Vector2<f32>>>,
+}
+
+impl FlockingQualityScore {
+    pub fn new(weights: QualityWeights) -> Self {
+        FlockingQualityScore {
+            weights,
+            ..Default::default()
+        }
+    }
+
+    /// Scores the flock after a step, given how many flockmates each boid
+    /// steered by, and remembers its velocities for the next frame's jerk
+    pub fn update(&mut self, boids: &[Boid], neighbor_counts: &[usize]) -> f32 {
+        let velocities: Vec<Vector2<f32>> = boids.iter().map(Boid::velocity).collect();
+        let mut jerk = 0.0;
+        if velocities.len() != self.velocities.len() {
+            self.accelerations = None;
+        } else {
+            let accelerations: Vec<Vector2<f32>> = velocities
+                .iter()
+                .zip(&self.velocities)
+                .map(|(vel, previous)| vel - previous)
+                .collect();
+            if let Some(previous) = &self.accelerations
+                && !accelerations.is_empty()
+            {
+                jerk = accelerations
+                    .iter()
+                    .zip(previous)
+                    .map(|(acc, previous)| (acc - previous).norm())
+                    .sum::<f32>()
+                    / accelerations.len() as f32;
+            }
+            self.accelerations = Some(accelerations);
+        }
+        self.velocities = velocities;
+        self.score(polarization(boids), mean_isolation(neighbor_counts), jerk)
+    }
+
+    /// The score from its parts, with the jerk capped at `max_jerk`
+    pub fn score(&self, polarization: f32, mean_isolation: f32, mean_jerk: f32) -> f32 {
+        let weights = &self.weights;
+        let jerk = (mean_jerk / weights.max_jerk).clamp(0.0, 1.0);
+        weights.polarization * polarization
+            + weights.connectedness * (1.0 - mean_isolation)
+            + weights.smoothness * (1.0 - jerk)
+    }
+}
@@ -0,0 +1,137 @@
+//! Ways of picking out the boids near a point from the spatial grid.
+use delaunator::{triangulate, Point};
+use nalgebra::Vector2;
+
+use crate::boids::{populate_grid, Boid, SpatialGrid};
+
+/// A flock bucketed into a spatial grid, for finding the boids near a point
+/// without checking every one. Steering finds flockmates through one too.
+///
+/// ```
+/// # use boids::Boid;
+/// # use boids::colour::Colour;
+/// # use nalgebra::Vector2;
+/// # let boid = |x, y| Boid::new(0, Vector2::new(x, y), Vector2::zeros(), Colour::BLACK);
+/// let boids = [boid(10.0, 10.0), boid(40.0, 10.0), boid(100.0, 100.0)];
+/// let grid = boids::query::Grid::build(&boids, 20.0);
+/// let mut near: Vec<usize> = grid.query_radius(Vector2::new(20.0, 20.0), 50.0).collect();
+/// near.sort_unstable();
+/// assert_eq!(near, [0, 1]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Grid<'a> {
+    boids: &'a [Boid],
+    cells: SpatialGrid,
+}
+
+impl<'a> Grid<'a> {
+    /// A grid of `cell_size` squares, reaching from the origin out to the
+    /// furthest boid
+    pub fn build(boids: &'a [Boid], cell_size: f32) -> Self {
+        let (width, height) = boids.iter().fold((1, 1), |(width, height), boid| {
+            (
+                width.max(boid.pos.x.max(0.0) as u32 + 1),
+                height.max(boid.pos.y.max(0.0) as u32 + 1),
+            )
+        });
+        Grid::new(boids, populate_grid(boids, cell_size, width, height))
+    }
+
+    /// Searches `cells`, which has to have been filled from `boids`
+    pub fn new(boids: &'a [Boid], cells: SpatialGrid) -> Self {
+        Grid { boids, cells }
+    }
+
+    pub fn boids(&self) -> &'a [Boid] {
+        self.boids
+    }
+
+    pub fn cells(&self) -> &SpatialGrid {
+        &self.cells
+    }
+
+    /// The boids in a cell, by their place in the flock. Cells outside the
+    /// grid are empty.
+    pub fn query_cell(&self, cx: u32, cy: u32) -> &[usize] {
+        self.cells.get_cell(cx, cy).unwrap_or_default()
+    }
+
+    /// Every boid closer than `radius` to `pos`, not counting exactly
+    /// `radius`, in no particular order. However large the radius, every
+    /// cell it reaches is searched.
+    pub fn query_radius(&self, pos: Vector2<f32>, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let reach = Vector2::repeat(radius);
+        let (x0, y0) = self.cells.cell_at(pos - reach);
+        let (x1, y1) = self.cells.cell_at(pos + reach);
+        let x1 = x1.min(self.cells.grid_cols.saturating_sub(1));
+        let y1 = y1.min(self.cells.grid_rows.saturating_sub(1));
+        let radius_squared = radius * radius;
+        (x0..=x1)
+            .flat_map(move |cx| (y0..=y1).map(move |cy| (cx, cy)))
+            .flat_map(|(cx, cy)| self.query_cell(cx, cy))
+            .copied()
+            .filter(move |idx| (self.boids[*idx].pos - pos).norm_squared() < radius_squared)
+    }
+}
+
+/// Neighbour searches that need more than the 3x3 cells steering looks at
+pub struct SpatialQuery;
+
+impl SpatialQuery {
+    /// The boids sharing a Delaunay edge with a point at `pos`, which are the
+    /// ones whose Voronoi cells touch its own. Only the 5x5 cells around
+    /// `pos` are triangulated, so boids further out than that are never
+    /// neighbours. Boids exactly at `pos`, including the one it's asked for,
+    /// are left out.
+    pub fn voronoi_cell(pos: Vector2<f32>, grid: &SpatialGrid, boids: &[Boid]) -> Vec<usize> {
+        let (cell_x, cell_y) = grid.cell_at(pos);
+        let mut candidates: Vec<usize> = Vec::new();
+        for x_offset in -2..=2 {
+            for y_offset in -2..=2 {
+                let near_boids = grid.get_cell(
+                    cell_x.wrapping_add_signed(x_offset),
+                    cell_y.wrapping_add_signed(y_offset),
+                );
+                candidates.extend(
+                    near_boids
+                        .unwrap_or_default()
+                        .iter()
+                        .filter(|idx| boids[**idx].pos != pos),
+                );
+            }
+        }
+        // `pos` goes first, so is point 0 of the triangulation
+        let points: Vec<Point> = std::iter::once(pos)
+            .chain(candidates.iter().map(|idx| boids[*idx].pos))
+            .map(|point| Point {
+                x: point.x as f64,
+                y: point.y as f64,
+            })
+            .collect();
+        let triangulation = triangulate(&points);
+        let mut neighbours: Vec<usize> = if triangulation.triangles.is_empty() {
+            // Everything is on a line, given in order along it as the hull
+            let hull = &triangulation.hull;
+            match hull.iter().position(|point| *point == 0) {
+                Some(at) => [at.checked_sub(1), Some(at + 1)]
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|at| hull.get(at))
+                    .map(|point| candidates[point - 1])
+                    .collect(),
+                None => Vec::new(),
+            }
+        } else {
+            triangulation
+                .triangles
+                .chunks_exact(3)
+                .filter(|triangle| triangle.contains(&0))
+                .flat_map(|triangle| triangle.iter().filter(|point| **point != 0))
+                .map(|point| candidates[point - 1])
+                .collect()
+        };
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+}
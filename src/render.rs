@@ -0,0 +1,178 @@
+//! Drawing the flock into frames. Overlays go over the top of what's drawn
+//! here, by way of `Renderer::image_mut`.
+use std::path::Path;
+
+use image::{Rgb, RgbImage};
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+pub use crate::colour::colour_by_width;
+use crate::error::Error;
+use crate::Parameters;
+
+/// Draws `boids` in their own colours over a `width` x `height` frame of
+/// raw RGB bytes, a row at a time, as `Renderer` would draw them with
+/// `params.draw_radius`. Nothing is cleared first.
+///
+/// Panics if `buf` is smaller than `width * height * 3` bytes.
+pub fn render_boids_to_buffer(
+    boids: &[Boid],
+    buf: &mut [u8],
+    width: u32,
+    height: u32,
+    params: &Parameters,
+) {
+    let needed = width as usize * height as usize * 3;
+    assert!(
+        buf.len() >= needed,
+        "a {width}x{height} frame needs {needed} bytes, not {}",
+        buf.len()
+    );
+    for boid in boids {
+        draw_boid(
+            buf,
+            width,
+            height,
+            boid.pos,
+            params.draw_radius,
+            boid.colour.into(),
+        );
+    }
+}
+
+/// Draws `boids` over `img` like `render_boids_to_buffer`
+pub fn render_boids(boids: &[Boid], img: &mut RgbImage, params: &Parameters) {
+    let (width, height) = img.dimensions();
+    render_boids_to_buffer(boids, img, width, height, params);
+}
+
+/// Owns the frame the boids are drawn into, and how they're drawn. Each
+/// render starts again from the background.
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    img: RgbImage,
+    pub background: Rgb<u8>,
+    /// Boids are filled circles of this radius, a single pixel at 0
+    pub draw_radius: i32,
+    /// Draws every boid in this colour rather than its own
+    pub colour: Option<Rgb<u8>>,
+}
+
+impl Renderer {
+    /// A black `width` x `height` frame
+    pub fn new(width: u32, height: u32, draw_radius: i32) -> Self {
+        Renderer {
+            img: RgbImage::new(width, height),
+            background: Rgb([0, 0, 0]),
+            draw_radius,
+            colour: None,
+        }
+    }
+
+    pub fn with_background(mut self, background: Rgb<u8>) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn with_colour(mut self, colour: Rgb<u8>) -> Self {
+        self.colour = Some(colour);
+        self
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.img.dimensions()
+    }
+
+    /// Draws `boids` where they are onto a fresh frame
+    pub fn render(&mut self, boids: &[Boid]) -> &RgbImage {
+        self.clear();
+        for boid in boids {
+            self.draw_boid(boid, boid.pos);
+        }
+        &self.img
+    }
+
+    /// Draws `boids` at `positions` rather than where they are, such as
+    /// smoothed positions, onto a fresh frame
+    pub fn render_at(&mut self, boids: &[Boid], positions: &[Vector2<f32>]) -> &RgbImage {
+        self.clear();
+        self.draw_at(boids, positions);
+        &self.img
+    }
+
+    /// Draws `boids` at `positions` over whatever is already in the frame,
+    /// for anything that has to go underneath them
+    pub fn draw_at(&mut self, boids: &[Boid], positions: &[Vector2<f32>]) {
+        for (boid, pos) in boids.iter().zip(positions) {
+            self.draw_boid(boid, *pos);
+        }
+    }
+
+    /// Fills the frame with the background
+    pub fn clear(&mut self) {
+        for pixel in self.img.pixels_mut() {
+            *pixel = self.background;
+        }
+    }
+
+    /// The last frame drawn
+    pub fn image(&self) -> &RgbImage {
+        &self.img
+    }
+
+    /// The last frame drawn, for overlays to paint over
+    pub fn image_mut(&mut self) -> &mut RgbImage {
+        &mut self.img
+    }
+
+    pub fn into_image(self) -> RgbImage {
+        self.img
+    }
+
+    /// Saves the last frame drawn, in a format picked from the extension
+    pub fn save(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let path = path.as_ref();
+        self.img.save(path).map_err(|source| Error::Render {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    fn draw_boid(&mut self, boid: &Boid, pos: Vector2<f32>) {
+        let (width, height) = self.img.dimensions();
+        let colour = self.colour.unwrap_or(boid.colour.into());
+        draw_boid(&mut self.img, width, height, pos, self.draw_radius, colour);
+    }
+}
+
+// A filled circle of `radius` around `pos`, clipped to the frame
+fn draw_boid(
+    buf: &mut [u8],
+    width: u32,
+    height: u32,
+    pos: Vector2<f32>,
+    radius: i32,
+    colour: Rgb<u8>,
+) {
+    let mut put = |x: u32, y: u32| {
+        let start = (y as usize * width as usize + x as usize) * 3;
+        buf[start..start + 3].copy_from_slice(&colour.0);
+    };
+    // Rather than a single pixel, going to create a circle
+    let boid_x_int = pos.x.round() as i32;
+    let boid_y_int = pos.y.round() as i32;
+    for dy_offset in -radius..=radius {
+        for dx_offset in -radius..=radius {
+            if (dx_offset * dx_offset + dy_offset * dy_offset) <= (radius * radius) {
+                let px = boid_x_int + dx_offset;
+                let py = boid_y_int + dy_offset;
+                if px >= 0 && px < width as i32 && py >= 0 && py < height as i32 {
+                    put(px as u32, py as u32);
+                }
+            }
+        }
+    }
+    if pos.x >= 0.0 && pos.y >= 0.0 && (pos.x as u32) < width && (pos.y as u32) < height {
+        put(pos.x as u32, pos.y as u32);
+    }
+}
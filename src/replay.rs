@@ -0,0 +1,163 @@
+//! Trajectories as plain CSV, one row per boid per frame, for loading into
+//! other tools or re-rendering a run without simulating it again.
+//!
+//! ```text
+//! frame,id,x,y,vx,vy,r,g,b
+//! 0,0,12.5,300.25,1.2,-0.5,255,0,0
+//! ```
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+use crate::colour::Colour;
+use crate::error::Error;
+
+pub const CSV_HEADER: &str = "frame,id,x,y,vx,vy,r,g,b";
+
+/// Appends frames of boids to a trajectory CSV
+pub struct CsvTrajectoryWriter<W: Write> {
+    out: W,
+    frame: usize,
+}
+
+impl CsvTrajectoryWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        CsvTrajectoryWriter::new(BufWriter::new(file)).map_err(|err| Error::io("write", path, err))
+    }
+}
+
+impl<W: Write> CsvTrajectoryWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        writeln!(out, "{CSV_HEADER}")?;
+        Ok(CsvTrajectoryWriter { out, frame: 0 })
+    }
+
+    pub fn write_frame(&mut self, boids: &[Boid]) -> io::Result<()> {
+        for boid in boids {
+            let Colour([r, g, b]) = boid.colour;
+            writeln!(
+                self.out,
+                "{},{},{},{},{},{},{r},{g},{b}",
+                self.frame, boid.id, boid.pos.x, boid.pos.y, boid.vel.x, boid.vel.y
+            )?;
+        }
+        self.frame += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+fn invalid_row(line: usize, row: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {line} isn't a valid trajectory row: {row}"),
+    )
+}
+
+fn parse_row(line: usize, row: &str) -> io::Result<(usize, Boid)> {
+    let fields: Vec<&str> = row.split(',').map(str::trim).collect();
+    let [frame, id, x, y, vx, vy, r, g, b] = fields[..] else {
+        return Err(invalid_row(line, row));
+    };
+    let float = |field: &str| field.parse::<f32>().map_err(|_| invalid_row(line, row));
+    let byte = |field: &str| field.parse::<u8>().map_err(|_| invalid_row(line, row));
+    let vel = Vector2::new(float(vx)?, float(vy)?);
+    let boid = Boid::new(
+        id.parse().map_err(|_| invalid_row(line, row))?,
+        Vector2::new(float(x)?, float(y)?),
+        vel,
+        Colour([byte(r)?, byte(g)?, byte(b)?]),
+    );
+    Ok((frame.parse().map_err(|_| invalid_row(line, row))?, boid))
+}
+
+/// Streams the frames of a trajectory CSV back as flocks. Rows must be
+/// grouped by frame, as `CsvTrajectoryWriter` writes them.
+pub struct ReplayReader<R: BufRead> {
+    lines: Lines<R>,
+    line: usize,
+    // The first row of the next frame, read while finding the end of this one
+    pending: Option<(usize, Boid)>,
+    frame: Option<usize>,
+}
+
+impl ReplayReader<BufReader<File>> {
+    pub fn new(path: &Path) -> crate::Result<Self> {
+        let file = File::open(path).map_err(|err| Error::io("open", path, err))?;
+        ReplayReader::from_reader(BufReader::new(file)).map_err(|err| Error::io("read", path, err))
+    }
+}
+
+impl<R: BufRead> ReplayReader<R> {
+    pub fn from_reader(input: R) -> io::Result<Self> {
+        let mut lines = input.lines();
+        match lines.next().transpose()? {
+            Some(header) if header.trim() == CSV_HEADER => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("trajectory CSV must start with {CSV_HEADER}"),
+                ));
+            }
+        }
+        Ok(ReplayReader {
+            lines,
+            line: 1,
+            pending: None,
+            frame: None,
+        })
+    }
+
+    /// The frame number of the flock `next_frame` last returned
+    pub fn frame(&self) -> Option<usize> {
+        self.frame
+    }
+
+    /// Reads every row of the next frame, or `None` at the end of the file
+    pub fn next_frame(&mut self) -> Option<io::Result<Vec<Boid>>> {
+        let (frame, first) = match self.pending.take() {
+            Some(pending) => pending,
+            None => match self.next_row()? {
+                Ok(row) => row,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+        let mut boids = vec![first];
+        while let Some(row) = self.next_row() {
+            match row {
+                Ok((row_frame, boid)) if row_frame == frame => boids.push(boid),
+                Ok(row) => {
+                    self.pending = Some(row);
+                    break;
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        self.frame = Some(frame);
+        Some(Ok(boids))
+    }
+
+    fn next_row(&mut self) -> Option<io::Result<(usize, Boid)>> {
+        loop {
+            let row = match self.lines.next()? {
+                Ok(row) => row,
+                Err(err) => return Some(Err(err)),
+            };
+            self.line += 1;
+            if !row.trim().is_empty() {
+                return Some(parse_row(self.line, &row));
+            }
+        }
+    }
+}
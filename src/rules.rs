@@ -0,0 +1,207 @@
+//! The rules a boid steers by. Each boid's neighbours are gathered once per
+//! frame into a `Neighborhood`, and every rule works from that rather than
+//! searching again, so adding one costs no more than the rule itself. The
+//! classic rules are rules like any other, so a `Simulation` can have its
+//! own added alongside them, or in place of them.
+//!
+//! ```
+//! use boids::rules::{Neighborhood, SteeringRule};
+//! use boids::{Boid, Simulation, SimulationConfig};
+//! # use boids::{Parameters, World};
+//!
+//! # let config = SimulationConfig::new(World::from_pixels(200, 100), Parameters::default(), 10);
+//! let mut sim = Simulation::new(config).unwrap();
+//! // Drawn towards the top left corner
+//! sim.add_rule(|boid: &Boid, _: &Neighborhood, _: &World, _: &Parameters| {
+//!     -boid.pos * 0.0001
+//! });
+//! sim.run_for(10);
+//! ```
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+use crate::world::World;
+use crate::Parameters;
+
+/// What a boid found around it this frame, shared by every rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighborhood {
+    /// Flockmates within the visible range, or its Voronoi neighbours with
+    /// `voronoi_neighbors`, that the boid aligns and coheres with. None are
+    /// looked for when `centering_factor` and `matching_factor` are both 0.
+    pub count: usize,
+    /// Average position of those flockmates, zero without any
+    pub mean_pos: Vector2<f32>,
+    /// Average velocity of those flockmates, zero without any
+    pub mean_vel: Vector2<f32>,
+    /// Total of the offsets from each boid within the protected range, zero
+    /// if neither they nor flockmates are looked for
+    pub close_offset: Vector2<f32>,
+    /// Centre of the whole flock, only worked out with a
+    /// `global_centering_factor`, and zero otherwise
+    pub centroid: Vector2<f32>,
+}
+
+impl Default for Neighborhood {
+    fn default() -> Self {
+        Neighborhood {
+            count: 0,
+            mean_pos: Vector2::zeros(),
+            mean_vel: Vector2::zeros(),
+            close_offset: Vector2::zeros(),
+            centroid: Vector2::zeros(),
+        }
+    }
+}
+
+/// A change to a boid's velocity, worked out from its neighbourhood. The
+/// changes from every rule are added to the velocity the boid already had,
+/// before `BoidBehavior` forces and speed limits.
+pub trait SteeringRule: Send + Sync {
+    fn steer(
+        &self,
+        boid: &Boid,
+        neighborhood: &Neighborhood,
+        world: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32>;
+}
+
+impl<F> SteeringRule for F
+where
+    F: Fn(&Boid, &Neighborhood, &World, &Parameters) -> Vector2<f32> + Send + Sync,
+{
+    fn steer(
+        &self,
+        boid: &Boid,
+        neighborhood: &Neighborhood,
+        world: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        self(boid, neighborhood, world, parameters)
+    }
+}
+
+/// Towards the average position of the flockmates, by `centering_factor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cohesion;
+
+impl SteeringRule for Cohesion {
+    fn steer(
+        &self,
+        boid: &Boid,
+        nearby: &Neighborhood,
+        _: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        if nearby.count == 0 {
+            return Vector2::zeros();
+        }
+        (nearby.mean_pos - boid.pos) * parameters.centering_factor
+    }
+}
+
+/// Towards the average velocity of the flockmates, by `matching_factor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Alignment;
+
+impl SteeringRule for Alignment {
+    fn steer(
+        &self,
+        boid: &Boid,
+        nearby: &Neighborhood,
+        _: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        if nearby.count == 0 {
+            return Vector2::zeros();
+        }
+        (nearby.mean_vel - boid.velocity()) * parameters.matching_factor
+    }
+}
+
+/// Away from boids within the protected range, by `avoid_factor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Separation;
+
+impl SteeringRule for Separation {
+    fn steer(
+        &self,
+        _: &Boid,
+        nearby: &Neighborhood,
+        _: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        nearby.close_offset * parameters.avoid_factor
+    }
+}
+
+/// Towards the centre of the whole flock, by `global_centering_factor`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlobalCentering;
+
+impl SteeringRule for GlobalCentering {
+    fn steer(
+        &self,
+        boid: &Boid,
+        nearby: &Neighborhood,
+        _: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        (nearby.centroid - boid.pos) * parameters.global_centering_factor
+    }
+}
+
+/// Back from the edge of the world, as `World::turn` does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EdgeTurn;
+
+impl SteeringRule for EdgeTurn {
+    fn steer(
+        &self,
+        boid: &Boid,
+        _: &Neighborhood,
+        world: &World,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        if parameters.turn_factor == 0.0 {
+            return Vector2::zeros();
+        }
+        world.turn(boid.pos, Vector2::zeros(), parameters)
+    }
+}
+
+/// The rules boids steer by unless told otherwise, in the order they're
+/// applied
+pub const CLASSIC_RULES: &[&dyn SteeringRule] = &[
+    &Cohesion,
+    &Alignment,
+    &Separation,
+    &GlobalCentering,
+    &EdgeTurn,
+];
+
+/// `CLASSIC_RULES`, boxed to add to or rearrange
+pub fn classic_rules() -> Vec<Box<dyn SteeringRule>> {
+    vec![
+        Box::new(Cohesion),
+        Box::new(Alignment),
+        Box::new(Separation),
+        Box::new(GlobalCentering),
+        Box::new(EdgeTurn),
+    ]
+}
+
+/// `boid`'s velocity with the change from every one of `rules` added, in
+/// order
+pub fn steer(
+    rules: &[&dyn SteeringRule],
+    boid: &Boid,
+    neighborhood: &Neighborhood,
+    world: &World,
+    parameters: &Parameters,
+) -> Vector2<f32> {
+    rules.iter().fold(boid.velocity(), |vel, rule| {
+        vel + rule.steer(boid, neighborhood, world, parameters)
+    })
+}
@@ -0,0 +1,83 @@
+//! Randomness let into a run at set frames, so a settled flock can be
+//! knocked about in exactly the same way every time.
+//!
+//! ```toml
+//! [[seed_schedule]]
+//! frame = 200
+//! seed = 7
+//! ```
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::boids::{nudge, Boid};
+
+/// Nudges every boid at `frame`, each from its own RNG seeded with
+/// `seed ^ index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedEvent {
+    pub frame: usize,
+    pub seed: u64,
+}
+
+impl FromStr for SeedEvent {
+    type Err = String;
+
+    /// `frame:seed`, such as `200:7`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (frame, seed) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected frame:seed, not {s}"))?;
+        Ok(SeedEvent {
+            frame: frame
+                .trim()
+                .parse()
+                .map_err(|err| format!("Bad frame {frame}: {err}"))?,
+            seed: seed
+                .trim()
+                .parse()
+                .map_err(|err| format!("Bad seed {seed}: {err}"))?,
+        })
+    }
+}
+
+/// The frames a run is nudged at, and the seeds each nudge comes from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RandomSeedSchedule {
+    pub events: Vec<SeedEvent>,
+}
+
+impl RandomSeedSchedule {
+    pub fn new(events: Vec<SeedEvent>) -> Self {
+        RandomSeedSchedule { events }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The seed for `frame`, from the first event at it
+    pub fn seed_at(&self, frame: usize) -> Option<u64> {
+        self.events
+            .iter()
+            .find(|event| event.frame == frame)
+            .map(|event| event.seed)
+    }
+
+    /// Adds a random nudge of up to `min_speed` each way to the velocity of
+    /// every boid, if there's an event at `frame`. The boid at `index` is
+    /// nudged from an RNG seeded with `seed ^ index`, so the same flock is
+    /// always nudged the same way. Returns whether there was an event.
+    pub fn apply(&self, boids: &mut [Boid], frame: usize, min_speed: f32) -> bool {
+        let Some(seed) = self.seed_at(frame) else {
+            return false;
+        };
+        for (idx, boid) in boids.iter_mut().enumerate() {
+            let mut rng = StdRng::seed_from_u64(seed ^ idx as u64);
+            boid.vel += nudge(&mut rng, min_speed);
+        }
+        true
+    }
+}
@@ -0,0 +1,885 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use nalgebra::Vector2;
+use rand::prelude::*;
+use rand::seq::index;
+
+use crate::balance::LoadBalancer;
+use crate::behavior::BoidBehavior;
+use crate::boids::{
+    grid_for, update_boids_with_behaviors, Boid, EventDrivenUpdate, FlockTally, SpatialGrid,
+};
+use crate::boundary::{Boundary, BoundaryError};
+use crate::cluster::cluster_membership;
+use crate::colour::{recolour, rotate_hues, ColourMode};
+use crate::flock::{components, FlockTracker};
+use crate::init::{spawn_boids, BoidSpawnDistribution, BoidSpawnVelocity};
+use crate::quality::{mean_isolation, FlockingQualityScore, QualityWeights};
+use crate::rules::{classic_rules, SteeringRule};
+use crate::turbulence::TurbulenceInjector;
+use crate::world::World;
+use crate::Parameters;
+
+/// A flock together with the world and rules it lives under
+#[derive(Debug, Clone)]
+pub struct SimulationState {
+    pub boids: Vec<Boid>,
+    pub parameters: Parameters,
+    /// Its boundary is loaded from `parameters.boundary` with
+    /// `Boundary::load`, the world's own edges until then
+    pub world: World,
+    /// Built from `boids` whenever the flock changes shape
+    pub grid: SpatialGrid,
+    /// How the boids are coloured, change it with `set_colour_mode`
+    pub colour_mode: ColourMode,
+    frame: usize,
+    // The flock as it was at the start of each of the last
+    // `parameters.rewind_depth` frames, oldest first
+    history: VecDeque<Vec<Boid>>,
+}
+
+/// Raised when two simulations can't be joined together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeError {
+    DimensionMismatch {
+        ours: (f32, f32),
+        theirs: (f32, f32),
+    },
+    /// The two flocks can't share a grid, see `Parameters::compatible_with`
+    IncompatibleParameters {
+        cell_sizes: (f32, f32),
+        visible_ranges: (f32, f32),
+    },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MergeError::DimensionMismatch { ours, theirs } => write!(
+                f,
+                "can't merge a {}x{} world into a {}x{} one",
+                theirs.0, theirs.1, ours.0, ours.1
+            ),
+            MergeError::IncompatibleParameters {
+                cell_sizes,
+                visible_ranges,
+            } => write!(
+                f,
+                "can't merge flocks on a grid of {} cells seeing {} into one on a grid of {} cells seeing {}",
+                cell_sizes.1, visible_ranges.1, cell_sizes.0, visible_ranges.0
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+// How far apart the cell sizes of merged flocks can be, relative to the
+// larger, for float noise from saving and loading them
+const MERGE_TOLERANCE: f32 = 1e-4;
+
+/// Raised when a state can't be rewound to a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewindError {
+    /// The frame hasn't been reached yet
+    Ahead { frame: usize, current: usize },
+    /// The frame is further back than the history goes
+    Forgotten { frame: usize, earliest: usize },
+}
+
+impl fmt::Display for RewindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewindError::Ahead { frame, current } => {
+                write!(f, "can't rewind to frame {frame}, only at frame {current}")
+            }
+            RewindError::Forgotten { frame, earliest } => write!(
+                f,
+                "can't rewind to frame {frame}, the history only goes back to frame {earliest}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RewindError {}
+
+impl SimulationState {
+    pub fn new(boids: Vec<Boid>, parameters: Parameters, world: World) -> Self {
+        let grid = grid_for(&boids, &parameters, &world);
+        SimulationState {
+            boids,
+            parameters,
+            world,
+            grid,
+            colour_mode: ColourMode::default(),
+            frame: 0,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Frames stepped through since the state was made, less any rewound
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Advances the flock by one frame, keeping colours that follow the
+    /// boids' motion up to date
+    pub fn step(&mut self) {
+        self.step_with_forces(&[]);
+    }
+
+    /// Advances the flock by one frame like `step`, with `extra_forces`
+    /// pushing on every boid for this frame only. The flock as it was is
+    /// kept for `rewind_to` if `parameters.rewind_depth` is more than 0,
+    /// which costs a copy of every boid for each frame kept.
+    pub fn step_with_forces(&mut self, extra_forces: &[Box<dyn BoidBehavior>]) {
+        let depth = self.parameters.rewind_depth;
+        if depth > 0 {
+            self.history.push_back(self.boids.clone());
+        }
+        while self.history.len() > depth {
+            self.history.pop_front();
+        }
+        self.advance(extra_forces);
+        self.frame += 1;
+    }
+
+    /// Goes back a frame, exactly if the history has it. Otherwise it's
+    /// only an approximation: every boid is turned round, stepped, and
+    /// turned back. Steering doesn't run the same in reverse and random
+    /// nudges can't be undone, so the further back it goes the further it
+    /// drifts from the flock that was.
+    pub fn step_backward(&mut self) {
+        if self.frame > 0 && self.rewind_to(self.frame - 1).is_ok() {
+            return;
+        }
+        for boid in &mut self.boids {
+            boid.vel = -boid.vel;
+        }
+        self.advance(&[]);
+        for boid in &mut self.boids {
+            boid.vel = -boid.vel;
+        }
+        self.frame = self.frame.saturating_sub(1);
+    }
+
+    /// Puts the flock back as it was at the start of `frame`, from the
+    /// history kept with `parameters.rewind_depth`. Frames after it are
+    /// forgotten, stepping on again makes them afresh.
+    pub fn rewind_to(&mut self, frame: usize) -> Result<(), RewindError> {
+        if frame > self.frame {
+            return Err(RewindError::Ahead {
+                frame,
+                current: self.frame,
+            });
+        }
+        let earliest = self.frame - self.history.len();
+        if frame < earliest {
+            return Err(RewindError::Forgotten { frame, earliest });
+        }
+        if frame < self.frame {
+            self.history.truncate(frame - earliest + 1);
+            self.boids = self
+                .history
+                .pop_back()
+                .expect("the frame is in the history");
+            self.frame = frame;
+            self.rebuild_grid();
+        }
+        Ok(())
+    }
+
+    // Moves the flock on a frame, without touching the history
+    fn advance(&mut self, extra_forces: &[Box<dyn BoidBehavior>]) {
+        update_boids_with_behaviors(&mut self.boids, &self.world, &self.parameters, extra_forces);
+        if self.colour_mode.is_dynamic() {
+            // Seeded from the frame, so the same flock is always coloured
+            // the same way
+            self.recolor_boids(&mut SmallRng::seed_from_u64(self.frame as u64));
+        }
+        if self.colour_mode == ColourMode::Rotating {
+            rotate_hues(
+                &mut self.boids,
+                self.parameters.colour_rotation_speed,
+                self.parameters.per_boid_colour_rotation,
+            );
+        }
+        self.rebuild_grid();
+    }
+
+    /// Works out every boid's colour again with `colour_mode`, ignoring
+    /// whatever colour it had, such as one loaded from a save
+    pub fn recolor_boids<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        recolour(
+            &mut self.boids,
+            self.colour_mode,
+            &self.world,
+            &self.parameters,
+            rng,
+        );
+    }
+
+    /// Switches to colouring by `mode`, recolouring the boids if it's a change
+    pub fn set_colour_mode<R: Rng + ?Sized>(&mut self, mode: ColourMode, rng: &mut R) {
+        if mode != self.colour_mode {
+            self.colour_mode = mode;
+            self.recolor_boids(rng);
+        }
+    }
+
+    pub fn rebuild_grid(&mut self) {
+        self.grid = grid_for(&self.boids, &self.parameters, &self.world);
+    }
+
+    // A new state for `boids` in this one's world, under the same rules
+    fn with_boids(&self, boids: Vec<Boid>) -> SimulationState {
+        SimulationState {
+            colour_mode: self.colour_mode,
+            ..SimulationState::new(boids, self.parameters.clone(), self.world.clone())
+        }
+    }
+
+    /// A copy keeping a uniformly random 1 in `factor` of the boids, in
+    /// their original order and with their original ids
+    pub fn downsample<R: Rng + ?Sized>(&self, factor: usize, rng: &mut R) -> SimulationState {
+        let count = self.boids.len() / factor.max(1);
+        let mut keep = index::sample(rng, self.boids.len(), count).into_vec();
+        keep.sort_unstable();
+        self.with_boids(
+            keep.into_iter()
+                .map(|idx| self.boids[idx].clone())
+                .collect(),
+        )
+    }
+
+    /// A copy with `factor` boids for every one here. Each boid is kept as
+    /// it is, and its copies get new ids past the largest one and are moved
+    /// up to `protected_range` either way, so they don't start on top of it.
+    pub fn upsample<R: Rng + ?Sized>(&self, factor: usize, rng: &mut R) -> SimulationState {
+        let mut next_id = self.boids.iter().map(|boid| boid.id + 1).max().unwrap_or(0);
+        let jitter = self.parameters.protected_range;
+        let mut boids = Vec::with_capacity(self.boids.len() * factor);
+        for boid in &self.boids {
+            boids.push(boid.clone());
+            for _ in 1..factor {
+                let mut copy = boid.clone();
+                copy.id = next_id;
+                next_id += 1;
+                if jitter > 0.0 {
+                    copy.pos += Vector2::new(
+                        rng.random_range(-jitter..=jitter),
+                        rng.random_range(-jitter..=jitter),
+                    );
+                }
+                copy.pos = self.world.clamp(copy.pos);
+                boids.push(copy);
+            }
+        }
+        self.with_boids(boids)
+    }
+
+    /// Joins `other`'s flock onto this one. Its ids are offset past the
+    /// largest id here, which is `self.boids.len()` for a flock numbered from
+    /// 0, so every id stays unique. Both worlds must be the same size, and
+    /// the parameters `compatible_with` each other. If they differ otherwise
+    /// these ones are kept, with a warning.
+    pub fn merge(mut self, other: SimulationState) -> Result<SimulationState, MergeError> {
+        let (ours, theirs) = (
+            (self.world.width, self.world.height),
+            (other.world.width, other.world.height),
+        );
+        if ours != theirs {
+            return Err(MergeError::DimensionMismatch { ours, theirs });
+        }
+        if !self
+            .parameters
+            .compatible_with(&other.parameters, MERGE_TOLERANCE)
+        {
+            return Err(MergeError::IncompatibleParameters {
+                cell_sizes: (self.parameters.cell_size, other.parameters.cell_size),
+                visible_ranges: (
+                    self.parameters.visible_range,
+                    other.parameters.visible_range,
+                ),
+            });
+        }
+        if self.parameters != other.parameters {
+            warn!("merging simulations with different parameters, keeping the first");
+        }
+        let offset = self.boids.iter().map(|boid| boid.id + 1).max().unwrap_or(0);
+        self.boids.extend(other.boids.into_iter().map(|mut boid| {
+            boid.id += offset;
+            boid
+        }));
+        // None of what's kept had the other flock in it
+        self.history.clear();
+        self.rebuild_grid();
+        Ok(self)
+    }
+}
+
+/// Everything a `Simulation` is started from
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// `parameters.boundary` is loaded into the world, unless it was given
+    /// a boundary of its own
+    pub world: World,
+    pub parameters: Parameters,
+    /// How many boids `Simulation::new` spawns
+    pub boids: usize,
+    pub spawn: BoidSpawnDistribution,
+    pub spawn_velocity: BoidSpawnVelocity,
+    /// Seeds spawning and any random colours, so a run can be repeated
+    pub seed: u64,
+    pub colour_mode: ColourMode,
+}
+
+impl SimulationConfig {
+    /// `boids` spread evenly over `world` in a rainbow across it, from a
+    /// random seed
+    pub fn new(world: World, parameters: Parameters, boids: usize) -> Self {
+        SimulationConfig {
+            world,
+            parameters,
+            boids,
+            spawn: BoidSpawnDistribution::Uniform,
+            spawn_velocity: BoidSpawnVelocity::Random,
+            seed: rand::rng().random(),
+            colour_mode: ColourMode::InitialX,
+        }
+    }
+}
+
+/// Raised when a `Simulation` can't be started from its config
+#[derive(Debug)]
+pub enum SimulationError {
+    EmptyWorld { width: f32, height: f32 },
+    Boundary(BoundaryError),
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulationError::EmptyWorld { width, height } => {
+                write!(f, "a {width}x{height} world has no room for boids")
+            }
+            SimulationError::Boundary(err) => write!(f, "unable to set up the boundary: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<BoundaryError> for SimulationError {
+    fn from(err: BoundaryError) -> Self {
+        SimulationError::Boundary(err)
+    }
+}
+
+/// How the flock looked after a `Simulation::step`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+    /// The frame just worked out, counting from 0
+    pub frame: usize,
+    pub population: usize,
+    pub polarization: f32,
+    pub mean_speed: f32,
+    /// Population standard deviation of the boids' speeds
+    pub speed_stddev: f32,
+    pub centroid: Vector2<f32>,
+    /// Top left and bottom right corners of the box around every boid, or
+    /// `None` with no boids
+    pub bounds: Option<(Vector2<f32>, Vector2<f32>)>,
+    /// Flockmates each boid aligned and cohered with, on average and at
+    /// most, counting boids that weren't re-steered by their last count
+    pub mean_neighbors: f32,
+    pub max_neighbors: usize,
+    /// Boids whose velocity was worked out again, which is all of them
+    /// unless `update_threshold` is set
+    pub re_steered: usize,
+    /// Whether the flock was nudged by the seed schedule first
+    pub seed_event: bool,
+    /// Boids given a random kick by `perturbation_fraction`
+    pub perturbed_count: usize,
+    /// Boids left stopped dead by `StallPolicy::Error`, which stops the run
+    pub stalled: usize,
+    /// Flocks being followed after the step, always 0 unless
+    /// `Simulation::track_flocks` was called
+    pub distinct_flocks: u32,
+    /// `FlockingQualityScore` of the flock after the step
+    pub quality_score: f32,
+    /// `LoadBalancer::imbalance` of the threads steering the flock, always
+    /// 0 unless `Simulation::balance_load` was called
+    pub load_imbalance: f32,
+    pub elapsed: Duration,
+}
+
+/// Told about every step a `Simulation` takes, once it's been added with
+/// `Simulation::add_observer` or passed to `Simulation::run_with`. Any
+/// `FnMut(usize, &[Boid], &FrameStats) -> ControlFlow<()>` is one.
+pub trait Observer {
+    /// Sees the flock after the step that worked out `frame`. `Break` asks
+    /// for the run to stop there.
+    fn on_step(&mut self, frame: usize, boids: &[Boid], stats: &FrameStats) -> ControlFlow<()>;
+
+    /// Why the run should stop, once `on_step` has given `Break`
+    fn stop_reason(&self) -> Option<String> {
+        None
+    }
+
+    /// Changes the flock once every observer has seen the step, such as to
+    /// add or take away boids
+    fn on_after_step(&mut self, _boids: &mut Vec<Boid>) {}
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(usize, &[Boid], &FrameStats) -> ControlFlow<()>,
+{
+    fn on_step(&mut self, frame: usize, boids: &[Boid], stats: &FrameStats) -> ControlFlow<()> {
+        self(frame, boids, stats)
+    }
+}
+
+fn stop_reason<O: Observer + ?Sized>(observer: &O) -> String {
+    observer
+        .stop_reason()
+        .unwrap_or_else(|| String::from("stopped by an observer"))
+}
+
+// Margins that meet are cut to half the world as boids are turned, which
+// `ParametersBuilder::world` refuses, but parameters made by hand get a
+// warning instead
+fn warn_if_margins_meet(parameters: &Parameters, world: &World) {
+    if world.boundary == Boundary::Rectangle && world.margins_meet(parameters.margin) {
+        let (width, height) = world.pixels();
+        warn!(
+            "A margin of {} leaves no room in the middle of a {width}x{height} world, so boids are turned from half way across",
+            parameters.margin
+        );
+    }
+}
+
+#[derive(Default)]
+struct Observers(Vec<Box<dyn Observer + Send>>);
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+struct Rules(Vec<Box<dyn SteeringRule>>);
+
+impl fmt::Debug for Rules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} steering rules", self.0.len())
+    }
+}
+
+/// A flock moving through its world a frame at a time. Colours that follow
+/// the boids' motion are kept up to date as it goes.
+///
+/// Spawning, random colours and turbulence draw from an `R` seeded from the
+/// config's seed, a `SmallRng` unless made with `with_rng`. `SmallRng` is
+/// quick, but a seed only repeats a run on the same platform and version of
+/// `rand`.
+#[derive(Debug)]
+pub struct Simulation<R = SmallRng> {
+    boids: Vec<Boid>,
+    world: World,
+    parameters: Parameters,
+    colour_mode: ColourMode,
+    frame: usize,
+    seed: u64,
+    rng: R,
+    updater: EventDrivenUpdate,
+    rules: Rules,
+    // Built when first asked for after each step, as most frames never need it
+    grid: OnceLock<SpatialGrid>,
+    observers: Observers,
+    stopped: Option<String>,
+    flocks: Option<FlockTracking>,
+    quality: FlockingQualityScore,
+}
+
+// The DBSCAN settings flocks are found with, and what's following them
+#[derive(Debug, Clone)]
+struct FlockTracking {
+    tracker: FlockTracker,
+    eps: f32,
+    min_points: usize,
+}
+
+impl Simulation {
+    /// Spawns `config.boids` boids with `config.spawn`, set moving by
+    /// `config.spawn_velocity`
+    pub fn new(config: SimulationConfig) -> Result<Self, SimulationError> {
+        Simulation::with_rng(config)
+    }
+
+    /// Carries on with an existing flock, such as a loaded one, from `frame`.
+    /// Their colours are kept unless the colour mode follows their motion.
+    pub fn from_boids(
+        config: SimulationConfig,
+        boids: Vec<Boid>,
+        frame: usize,
+    ) -> Result<Self, SimulationError> {
+        Simulation::from_boids_with_rng(config, boids, frame)
+    }
+}
+
+impl<R: RngCore + SeedableRng> Simulation<R> {
+    /// `new`, drawing from an `R` in place of a `SmallRng`, such as one
+    /// that counts what's drawn in a test
+    pub fn with_rng(config: SimulationConfig) -> Result<Self, SimulationError> {
+        let mut simulation = Simulation::from_boids_with_rng(config.clone(), Vec::new(), 0)?;
+        let boids = spawn_boids(
+            config.boids,
+            &config.spawn,
+            config.spawn_velocity,
+            &mut simulation.rng,
+            &config.parameters,
+            &simulation.world,
+        );
+        simulation.boids = boids;
+        // Spawned boids already have the initial-x rainbow
+        if config.colour_mode != ColourMode::InitialX {
+            simulation.recolour();
+        }
+        Ok(simulation)
+    }
+
+    /// `from_boids`, drawing from an `R` in place of a `SmallRng`
+    pub fn from_boids_with_rng(
+        config: SimulationConfig,
+        boids: Vec<Boid>,
+        frame: usize,
+    ) -> Result<Self, SimulationError> {
+        let mut world = config.world;
+        if world.is_empty() {
+            return Err(SimulationError::EmptyWorld {
+                width: world.width,
+                height: world.height,
+            });
+        }
+        if world.boundary == Boundary::Rectangle {
+            let (width, height) = world.pixels();
+            world.boundary = Boundary::load(&config.parameters.boundary, width, height)?;
+        }
+        warn_if_margins_meet(&config.parameters, &world);
+        let mut simulation = Simulation {
+            boids,
+            world,
+            parameters: config.parameters,
+            colour_mode: config.colour_mode,
+            frame,
+            seed: config.seed,
+            rng: R::seed_from_u64(config.seed),
+            updater: EventDrivenUpdate::new(),
+            rules: Rules(classic_rules()),
+            grid: OnceLock::new(),
+            observers: Observers::default(),
+            stopped: None,
+            flocks: None,
+            quality: FlockingQualityScore::default(),
+        };
+        if simulation.colour_mode.is_dynamic() {
+            simulation.recolour();
+        }
+        Ok(simulation)
+    }
+
+    /// Moves the flock on by one frame, then tells every observer about it
+    pub fn step(&mut self) -> FrameStats {
+        let started = Instant::now();
+        let seed_event = self.parameters.seed_schedule.apply(
+            &mut self.boids,
+            self.frame,
+            self.parameters.min_speed,
+        );
+        if seed_event {
+            debug!("Nudged the flock at frame {}", self.frame);
+        }
+        // Seeded from the frame too, so a run picked up from a checkpoint
+        // is kicked the same way
+        let turbulence = TurbulenceInjector::from(&self.parameters);
+        let kicks = turbulence.kicks(
+            self.boids.len(),
+            &mut R::seed_from_u64(self.seed ^ self.frame as u64),
+        );
+        let perturbed_count = kicks
+            .iter()
+            .filter(|kick| **kick != Vector2::zeros())
+            .count();
+        self.updater.perturb(kicks);
+        let rules: Vec<&dyn SteeringRule> = self.rules.0.iter().map(Box::as_ref).collect();
+        let tally =
+            self.updater
+                .update_with_rules(&mut self.boids, &self.world, &self.parameters, &rules);
+        if self.colour_mode.is_dynamic() {
+            self.recolour();
+        }
+        if self.colour_mode == ColourMode::Rotating {
+            rotate_hues(
+                &mut self.boids,
+                self.parameters.colour_rotation_speed,
+                self.parameters.per_boid_colour_rotation,
+            );
+        }
+        self.grid = OnceLock::new();
+        let distinct_flocks = match &mut self.flocks {
+            Some(tracking) => {
+                let positions: Vec<Vector2<f32>> = self.boids.iter().map(|boid| boid.pos).collect();
+                let membership = cluster_membership(&positions, tracking.eps, tracking.min_points);
+                tracking
+                    .tracker
+                    .assign(&mut self.boids, &components(&membership));
+                tracking.tracker.distinct_flocks()
+            }
+            None => 0,
+        };
+        let stats = FrameStats {
+            frame: self.frame,
+            population: tally.population(),
+            polarization: tally.polarization(),
+            mean_speed: tally.mean_speed(),
+            speed_stddev: tally.speed_stddev(),
+            centroid: tally.centroid(),
+            bounds: tally.bounds(),
+            mean_neighbors: tally.mean_neighbors(),
+            max_neighbors: tally.max_neighbors(),
+            re_steered: self.updater.dirty_count(),
+            seed_event,
+            perturbed_count,
+            stalled: tally.stalled(),
+            distinct_flocks,
+            quality_score: self
+                .quality
+                .update(&self.boids, self.updater.neighbor_counts()),
+            load_imbalance: self
+                .updater
+                .load_balancer()
+                .map_or(0.0, LoadBalancer::imbalance),
+            elapsed: started.elapsed(),
+        };
+        self.frame += 1;
+        self.stopped = (stats.stalled > 0).then(|| {
+            format!(
+                "{} boids stopped dead, which stall_policy error doesn't allow",
+                stats.stalled
+            )
+        });
+        for observer in &mut self.observers.0 {
+            if observer
+                .on_step(stats.frame, &self.boids, &stats)
+                .is_break()
+            {
+                self.stopped.get_or_insert_with(|| stop_reason(&**observer));
+            }
+        }
+        for observer in &mut self.observers.0 {
+            observer.on_after_step(&mut self.boids);
+        }
+        if !self.observers.0.is_empty() {
+            self.grid = OnceLock::new();
+        }
+        stats
+    }
+
+    /// Stats of the flock as it stands, without stepping it, such as for
+    /// the starting state before the first step. Counted as `frame()`, with
+    /// flockmates as of the last step, none before the first, and no jerk in
+    /// the quality score. Observers aren't told.
+    pub fn current_stats(&self) -> FrameStats {
+        let started = Instant::now();
+        let neighbor_counts = self.neighbor_counts();
+        let tally = FlockTally::of(&self.boids, neighbor_counts);
+        let isolation = match neighbor_counts.len() {
+            0 if !self.boids.is_empty() => 1.0,
+            _ => mean_isolation(neighbor_counts),
+        };
+        FrameStats {
+            frame: self.frame,
+            population: tally.population(),
+            polarization: tally.polarization(),
+            mean_speed: tally.mean_speed(),
+            speed_stddev: tally.speed_stddev(),
+            centroid: tally.centroid(),
+            bounds: tally.bounds(),
+            mean_neighbors: tally.mean_neighbors(),
+            max_neighbors: tally.max_neighbors(),
+            re_steered: 0,
+            seed_event: false,
+            perturbed_count: 0,
+            stalled: 0,
+            distinct_flocks: self
+                .flocks
+                .as_ref()
+                .map_or(0, |tracking| tracking.tracker.distinct_flocks()),
+            quality_score: self.quality.score(tally.polarization(), isolation, 0.0),
+            load_imbalance: self
+                .updater
+                .load_balancer()
+                .map_or(0.0, LoadBalancer::imbalance),
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// Steps `frames` times, or until an observer stops the run, giving the
+    /// stats of the last step, if any
+    pub fn run_for(&mut self, frames: usize) -> Option<FrameStats> {
+        self.run_with(frames, |_: usize, _: &[Boid], _: &FrameStats| {
+            ControlFlow::Continue(())
+        })
+    }
+
+    /// `run_for`, with `observer` told about each step after the ones that
+    /// were added
+    pub fn run_with<O: Observer>(&mut self, frames: usize, mut observer: O) -> Option<FrameStats> {
+        let mut last = None;
+        for _ in 0..frames {
+            let stats = self.step();
+            last = Some(stats);
+            if observer
+                .on_step(stats.frame, &self.boids, &stats)
+                .is_break()
+            {
+                self.stopped.get_or_insert_with(|| stop_reason(&observer));
+            }
+            observer.on_after_step(&mut self.boids);
+            self.grid = OnceLock::new();
+            if self.stopped.is_some() {
+                break;
+            }
+        }
+        last
+    }
+
+    /// Follows the flocks DBSCAN finds with `eps` and `min_points` after
+    /// every step from now on, setting each boid's `flock_id` and counting
+    /// them in `FrameStats::distinct_flocks`
+    pub fn track_flocks(&mut self, eps: f32, min_points: usize) {
+        self.flocks = Some(FlockTracking {
+            tracker: FlockTracker::new(),
+            eps,
+            min_points,
+        });
+    }
+
+    /// The rules every boid steers by, in order, `classic_rules` unless
+    /// they've been changed
+    pub fn rules(&self) -> &[Box<dyn SteeringRule>] {
+        &self.rules.0
+    }
+
+    /// Steers by `rule` as well, after the rules already there, with every
+    /// boid steered afresh on the next step
+    pub fn add_rule(&mut self, rule: impl SteeringRule + 'static) {
+        self.rules.0.push(Box::new(rule));
+        self.updater.reset();
+    }
+
+    /// Steers by `rules` alone from the next step on, in their order
+    pub fn set_rules(&mut self, rules: Vec<Box<dyn SteeringRule>>) {
+        self.rules.0 = rules;
+        self.updater.reset();
+    }
+
+    /// Splits the flock between threads by how long each part took to steer
+    /// on the frame before, rather than evenly, from the next step on
+    pub fn balance_load(&mut self, on: bool) {
+        self.updater.balance_load(on.then(LoadBalancer::default));
+    }
+
+    /// Weighs the parts of `FrameStats::quality_score` by `weights` from the
+    /// next step on
+    pub fn set_quality_weights(&mut self, weights: QualityWeights) {
+        self.quality.weights = weights;
+    }
+
+    /// What's following the flocks, once `track_flocks` has been called
+    pub fn flock_tracker(&self) -> Option<&FlockTracker> {
+        self.flocks.as_ref().map(|tracking| &tracking.tracker)
+    }
+
+    /// Tells `observer` about every step from now on
+    pub fn add_observer(&mut self, observer: impl Observer + Send + 'static) {
+        self.observers.0.push(Box::new(observer));
+    }
+
+    /// Why the last step asked for the run to stop, if a boid stalled or an
+    /// observer asked
+    pub fn stopped(&self) -> Option<&str> {
+        self.stopped.as_deref()
+    }
+
+    /// Works out every boid's colour again with the colour mode, ignoring
+    /// whatever colour it had
+    pub fn recolour(&mut self) {
+        recolour(
+            &mut self.boids,
+            self.colour_mode,
+            &self.world,
+            &self.parameters,
+            &mut self.rng,
+        );
+    }
+
+    pub fn boids(&self) -> &[Boid] {
+        &self.boids
+    }
+
+    /// Takes the flock back, such as to save it
+    pub fn into_boids(self) -> Vec<Boid> {
+        self.boids
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+
+    /// Flies by `parameters` from the next step on. The world keeps the
+    /// boundary it was set up with, and every boid is steered afresh.
+    pub fn set_parameters(&mut self, parameters: Parameters) {
+        warn_if_margins_meet(&parameters, &self.world);
+        self.parameters = parameters;
+        self.updater.reset();
+        self.grid = OnceLock::new();
+    }
+
+    /// How many frames have been stepped through, which is also the number
+    /// of the next one
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn colour_mode(&self) -> ColourMode {
+        self.colour_mode
+    }
+
+    /// The spatial grid over the flock as it is now
+    pub fn grid(&self) -> &SpatialGrid {
+        self.grid
+            .get_or_init(|| grid_for(&self.boids, &self.parameters, &self.world))
+    }
+
+    /// Flockmates each boid aligned and cohered with when it was last
+    /// steered, empty until the first step
+    pub fn neighbor_counts(&self) -> &[usize] {
+        self.updater.neighbor_counts()
+    }
+}
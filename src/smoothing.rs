@@ -0,0 +1,35 @@
+use nalgebra::Vector2;
+
+use crate::boids::Boid;
+
+/// Positions to draw boids at, trailing their real positions so that boids
+/// jittering back and forth look steady on screen. Purely cosmetic, the
+/// simulation never sees these.
+#[derive(Debug, Clone, Default)]
+pub struct TemporalSmoothing {
+    render_pos: Vec<Vector2<f32>>,
+}
+
+impl TemporalSmoothing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves each render position `rate` of the way towards its boid. A rate
+    /// of 0 (off) or 1 snaps straight to the boid. If the flock has changed
+    /// size everything snaps.
+    pub fn update(&mut self, boids: &[Boid], rate: f32) {
+        if rate <= 0.0 || rate >= 1.0 || self.render_pos.len() != boids.len() {
+            self.render_pos = boids.iter().map(|boid| boid.pos).collect();
+            return;
+        }
+        for (render_pos, boid) in self.render_pos.iter_mut().zip(boids) {
+            *render_pos += (boid.pos - *render_pos) * rate;
+        }
+    }
+
+    /// Where to draw each boid, in the same order as the flock
+    pub fn positions(&self) -> &[Vector2<f32>] {
+        &self.render_pos
+    }
+}
@@ -0,0 +1,838 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{debug, trace};
+use nalgebra::Vector2;
+use rand::seq::index;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::boids::{Boid, BoidId};
+use crate::error::Error;
+use crate::world::World;
+use crate::Parameters;
+
+/// The current version of the save file layout. Bump this, and add a
+/// migration, whenever the on disk shape of `SaveFile` changes.
+pub const SAVE_FILE_VERSION: u32 = 4;
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// How and when a saved flock came to be. Everything is optional, files
+/// from before version 2 have none of it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[serde(default)]
+pub struct Metadata {
+    /// Seed the run's random choices were made from
+    pub seed: Option<u64>,
+    /// How the boids were placed, and with what arguments
+    pub spawn: Option<String>,
+    /// Version of the crate that wrote the file
+    pub crate_version: Option<String>,
+    /// When the file was written, as an ISO 8601 UTC timestamp
+    pub created: Option<String>,
+    pub note: Option<String>,
+    /// Frames the flock had run for when saved, for checkpoints taken
+    /// partway through a run. Added in version 3.
+    pub frame: Option<u64>,
+    /// What the flock was flying by. Added in version 4, and not kept in
+    /// rkyv archives.
+    #[serde(with = "embedded_parameters")]
+    #[cfg_attr(feature = "rkyv", rkyv(with = rkyv::with::Skip))]
+    pub parameters: Option<Parameters>,
+}
+
+// Binary formats can't skip over fields they don't know, so parameters go
+// into them as JSON, which can, leaving their layout the same however many
+// parameters are added
+mod embedded_parameters {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Parameters;
+
+    pub fn serialize<S: Serializer>(
+        parameters: &Option<Parameters>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return parameters.serialize(serializer);
+        }
+        parameters
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Parameters>, D::Error> {
+        if deserializer.is_human_readable() {
+            return Option::<Parameters>::deserialize(deserializer);
+        }
+        Option::<String>::deserialize(deserializer)?
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(D::Error::custom)
+    }
+}
+
+impl Metadata {
+    /// Metadata stamped with this crate's version and the current time
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        Metadata {
+            crate_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            created: Some(utc_timestamp(seconds)),
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn utc_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Howard Hinnant's civil_from_days, shifted so years start in March
+    let days = days as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// A saved flock, along with the information needed to interpret it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub struct SaveFile {
+    /// The layout the save is in. Older saves are migrated as they're
+    /// loaded, so this is always `SAVE_FILE_VERSION` once they are.
+    pub version: u32,
+    /// Width and height of the world the boids were saved from. Old bare
+    /// JSON saves didn't record this, so it may be missing.
+    pub world_size: Option<(u32, u32)>,
+    #[serde(default)]
+    pub metadata: Metadata,
+    pub boids: Vec<Boid>,
+}
+
+// The layout before metadata was added, for the formats that aren't self
+// describing
+#[derive(Deserialize)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize))]
+struct SaveFileV1 {
+    #[serde(rename = "version")]
+    _version: u32,
+    world_size: Option<(u32, u32)>,
+    boids: Vec<Boid>,
+}
+
+// Metadata before the frame was added in version 3
+#[derive(Default, Deserialize)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize))]
+struct MetadataV2 {
+    seed: Option<u64>,
+    spawn: Option<String>,
+    crate_version: Option<String>,
+    created: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Deserialize))]
+struct SaveFileV2 {
+    #[serde(rename = "version")]
+    _version: u32,
+    world_size: Option<(u32, u32)>,
+    #[serde(default)]
+    metadata: MetadataV2,
+    boids: Vec<Boid>,
+}
+
+// Metadata before the parameters were added in version 4. Archives skip the
+// parameters, so only bincode needs this.
+#[derive(Deserialize)]
+struct MetadataV3 {
+    seed: Option<u64>,
+    spawn: Option<String>,
+    crate_version: Option<String>,
+    created: Option<String>,
+    note: Option<String>,
+    frame: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct SaveFileV3 {
+    #[serde(rename = "version")]
+    _version: u32,
+    world_size: Option<(u32, u32)>,
+    metadata: MetadataV3,
+    boids: Vec<Boid>,
+}
+
+impl From<SaveFileV3> for SaveFile {
+    fn from(old: SaveFileV3) -> Self {
+        SaveFile {
+            version: SAVE_FILE_VERSION,
+            world_size: old.world_size,
+            metadata: Metadata {
+                seed: old.metadata.seed,
+                spawn: old.metadata.spawn,
+                crate_version: old.metadata.crate_version,
+                created: old.metadata.created,
+                note: old.metadata.note,
+                frame: old.metadata.frame,
+                parameters: None,
+            },
+            boids: old.boids,
+        }
+    }
+}
+
+impl From<SaveFileV2> for SaveFile {
+    fn from(old: SaveFileV2) -> Self {
+        SaveFile {
+            version: SAVE_FILE_VERSION,
+            world_size: old.world_size,
+            metadata: Metadata {
+                seed: old.metadata.seed,
+                spawn: old.metadata.spawn,
+                crate_version: old.metadata.crate_version,
+                created: old.metadata.created,
+                note: old.metadata.note,
+                frame: None,
+                parameters: None,
+            },
+            boids: old.boids,
+        }
+    }
+}
+
+impl From<SaveFileV1> for SaveFile {
+    fn from(old: SaveFileV1) -> Self {
+        SaveFile {
+            version: SAVE_FILE_VERSION,
+            world_size: old.world_size,
+            metadata: Metadata::default(),
+            boids: old.boids,
+        }
+    }
+}
+
+impl SaveFile {
+    pub fn new(width: u32, height: u32, boids: Vec<Boid>) -> Self {
+        SaveFile {
+            version: SAVE_FILE_VERSION,
+            world_size: Some((width, height)),
+            metadata: Metadata::now(),
+            boids,
+        }
+    }
+
+    /// The world the boids were saved in, if its size was recorded. Only the
+    /// size is saved, so its edge is always the rectangle.
+    pub fn world(&self) -> Option<World> {
+        self.world_size
+            .map(|(width, height)| World::from_pixels(width, height))
+    }
+
+    /// Renumbers the boids 0..n in their current order
+    pub fn reseed_ids(&mut self) {
+        for (id, boid) in self.boids.iter_mut().enumerate() {
+            boid.id = id as BoidId;
+        }
+    }
+
+    /// Scales every position so the flock occupies the same relative area
+    /// of a `width` x `height` world, and optionally the velocities by the
+    /// same ratio.
+    pub fn scale_to(
+        &mut self,
+        width: u32,
+        height: u32,
+        scale_velocities: bool,
+    ) -> Result<(), StateError> {
+        let (old_width, old_height) = self.world_size.ok_or(StateError::MissingWorldSize)?;
+        let scale_x = width as f32 / old_width as f32;
+        let scale_y = height as f32 / old_height as f32;
+        let world = World::from_pixels(width, height);
+        for boid in &mut self.boids {
+            boid.pos = world.clamp(Vector2::new(boid.pos.x * scale_x, boid.pos.y * scale_y));
+            if scale_velocities {
+                boid.vel.x *= scale_x;
+                boid.vel.y *= scale_y;
+            }
+        }
+        self.world_size = Some((width, height));
+        Ok(())
+    }
+
+    /// Keeps a uniformly random `count` of the boids, in their original
+    /// order and with their original ids
+    pub fn sample<R: Rng + ?Sized>(&mut self, count: usize, rng: &mut R) {
+        if count >= self.boids.len() {
+            return;
+        }
+        let mut keep = index::sample(rng, self.boids.len(), count).into_vec();
+        keep.sort_unstable();
+        let mut keep = keep.into_iter().peekable();
+        let mut index = 0;
+        self.boids.retain(|_| {
+            let kept = keep.next_if_eq(&index).is_some();
+            index += 1;
+            kept
+        });
+    }
+
+    /// Keeps only the boids inside the `width` x `height` rectangle with its
+    /// top left corner at `x`, `y`
+    pub fn retain_region(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.boids.retain(|boid| {
+            boid.pos.x >= x && boid.pos.x < x + width && boid.pos.y >= y && boid.pos.y < y + height
+        });
+    }
+}
+
+/// Where a boid in a merged state came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdMapping {
+    /// Index of the source in the list given to `merge`
+    pub source: usize,
+    pub old_id: BoidId,
+    pub new_id: BoidId,
+}
+
+/// Combines several states into one `width` x `height` world, translating
+/// each by its offset and renumbering ids so they are unique. Boids that end
+/// up outside the world are an error unless `clamp` is set.
+pub fn merge(
+    sources: Vec<(SaveFile, Vector2<f32>)>,
+    width: u32,
+    height: u32,
+    clamp: bool,
+) -> Result<(SaveFile, Vec<IdMapping>), StateError> {
+    let mut boids = Vec::new();
+    let mut mapping = Vec::new();
+    let mut out_of_bounds = 0;
+    let world = World::from_pixels(width, height);
+    for (source, (state, offset)) in sources.into_iter().enumerate() {
+        for mut boid in state.boids {
+            boid.pos += offset;
+            if !world.contains(boid.pos) {
+                out_of_bounds += 1;
+                boid.pos = world.clamp(boid.pos);
+            }
+            mapping.push(IdMapping {
+                source,
+                old_id: boid.id,
+                new_id: boids.len() as BoidId,
+            });
+            boid.id = boids.len() as BoidId;
+            boids.push(boid);
+        }
+    }
+    if out_of_bounds > 0 && !clamp {
+        return Err(StateError::OutOfBounds(out_of_bounds));
+    }
+    Ok((SaveFile::new(width, height, boids), mapping))
+}
+
+/// The serialization formats a `SaveFile` can be stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Bincode,
+    Ron,
+    /// Zero copy archive that can be memory mapped, needs the `rkyv` feature
+    #[cfg(feature = "rkyv")]
+    Rkyv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Encoding {
+    pub format: Format,
+    pub compressed: bool,
+}
+
+impl Encoding {
+    /// Works out the encoding from a file name, e.g. `state.json`,
+    /// `state.bin.zst` or `state.ron`. Anything unrecognised is JSON.
+    pub fn from_path(path: &Path) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let (name, compressed) = match name.strip_suffix(".zst") {
+            Some(name) => (name.to_string(), true),
+            None => (name, false),
+        };
+        let format = name
+            .rsplit_once('.')
+            .and_then(|(_, extension)| format_for_extension(extension))
+            .unwrap_or(Format::Json);
+        Encoding { format, compressed }
+    }
+}
+
+fn format_for_extension(extension: &str) -> Option<Format> {
+    match extension {
+        "json" => Some(Format::Json),
+        "bin" | "bincode" => Some(Format::Bincode),
+        "ron" => Some(Format::Ron),
+        #[cfg(feature = "rkyv")]
+        "rkyv" => Some(Format::Rkyv),
+        _ => None,
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    /// Parses an encoding written the way it would be as a file extension,
+    /// e.g. `json`, `bin.zst` or `ron`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let (name, compressed) = match lower.strip_suffix(".zst") {
+            Some(name) => (name, true),
+            None => (lower.as_str(), false),
+        };
+        let format = format_for_extension(name).ok_or_else(|| {
+            format!("Unknown state format {s}, expected json, bin or ron, optionally ending .zst")
+        })?;
+        Ok(Encoding { format, compressed })
+    }
+}
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Bincode(String),
+    Ron(String),
+    #[cfg(feature = "rkyv")]
+    Rkyv(String),
+    UnsupportedVersion(u32),
+    /// Fields this version doesn't know about, refused by `Loaded::strict`
+    UnknownFields(Vec<String>),
+    MissingWorldSize,
+    OutOfBounds(usize),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::Io(err) => write!(f, "{err}"),
+            StateError::Json(err) => write!(f, "invalid JSON state: {err}"),
+            StateError::Bincode(err) => write!(f, "invalid bincode state: {err}"),
+            StateError::Ron(err) => write!(f, "invalid RON state: {err}"),
+            #[cfg(feature = "rkyv")]
+            StateError::Rkyv(err) => write!(f, "invalid rkyv state: {err}"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "state file is version {version}, but only up to version {SAVE_FILE_VERSION} is understood"
+            ),
+            StateError::UnknownFields(fields) => write!(
+                f,
+                "state file has fields this version doesn't know about: {}",
+                fields.join(", ")
+            ),
+            StateError::MissingWorldSize => {
+                write!(f, "state file doesn't record the size of its world")
+            }
+            StateError::OutOfBounds(count) => {
+                write!(f, "{count} boids are outside of the world")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> Self {
+        StateError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for StateError {
+    fn from(err: serde_json::Error) -> Self {
+        StateError::Json(err)
+    }
+}
+
+// Just enough of a SaveFile to find out if we can read the rest of it
+#[derive(Deserialize)]
+struct VersionProbe {
+    version: u32,
+}
+
+fn check_version(version: u32) -> Result<(), StateError> {
+    if version > SAVE_FILE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+// Something a save can be decoded from in any layout it's had
+trait Decoder {
+    fn decode<T: DeserializeOwned>(&mut self) -> Result<T, StateError>;
+}
+
+// Decodes a save of `version` in the layout that version was written in,
+// then migrates it to the current one. A new version adds its old layout
+// here, along with a `From` for it. Past checking it, nothing reads the old
+// layouts' `_version`, as a migrated save is always the current version.
+fn decode_version(version: u32, decoder: &mut impl Decoder) -> Result<SaveFile, StateError> {
+    check_version(version)?;
+    Ok(match version {
+        1 => decoder.decode::<SaveFileV1>()?.into(),
+        2 => decoder.decode::<SaveFileV2>()?.into(),
+        3 => decoder.decode::<SaveFileV3>()?.into(),
+        _ => decoder.decode()?,
+    })
+}
+
+struct JsonDecoder<'a> {
+    bytes: &'a [u8],
+    unknown: &'a mut Vec<String>,
+}
+
+impl Decoder for JsonDecoder<'_> {
+    fn decode<T: DeserializeOwned>(&mut self) -> Result<T, StateError> {
+        decode_json(self.bytes, self.unknown)
+    }
+}
+
+struct RonDecoder<'a> {
+    text: &'a str,
+    unknown: &'a mut Vec<String>,
+}
+
+impl Decoder for RonDecoder<'_> {
+    fn decode<T: DeserializeOwned>(&mut self) -> Result<T, StateError> {
+        decode_ron(self.text, self.unknown)
+    }
+}
+
+struct BincodeDecoder<'a>(&'a [u8]);
+
+impl Decoder for BincodeDecoder<'_> {
+    fn decode<T: DeserializeOwned>(&mut self) -> Result<T, StateError> {
+        bincode::serde::decode_from_slice(self.0, bincode_config())
+            .map(|(value, _)| value)
+            .map_err(|err| StateError::Bincode(err.to_string()))
+    }
+}
+
+fn bincode_config() -> bincode::config::Configuration {
+    bincode::config::standard()
+}
+
+/// A decoded state, along with any fields in it that were ignored because
+/// this version doesn't know about them, such as ones added by a newer
+/// version. Indices into lists are written as `*`, so a field on every boid
+/// is only listed once.
+#[derive(Debug)]
+pub struct Loaded {
+    pub state: SaveFile,
+    pub unknown_fields: Vec<String>,
+}
+
+impl Loaded {
+    /// The state, or an error if any of it had to be ignored
+    pub fn strict(self) -> Result<SaveFile, StateError> {
+        if self.unknown_fields.is_empty() {
+            Ok(self.state)
+        } else {
+            Err(StateError::UnknownFields(self.unknown_fields))
+        }
+    }
+}
+
+/// Loads a state file, detecting the format from its contents where
+/// possible and falling back to the file extension. Unknown fields are
+/// ignored, use `load_tolerant` to find out about them.
+pub fn load(path: &Path) -> crate::Result<SaveFile> {
+    load_tolerant(path).map(|loaded| loaded.state)
+}
+
+/// Loads a state file like `load`, reporting any fields that were ignored
+pub fn load_tolerant(path: &Path) -> crate::Result<Loaded> {
+    debug!("Loading state from {}", path.display());
+    #[cfg(feature = "rkyv")]
+    if Encoding::from_path(path)
+        == (Encoding {
+            format: Format::Rkyv,
+            compressed: false,
+        })
+    {
+        return load_rkyv_mapped(path)
+            .map(|state| Loaded {
+                state,
+                unknown_fields: Vec::new(),
+            })
+            .map_err(|err| Error::state("read", path, err));
+    }
+    let bytes = fs::read(path).map_err(|err| Error::io("read", path, err))?;
+    from_bytes_tolerant(&bytes, Encoding::from_path(path).format)
+        .map_err(|err| Error::state("read", path, err))
+}
+
+// Uncompressed archives are validated in place in a memory map, so the only
+// full copy of the flock is the one being built
+#[cfg(feature = "rkyv")]
+fn load_rkyv_mapped(path: &Path) -> Result<SaveFile, StateError> {
+    let file = fs::File::open(path)?;
+    // Safety: the map is only read while validating and deserializing below,
+    // the usual caveat of another process truncating the file applies
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    from_rkyv(&map)
+}
+
+#[cfg(feature = "rkyv")]
+fn from_rkyv(bytes: &[u8]) -> Result<SaveFile, StateError> {
+    use rkyv::rancor::Error;
+
+    // rkyv needs its input aligned, which a memory map always is but a
+    // decompressed buffer might not be
+    let mut aligned = rkyv::util::AlignedVec::<16>::new();
+    let bytes = if bytes.as_ptr().align_offset(16) == 0 {
+        bytes
+    } else {
+        aligned.extend_from_slice(bytes);
+        &aligned
+    };
+    let archived = match rkyv::access::<ArchivedSaveFile, Error>(bytes) {
+        Ok(archived) => archived,
+        // Archives aren't self describing, so an older layout only shows up
+        // as failing to validate
+        Err(err) => {
+            if let Ok(old) = rkyv::access::<ArchivedSaveFileV2, Error>(bytes) {
+                check_version(old._version.to_native())?;
+                return rkyv::deserialize::<SaveFileV2, Error>(old)
+                    .map(SaveFile::from)
+                    .map_err(|err| StateError::Rkyv(err.to_string()));
+            }
+            let Ok(old) = rkyv::access::<ArchivedSaveFileV1, Error>(bytes) else {
+                return Err(StateError::Rkyv(err.to_string()));
+            };
+            check_version(old._version.to_native())?;
+            return rkyv::deserialize::<SaveFileV1, Error>(old)
+                .map(SaveFile::from)
+                .map_err(|err| StateError::Rkyv(err.to_string()));
+        }
+    };
+    check_version(archived.version.to_native())?;
+    rkyv::deserialize::<SaveFile, Error>(archived).map_err(|err| StateError::Rkyv(err.to_string()))
+}
+
+/// Saves a state file in the format implied by its extension
+pub fn save(path: &Path, state: &SaveFile) -> crate::Result<()> {
+    let bytes = to_bytes(state, Encoding::from_path(path))
+        .map_err(|err| Error::state("write", path, err))?;
+    debug!(
+        "Saving {} boids to {}, {} bytes",
+        state.boids.len(),
+        path.display(),
+        bytes.len()
+    );
+    fs::write(path, bytes).map_err(|err| Error::io("write", path, err))
+}
+
+/// Decodes a state, `hint` is used when the contents aren't self describing
+pub fn from_bytes(bytes: &[u8], hint: Format) -> Result<SaveFile, StateError> {
+    from_bytes_tolerant(bytes, hint).map(|loaded| loaded.state)
+}
+
+// Deserializes while noting down every field that gets skipped over
+fn track_unknown<'de, D, T>(deserializer: D, unknown: &mut Vec<String>) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    serde_ignored::deserialize(deserializer, |path| {
+        // Optional values show up as a `?` step of their own
+        let field = path
+            .to_string()
+            .split('.')
+            .filter(|part| *part != "?")
+            .map(|part| {
+                if part.parse::<usize>().is_ok() {
+                    "*"
+                } else {
+                    part
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+        if !unknown.contains(&field) {
+            unknown.push(field);
+        }
+    })
+}
+
+fn decode_json<T: DeserializeOwned>(
+    bytes: &[u8],
+    unknown: &mut Vec<String>,
+) -> Result<T, StateError> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let value = track_unknown(&mut deserializer, unknown)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+fn decode_ron<T: DeserializeOwned>(text: &str, unknown: &mut Vec<String>) -> Result<T, StateError> {
+    let ron_error = |err: ron::Error| StateError::Ron(err.to_string());
+    let mut deserializer =
+        ron::Deserializer::from_str(text).map_err(|err| StateError::Ron(err.to_string()))?;
+    let value = track_unknown(&mut deserializer, unknown).map_err(ron_error)?;
+    deserializer.end().map_err(ron_error)?;
+    Ok(value)
+}
+
+/// Decodes a state like `from_bytes`, reporting any fields that were ignored
+pub fn from_bytes_tolerant(bytes: &[u8], hint: Format) -> Result<Loaded, StateError> {
+    if bytes.starts_with(&ZSTD_MAGIC) {
+        let decompressed = decompress(bytes)?;
+        trace!(
+            "Decompressed {} bytes of state to {}",
+            bytes.len(),
+            decompressed.len()
+        );
+        return from_bytes_tolerant(&decompressed, hint);
+    }
+    let first = bytes.iter().find(|byte| !byte.is_ascii_whitespace());
+    let format = match first {
+        Some(b'[') | Some(b'{') => Format::Json,
+        Some(b'(') => Format::Ron,
+        _ => hint,
+    };
+    trace!("Decoding {} bytes of state as {format:?}", bytes.len());
+    let mut unknown_fields = Vec::new();
+    let state = match format {
+        Format::Json => {
+            // Bare arrays of boids are what we used to save, before there was
+            // a header at all
+            if first == Some(&b'[') {
+                SaveFile {
+                    version: SAVE_FILE_VERSION,
+                    world_size: None,
+                    metadata: Metadata::default(),
+                    boids: decode_json(bytes, &mut unknown_fields)?,
+                }
+            } else {
+                let probe: VersionProbe = serde_json::from_slice(bytes)?;
+                let mut decoder = JsonDecoder {
+                    bytes,
+                    unknown: &mut unknown_fields,
+                };
+                decode_version(probe.version, &mut decoder)?
+            }
+        }
+        // Neither binary format can carry fields we don't know about
+        Format::Bincode => {
+            let mut decoder = BincodeDecoder(bytes);
+            let version: u32 = decoder.decode()?;
+            decode_version(version, &mut decoder)?
+        }
+        Format::Ron => {
+            let text =
+                std::str::from_utf8(bytes).map_err(|err| StateError::Ron(err.to_string()))?;
+            let probe: VersionProbe =
+                ron::from_str(text).map_err(|err| StateError::Ron(err.to_string()))?;
+            let mut decoder = RonDecoder {
+                text,
+                unknown: &mut unknown_fields,
+            };
+            decode_version(probe.version, &mut decoder)?
+        }
+        #[cfg(feature = "rkyv")]
+        Format::Rkyv => from_rkyv(bytes)?,
+    };
+    debug!(
+        "Decoded version {} state with {} boids",
+        state.version,
+        state.boids.len()
+    );
+    if !unknown_fields.is_empty() {
+        debug!("Ignored unknown fields: {}", unknown_fields.join(", "));
+    }
+    Ok(Loaded {
+        state,
+        unknown_fields,
+    })
+}
+
+pub fn to_bytes(state: &SaveFile, encoding: Encoding) -> Result<Vec<u8>, StateError> {
+    let bytes = match encoding.format {
+        Format::Json => serde_json::to_vec(state)?,
+        Format::Bincode => bincode::serde::encode_to_vec(state, bincode_config())
+            .map_err(|err| StateError::Bincode(err.to_string()))?,
+        Format::Ron => ron::to_string(state)
+            .map_err(|err| StateError::Ron(err.to_string()))?
+            .into_bytes(),
+        #[cfg(feature = "rkyv")]
+        Format::Rkyv => rkyv::to_bytes::<rkyv::rancor::Error>(state)
+            .map_err(|err| StateError::Rkyv(err.to_string()))?
+            .into_vec(),
+    };
+    if encoding.compressed {
+        return Ok(compress(&bytes)?);
+    }
+    Ok(bytes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn compress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::encode_all(bytes, 0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(bytes)
+}
+
+// zstd is C, which doesn't build for the browser
+#[cfg(target_arch = "wasm32")]
+fn compress(_: &[u8]) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "compressed states aren't supported in WebAssembly builds",
+    ))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    compress(bytes)
+}
@@ -0,0 +1,232 @@
+//! Conditions for ending a run early, such as once the flock has settled
+//! down, written like `polarization>0.98 for 200`.
+use std::fmt;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+use crate::boids::{angular_momentum, mean_speed, polarization, Boid};
+use crate::simulation::{FrameStats, Observer};
+
+/// Something measured over the whole flock every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Polarization,
+    AngularMomentum,
+    MeanSpeed,
+    Population,
+    /// `FrameStats::quality_score`, which depends on the frames before
+    QualityScore,
+}
+
+impl Metric {
+    const NAMES: [(&'static str, Metric); 5] = [
+        ("polarization", Metric::Polarization),
+        ("angular-momentum", Metric::AngularMomentum),
+        ("mean-speed", Metric::MeanSpeed),
+        ("population", Metric::Population),
+        ("quality-score", Metric::QualityScore),
+    ];
+
+    /// The metric for `boids` alone. The quality score can't be worked out
+    /// from a single frame, so is NaN, which never meets a threshold.
+    pub fn measure(self, boids: &[Boid]) -> f32 {
+        match self {
+            Metric::Polarization => polarization(boids),
+            Metric::AngularMomentum => angular_momentum(boids),
+            Metric::MeanSpeed => mean_speed(boids),
+            Metric::Population => boids.len() as f32,
+            Metric::QualityScore => f32::NAN,
+        }
+    }
+
+    /// The metric for `boids` after the step `stats` describes, taken from
+    /// `stats` where it's there
+    pub fn measure_step(self, boids: &[Boid], stats: &FrameStats) -> f32 {
+        match self {
+            Metric::Polarization => stats.polarization,
+            Metric::MeanSpeed => stats.mean_speed,
+            Metric::Population => stats.population as f32,
+            Metric::QualityScore => stats.quality_score,
+            Metric::AngularMomentum => self.measure(boids),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        Metric::NAMES
+            .iter()
+            .find(|(_, metric)| *metric == self)
+            .map(|(name, _)| *name)
+            .expect("every metric has a name")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual,
+}
+
+impl Comparison {
+    // Longest first, so `>=` isn't read as `>`
+    const SYMBOLS: [(&'static str, Comparison); 4] = [
+        (">=", Comparison::GreaterOrEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">", Comparison::Greater),
+        ("<", Comparison::Less),
+    ];
+
+    pub fn holds(self, value: f32, threshold: f32) -> bool {
+        match self {
+            Comparison::Greater => value > threshold,
+            Comparison::GreaterOrEqual => value >= threshold,
+            Comparison::Less => value < threshold,
+            Comparison::LessOrEqual => value <= threshold,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        Comparison::SYMBOLS
+            .iter()
+            .find(|(_, comparison)| *comparison == self)
+            .map(|(symbol, _)| *symbol)
+            .expect("every comparison has a symbol")
+    }
+}
+
+/// A metric compared against a threshold, which has to hold for `sustain`
+/// frames in a row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StopCondition {
+    pub metric: Metric,
+    pub comparison: Comparison,
+    pub threshold: f32,
+    pub sustain: usize,
+}
+
+impl fmt::Display for StopCondition {
+    /// Writes the same form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{} for {}",
+            self.metric.name(),
+            self.comparison.symbol(),
+            self.threshold,
+            self.sustain
+        )
+    }
+}
+
+impl FromStr for StopCondition {
+    type Err = String;
+
+    /// Parses `METRIC COMPARISON THRESHOLD [for FRAMES]`, e.g.
+    /// `polarization>0.98 for 200` or `population<10`. Without a number of
+    /// frames the condition only has to hold once.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| format!("Invalid stop condition {s}: {reason}");
+        let (condition, sustain) = match s.split_once(" for ") {
+            Some((condition, frames)) => {
+                let frames = frames
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| invalid("the number of frames isn't a whole number"))?;
+                (condition, frames)
+            }
+            None => (s, 1),
+        };
+        if sustain == 0 {
+            return Err(invalid("it has to hold for at least 1 frame"));
+        }
+        let (metric, rest) = Metric::NAMES
+            .iter()
+            .find_map(|(name, metric)| Some((*metric, condition.trim().strip_prefix(name)?)))
+            .ok_or_else(|| {
+                invalid(
+                    "expected polarization, angular-momentum, mean-speed, population or quality-score",
+                )
+            })?;
+        let (comparison, threshold) = Comparison::SYMBOLS
+            .iter()
+            .find_map(|(symbol, comparison)| {
+                Some((*comparison, rest.trim_start().strip_prefix(symbol)?))
+            })
+            .ok_or_else(|| invalid("expected >, >=, < or <="))?;
+        let threshold = threshold
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|threshold| threshold.is_finite())
+            .ok_or_else(|| invalid("the threshold isn't a number"))?;
+        Ok(StopCondition {
+            metric,
+            comparison,
+            threshold,
+            sustain,
+        })
+    }
+}
+
+/// Tracks how long each of a set of conditions has held, any one of which
+/// ends the run
+#[derive(Debug, Clone, Default)]
+pub struct StopWhen {
+    conditions: Vec<StopCondition>,
+    streaks: Vec<usize>,
+    reason: Option<String>,
+}
+
+impl StopWhen {
+    pub fn new(conditions: Vec<StopCondition>) -> Self {
+        let streaks = vec![0; conditions.len()];
+        StopWhen {
+            conditions,
+            streaks,
+            reason: None,
+        }
+    }
+
+    /// Checks the flock after a frame, returning why the run should stop if
+    /// any condition has now held for long enough. Conditions on the quality
+    /// score never hold, as that needs `check_step`.
+    pub fn check(&mut self, boids: &[Boid]) -> Option<String> {
+        self.check_with(|metric| metric.measure(boids))
+    }
+
+    /// `check`, for the flock after the step `stats` describes
+    pub fn check_step(&mut self, boids: &[Boid], stats: &FrameStats) -> Option<String> {
+        self.check_with(|metric| metric.measure_step(boids, stats))
+    }
+
+    fn check_with(&mut self, measure: impl Fn(Metric) -> f32) -> Option<String> {
+        let mut reason = None;
+        for (condition, streak) in self.conditions.iter().zip(&mut self.streaks) {
+            let value = measure(condition.metric);
+            if condition.comparison.holds(value, condition.threshold) {
+                *streak += 1;
+            } else {
+                *streak = 0;
+            }
+            if reason.is_none() && *streak >= condition.sustain {
+                reason = Some(format!("{condition} (now {value})"));
+            }
+        }
+        self.reason.clone_from(&reason);
+        reason
+    }
+}
+
+impl Observer for StopWhen {
+    fn on_step(&mut self, _frame: usize, boids: &[Boid], stats: &FrameStats) -> ControlFlow<()> {
+        match self.check_step(boids, stats) {
+            Some(_) => ControlFlow::Break(()),
+            None => ControlFlow::Continue(()),
+        }
+    }
+
+    fn stop_reason(&self) -> Option<String> {
+        self.reason.clone()
+    }
+}
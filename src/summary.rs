@@ -0,0 +1,296 @@
+//! Timings and totals collected over a run, reported when it finishes.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use nalgebra::Vector2;
+use serde::Serialize;
+
+use crate::boids::{flock_centroid, mean_speed, polarization, Boid};
+
+/// The parts of a frame that are timed separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Moving the flock, smoothing and recolouring
+    Simulate,
+    /// Drawing the frame
+    Rasterize,
+    /// Turning the frame into a PNG
+    Encode,
+    /// Writing frames, states and trajectories out
+    Io,
+}
+
+/// Collects timings and totals as a run goes
+#[derive(Debug)]
+pub struct RunRecorder {
+    started: Instant,
+    stages: [Duration; 4],
+    frames: usize,
+    frame_total: Duration,
+    frame_min: Option<Duration>,
+    frame_max: Duration,
+    frames_dropped: usize,
+    frames_overrun: usize,
+    achieved_fps: Option<f64>,
+    bytes_written: u64,
+    artifacts: Vec<String>,
+    stop_reason: Option<String>,
+}
+
+impl Default for RunRecorder {
+    fn default() -> Self {
+        RunRecorder::new()
+    }
+}
+
+impl RunRecorder {
+    /// Starts the wall clock
+    pub fn new() -> Self {
+        RunRecorder {
+            started: Instant::now(),
+            stages: [Duration::ZERO; 4],
+            frames: 0,
+            frame_total: Duration::ZERO,
+            frame_min: None,
+            frame_max: Duration::ZERO,
+            frames_dropped: 0,
+            frames_overrun: 0,
+            achieved_fps: None,
+            bytes_written: 0,
+            artifacts: Vec::new(),
+            stop_reason: None,
+        }
+    }
+
+    pub fn add(&mut self, stage: Stage, elapsed: Duration) {
+        self.stages[stage as usize] += elapsed;
+    }
+
+    /// Runs `f`, adding the time it took to `stage`
+    pub fn time<T>(&mut self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add(stage, started.elapsed());
+        result
+    }
+
+    /// Time spent in `stage` so far
+    pub fn stage_time(&self, stage: Stage) -> Duration {
+        self.stages[stage as usize]
+    }
+
+    /// Bytes counted with `wrote` so far, not including artifacts
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Records a frame that took `elapsed` from start to finish
+    pub fn frame(&mut self, elapsed: Duration) {
+        self.frames += 1;
+        self.frame_total += elapsed;
+        self.frame_min = Some(self.frame_min.map_or(elapsed, |min| min.min(elapsed)));
+        self.frame_max = self.frame_max.max(elapsed);
+    }
+
+    /// Counts a frame that was simulated but not drawn, to catch up with
+    /// the pace
+    pub fn dropped(&mut self) {
+        self.frames_dropped += 1;
+    }
+
+    /// Records how a paced run kept up: the frames a second it managed, and
+    /// how many frames were finished late
+    pub fn paced(&mut self, achieved_fps: f64, overruns: usize) {
+        self.achieved_fps = Some(achieved_fps);
+        self.frames_overrun = overruns;
+    }
+
+    /// Counts bytes written that aren't in a listed artifact, such as frames
+    pub fn wrote(&mut self, bytes: u64) {
+        self.bytes_written += bytes;
+    }
+
+    /// Lists a file or directory the run produced. Files still on disk when
+    /// the run finishes count towards the bytes written.
+    pub fn artifact(&mut self, path: impl Into<String>) {
+        self.artifacts.push(path.into());
+    }
+
+    /// Records why the run ended before its last frame
+    pub fn stopped(&mut self, reason: impl Into<String>) {
+        self.stop_reason = Some(reason.into());
+    }
+
+    /// Stops the clock, with `boids` being the flock as it ended
+    pub fn finish(self, boids: &[Boid]) -> RunSummary {
+        let seconds = |duration: Duration| duration.as_secs_f64();
+        let files: u64 = self
+            .artifacts
+            .iter()
+            .filter_map(|path| fs::metadata(Path::new(path)).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        RunSummary {
+            wall_time: seconds(self.started.elapsed()),
+            simulate_time: seconds(self.stages[Stage::Simulate as usize]),
+            rasterize_time: seconds(self.stages[Stage::Rasterize as usize]),
+            encode_time: seconds(self.stages[Stage::Encode as usize]),
+            io_time: seconds(self.stages[Stage::Io as usize]),
+            frames_written: self.frames,
+            bytes_written: self.bytes_written + files,
+            mean_frame_time: if self.frames == 0 {
+                0.0
+            } else {
+                seconds(self.frame_total) / self.frames as f64
+            },
+            min_frame_time: seconds(self.frame_min.unwrap_or_default()),
+            max_frame_time: seconds(self.frame_max),
+            frames_dropped: self.frames_dropped,
+            frames_overrun: self.frames_overrun,
+            achieved_fps: self.achieved_fps,
+            final_population: boids.len(),
+            final_polarization: polarization(boids),
+            artifacts: self.artifacts,
+            stop_reason: self.stop_reason,
+        }
+    }
+}
+
+/// What a run did and where its time went, times being in seconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub wall_time: f64,
+    pub simulate_time: f64,
+    pub rasterize_time: f64,
+    pub encode_time: f64,
+    pub io_time: f64,
+    pub frames_written: usize,
+    pub bytes_written: u64,
+    pub mean_frame_time: f64,
+    pub min_frame_time: f64,
+    pub max_frame_time: f64,
+    /// Frames simulated but not drawn, to keep up with `--target-fps`
+    pub frames_dropped: usize,
+    /// Frames finished after they were due by `--target-fps`
+    pub frames_overrun: usize,
+    /// Frames a second a paced run managed, `None` if it wasn't paced
+    pub achieved_fps: Option<f64>,
+    pub final_population: usize,
+    pub final_polarization: f32,
+    pub artifacts: Vec<String>,
+    /// Why the run ended early, if it did
+    pub stop_reason: Option<String>,
+}
+
+impl RunSummary {
+    /// The summary as lines for a person to read
+    pub fn lines(&self) -> Vec<String> {
+        let share = |time: f64| {
+            if self.wall_time > 0.0 {
+                time / self.wall_time * 100.0
+            } else {
+                0.0
+            }
+        };
+        let mut lines = vec![
+            format!("Wall time: {:.2}s", self.wall_time),
+            format!(
+                "Simulate {:.2}s ({:.0}%), rasterize {:.2}s ({:.0}%), encode {:.2}s ({:.0}%), IO {:.2}s ({:.0}%)",
+                self.simulate_time,
+                share(self.simulate_time),
+                self.rasterize_time,
+                share(self.rasterize_time),
+                self.encode_time,
+                share(self.encode_time),
+                self.io_time,
+                share(self.io_time),
+            ),
+            format!(
+                "Frames: {}, {:.1}ms mean, {:.1}ms min, {:.1}ms max",
+                self.frames_written,
+                self.mean_frame_time * 1000.0,
+                self.min_frame_time * 1000.0,
+                self.max_frame_time * 1000.0,
+            ),
+        ];
+        if let Some(fps) = self.achieved_fps {
+            lines.push(format!(
+                "Paced at {fps:.1} fps, {} frames late, {} not drawn",
+                self.frames_overrun, self.frames_dropped
+            ));
+        }
+        lines.extend([
+            format!("Bytes written: {}", self.bytes_written),
+            format!(
+                "Final population: {}, polarization {:.3}",
+                self.final_population, self.final_polarization
+            ),
+        ]);
+        if let Some(reason) = &self.stop_reason {
+            lines.push(format!("Stopped early: {reason}"));
+        }
+        lines.extend(
+            self.artifacts
+                .iter()
+                .map(|artifact| format!("Wrote {artifact}")),
+        );
+        lines
+    }
+}
+
+/// The size, spread and motion of a flock at a glance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlockSummary {
+    pub count: usize,
+    /// Top left and bottom right corners of the box around every boid, or
+    /// `None` with no boids
+    pub bounds: Option<(Vector2<f32>, Vector2<f32>)>,
+    pub centroid: Vector2<f32>,
+    pub mean_speed: f32,
+    pub polarization: f32,
+}
+
+/// Sums `boids` up in a `FlockSummary`
+pub fn summarize(boids: &[Boid]) -> FlockSummary {
+    let bounds = boids
+        .iter()
+        .map(|boid| (boid.pos, boid.pos))
+        .reduce(|(min, max), (pos, _)| (min.inf(&pos), max.sup(&pos)));
+    FlockSummary {
+        count: boids.len(),
+        bounds,
+        centroid: flock_centroid(boids),
+        mean_speed: mean_speed(boids),
+        polarization: polarization(boids),
+    }
+}
+
+/// One line for logs, in a stable format:
+///
+/// ```text
+/// 12 boids in (10.0, 20.0)-(200.0, 150.0), centroid (100.5, 80.2), mean speed 1.50, polarization 0.873
+/// ```
+impl fmt::Display for FlockSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((min, max)) = self.bounds else {
+            return write!(f, "no boids");
+        };
+        write!(
+            f,
+            "{} boid{} in ({:.1}, {:.1})-({:.1}, {:.1}), centroid ({:.1}, {:.1}), mean speed {:.2}, polarization {:.3}",
+            self.count,
+            if self.count == 1 { "" } else { "s" },
+            min.x,
+            min.y,
+            max.x,
+            max.y,
+            self.centroid.x,
+            self.centroid.y,
+            self.mean_speed,
+            self.polarization
+        )
+    }
+}
@@ -0,0 +1,176 @@
+//! Runs over every combination of a few parameters' values, for comparing
+//! how each changes the flock without a loop of separate runs.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::error::Error;
+use crate::Parameters;
+
+/// The values one parameter is swept over
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepAxis {
+    pub field: String,
+    pub values: Vec<f32>,
+}
+
+impl fmt::Display for SweepAxis {
+    /// Writes the values as a list, the first form `from_str` reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.field)?;
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SweepAxis {
+    type Err = String;
+
+    /// Parses `FIELD=VALUE[,VALUE...]`, or `FIELD=START..END:COUNT` for
+    /// `COUNT` evenly spaced values from `START` to `END`. The field has to
+    /// be one `Parameters::with_field` can set to every value.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expected = || {
+            format!(
+                "Sweep {s} must be in the form FIELD=VALUE[,VALUE...] or FIELD=START..END:COUNT"
+            )
+        };
+        let (field, values) = s.split_once('=').ok_or_else(expected)?;
+        let number = |text: &str| text.trim().parse::<f32>().map_err(|_| expected());
+        let values = match values.split_once("..") {
+            Some((start, rest)) => {
+                let (end, count) = rest.split_once(':').ok_or_else(expected)?;
+                let count: usize = count.trim().parse().map_err(|_| expected())?;
+                linspace(number(start)?, number(end)?, count)
+            }
+            None => values.split(',').map(number).collect::<Result<_, _>>()?,
+        };
+        if values.is_empty() || values.iter().any(|value| !value.is_finite()) {
+            return Err(expected());
+        }
+        let default = Parameters::default();
+        for value in &values {
+            default.with_field(field, *value)?;
+        }
+        Ok(SweepAxis {
+            field: field.to_string(),
+            values,
+        })
+    }
+}
+
+// `count` values from `start` to `end` inclusive, just `start` for one
+fn linspace(start: f32, end: f32, count: usize) -> Vec<f32> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![start],
+        _ => (0..count)
+            .map(|i| start + (end - start) * i as f32 / (count - 1) as f32)
+            .collect(),
+    }
+}
+
+/// One value from each of the axes, in the same order
+pub type Combination = Vec<(String, f32)>;
+
+/// Every combination of the axes' values, the last axis changing fastest.
+/// No axes at all is a single run with nothing changed.
+pub fn combinations(axes: &[SweepAxis]) -> Vec<Combination> {
+    axes.iter().fold(vec![Vec::new()], |combinations, axis| {
+        combinations
+            .iter()
+            .flat_map(|combination| {
+                axis.values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push((axis.field.clone(), *value));
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+/// A name for the run of `combination` that's safe as a directory name,
+/// e.g. `matching_factor=0.05_centering_factor=0.0002`
+pub fn run_name(combination: &[(String, f32)]) -> String {
+    if combination.is_empty() {
+        return String::from("default");
+    }
+    combination
+        .iter()
+        .map(|(field, value)| format!("{field}={value}"))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// `parameters` with every value in `combination` set
+pub fn apply(parameters: &Parameters, combination: &[(String, f32)]) -> Result<Parameters, String> {
+    combination
+        .iter()
+        .try_fold(parameters.clone(), |parameters, (field, value)| {
+            parameters.with_field(field, *value)
+        })
+}
+
+/// How a sweep's run ended up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweepResult {
+    pub polarization: f32,
+    pub angular_momentum: f32,
+    pub mean_speed: f32,
+}
+
+/// Writes a line for each run of a sweep, naming its directory, the values
+/// it swept and how its flock ended up
+pub struct SweepCsvWriter<W: Write> {
+    out: W,
+}
+
+impl SweepCsvWriter<BufWriter<File>> {
+    pub fn create(path: &Path, axes: &[SweepAxis]) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        SweepCsvWriter::new(BufWriter::new(file), axes).map_err(|err| Error::io("write", path, err))
+    }
+}
+
+impl<W: Write> SweepCsvWriter<W> {
+    pub fn new(mut out: W, axes: &[SweepAxis]) -> io::Result<Self> {
+        write!(out, "run")?;
+        for axis in axes {
+            write!(out, ",{}", axis.field)?;
+        }
+        writeln!(out, ",polarization,angular_momentum,mean_speed")?;
+        Ok(SweepCsvWriter { out })
+    }
+
+    pub fn write_run(
+        &mut self,
+        combination: &[(String, f32)],
+        result: &SweepResult,
+    ) -> io::Result<()> {
+        write!(self.out, "{}", run_name(combination))?;
+        for (_, value) in combination {
+            write!(self.out, ",{value}")?;
+        }
+        writeln!(
+            self.out,
+            ",{},{},{}",
+            result.polarization, result.angular_momentum, result.mean_speed
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
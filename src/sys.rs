@@ -0,0 +1,45 @@
+//! Platform specific queries about the running process.
+
+/// Resident memory of this process in bytes, `None` where that can't be
+/// found out
+#[cfg(target_os = "linux")]
+pub fn memory_usage_bytes() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    // e.g. "VmRSS:	   10240 kB"
+    let kilobytes: usize = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Resident memory of this process in bytes, `None` where that can't be
+/// found out
+#[cfg(target_os = "macos")]
+pub fn memory_usage_bytes() -> Option<usize> {
+    let mut info = std::mem::MaybeUninit::<libc::mach_task_basic_info>::uninit();
+    let mut count = (std::mem::size_of::<libc::mach_task_basic_info>()
+        / std::mem::size_of::<libc::natural_t>())
+        as libc::mach_msg_type_number_t;
+    // SAFETY: count says how big the buffer task_info may fill is, and the
+    // info is only read if it reports success
+    #[allow(deprecated)]
+    let result = unsafe {
+        libc::task_info(
+            libc::mach_task_self(),
+            libc::MACH_TASK_BASIC_INFO,
+            info.as_mut_ptr() as libc::task_info_t,
+            &mut count,
+        )
+    };
+    if result != libc::KERN_SUCCESS {
+        return None;
+    }
+    let info = unsafe { info.assume_init() };
+    usize::try_from(info.resident_size).ok()
+}
+
+/// Resident memory of this process in bytes, `None` where that can't be
+/// found out
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn memory_usage_bytes() -> Option<usize> {
+    None
+}
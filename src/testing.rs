@@ -0,0 +1,115 @@
+//! Flocks and runs for tests, so tests of code built on the simulation
+//! needn't copy the spawn loop or set scenes up boid by boid. Only built with
+//! the `testing` feature, so the integration tests using it need it too.
+use nalgebra::Vector2;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::init::{spawn_boids, BoidSpawnDistribution, BoidSpawnVelocity};
+use crate::{update_boids, Boid, BoidId, FlockTally, Parameters, World};
+
+/// `n` boids spread evenly over `world` with random velocities, the same
+/// ones every time for the same `seed`
+pub fn spawn_seeded(n: usize, world: &World, seed: u64) -> Vec<Boid> {
+    spawn_boids(
+        n,
+        &BoidSpawnDistribution::Uniform,
+        BoidSpawnVelocity::Random,
+        &mut StdRng::seed_from_u64(seed),
+        &Parameters::default(),
+        world,
+    )
+}
+
+/// Steps `boids` `n` times with `update_boids`, giving the tally of the
+/// last step, or an empty one if there were none
+pub fn run_steps(
+    boids: &mut [Boid],
+    world: &World,
+    parameters: &Parameters,
+    n: usize,
+) -> FlockTally {
+    let mut tally = FlockTally::default();
+    for _ in 0..n {
+        tally = update_boids(boids, world, parameters);
+    }
+    tally
+}
+
+/// A few boids set up to show one behaviour, in the world they're set up
+/// in, flying by the default parameters
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub boids: Vec<Boid>,
+    pub world: World,
+    pub parameters: Parameters,
+}
+
+impl Scenario {
+    /// Two boids 40 pixels apart in the middle of a 200x100 world, flying
+    /// straight at each other at the top speed
+    pub fn head_on() -> Self {
+        let speed = Parameters::default().max_speed;
+        Scenario::new(
+            200,
+            100,
+            vec![
+                boid(0, (80.0, 50.0), (speed, 0.0)),
+                boid(1, (120.0, 50.0), (-speed, 0.0)),
+            ],
+        )
+    }
+
+    /// One boid just outside the margin of a 200x100 world, flying at its
+    /// right hand edge at the top speed, a little downwards. Flown straight
+    /// at an edge a boid can't turn back, as the minimum speed undoes the
+    /// turn once it slows down.
+    pub fn aimed_at_wall() -> Self {
+        let parameters = Parameters::default();
+        let x = 200.0 - parameters.margin as f32 - 2.0 * parameters.max_speed;
+        let vel = Vector2::new(0.8, 0.6) * parameters.max_speed;
+        Scenario::new(200, 100, vec![boid(0, (x, 50.0), (vel.x, vel.y))])
+    }
+
+    /// 25 boids a pixel apart in a 5x5 square in the middle of a 200x100
+    /// world, flying the same way with their neighbours well inside their
+    /// protected range
+    pub fn dense_cluster() -> Self {
+        let boids = (0..25)
+            .map(|id| {
+                let pos = (98.0 + (id % 5) as f32, 48.0 + (id / 5) as f32);
+                boid(id, pos, (1.0, 0.0))
+            })
+            .collect();
+        Scenario::new(200, 100, boids)
+    }
+
+    fn new(width: u32, height: u32, boids: Vec<Boid>) -> Self {
+        Scenario {
+            boids,
+            world: World::from_pixels(width, height),
+            parameters: Parameters::default(),
+        }
+    }
+
+    /// Steps the scenario's boids `n` times, as `run_steps` does
+    pub fn run(&mut self, n: usize) -> FlockTally {
+        run_steps(&mut self.boids, &self.world, &self.parameters, n)
+    }
+}
+
+/// `n` boids 5 pixels apart down the line at `x`, starting at the top and
+/// all flying right at a speed of 1
+pub fn column(n: usize, x: f32) -> Vec<Boid> {
+    (0..n)
+        .map(|id| boid(id, (x, id as f32 * 5.0), (1.0, 0.0)))
+        .collect()
+}
+
+/// A white boid numbered `id` at `pos`, flying at `vel`
+pub fn boid(id: usize, pos: (f32, f32), vel: (f32, f32)) -> Boid {
+    Boid::at(Vector2::new(pos.0, pos.1))
+        .with_id(id as BoidId)
+        .with_velocity(Vector2::new(vel.0, vel.1))
+        .build()
+}
@@ -0,0 +1,53 @@
+//! Spans around the stages of a frame, written out in the Chrome trace
+//! format to open in chrome://tracing or Perfetto.
+//!
+//! Without the trace feature `in_span!` is just its body, so nothing is
+//! paid for the spans in normal builds.
+#[cfg(feature = "trace")]
+use std::fs::File;
+#[cfg(feature = "trace")]
+use std::io::{self, BufWriter};
+#[cfg(feature = "trace")]
+use std::path::Path;
+
+#[cfg(feature = "trace")]
+use tracing_chrome::ChromeLayerBuilder;
+#[cfg(feature = "trace")]
+pub use tracing_chrome::FlushGuard;
+#[cfg(feature = "trace")]
+use tracing_subscriber::prelude::*;
+
+#[cfg(feature = "trace")]
+#[doc(hidden)]
+pub use tracing;
+
+/// Evaluates `$body` inside a span called `$name`
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! in_span {
+    ($name:literal, $body:expr) => {{
+        let _span = $crate::trace::tracing::info_span!($name).entered();
+        $body
+    }};
+}
+
+/// Evaluates `$body`, spans are only kept with the trace feature
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! in_span {
+    ($name:literal, $body:expr) => {{
+        $body
+    }};
+}
+
+/// Records every span from here on to `path`. The trace is only complete
+/// once the returned guard is dropped.
+#[cfg(feature = "trace")]
+pub fn install(path: &Path) -> io::Result<FlushGuard> {
+    let out = BufWriter::new(File::create(path)?);
+    let (layer, guard) = ChromeLayerBuilder::new().writer(out).build();
+    // Not `try_init`, which would also try to take over from the logger
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .map_err(io::Error::other)?;
+    Ok(guard)
+}
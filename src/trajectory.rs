@@ -0,0 +1,420 @@
+//! Compact recordings of a whole run.
+//!
+//! A trajectory file starts with a header, followed by one record per frame.
+//! Keyframes store every boid in full, the frames in between store each
+//! boid's movement since the previous frame quantized to `1 / precision` of
+//! a pixel as an `i16`, along with its quantized velocity. Deltas are taken
+//! against the positions a reader will have reconstructed rather than the
+//! true ones, so rounding errors never build up beyond half a step.
+//!
+//! All values are little endian.
+//!
+//! ```text
+//! header:   b"BTRJ" version:u32 precision:u32 keyframe_interval:u32 width:u32 height:u32
+//! frame:    kind:u8 boid_count:u32 payload_len:u32 payload
+//! keyframe: (id:u64 x:f32 y:f32 vx:f32 vy:f32 r:u8 g:u8 b:u8) per boid
+//! delta:    (dx:i16 dy:i16 vx:i16 vy:i16) per boid
+//! ```
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use log::trace;
+use nalgebra::Vector2;
+
+use crate::boids::{Boid, BoidId};
+use crate::colour::Colour;
+use crate::error::Error;
+
+pub const TRAJECTORY_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"BTRJ";
+const KEYFRAME: u8 = 0;
+const DELTA: u8 = 1;
+const KEYFRAME_BOID_BYTES: usize = 8 + 4 * 4 + 3;
+const DELTA_BOID_BYTES: usize = 4 * 2;
+const HEADER_BYTES: u64 = 4 + 5 * 4;
+const FRAME_HEADER_BYTES: u64 = 1 + 2 * 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrajectoryHeader {
+    pub version: u32,
+    /// Subdivisions of a pixel that positions are quantized to
+    pub precision: u32,
+    /// Frames between keyframes
+    pub keyframe_interval: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TrajectoryHeader {
+    /// The largest error in any reconstructed position
+    pub fn quantization_step(&self) -> f32 {
+        1.0 / self.precision as f32
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub struct TrajectoryWriter<W: Write> {
+    out: W,
+    header: TrajectoryHeader,
+    frame: u64,
+    // What a reader will have decoded for the previous frame
+    decoded: Vec<Vector2<f32>>,
+    payload: Vec<u8>,
+}
+
+impl TrajectoryWriter<BufWriter<File>> {
+    pub fn create(
+        path: &Path,
+        precision: u32,
+        keyframe_interval: u32,
+        width: u32,
+        height: u32,
+    ) -> crate::Result<Self> {
+        let file = File::create(path).map_err(|err| Error::io("create", path, err))?;
+        TrajectoryWriter::new(
+            BufWriter::new(file),
+            precision,
+            keyframe_interval,
+            width,
+            height,
+        )
+        .map_err(|err| Error::io("write", path, err))
+    }
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    pub fn new(
+        mut out: W,
+        precision: u32,
+        keyframe_interval: u32,
+        width: u32,
+        height: u32,
+    ) -> io::Result<Self> {
+        if precision == 0 || keyframe_interval == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "precision and keyframe interval must be at least 1",
+            ));
+        }
+        let header = TrajectoryHeader {
+            version: TRAJECTORY_VERSION,
+            precision,
+            keyframe_interval,
+            width,
+            height,
+        };
+        out.write_all(MAGIC)?;
+        for value in [
+            TRAJECTORY_VERSION,
+            precision,
+            keyframe_interval,
+            width,
+            height,
+        ] {
+            out.write_all(&value.to_le_bytes())?;
+        }
+        Ok(TrajectoryWriter {
+            out,
+            header,
+            frame: 0,
+            decoded: Vec::new(),
+            payload: Vec::new(),
+        })
+    }
+
+    pub fn header(&self) -> TrajectoryHeader {
+        self.header
+    }
+
+    /// Records the next frame of the run
+    pub fn write_frame(&mut self, boids: &[Boid]) -> io::Result<()> {
+        let keyframe = self
+            .frame
+            .is_multiple_of(self.header.keyframe_interval as u64)
+            || self.decoded.len() != boids.len()
+            || !self.encode_delta(boids);
+        if keyframe {
+            trace!("Writing frame {} as a keyframe", self.frame);
+            self.encode_keyframe(boids);
+        }
+        self.frame += 1;
+        self.out
+            .write_all(&[if keyframe { KEYFRAME } else { DELTA }])?;
+        self.out.write_all(&(boids.len() as u32).to_le_bytes())?;
+        self.out
+            .write_all(&(self.payload.len() as u32).to_le_bytes())?;
+        self.out.write_all(&self.payload)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn encode_keyframe(&mut self, boids: &[Boid]) {
+        self.payload.clear();
+        self.decoded.clear();
+        for boid in boids {
+            self.payload.extend_from_slice(&boid.id_u64().to_le_bytes());
+            for value in [boid.pos.x, boid.pos.y, boid.vel.x, boid.vel.y] {
+                self.payload.extend_from_slice(&value.to_le_bytes());
+            }
+            self.payload.extend_from_slice(&boid.colour.0);
+            self.decoded.push(boid.pos);
+        }
+    }
+
+    // Returns false, leaving `decoded` alone, if any boid moved too far to
+    // fit in an i16
+    fn encode_delta(&mut self, boids: &[Boid]) -> bool {
+        let precision = self.header.precision as f32;
+        let quantize = |value: f32| {
+            let steps = (value * precision).round();
+            (steps >= i16::MIN as f32 && steps <= i16::MAX as f32).then_some(steps as i16)
+        };
+        self.payload.clear();
+        let mut decoded = Vec::with_capacity(boids.len());
+        for (boid, previous) in boids.iter().zip(&self.decoded) {
+            let delta = boid.pos - previous;
+            let (Some(dx), Some(dy), Some(vx), Some(vy)) = (
+                quantize(delta.x),
+                quantize(delta.y),
+                quantize(boid.vel.x),
+                quantize(boid.vel.y),
+            ) else {
+                return false;
+            };
+            for value in [dx, dy, vx, vy] {
+                self.payload.extend_from_slice(&value.to_le_bytes());
+            }
+            decoded.push(previous + Vector2::new(dx as f32, dy as f32) / precision);
+        }
+        self.decoded = decoded;
+        true
+    }
+}
+
+/// Reads back a trajectory one frame at a time
+pub struct TrajectoryReader<R: Read + Seek> {
+    input: R,
+    header: TrajectoryHeader,
+    frame: usize,
+    boids: Vec<Boid>,
+    // Offset, kind and payload length of every frame skimmed by `seek`
+    index: Vec<(u64, u8, u32)>,
+}
+
+impl TrajectoryReader<BufReader<File>> {
+    pub fn open(path: &Path) -> crate::Result<Self> {
+        let file = File::open(path).map_err(|err| Error::io("open", path, err))?;
+        TrajectoryReader::new(BufReader::new(file)).map_err(|err| Error::io("read", path, err))
+    }
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(input)?))
+}
+
+impl<R: Read + Seek> TrajectoryReader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        if &read_array::<4>(&mut input)? != MAGIC {
+            return Err(invalid_data("not a boids trajectory file"));
+        }
+        let version = read_u32(&mut input)?;
+        if version > TRAJECTORY_VERSION {
+            return Err(invalid_data(&format!(
+                "trajectory is version {version}, only up to {TRAJECTORY_VERSION} is understood"
+            )));
+        }
+        let header = TrajectoryHeader {
+            version,
+            precision: read_u32(&mut input)?,
+            keyframe_interval: read_u32(&mut input)?,
+            width: read_u32(&mut input)?,
+            height: read_u32(&mut input)?,
+        };
+        if header.precision == 0 {
+            return Err(invalid_data("trajectory has a precision of 0"));
+        }
+        Ok(TrajectoryReader {
+            input,
+            header,
+            frame: 0,
+            boids: Vec::new(),
+            index: Vec::new(),
+        })
+    }
+
+    pub fn header(&self) -> TrajectoryHeader {
+        self.header
+    }
+
+    /// The number of the frame `next_frame` will return
+    pub fn position(&self) -> usize {
+        self.frame
+    }
+
+    /// Decodes the next frame, or `None` at the end of the file
+    pub fn next_frame(&mut self) -> Option<io::Result<Vec<Boid>>> {
+        let mut kind = [0; 1];
+        match self.input.read(&mut kind) {
+            Ok(0) => None,
+            Ok(_) => Some(self.read_frame(kind[0])),
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    /// Moves so that `next_frame` returns frame `frame`, decoding forward from
+    /// the closest keyframe before it
+    pub fn seek(&mut self, frame: usize) -> io::Result<()> {
+        self.index_until(frame)?;
+        let keyframe = (0..=frame)
+            .rev()
+            .find(|&frame| self.index[frame].1 == KEYFRAME)
+            .ok_or_else(|| invalid_data("trajectory doesn't start with a keyframe"))?;
+        // Carry on from where we are if that's past the keyframe anyway
+        let start = if (keyframe + 1..=frame).contains(&self.frame) {
+            self.frame
+        } else {
+            keyframe
+        };
+        self.input.seek(SeekFrom::Start(self.index[start].0))?;
+        self.frame = start;
+        while self.frame < frame {
+            self.next_frame()
+                .unwrap_or_else(|| Err(io::ErrorKind::UnexpectedEof.into()))?;
+        }
+        Ok(())
+    }
+
+    // Skims frame headers, without decoding them, until `frame` is indexed
+    fn index_until(&mut self, frame: usize) -> io::Result<()> {
+        while self.index.len() <= frame {
+            let offset = match self.index.last() {
+                Some(&(offset, _, len)) => offset + FRAME_HEADER_BYTES + len as u64,
+                None => HEADER_BYTES,
+            };
+            self.input.seek(SeekFrom::Start(offset))?;
+            let kind = read_array::<1>(&mut self.input)?[0];
+            let _count = read_u32(&mut self.input)?;
+            let len = read_u32(&mut self.input)?;
+            self.index.push((offset, kind, len));
+        }
+        Ok(())
+    }
+
+    fn read_frame(&mut self, kind: u8) -> io::Result<Vec<Boid>> {
+        let count = read_u32(&mut self.input)? as usize;
+        let len = read_u32(&mut self.input)? as usize;
+        let mut payload = vec![0; len];
+        self.input.read_exact(&mut payload)?;
+        match kind {
+            KEYFRAME => {
+                if len != count * KEYFRAME_BOID_BYTES {
+                    return Err(invalid_data("keyframe is the wrong length"));
+                }
+                self.boids = payload
+                    .chunks_exact(KEYFRAME_BOID_BYTES)
+                    .map(|chunk| {
+                        let f32_at =
+                            |at: usize| f32::from_le_bytes(chunk[at..at + 4].try_into().unwrap());
+                        let vel = Vector2::new(f32_at(16), f32_at(20));
+                        Boid::new(
+                            u64::from_le_bytes(chunk[0..8].try_into().unwrap()) as BoidId,
+                            Vector2::new(f32_at(8), f32_at(12)),
+                            vel,
+                            Colour([chunk[24], chunk[25], chunk[26]]),
+                        )
+                    })
+                    .collect();
+            }
+            DELTA => {
+                if len != count * DELTA_BOID_BYTES || count != self.boids.len() {
+                    return Err(invalid_data(
+                        "delta frame doesn't follow on from the previous frame",
+                    ));
+                }
+                let step = self.header.quantization_step();
+                for (boid, chunk) in self
+                    .boids
+                    .iter_mut()
+                    .zip(payload.chunks_exact(DELTA_BOID_BYTES))
+                {
+                    let i16_at =
+                        |at: usize| i16::from_le_bytes([chunk[at], chunk[at + 1]]) as f32 * step;
+                    boid.pos += Vector2::new(i16_at(0), i16_at(2));
+                    boid.vel = Vector2::new(i16_at(4), i16_at(6));
+                    boid.current_speed = boid.vel.norm();
+                }
+            }
+            _ => return Err(invalid_data("unknown frame kind")),
+        }
+        self.frame += 1;
+        Ok(self.boids.clone())
+    }
+}
+
+/// How to fill in positions between two recorded frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight lines between the recorded positions
+    Linear,
+    /// Cubic curves that also match the recorded velocities
+    Hermite,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Interpolation::Linear),
+            "hermite" => Ok(Interpolation::Hermite),
+            _ => Err(format!(
+                "Unknown interpolation {s}, expected linear or hermite"
+            )),
+        }
+    }
+}
+
+/// The flock `t` of the way from one recorded frame to the next, matching
+/// boids up by id. `t` of 0 gives back `from` unchanged, otherwise boids that
+/// aren't in both frames are left out, so boids that despawn vanish at the
+/// earlier frame and new ones appear at the later one.
+pub fn interpolate(from: &[Boid], to: &[Boid], t: f32, mode: Interpolation) -> Vec<Boid> {
+    if t <= 0.0 {
+        return from.to_vec();
+    }
+    let by_id: HashMap<BoidId, &Boid> = to.iter().map(|boid| (boid.id, boid)).collect();
+    from.iter()
+        .filter_map(|start| {
+            let end = by_id.get(&start.id)?;
+            let pos = match mode {
+                Interpolation::Linear => start.pos.lerp(&end.pos, t),
+                Interpolation::Hermite => {
+                    // Velocities are in pixels per frame, the same units as
+                    // the span between the two frames
+                    let (t2, t3) = (t * t, t * t * t);
+                    start.pos * (2.0 * t3 - 3.0 * t2 + 1.0)
+                        + start.vel * (t3 - 2.0 * t2 + t)
+                        + end.pos * (-2.0 * t3 + 3.0 * t2)
+                        + end.vel * (t3 - t2)
+                }
+            };
+            let vel = start.vel.lerp(&end.vel, t);
+            Some(Boid::new(start.id, pos, vel, start.colour))
+        })
+        .collect()
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::str::FromStr;
+
+use nalgebra::{Matrix2, Vector2};
+
+use crate::boids::Boid;
+use crate::world::World;
+
+/// A rigid transform of a flock about the centre of its world
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Mirror left to right
+    FlipH,
+    /// Mirror top to bottom
+    FlipV,
+    /// Rotate by this many degrees, clockwise on screen
+    Rotate(f32),
+}
+
+impl FromStr for Transform {
+    type Err = String;
+
+    /// Parses `fliph`, `flipv`, `rot90`, `rot180` or `rotate:DEGREES`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fliph" => Ok(Transform::FlipH),
+            "flipv" => Ok(Transform::FlipV),
+            "rot90" => Ok(Transform::Rotate(90.0)),
+            "rot180" => Ok(Transform::Rotate(180.0)),
+            _ => s
+                .strip_prefix("rotate:")
+                .and_then(|degrees| degrees.parse().ok())
+                .filter(|degrees: &f32| degrees.is_finite())
+                .map(Transform::Rotate)
+                .ok_or_else(|| {
+                    format!("Unknown transform {s}, expected fliph, flipv, rot90, rot180 or rotate:DEGREES")
+                }),
+        }
+    }
+}
+
+/// Raised when a transform would leave boids outside of the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfBounds(pub usize);
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} boids would end up outside of the world", self.0)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+impl Transform {
+    /// The linear part of the transform, applied to offsets from the centre
+    /// and to velocities
+    pub fn matrix(&self) -> Matrix2<f32> {
+        match *self {
+            Transform::FlipH => Matrix2::new(-1.0, 0.0, 0.0, 1.0),
+            Transform::FlipV => Matrix2::new(1.0, 0.0, 0.0, -1.0),
+            Transform::Rotate(degrees) => {
+                let (sin, cos) = exact_sin_cos(degrees);
+                // y points down the screen, so this turns clockwise
+                Matrix2::new(cos, -sin, sin, cos)
+            }
+        }
+    }
+}
+
+// Quarter turns come out exact, rather than leaving boids a hair outside of
+// the world because cos(90) isn't quite zero
+fn exact_sin_cos(degrees: f32) -> (f32, f32) {
+    let degrees = degrees.rem_euclid(360.0);
+    match degrees {
+        0.0 => (0.0, 1.0),
+        90.0 => (1.0, 0.0),
+        180.0 => (0.0, -1.0),
+        270.0 => (-1.0, 0.0),
+        _ => degrees.to_radians().sin_cos(),
+    }
+}
+
+/// Applies `transform` to the positions and velocities of `boids` in
+/// `world`. Boids that would leave the world are clamped to its edge when
+/// `clamp` is set, otherwise the flock is left untouched and the number that
+/// would have left is returned as an error.
+pub fn apply(
+    boids: &mut [Boid],
+    transform: Transform,
+    world: &World,
+    clamp: bool,
+) -> Result<(), OutOfBounds> {
+    let max = world.max();
+    let centre = max / 2.0;
+    let matrix = transform.matrix();
+    let moved: Vec<Vector2<f32>> = boids
+        .iter()
+        .map(|boid| centre + matrix * (boid.pos - centre))
+        .collect();
+    let outside = moved
+        .iter()
+        .filter(|pos| pos.x < 0.0 || pos.y < 0.0 || pos.x > max.x || pos.y > max.y)
+        .count();
+    if outside > 0 && !clamp {
+        return Err(OutOfBounds(outside));
+    }
+    for (boid, pos) in boids.iter_mut().zip(moved) {
+        boid.pos = world.clamp(pos);
+        boid.vel = matrix * boid.vel;
+    }
+    Ok(())
+}
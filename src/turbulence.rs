@@ -0,0 +1,56 @@
+//! Random kicks to a few boids every frame, to see how well a flock holds
+//! together while being knocked about.
+use std::f32::consts::TAU;
+
+use nalgebra::Vector2;
+use rand::prelude::*;
+use rand::seq::index;
+
+use crate::Parameters;
+
+/// Kicks `fraction` of the flock, picked afresh every frame, by
+/// `strength` pixels per frame in a random direction
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TurbulenceInjector {
+    pub fraction: f32,
+    pub strength: f32,
+}
+
+impl From<&Parameters> for TurbulenceInjector {
+    fn from(parameters: &Parameters) -> Self {
+        TurbulenceInjector {
+            fraction: parameters.perturbation_fraction,
+            strength: parameters.perturbation_strength,
+        }
+    }
+}
+
+impl TurbulenceInjector {
+    /// Whether any boid could ever be kicked
+    pub fn is_off(&self) -> bool {
+        self.fraction <= 0.0 || self.strength == 0.0
+    }
+
+    /// How many of `boids` are kicked each frame, rounded to the nearest
+    pub fn count(&self, boids: usize) -> usize {
+        if self.is_off() {
+            return 0;
+        }
+        ((boids as f32 * self.fraction).round() as usize).min(boids)
+    }
+
+    /// A kick for each of `boids` boids by its place in the flock, zero for
+    /// those left alone this frame. Empty if none are kicked.
+    pub fn kicks<R: Rng + ?Sized>(&self, boids: usize, rng: &mut R) -> Vec<Vector2<f32>> {
+        let count = self.count(boids);
+        if count == 0 {
+            return Vec::new();
+        }
+        let mut kicks = vec![Vector2::zeros(); boids];
+        for idx in index::sample(rng, boids, count) {
+            let angle = rng.random_range(0.0..TAU);
+            kicks[idx] = Vector2::new(angle.cos(), angle.sin()) * self.strength;
+        }
+        kicks
+    }
+}
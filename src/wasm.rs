@@ -0,0 +1,119 @@
+//! A `wasm-bindgen` wrapper for running a flock in the browser, with the
+//! drawing left to JavaScript. `examples/web` has a page using it.
+//!
+//! This uses `SimulationState` rather than `Simulation`, as the latter times
+//! every step and there's no clock in `wasm32-unknown-unknown`.
+use js_sys::{Float32Array, Uint8Array};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wasm_bindgen::prelude::*;
+
+use crate::boundary::Boundary;
+use crate::init::{spawn_boids, BoidSpawnDistribution, BoidSpawnVelocity};
+use crate::parameters::{ParametersBuilder, ValidationError};
+use crate::simulation::SimulationState;
+use crate::world::World;
+use crate::Parameters;
+
+#[wasm_bindgen]
+pub struct WasmSim {
+    state: SimulationState,
+    // x, y for each boid, in the flock's order
+    positions: Vec<f32>,
+    // r, g, b for each boid, in the flock's order
+    colours: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WasmSim {
+    /// `count` boids spread over a `width` by `height` world from `seed`.
+    /// `params_json` is a JSON object of any `Parameters` to change from
+    /// the defaults, such as `{"visible_range": 30}`, or empty for none.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        width: u32,
+        height: u32,
+        count: usize,
+        seed: u64,
+        params_json: &str,
+    ) -> Result<WasmSim, JsError> {
+        let parameters = parameters_from_json(params_json)?;
+        let mut world = World::from_pixels(width, height);
+        if world.is_empty() {
+            return Err(JsError::new(&format!(
+                "a {width}x{height} world has no room for boids"
+            )));
+        }
+        world.boundary = Boundary::load(&parameters.boundary, width, height)?;
+        let boids = spawn_boids(
+            count,
+            &BoidSpawnDistribution::Uniform,
+            BoidSpawnVelocity::Random,
+            &mut StdRng::seed_from_u64(seed),
+            &parameters,
+            &world,
+        );
+        let mut sim = WasmSim {
+            state: SimulationState::new(boids, parameters, world),
+            positions: Vec::with_capacity(count * 2),
+            colours: Vec::with_capacity(count * 3),
+        };
+        sim.refresh();
+        Ok(sim)
+    }
+
+    pub fn step(&mut self) {
+        self.state.step();
+        self.refresh();
+    }
+
+    pub fn count(&self) -> usize {
+        self.state.boids.len()
+    }
+
+    /// Each boid's x and y in turn, in pixels. This is a view straight into
+    /// the module's memory, so it's only good until the next call into it.
+    pub fn positions(&self) -> Float32Array {
+        // SAFETY: nothing in Rust runs to move or free the buffer while the
+        // view is in use, as long as JavaScript is done with it by the next
+        // call, as documented
+        unsafe { Float32Array::view(&self.positions) }
+    }
+
+    /// Each boid's red, green and blue in turn. Like `positions`, it's only
+    /// good until the next call into the module.
+    pub fn colors(&self) -> Uint8Array {
+        // SAFETY: as for `positions`
+        unsafe { Uint8Array::view(&self.colours) }
+    }
+}
+
+impl WasmSim {
+    // Copies the flock out into the buffers JavaScript sees
+    fn refresh(&mut self) {
+        self.positions.clear();
+        self.colours.clear();
+        for boid in &self.state.boids {
+            self.positions.extend([boid.pos.x, boid.pos.y]);
+            self.colours.extend(boid.colour.0);
+        }
+    }
+}
+
+// The defaults with any fields in `json` replacing them, checked together
+fn parameters_from_json(json: &str) -> Result<Parameters, JsError> {
+    let mut value = serde_json::to_value(Parameters::default())?;
+    if !json.trim().is_empty() {
+        let serde_json::Value::Object(changes) = serde_json::from_str(json)? else {
+            return Err(JsError::new("parameters must be a JSON object"));
+        };
+        value
+            .as_object_mut()
+            .expect("parameters serialize to an object")
+            .extend(changes);
+    }
+    let parameters: Parameters = serde_json::from_value(value)?;
+    ParametersBuilder::from(parameters)
+        .build()
+        .map_err(|errors| JsError::new(&ValidationError::join(&errors)))
+}
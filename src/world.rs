@@ -0,0 +1,137 @@
+//! The space a flock lives in, how big it is and the shape of its edge.
+//!
+//! Everything that needs the size of the world takes a `World`, rather than
+//! a width and height that are all too easy to pass the wrong way round:
+//!
+//! ```compile_fail
+//! # let parameters = boids::Parameters::default();
+//! # let mut boids = Vec::new();
+//! // Height then width, or was it the other way? Neither compiles now.
+//! boids::update_boids(&mut boids, 1080, 1920, &parameters);
+//! ```
+//!
+//! ```
+//! # let parameters = boids::Parameters::default();
+//! # let mut boids = Vec::new();
+//! let world = boids::World::from_pixels(1920, 1080);
+//! boids::update_boids(&mut boids, &world, &parameters);
+//! ```
+use nalgebra::Vector2;
+
+use crate::boundary::{Boundary, BoundaryError, BoundaryMode};
+use crate::Parameters;
+
+/// A `width` x `height` world with its top left corner at the origin, and
+/// the boundary boids turn back from
+#[derive(Debug, Clone, PartialEq)]
+pub struct World {
+    pub width: f32,
+    pub height: f32,
+    pub boundary: Boundary,
+}
+
+impl World {
+    /// A world whose edge is its own rectangle
+    pub fn new(width: f32, height: f32) -> Self {
+        World {
+            width,
+            height,
+            boundary: Boundary::Rectangle,
+        }
+    }
+
+    /// A world the size of a `width` x `height` pixel frame, such as given
+    /// on the command line
+    pub fn from_pixels(width: u32, height: u32) -> Self {
+        World::new(width as f32, height as f32)
+    }
+
+    /// A `width` x `height` pixel world with the boundary `mode` describes
+    pub fn load(width: u32, height: u32, mode: &BoundaryMode) -> Result<Self, BoundaryError> {
+        Ok(World::from_pixels(width, height).with_boundary(Boundary::load(mode, width, height)?))
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// `(width, height)` of a frame that shows all of the world
+    pub fn pixels(&self) -> (u32, u32) {
+        (self.width.ceil() as u32, self.height.ceil() as u32)
+    }
+
+    pub fn size(&self) -> Vector2<f32> {
+        Vector2::new(self.width, self.height)
+    }
+
+    /// Whether a frame of the world would have no pixels at all
+    pub fn is_empty(&self) -> bool {
+        !(self.width >= 1.0 && self.height >= 1.0)
+    }
+
+    /// The furthest corner from the origin boids are kept to, in the last
+    /// row and column of pixels
+    pub fn max(&self) -> Vector2<f32> {
+        Vector2::new(self.width - 1.0, self.height - 1.0)
+    }
+
+    /// Whether `pos` is on a pixel of the world
+    pub fn contains(&self, pos: Vector2<f32>) -> bool {
+        (0.0..self.width).contains(&pos.x) && (0.0..self.height).contains(&pos.y)
+    }
+
+    /// `pos`, moved onto the nearest point between the origin and `max`
+    pub fn clamp(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        let max = self.max();
+        Vector2::new(pos.x.clamp(0.0, max.x), pos.y.clamp(0.0, max.y))
+    }
+
+    /// Whether margins of `margin` on opposite sides meet or cross, leaving
+    /// nowhere for boids to fly without being turned
+    pub fn margins_meet(&self, margin: u32) -> bool {
+        let (width, height) = self.pixels();
+        margin.saturating_mul(2) >= width.min(height)
+    }
+
+    /// Turns the velocity of a boid at `pos` away from the edge. Against an
+    /// SDF the turn grows from nothing `margin` inside the edge to
+    /// `turn_factor` on it, and on to twice that `margin` outside.
+    pub fn turn(
+        &self,
+        pos: Vector2<f32>,
+        mut vel: Vector2<f32>,
+        parameters: &Parameters,
+    ) -> Vector2<f32> {
+        match &self.boundary {
+            Boundary::Rectangle => {
+                // Margins that would meet are cut to half the world, so a
+                // boid is still turned towards the middle rather than both
+                // ways at once, which cancels out
+                let margin = parameters.margin as f32;
+                let (margin_x, margin_y) =
+                    (margin.min(self.width / 2.0), margin.min(self.height / 2.0));
+                if pos.y > self.height - margin_y {
+                    vel.y -= parameters.turn_factor;
+                }
+                if pos.x > self.width - margin_x {
+                    vel.x -= parameters.turn_factor;
+                }
+                if pos.x < margin_x {
+                    vel.x += parameters.turn_factor;
+                }
+                if pos.y < margin_y {
+                    vel.y += parameters.turn_factor;
+                }
+            }
+            Boundary::Sdf(sdf) => {
+                let margin = parameters.margin.max(1) as f32;
+                let proximity = 1.0 - sdf.distance_at(pos) / margin;
+                if proximity > 0.0 {
+                    vel += sdf.normal_at(pos) * parameters.turn_factor * proximity.min(2.0);
+                }
+            }
+        }
+        vel
+    }
+}
@@ -0,0 +1,90 @@
+//! Regions of the world with speed limits of their own, such as slow lanes
+//! around a food source or fast lanes boids are swept along.
+//!
+//! ```toml
+//! [[speed_zones]]
+//! rect = [100.0, 100.0, 300.0, 200.0]
+//! max_speed = 1.0
+//! ```
+use std::str::FromStr;
+
+use nalgebra::Vector2;
+use serde::{Deserialize, Serialize};
+
+use crate::Parameters;
+
+/// A rectangle, `(x0, y0, x1, y1)` from its top left to bottom right
+/// corner, where boids are held to `max_speed` instead of the usual limit.
+/// One with a `max_speed` over the usual limit speeds them up instead.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedLimitZone {
+    pub rect: (f32, f32, f32, f32),
+    pub max_speed: f32,
+    /// Replaces the usual minimum speed inside the zone too
+    #[serde(default)]
+    pub min_speed: Option<f32>,
+}
+
+impl SpeedLimitZone {
+    /// Whether `pos` is inside, counting the top and left edges but not the
+    /// bottom and right
+    pub fn contains(&self, pos: Vector2<f32>) -> bool {
+        let (x0, y0, x1, y1) = self.rect;
+        (x0..x1).contains(&pos.x) && (y0..y1).contains(&pos.y)
+    }
+
+    /// Whether boids go faster in here than `max_speed` lets them elsewhere
+    pub fn is_boost(&self, max_speed: f32) -> bool {
+        self.max_speed > max_speed
+    }
+
+    /// The `(min_speed, max_speed)` a boid at `pos` is held to, from the
+    /// first of `parameters.speed_zones` it's in or the usual limits if none
+    pub fn limits_at(pos: Vector2<f32>, parameters: &Parameters) -> (f32, f32) {
+        match parameters
+            .speed_zones
+            .iter()
+            .find(|zone| zone.contains(pos))
+        {
+            Some(zone) => (
+                zone.min_speed
+                    .unwrap_or(parameters.min_speed)
+                    .min(zone.max_speed),
+                zone.max_speed,
+            ),
+            None => (parameters.min_speed, parameters.max_speed),
+        }
+    }
+}
+
+impl FromStr for SpeedLimitZone {
+    type Err = String;
+
+    /// `x0,y0,x1,y1,max_speed` with an optional `,min_speed` on the end.
+    /// The corners can be given either way round.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values = s
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|err| format!("Bad number {value}: {err}"))
+            })
+            .collect::<Result<Vec<f32>, String>>()?;
+        let (x0, y0, x1, y1, max_speed, min_speed) = match values[..] {
+            [x0, y0, x1, y1, max_speed] => (x0, y0, x1, y1, max_speed, None),
+            [x0, y0, x1, y1, max_speed, min_speed] => (x0, y0, x1, y1, max_speed, Some(min_speed)),
+            _ => {
+                return Err(format!(
+                    "Expected x0,y0,x1,y1,max_speed[,min_speed], not {s}"
+                ))
+            }
+        };
+        Ok(SpeedLimitZone {
+            rect: (x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)),
+            max_speed,
+            min_speed,
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+use boids::balance::LoadBalancer;
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::world::World;
+use boids::Parameters;
+
+#[test]
+fn runs_start_out_even() {
+    let balancer = LoadBalancer::new(4);
+    assert_eq!(balancer.next_partition(10), [0..2, 2..5, 5..7, 7..10]);
+    assert_eq!(LoadBalancer::new(8).next_partition(3), [0..1, 1..2, 2..3]);
+    assert_eq!(balancer.imbalance(), 0.0);
+}
+
+#[test]
+fn slow_runs_are_given_fewer_boids() {
+    let mut balancer = LoadBalancer::new(2);
+    balancer.record(
+        vec![0..5, 5..10],
+        vec![Duration::from_millis(30), Duration::from_millis(10)],
+    );
+    assert_eq!(balancer.imbalance(), 1.5);
+    assert_eq!(balancer.next_partition(10), [0..4, 4..10]);
+
+    // Times for a different flock are no use
+    assert_eq!(balancer.next_partition(12), [0..6, 6..12]);
+}
+
+#[test]
+fn balanced_steps_match_even_ones() {
+    let config = SimulationConfig {
+        seed: 5,
+        ..SimulationConfig::new(World::from_pixels(300, 200), Parameters::default(), 200)
+    };
+    let mut even = Simulation::new(config.clone()).unwrap();
+    let mut balanced = Simulation::new(config).unwrap();
+    balanced.balance_load(true);
+    for _ in 0..3 {
+        assert_eq!(even.step().load_imbalance, 0.0);
+        assert!(balanced.step().load_imbalance >= 1.0);
+    }
+    assert_eq!(even.boids(), balanced.boids());
+}
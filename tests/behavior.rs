@@ -0,0 +1,72 @@
+use nalgebra::Vector2;
+
+use boids::behavior::{BoidBehavior, Wind};
+use boids::boids::Boid;
+use boids::simulation::SimulationState;
+use boids::testing::boid;
+use boids::world::World;
+use boids::Parameters;
+
+// Two boids too far apart to see each other, well away from the edges
+fn state() -> SimulationState {
+    let boids = vec![
+        boid(0, (50.0, 50.0), (1.0, 0.0)),
+        boid(1, (150.0, 50.0), (0.0, 1.0)),
+    ];
+    SimulationState::new(boids, Parameters::default(), World::from_pixels(200, 100))
+}
+
+fn velocities(state: &SimulationState) -> Vec<Vector2<f32>> {
+    state.boids.iter().map(Boid::velocity).collect()
+}
+
+#[test]
+fn extra_forces_only_push_for_one_step() {
+    let mut plain = state();
+    let mut gusted = state();
+    let gust: [Box<dyn BoidBehavior>; 1] = [Box::new(Wind(Vector2::new(0.5, 0.0)))];
+    plain.step();
+    gusted.step_with_forces(&gust);
+    assert_eq!(
+        velocities(&gusted),
+        [Vector2::new(1.5, 0.0), Vector2::new(0.5, 1.0)]
+    );
+    assert_eq!(gusted.boids[0].pos, Vector2::new(51.5, 50.0));
+    assert_eq!(
+        velocities(&plain),
+        [Vector2::new(1.0, 0.0), Vector2::new(0.0, 1.0)]
+    );
+
+    // Nothing is kept for the next one
+    let before = velocities(&gusted);
+    gusted.step();
+    assert_eq!(velocities(&gusted), before);
+}
+
+#[test]
+fn forces_add_up_and_respect_speed_limits() {
+    let mut state = state();
+    let pull_down = |boid: &Boid, _: &World, _: &Parameters| {
+        Vector2::new(0.0, if boid.id() == 1 { 1.0 } else { 0.0 })
+    };
+    let forces: [Box<dyn BoidBehavior>; 2] =
+        [Box::new(pull_down), Box::new(Wind(Vector2::new(0.0, 5.0)))];
+    state.step_with_forces(&forces);
+    let max_speed = Parameters::default().max_speed;
+    assert!(state
+        .boids
+        .iter()
+        .all(|boid| (boid.speed() - max_speed).abs() < 1e-5));
+    assert_eq!(state.boids[1].velocity(), Vector2::new(0.0, max_speed));
+}
+
+#[test]
+fn no_extra_forces_is_a_plain_step() {
+    let mut plain = state();
+    let mut forced = state();
+    for _ in 0..20 {
+        plain.step();
+        forced.step_with_forces(&[]);
+    }
+    assert_eq!(plain.boids, forced.boids);
+}
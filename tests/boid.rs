@@ -0,0 +1,134 @@
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::colour::Colour;
+use boids::testing::boid;
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+}
+
+#[test]
+fn heading_follows_velocity() {
+    assert_eq!(boid(0, (0.0, 0.0), (2.0, 0.0)).heading(), 0.0);
+    assert_close(boid(0, (0.0, 0.0), (0.0, 3.0)).heading(), FRAC_PI_2);
+    assert_close(boid(0, (0.0, 0.0), (0.0, -3.0)).heading(), -FRAC_PI_2);
+    assert_close(boid(0, (0.0, 0.0), (-1.0, 0.0)).heading(), PI);
+    assert_close(boid(0, (0.0, 0.0), (1.0, 1.0)).heading(), FRAC_PI_4);
+    assert_close(
+        boid(0, (0.0, 0.0), (-1.0, -1.0)).heading(),
+        -3.0 * FRAC_PI_4,
+    );
+    // Standing still, however the zero is signed
+    assert_eq!(boid(0, (0.0, 0.0), (0.0, 0.0)).heading(), 0.0);
+    assert_eq!(boid(0, (0.0, 0.0), (-0.0, -0.0)).heading(), 0.0);
+}
+
+#[test]
+fn distances_between_boids() {
+    let a = boid(0, (1.0, 2.0), (0.0, 0.0));
+    let b = boid(0, (4.0, 6.0), (0.0, 0.0));
+    assert_eq!(a.distance_squared_to(&b), 25.0);
+    assert_eq!(a.distance_to(&b), 5.0);
+    assert_eq!(b.distance_to(&a), 5.0);
+    assert_eq!(a.distance_to(&boid(0, (1.0, -3.0), (0.0, 0.0))), 5.0);
+    assert_eq!(a.distance_to(&a), 0.0);
+
+    assert!(a.is_within(&b, 5.1));
+    // The range itself is out
+    assert!(!a.is_within(&b, 5.0));
+    assert!(!a.is_within(&a, 0.0));
+}
+
+#[test]
+fn bearings_are_relative_to_heading() {
+    let east = boid(0, (0.0, 0.0), (1.0, 0.0));
+    assert_eq!(
+        east.relative_bearing_to(&boid(0, (5.0, 0.0), (0.0, 0.0))),
+        0.0
+    );
+    assert_close(
+        east.relative_bearing_to(&boid(0, (0.0, 5.0), (0.0, 0.0))),
+        FRAC_PI_2,
+    );
+    assert_close(
+        east.relative_bearing_to(&boid(0, (0.0, -5.0), (0.0, 0.0))),
+        -FRAC_PI_2,
+    );
+    assert_close(
+        east.relative_bearing_to(&boid(0, (-5.0, 0.0), (0.0, 0.0))),
+        -PI,
+    );
+
+    // Wraps round rather than turning the long way
+    let north_west = boid(0, (0.0, 0.0), (-1.0, -1.0));
+    assert_close(
+        north_west.relative_bearing_to(&boid(0, (-1.0, 1.0), (0.0, 0.0))),
+        -FRAC_PI_2,
+    );
+    assert_close(
+        north_west.relative_bearing_to(&boid(0, (1.0, -1.0), (0.0, 0.0))),
+        FRAC_PI_2,
+    );
+
+    // A boid standing still faces along +x, and one on top of it is ahead
+    let still = boid(0, (0.0, 0.0), (0.0, 0.0));
+    assert_close(
+        still.relative_bearing_to(&boid(0, (0.0, 2.0), (0.0, 0.0))),
+        FRAC_PI_2,
+    );
+    assert_eq!(east.relative_bearing_to(&east), 0.0);
+}
+
+#[test]
+fn displays_on_one_line() {
+    let mut boid = boid(0, (512.34, 98.06), (1.2, -0.4));
+    assert_eq!(
+        boid.to_string(),
+        "#0 pos=(512.3, 98.1) vel=(1.2, -0.4) spd=1.26"
+    );
+    boid.set_velocity(Vector2::zeros());
+    assert_eq!(
+        boid.to_string(),
+        "#0 pos=(512.3, 98.1) vel=(0.0, 0.0) spd=0.00"
+    );
+}
+
+#[test]
+fn built_boids_fill_in_what_isnt_given() {
+    let built = Boid::at(Vector2::new(5.0, 6.0)).build();
+    assert_eq!(built, boid(0, (5.0, 6.0), (0.0, 0.0)));
+    let json = serde_json::json!({ "id": 0, "pos": [5.0, 6.0] });
+    assert_eq!(serde_json::from_value::<Boid>(json).unwrap(), built);
+
+    let built = Boid::at(Vector2::new(1.0, 2.0))
+        .with_id(9)
+        .with_velocity(Vector2::new(-3.0, 4.0))
+        .with_colour([10, 20, 30])
+        .build();
+    assert_eq!(built.id(), 9);
+    assert_eq!(built.speed(), 5.0);
+    assert_eq!(built.colour, Colour([10, 20, 30]));
+    assert_eq!(
+        built,
+        Boid::new(
+            9,
+            Vector2::new(1.0, 2.0),
+            Vector2::new(-3.0, 4.0),
+            [10, 20, 30]
+        )
+    );
+}
+
+#[test]
+fn boids_can_be_moved_and_nudged() {
+    let mut moved = boid(0, (1.0, 2.0), (1.0, 0.0));
+    moved.set_position(Vector2::new(30.0, 40.0));
+    assert_eq!(moved.pos, Vector2::new(30.0, 40.0));
+    assert_eq!(moved.velocity(), Vector2::new(1.0, 0.0));
+
+    moved.nudge(Vector2::new(-1.0, 2.0));
+    assert_eq!(moved, boid(0, (30.0, 40.0), (0.0, 2.0)));
+}
@@ -0,0 +1,124 @@
+#[cfg(feature = "render")]
+use image::GrayImage;
+use nalgebra::Vector2;
+
+use boids::boundary::{Boundary, BoundaryError, BoundaryMode, SdfBoundary};
+use boids::testing::Scenario;
+use boids::world::World;
+use boids::Parameters;
+
+// A disc of radius 30 in the middle of a 100x100 world, one grey level per
+// pixel of distance from its edge
+fn disc_levels() -> Vec<u8> {
+    (0..100)
+        .flat_map(|y| (0..100).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let distance =
+                30.0 - (Vector2::new(x as f32, y as f32) - Vector2::new(50.0, 50.0)).norm();
+            (128.0 + distance).clamp(0.0, 255.0) as u8
+        })
+        .collect()
+}
+
+fn disc() -> SdfBoundary {
+    SdfBoundary::from_levels(100, 100, &disc_levels())
+}
+
+#[test]
+fn normals_point_inside() {
+    let sdf = disc();
+    assert_eq!(sdf.distance_at(Vector2::new(50.0, 50.0)), 30.0);
+    assert_eq!(sdf.distance_at(Vector2::new(50.0, 85.0)), -5.0);
+    let normal = sdf.normal_at(Vector2::new(80.0, 50.0));
+    assert!((normal - Vector2::new(-1.0, 0.0)).norm() < 1e-3, "{normal}");
+    let flat = SdfBoundary::from_levels(4, 4, &[200; 16]);
+    assert_eq!(flat.normal_at(Vector2::new(1.0, 1.0)), Vector2::zeros());
+}
+
+#[test]
+fn turns_harder_closer_to_the_edge() {
+    let world = World::from_pixels(100, 100).with_boundary(Boundary::Sdf(disc()));
+    let parameters = Parameters::default();
+    let turn = |x: f32| world.turn(Vector2::new(x, 50.0), Vector2::zeros(), &parameters);
+    // Further in than the margin, so left alone
+    assert_eq!(turn(50.0), Vector2::zeros());
+    // Half the margin in, on the edge, and a margin outside
+    assert!((turn(75.0).x + 0.1).abs() < 1e-3, "{}", turn(75.0));
+    assert!((turn(80.0).x + 0.2).abs() < 1e-3, "{}", turn(80.0));
+    assert!((turn(90.0).x + 0.4).abs() < 1e-3, "{}", turn(90.0));
+    // No harder than twice the turn factor however far out
+    assert!((turn(99.0).x + 0.4).abs() < 1e-3, "{}", turn(99.0));
+}
+
+#[test]
+fn rectangle_turns_from_the_frame_edges() {
+    let Scenario {
+        world, parameters, ..
+    } = Scenario::aimed_at_wall();
+    let vel = Vector2::new(1.0, 1.0);
+    let turn = |x: f32, y: f32| world.turn(Vector2::new(x, y), vel, &parameters);
+    assert_eq!(turn(100.0, 50.0), vel);
+    assert_eq!(turn(5.0, 95.0), Vector2::new(1.2, 0.8));
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn sdf_must_match_the_world() {
+    let path = std::env::temp_dir().join(format!("boids_sdf_{}.png", std::process::id()));
+    GrayImage::from_raw(100, 100, disc_levels())
+        .unwrap()
+        .save(&path)
+        .unwrap();
+    let mode = BoundaryMode::Sdf { path: path.clone() };
+    assert!(matches!(
+        Boundary::load(&mode, 100, 100),
+        Ok(Boundary::Sdf(sdf)) if sdf == disc()
+    ));
+    assert!(matches!(
+        Boundary::load(&mode, 100, 50),
+        Err(BoundaryError::SizeMismatch {
+            world: (100, 50),
+            image: (100, 100)
+        })
+    ));
+    std::fs::remove_file(path).unwrap();
+    assert!(matches!(
+        Boundary::load(&mode, 100, 100),
+        Err(BoundaryError::Image(_))
+    ));
+}
+
+#[cfg(not(feature = "render"))]
+#[test]
+fn sdf_needs_the_render_feature() {
+    let mode = BoundaryMode::Sdf {
+        path: "disc.png".into(),
+    };
+    assert!(matches!(
+        Boundary::load(&mode, 100, 100),
+        Err(BoundaryError::NoImageSupport)
+    ));
+}
+
+#[test]
+fn boundary_round_trips_through_toml() {
+    let parameters = Parameters {
+        boundary: BoundaryMode::Sdf {
+            path: "tank.png".into(),
+        },
+        ..Parameters::default()
+    };
+    let text = toml::to_string(&parameters).unwrap();
+    assert_eq!(toml::from_str::<Parameters>(&text).unwrap(), parameters);
+    // Left out, it's the frame
+    let text = toml::to_string(&Parameters::default()).unwrap();
+    let without: String = text
+        .lines()
+        .filter(|line| !line.starts_with("boundary"))
+        .map(|line| format!("{line}\n"))
+        .collect();
+    assert_eq!(
+        toml::from_str::<Parameters>(&without).unwrap(),
+        Parameters::default()
+    );
+}
@@ -0,0 +1,1209 @@
+#![cfg(feature = "cli")]
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use nalgebra::Vector2;
+
+use boids::manifest::Manifest;
+use boids::state::{self, Format, SaveFile};
+use boids::{Boid, Parameters, PRESETS};
+
+fn frames_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("boids_cli_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn boids(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width", "64", "--height", "48", "--boids", "12", "--seed", "3",
+        ])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[test]
+fn saves_to_stdout() {
+    let dir = frames_dir("stdout");
+    let output = boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "0",
+            "--save-file",
+            "-",
+        ],
+        b"",
+    );
+    // Nothing but the state, so it can be piped straight into something else
+    let save = state::from_bytes(&output.stdout, Format::Json).unwrap();
+    assert_eq!(save.boids.len(), 12);
+    assert_eq!(save.world_size, Some((64, 48)));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Saving starting state to -"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn pipes_binary_states_through() {
+    let dir = frames_dir("pipe");
+    let dir = dir.to_str().unwrap();
+    let first = boids(
+        &[
+            "--dir",
+            dir,
+            "--frames",
+            "0",
+            "--save-file",
+            "-",
+            "--stdio-format",
+            "bin.zst",
+        ],
+        b"",
+    );
+    let sent = state::from_bytes(&first.stdout, Format::Bincode).unwrap();
+
+    let second = boids(
+        &[
+            "--dir",
+            dir,
+            "--frames",
+            "0",
+            "--load-file",
+            "-",
+            "--save-file",
+            "-",
+            "--stdio-format",
+            "bin",
+        ],
+        &first.stdout,
+    );
+    let received = state::from_bytes(&second.stdout, Format::Bincode).unwrap();
+    assert_eq!(received.boids, sent.boids);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn loaded_states_bring_their_parameters() {
+    let dir = frames_dir("restore");
+    let dir = dir.to_str().unwrap();
+    let first = boids(
+        &[
+            "--dir",
+            dir,
+            "--frames",
+            "0",
+            "--save-file",
+            "-",
+            "--preset",
+            "dense",
+        ],
+        b"",
+    );
+    let saved = state::from_bytes(&first.stdout, Format::Json).unwrap();
+    assert_eq!(saved.metadata.parameters, Some(Parameters::dense()));
+
+    let second = boids(
+        &[
+            "--dir",
+            dir,
+            "--frames",
+            "0",
+            "--load-file",
+            "-",
+            "--save-file",
+            "-",
+            "--global-centering-factor",
+            "0.01",
+        ],
+        &first.stdout,
+    );
+    let restored = state::from_bytes(&second.stdout, Format::Json).unwrap();
+    assert_eq!(
+        restored.metadata.parameters,
+        Some(Parameters {
+            global_centering_factor: 0.01,
+            ..Parameters::dense()
+        })
+    );
+    assert!(String::from_utf8_lossy(&second.stderr).contains("global_centering_factor: 0 -> 0.01"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn flags_override_the_params_file_and_preset() {
+    let dir = frames_dir("layered");
+    let params = dir.join("params.toml");
+    let text = toml::to_string(&Parameters {
+        max_speed: 5.0,
+        avoid_factor: 0.2,
+        ..Parameters::default()
+    })
+    .unwrap();
+    std::fs::write(&params, text).unwrap();
+    let output = boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "0",
+            "--save-file",
+            "-",
+            "--preset",
+            "dense",
+            "--params",
+            params.to_str().unwrap(),
+            "--avoid-factor",
+            "0.3",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    let saved = state::from_bytes(&output.stdout, Format::Json).unwrap();
+    let expected = Parameters {
+        max_speed: 5.0,
+        avoid_factor: 0.3,
+        ..Parameters::dense()
+    };
+    assert_eq!(saved.metadata.parameters, Some(expected.clone()));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!(
+        "max_speed 5, min_speed {}, margin {}, visible_range {}",
+        expected.min_speed, expected.margin, expected.visible_range
+    )));
+    assert!(stderr.contains("avoid_factor 0.3,"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_a_summary() {
+    let dir = frames_dir("summary");
+    let summary_file = dir.join("summary.json");
+    let save_file = dir.join("state.json");
+    let output = boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "2",
+            "--save-file",
+            save_file.to_str().unwrap(),
+            "--summary-file",
+            summary_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Final population: 12"));
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&summary_file).unwrap()).unwrap();
+    assert_eq!(summary["frames_written"], 2);
+    assert_eq!(summary["final_population"], 12);
+    let frames: u64 = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .map(|path| std::fs::metadata(path).unwrap().len())
+        .sum();
+    let saved = std::fs::metadata(&save_file).unwrap().len();
+    assert_eq!(summary["bytes_written"], frames + saved);
+    assert_eq!(summary["artifacts"][0], save_file.to_str().unwrap());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn manifest_lists_every_frame() {
+    let dir = frames_dir("manifest");
+    let manifest_file = dir.join("manifest.json");
+    for live in [false, true] {
+        let mut args = vec![
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "2",
+            "--trail-length",
+            "4",
+            "--output-manifest",
+            manifest_file.to_str().unwrap(),
+        ];
+        if live {
+            args.push("--live-manifest");
+        }
+        boids(&args, b"");
+        let manifest = Manifest::load(&manifest_file).unwrap();
+        assert_eq!(manifest.parameters.trail_length, 4);
+        let frames: Vec<usize> = manifest.frames.iter().map(|frame| frame.frame).collect();
+        assert_eq!(frames, [1, 2]);
+        for frame in &manifest.frames {
+            assert!(dir.join(&frame.path).is_file(), "{}", frame.path);
+            assert_eq!(frame.metrics.population, 12);
+        }
+        assert!(!dir.join("manifest.json.tmp").exists());
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn frame_hashes_repeat_for_a_seed() {
+    let run = |name: &str| {
+        let dir = frames_dir(name);
+        let output = boids(
+            &[
+                "--dir",
+                dir.to_str().unwrap(),
+                "--frames",
+                "3",
+                "--hash-frames",
+            ],
+            b"",
+        );
+        let hashes = std::fs::read_to_string(dir.join("frame_hashes.txt")).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let combined = stderr
+            .lines()
+            .find_map(|line| line.strip_prefix("Combined frame hash: "))
+            .unwrap()
+            .to_string();
+        (hashes, combined)
+    };
+    let (hashes, combined) = run("hash_a");
+    assert_eq!(hashes.lines().count(), 3);
+    assert_eq!(run("hash_b"), (hashes, combined));
+}
+
+#[test]
+fn ctrl_c_writes_a_checkpoint() {
+    let dir = frames_dir("interrupt");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width", "64", "--height", "48", "--boids", "12", "--seed", "3",
+        ])
+        .args(["--dir", dir.to_str().unwrap(), "--frames", "100000000"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Frames only appear once the handler is in place
+    while !dir.join("frames_00000001.png").exists() {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+
+    let checkpoint = state::load(&dir.join("checkpoint.json")).unwrap();
+    let frame = checkpoint.metadata.frame.unwrap();
+    assert_eq!(checkpoint.boids.len(), 12);
+    assert_eq!(checkpoint.metadata.seed, Some(3));
+    // Every frame before the checkpoint was finished
+    let pngs = std::fs::read_dir(&dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "png")
+        })
+        .count();
+    assert_eq!(pngs as u64, frame);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn sigusr1_dumps_state_and_keeps_running() {
+    let dir = frames_dir("sigusr1");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width", "64", "--height", "48", "--boids", "12", "--seed", "3",
+        ])
+        .args(["--dir", dir.to_str().unwrap(), "--frames", "100000000"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let signal = |name: &str| {
+        let sent = Command::new("kill")
+            .args([name, &child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(sent.success());
+    };
+    let wait_for = |check: &dyn Fn() -> bool| {
+        while !check() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    };
+    wait_for(&|| dir.join("frames_00000001.png").exists());
+    signal("-USR1");
+    let dump = || {
+        std::fs::read_dir(&dir).unwrap().find_map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            name.starts_with("state_frame_").then_some((path, name))
+        })
+    };
+    wait_for(&|| dump().is_some());
+    let (path, name) = dump().unwrap();
+    // Still going afterwards
+    let frame: u64 = name
+        .trim_start_matches("state_frame_")
+        .trim_end_matches(".json")
+        .parse()
+        .unwrap();
+    wait_for(&|| dir.join(format!("frames_{:0>8}.png", frame + 2)).exists());
+    signal("-INT");
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+
+    let dumped = state::load(&path).unwrap();
+    assert_eq!(dumped.metadata.frame, Some(frame));
+    assert_eq!(dumped.boids.len(), 12);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn lists_the_presets() {
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .arg("--list-presets")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), PRESETS.len());
+    for name in PRESETS {
+        assert!(stdout.lines().any(|line| line.starts_with(name)), "{name}");
+    }
+}
+
+#[test]
+fn refuses_invalid_parameters() {
+    let dir = frames_dir("invalid");
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(["--width", "64", "--height", "48", "--cell-size", "0"])
+        .args(["--dir", dir.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Invalid parameters: the cell size must be more than 0, not 0"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(["--width", "64", "--height", "48", "--min-speed", "5"])
+        .args(["--dir", dir.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Invalid parameters: the min speed of 5 is more than the max speed of 3"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(["--width", "64", "--height", "48", "--margin", "2000"])
+        .args(["--dir", dir.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Invalid parameters: a margin of 2000 leaves no room in the middle of a 64x48 world, lower it to at most 23"
+    ));
+
+    let compared = dir.join("compared.toml");
+    let text = toml::to_string(&Parameters {
+        min_speed: 4.0,
+        ..Parameters::default()
+    })
+    .unwrap();
+    std::fs::write(&compared, text).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(["--width", "64", "--height", "48", "--frames", "0"])
+        .args(["--dir", dir.to_str().unwrap()])
+        .args(["--params-compare", compared.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("the min speed of 4 is more than the max speed of 3"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn max_memory_stops_the_run() {
+    let dir = frames_dir("memory");
+    let trajectory = dir.join("run.btrj");
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width", "64", "--height", "48", "--boids", "12", "--seed", "3",
+        ])
+        .args([
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "1000",
+            "--max-memory",
+            "1",
+        ])
+        .args(["--trajectory-out", trajectory.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("times --max-memory, stopping"));
+    // Stopped on the first check, before any frame was drawn
+    assert!(!dir.join("frames_00000001.png").exists());
+    assert!(trajectory.exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+// /dev/full takes the writes into its buffer, then fails the flush at the end
+#[cfg(target_os = "linux")]
+#[test]
+fn full_disks_exit_without_a_panic() {
+    let dir = frames_dir("full");
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width", "64", "--height", "48", "--boids", "12", "--seed", "3",
+        ])
+        .args(["--dir", dir.to_str().unwrap(), "--frames", "2"])
+        .args(["--trajectory-csv", "/dev/full"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(74), "{stderr}");
+    assert!(stderr.contains("unable to write /dev/full"), "{stderr}");
+    assert!(!stderr.contains("panicked"), "{stderr}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stop_when_ends_the_run_early() {
+    let dir = frames_dir("stop_when");
+    let summary_file = dir.join("summary.json");
+    let output = boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "1000",
+            "--stop-when",
+            "population<5",
+            "--stop-when",
+            "population>5 for 3",
+            "--summary-file",
+            summary_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Stopping at frame 3"));
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&summary_file).unwrap()).unwrap();
+    assert_eq!(summary["frames_written"], 3);
+    assert_eq!(summary["stop_reason"], "population>5 for 3 (now 12)");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_exactly_the_frames_asked_for() {
+    let pngs = |name: &str, include_initial_frame: bool| {
+        let dir = frames_dir(name);
+        let mut args = vec!["--dir", dir.to_str().unwrap(), "--frames", "5"];
+        if include_initial_frame {
+            args.push("--include-initial-frame");
+        }
+        boids(&args, b"");
+        let mut frames: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".png"))
+            .collect();
+        frames.sort();
+        std::fs::remove_dir_all(dir).unwrap();
+        frames
+    };
+    let stepped: Vec<String> = (1..=5).map(|n| format!("frames_{n:0>8}.png")).collect();
+    assert_eq!(pngs("exact_frames", false), stepped);
+    let with_initial = pngs("exact_frames_initial", true);
+    assert_eq!(with_initial.len(), 6);
+    assert_eq!(with_initial[0], "frames_00000000.png");
+    assert_eq!(with_initial[1..], stepped);
+}
+
+#[test]
+fn initial_frame_shows_the_loaded_flock() {
+    let dir = frames_dir("initial_frame");
+    let load = dir.join("start.json");
+    let boid = |x, y| {
+        Boid::at(Vector2::new(x, y))
+            .with_velocity(Vector2::new(1.0, 0.0))
+            .build()
+    };
+    let mut save = SaveFile::new(64, 48, vec![boid(10.0, 10.0), boid(40.0, 30.0)]);
+    save.metadata.seed = Some(3);
+    state::save(&load, &save).unwrap();
+    let manifest_file = dir.join("manifest.json");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "1",
+            "--include-initial-frame",
+            "--load-file",
+            load.to_str().unwrap(),
+            "--output-manifest",
+            manifest_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let manifest = Manifest::load(&manifest_file).unwrap();
+    let frames: Vec<usize> = manifest.frames.iter().map(|frame| frame.frame).collect();
+    assert_eq!(frames, [0, 1]);
+    // Not yet moved by a step
+    assert_eq!(
+        manifest.frames[0].metrics.centroid,
+        Vector2::new(25.0, 20.0)
+    );
+    assert_ne!(
+        manifest.frames[1].metrics.centroid,
+        Vector2::new(25.0, 20.0)
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn plain_runs_ignore_leftover_checkpoints() {
+    let dir = frames_dir("leftover_checkpoint");
+    let boid = Boid::at(Vector2::new(10.0, 10.0))
+        .with_velocity(Vector2::new(1.0, 0.0))
+        .build();
+    let mut save = SaveFile::new(64, 48, vec![boid]);
+    save.metadata.seed = Some(3);
+    save.metadata.frame = Some(1);
+    state::save(&dir.join("checkpoint.json"), &save).unwrap();
+    let manifest_file = dir.join("manifest.json");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "2",
+            "--output-manifest",
+            manifest_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let manifest = Manifest::load(&manifest_file).unwrap();
+    assert!(manifest
+        .frames
+        .iter()
+        .all(|frame| frame.metrics.population == 12));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn frame_range_matches_the_full_run() {
+    let hashes = |name: &str, range: &[&str]| {
+        let dir = frames_dir(name);
+        let mut args = vec![
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "8",
+            "--hash-frames",
+        ];
+        args.extend(range);
+        boids(&args, b"");
+        let hashes = std::fs::read_to_string(dir.join("frame_hashes.txt")).unwrap();
+        let mut frames: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name.ends_with(".png"))
+            .collect();
+        frames.sort();
+        std::fs::remove_dir_all(dir).unwrap();
+        (hashes, frames)
+    };
+    let (full, _) = hashes("range_full", &[]);
+    let (shard, frames) = hashes("range_shard", &["--frame-start", "3", "--frame-end", "6"]);
+    // The full run's hashes start at frame 1
+    let expected: Vec<&str> = full.lines().skip(2).take(3).collect();
+    assert_eq!(shard.lines().collect::<Vec<_>>(), expected);
+    assert_eq!(
+        frames,
+        [
+            "frames_00000003.png",
+            "frames_00000004.png",
+            "frames_00000005.png"
+        ]
+    );
+}
+
+#[test]
+fn writes_the_correlation_function() {
+    let dir = frames_dir("correlation");
+    let csv = dir.join("correlation.csv");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "5",
+            "--correlation-function-csv",
+            csv.to_str().unwrap(),
+            "--correlation-interval",
+            "2",
+            "--correlation-bins",
+            "4",
+        ],
+        b"",
+    );
+    let text = std::fs::read_to_string(&csv).unwrap();
+    let frames: Vec<&str> = text
+        .lines()
+        .skip(1)
+        .map(|row| row.split(',').next().unwrap())
+        .collect();
+    assert_eq!(frames, ["2", "2", "2", "2", "4", "4", "4", "4"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_the_heading_histogram() {
+    let dir = frames_dir("heading");
+    let csv = dir.join("headings.csv");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "3",
+            "--heading-histogram-csv",
+            csv.to_str().unwrap(),
+            "--heading-histogram-bins",
+            "6",
+        ],
+        b"",
+    );
+    let text = std::fs::read_to_string(&csv).unwrap();
+    let rows: Vec<Vec<&str>> = text.lines().map(|row| row.split(',').collect()).collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0].len(), 7);
+    for (frame, row) in (1..).zip(&rows[1..]) {
+        assert_eq!(row[0], frame.to_string());
+        let total: usize = row[1..]
+            .iter()
+            .map(|count| count.parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(total, 12);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn serves_metrics_during_a_run() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+
+    let dir = frames_dir("metrics");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args([
+            "--width",
+            "64",
+            "--height",
+            "48",
+            "--boids",
+            "12",
+            "--frames",
+            "1000000",
+            "--metrics-addr",
+            "127.0.0.1:0",
+            "--dir",
+            dir.to_str().unwrap(),
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let addr = loop {
+        let mut line = String::new();
+        assert!(stderr.read_line(&mut line).unwrap() > 0, "never served");
+        if let Some(url) = line.trim().strip_prefix("Serving metrics on http://") {
+            break url.strip_suffix("/metrics").unwrap().to_string();
+        }
+    };
+    // Long enough for a few frames to have been recorded
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let mut stream = TcpStream::connect(&addr).unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    child.kill().unwrap();
+    child.wait().unwrap();
+    for name in [
+        "boids_frame",
+        "boids_frames_per_second",
+        "boids_population",
+        "boids_polarization",
+        "boids_encode_queue_depth",
+        "boids_bytes_written_total",
+        "boids_stage_seconds_total",
+    ] {
+        assert!(
+            response.contains(&format!("\n{name}")),
+            "{name} missing from\n{response}"
+        );
+    }
+    assert!(response.contains("\nboids_population 12\n"), "{response}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn writes_clusters_after_the_run() {
+    let dir = frames_dir("clusters");
+    let clusters = dir.join("clusters.json");
+    let trajectory = dir.join("run.csv");
+    let output = boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "3",
+            "--cluster-output",
+            clusters.to_str().unwrap(),
+            "--cluster-eps",
+            "1000",
+            "--trajectory-csv",
+            trajectory.to_str().unwrap(),
+        ],
+        b"",
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Found 1 clusters over 3 frames"));
+    let clusters: std::collections::BTreeMap<String, Vec<usize>> =
+        serde_json::from_slice(&std::fs::read(&clusters).unwrap()).unwrap();
+    assert_eq!(clusters.keys().collect::<Vec<_>>(), ["0"]);
+    assert_eq!(clusters["0"].len(), 12);
+    let membership = std::fs::read_to_string(dir.join("run_clusters.csv")).unwrap();
+    assert_eq!(membership.lines().count(), 13);
+    assert!(membership.lines().skip(1).all(|row| row.ends_with(",0")));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn verbosity_flags_change_what_is_logged() {
+    let run = |flag: &str| {
+        let dir = frames_dir(&format!("verbosity{flag}"));
+        let output = boids(
+            &[flag, "--dir", dir.to_str().unwrap(), "--frames", "1"],
+            b"",
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(output.stdout.is_empty());
+        String::from_utf8(output.stderr).unwrap()
+    };
+    let quiet = run("-q");
+    assert!(!quiet.contains("Final population"), "{quiet}");
+    let verbose = run("-v");
+    assert!(verbose.contains("Final population"), "{verbose}");
+    assert!(verbose.contains("Frame 1 in "), "{verbose}");
+    assert!(!verbose.contains("[TRACE"), "{verbose}");
+    let very_verbose = run("-vv");
+    assert!(
+        very_verbose.contains("[TRACE boids::boids]"),
+        "{very_verbose}"
+    );
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn writes_a_chrome_trace() {
+    let dir = frames_dir("trace");
+    let trace_file = dir.join("trace.json");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "2",
+            "--trace-out",
+            trace_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let trace: Vec<serde_json::Value> =
+        serde_json::from_slice(&std::fs::read(&trace_file).unwrap()).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    let started = |name: &str| {
+        trace
+            .iter()
+            .filter(|event| event["ph"] == "B" && event["name"] == name)
+            .count()
+    };
+    for name in [
+        "frame",
+        "simulate",
+        "grid",
+        "neighbours",
+        "integrate",
+        "rasterize",
+        "encode",
+        "write",
+    ] {
+        assert_eq!(started(name), 2, "{name}");
+    }
+}
+
+#[test]
+fn info_summarizes_the_flock() {
+    let dir = frames_dir("info");
+    let path = dir.join("start.json");
+    let save = state::SaveFile::new(
+        200,
+        100,
+        vec![
+            boids::boids::Boid::new(
+                0,
+                nalgebra::Vector2::new(10.0, 20.0),
+                nalgebra::Vector2::new(1.0, 0.0),
+                image::Rgb([255, 255, 255]),
+            ),
+            boids::boids::Boid::new(
+                1,
+                nalgebra::Vector2::new(30.0, 60.0),
+                nalgebra::Vector2::new(1.0, 0.0),
+                image::Rgb([255, 255, 255]),
+            ),
+        ],
+    );
+    state::save(&path, &save).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(["info", path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(
+            "Flock: 2 boids in (10.0, 20.0)-(30.0, 60.0), centroid (20.0, 40.0), mean speed 1.00, polarization 1.000"
+        ),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+fn subcommand(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{args:?}: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+fn files_in(dir: &std::path::Path) -> Vec<String> {
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn every_subcommand_has_help() {
+    for name in [
+        "run", "bench", "sweep", "convert", "merge", "replay", "info",
+    ] {
+        let output = subcommand(&[name, "--help"]);
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains(&format!("Usage: boids {name}")),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn run_is_the_default_subcommand() {
+    let explicit = frames_dir("run_explicit");
+    let implicit = frames_dir("run_implicit");
+    subcommand(&[
+        "-q",
+        "run",
+        "--width",
+        "64",
+        "--height",
+        "48",
+        "--boids",
+        "12",
+        "--seed",
+        "3",
+        "--frames",
+        "2",
+        "--dir",
+        explicit.to_str().unwrap(),
+    ]);
+    boids(
+        &["--frames", "2", "--dir", implicit.to_str().unwrap(), "-q"],
+        b"",
+    );
+    let written = files_in(&explicit);
+    assert!(!written.is_empty());
+    assert_eq!(written, files_in(&implicit));
+    assert_eq!(
+        std::fs::read(explicit.join(&written[0])).unwrap(),
+        std::fs::read(implicit.join(&written[0])).unwrap()
+    );
+    std::fs::remove_dir_all(explicit).unwrap();
+    std::fs::remove_dir_all(implicit).unwrap();
+}
+
+#[test]
+fn bench_times_steps() {
+    let output = subcommand(&[
+        "bench", "--width", "64", "--height", "48", "--boids", "12", "--seed", "3", "--frames", "5",
+    ]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("5 frames of 12 boids in "), "{stdout}");
+    assert!(stdout.contains("Frames per second: "), "{stdout}");
+}
+
+#[test]
+fn convert_rewrites_a_state() {
+    let dir = frames_dir("convert");
+    let json = dir.join("start.json");
+    let ron = dir.join("start.ron");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "0",
+            "--save-file",
+            json.to_str().unwrap(),
+        ],
+        b"",
+    );
+    subcommand(&["convert", json.to_str().unwrap(), ron.to_str().unwrap()]);
+    let original = state::load(&json).unwrap();
+    let converted = state::load(&ron).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(converted.boids, original.boids);
+    assert_eq!(converted.world_size, Some((64, 48)));
+}
+
+#[test]
+fn merge_combines_states() {
+    let dir = frames_dir("merge");
+    let start = dir.join("start.json");
+    let merged = dir.join("merged.json");
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "0",
+            "--save-file",
+            start.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let start = start.to_str().unwrap();
+    subcommand(&[
+        "merge",
+        start,
+        start,
+        "-o",
+        merged.to_str().unwrap(),
+        "--world",
+        "128x48",
+        "--offset",
+        "0,0",
+        "--offset",
+        "64,0",
+    ]);
+    let merged = state::load(&merged).unwrap();
+    std::fs::remove_dir_all(dir).unwrap();
+    assert_eq!(merged.boids.len(), 24);
+    assert_eq!(merged.world_size, Some((128, 48)));
+}
+
+#[test]
+fn replay_draws_a_recorded_run() {
+    let recorded = frames_dir("replay_recorded");
+    let replayed = frames_dir("replay_replayed");
+    let trajectory = recorded.join("run.traj");
+    boids(
+        &[
+            "--dir",
+            recorded.to_str().unwrap(),
+            "--frames",
+            "3",
+            "--trajectory-out",
+            trajectory.to_str().unwrap(),
+        ],
+        b"",
+    );
+    subcommand(&[
+        "replay",
+        trajectory.to_str().unwrap(),
+        "--dir",
+        replayed.to_str().unwrap(),
+    ]);
+    let frames = files_in(&replayed);
+    std::fs::remove_dir_all(recorded).unwrap();
+    std::fs::remove_dir_all(replayed).unwrap();
+    assert!(!frames.is_empty());
+    assert!(frames.iter().all(|frame| frame.ends_with(".png")));
+}
+
+#[test]
+fn sweep_runs_every_combination() {
+    let dir = frames_dir("sweep");
+    subcommand(&[
+        "-q",
+        "sweep",
+        "--width",
+        "64",
+        "--height",
+        "48",
+        "--boids",
+        "12",
+        "--seed",
+        "7",
+        "--frames",
+        "3",
+        "--vary",
+        "matching_factor=0.01,0.1",
+        "--vary",
+        "margin=0..10:3",
+        "--dir",
+        dir.to_str().unwrap(),
+    ]);
+    let csv = std::fs::read_to_string(dir.join("sweep.csv")).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "run,matching_factor,margin,polarization,angular_momentum,mean_speed"
+    );
+    assert_eq!(lines.len(), 7);
+    assert!(lines[2].starts_with("matching_factor=0.01_margin=5,0.01,5,"));
+    for line in &lines[1..] {
+        let run = line.split(',').next().unwrap();
+        let save = state::load(&dir.join(run).join("final.json")).unwrap();
+        assert_eq!(save.boids.len(), 12);
+        assert_eq!(save.metadata.seed, Some(7));
+        assert!(dir.join(run).join("final.png").is_file());
+    }
+    let last = state::load(&dir.join("matching_factor=0.1_margin=10/final.json")).unwrap();
+    assert_eq!(
+        last.metadata.parameters,
+        Some(Parameters {
+            matching_factor: 0.1,
+            margin: 10,
+            ..Parameters::default()
+        })
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn sweeps_are_checked_before_running() {
+    let dir = frames_dir("sweep_invalid");
+    let sweep = |vary: &str| {
+        Command::new(env!("CARGO_BIN_EXE_boids"))
+            .args(["sweep", "--width", "64", "--height", "48", "--frames", "1"])
+            .args(["--vary", vary, "--dir", dir.to_str().unwrap()])
+            .output()
+            .unwrap()
+    };
+    let unknown = sweep("matching_factr=0.1");
+    assert_eq!(unknown.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&unknown.stderr).contains("no parameter named matching_factr"));
+    let invalid = sweep("min_speed=1,5");
+    assert_eq!(invalid.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&invalid.stderr).contains(
+        "Invalid parameters for min_speed=5: the min speed of 5 is more than the max speed of 3"
+    ));
+    assert!(!dir.join("min_speed=1").exists());
+    assert!(!dir.join("sweep.csv").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn paces_frames() {
+    let dir = frames_dir("paced");
+    let summary_file = dir.join("summary.json");
+    let started = std::time::Instant::now();
+    boids(
+        &[
+            "--dir",
+            dir.to_str().unwrap(),
+            "--frames",
+            "5",
+            "--target-fps",
+            "20",
+            "--summary-file",
+            summary_file.to_str().unwrap(),
+        ],
+        b"",
+    );
+    // Five frames at 50ms each
+    assert!(started.elapsed() >= std::time::Duration::from_millis(250));
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&summary_file).unwrap()).unwrap();
+    assert_eq!(summary["frames_written"], 5);
+    assert_eq!(summary["frames_dropped"], 0);
+    let fps = summary["achieved_fps"].as_f64().unwrap();
+    assert!(fps > 15.0 && fps <= 20.5, "{fps}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn frame_drops_need_a_pace() {
+    let dir = frames_dir("frame_drop");
+    for (args, message) in [
+        (
+            &["--allow-frame-drop"][..],
+            "--allow-frame-drop needs --target-fps",
+        ),
+        (
+            &["--target-fps", "0"][..],
+            "--target-fps must be greater than 0",
+        ),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_boids"))
+            .args(["--frames", "1", "--dir", dir.to_str().unwrap()])
+            .args(args)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains(message));
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
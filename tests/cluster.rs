@@ -0,0 +1,60 @@
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::cluster::{
+    boids_by_cluster, cluster_membership, write_membership_csv, PositionAverager, NOISE,
+};
+use boids::testing::boid;
+
+#[test]
+fn averages_positions_over_frames() {
+    let mut averager = PositionAverager::new();
+    averager.add(&[
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (10.0, 4.0), (0.0, 0.0)),
+    ]);
+    averager.add(&[
+        boid(0, (2.0, 6.0), (0.0, 0.0)),
+        boid(1, (20.0, 4.0), (0.0, 0.0)),
+    ]);
+    assert_eq!(averager.frames(), 2);
+    assert_eq!(
+        averager.means(),
+        vec![Vector2::new(1.0, 3.0), Vector2::new(15.0, 4.0)]
+    );
+    assert!(PositionAverager::new().means().is_empty());
+}
+
+#[test]
+fn separates_groups_and_noise() {
+    let positions = [
+        (0.0, 0.0),
+        (1.0, 0.0),
+        (0.0, 1.0),
+        (50.0, 50.0),
+        (51.0, 50.0),
+        (50.0, 51.0),
+        (100.0, 0.0),
+    ]
+    .map(|(x, y)| Vector2::new(x, y));
+    let membership = cluster_membership(&positions, 2.0, 3);
+    assert_eq!(membership, vec![0, 0, 0, 1, 1, 1, NOISE]);
+
+    let boids: Vec<Boid> = [10, 11, 12, 13, 14, 15, 16]
+        .into_iter()
+        .zip(positions)
+        .map(|(id, pos)| boid(id, (pos.x, pos.y), (0.0, 0.0)))
+        .collect();
+    let clusters = boids_by_cluster(&boids, &membership);
+    assert_eq!(
+        serde_json::to_string(&clusters).unwrap(),
+        r#"{"-1":[16],"0":[10,11,12],"1":[13,14,15]}"#
+    );
+
+    let mut csv = Vec::new();
+    write_membership_csv(&mut csv, &boids[5..], &membership[5..]).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "id,cluster_membership\n15,1\n16,-1\n"
+    );
+}
@@ -0,0 +1,281 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::Boid;
+use boids::colour::{
+    colour_by_density, colour_by_divergence, colour_by_trajectory_entropy, colour_by_width,
+    divergence_colour, heading_change_variance, recolour, rotate_hues, Colour, ColourGradient,
+    ColourMode,
+};
+use boids::testing::boid;
+use boids::world::World;
+use boids::Parameters;
+
+fn colour(mode: ColourMode, boid: &Boid) -> Colour {
+    mode.colour(boid, 100, 3.0, &mut StdRng::seed_from_u64(1))
+}
+
+#[test]
+fn parses_colour_modes() {
+    assert_eq!("initial-x".parse(), Ok(ColourMode::InitialX));
+    assert_eq!("heading".parse(), Ok(ColourMode::Heading));
+    assert!("rainbow".parse::<ColourMode>().is_err());
+    assert!(ColourMode::Speed.is_dynamic());
+    assert!(!ColourMode::IdHash.is_dynamic());
+}
+
+#[test]
+fn initial_x_follows_the_new_width() {
+    let mut boids = vec![
+        boid(0, (0.0, 10.0), (1.0, 0.0)),
+        boid(1, (960.0, 10.0), (1.0, 0.0)),
+    ];
+    let mut rng = StdRng::seed_from_u64(1);
+    let world = World::from_pixels(3840, 2160);
+    recolour(
+        &mut boids,
+        ColourMode::InitialX,
+        &world,
+        &Parameters::default(),
+        &mut rng,
+    );
+    assert_eq!(boids[0].colour, Colour([255, 0, 0]));
+    assert_eq!(boids[1].colour, colour_by_width(960.0, 3840));
+    assert_ne!(boids[1].colour, colour_by_width(960.0, 1920));
+}
+
+#[test]
+fn id_hash_is_stable_and_varied() {
+    let mode = ColourMode::IdHash;
+    assert_eq!(
+        colour(mode, &boid(5, (0.0, 10.0), (1.0, 0.0))),
+        colour(mode, &boid(5, (90.0, 10.0), (0.0, 1.0)))
+    );
+    assert_ne!(
+        colour(mode, &boid(5, (0.0, 10.0), (1.0, 0.0))),
+        colour(mode, &boid(6, (0.0, 10.0), (1.0, 0.0)))
+    );
+}
+
+#[test]
+fn random_depends_on_seed() {
+    let colours = |seed| {
+        let mut boids: Vec<Boid> = (0..5).map(|id| boid(id, (0.0, 10.0), (1.0, 0.0))).collect();
+        recolour(
+            &mut boids,
+            ColourMode::Random,
+            &World::from_pixels(100, 100),
+            &Parameters::default(),
+            &mut StdRng::seed_from_u64(seed),
+        );
+        boids.iter().map(|boid| boid.colour).collect::<Vec<_>>()
+    };
+    assert_eq!(colours(3), colours(3));
+    assert_ne!(colours(3), colours(4));
+}
+
+#[test]
+fn motion_modes_follow_velocity() {
+    let speed = |vel| colour(ColourMode::Speed, &boid(0, (0.0, 10.0), vel));
+    assert_ne!(speed((0.5, 0.0)), speed((3.0, 0.0)));
+    assert_eq!(speed((3.0, 0.0)), speed((0.0, -3.0)));
+
+    let heading = |vel| colour(ColourMode::Heading, &boid(0, (0.0, 10.0), vel));
+    assert_eq!(heading((1.0, 0.0)), Colour([255, 0, 0]));
+    assert_eq!(heading((2.0, 0.0)), Colour([255, 0, 0]));
+    assert_ne!(heading((0.0, 1.0)), heading((0.0, -1.0)));
+}
+
+#[test]
+fn rotating_turns_from_the_id_hash_hue() {
+    let mut boids: Vec<Boid> = (0..4).map(|id| boid(id, (0.0, 10.0), (1.0, 0.0))).collect();
+    let start: Vec<Colour> = boids
+        .iter()
+        .map(|boid| colour(ColourMode::IdHash, boid))
+        .collect();
+    let colours = |boids: &[Boid]| {
+        boids
+            .iter()
+            .map(|boid| colour(ColourMode::Rotating, boid))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!("rotating".parse(), Ok(ColourMode::Rotating));
+    assert!(ColourMode::Rotating.is_dynamic());
+    assert_eq!(colours(&boids), start);
+    rotate_hues(&mut boids, 120.0, false);
+    assert_ne!(colours(&boids), start);
+    rotate_hues(&mut boids, 120.0, false);
+    rotate_hues(&mut boids, 120.0, false);
+    assert_eq!(colours(&boids), start);
+    // Nothing turns at all without a speed
+    rotate_hues(&mut boids, 0.0, true);
+    assert_eq!(colours(&boids), start);
+}
+
+#[test]
+fn per_boid_rotation_is_picked_from_the_id() {
+    let turned = |per_boid| {
+        let mut boids: Vec<Boid> = (0..8).map(|id| boid(id, (0.0, 10.0), (1.0, 0.0))).collect();
+        // A whole turn at the shared speed comes back round
+        rotate_hues(&mut boids, 360.0, per_boid);
+        boids
+            .iter()
+            .map(|boid| colour(ColourMode::Rotating, boid) == colour(ColourMode::IdHash, boid))
+            .collect::<Vec<_>>()
+    };
+    assert!(turned(false).iter().all(|same| *same));
+    assert!(turned(true).iter().all(|same| !same));
+    assert_eq!(turned(true), turned(true));
+}
+
+#[test]
+fn divergence_colours_spreading_and_closing_flocks() {
+    assert_eq!(
+        "velocity-divergence".parse(),
+        Ok(ColourMode::VelocityDivergence)
+    );
+    assert!(ColourMode::VelocityDivergence.is_dynamic());
+    assert_eq!(divergence_colour(-1.0), Colour([0, 0, 255]));
+    assert_eq!(divergence_colour(0.0), Colour([0, 255, 0]));
+    assert_eq!(divergence_colour(1.0), Colour([255, 0, 0]));
+    assert_eq!(
+        colour(
+            ColourMode::VelocityDivergence,
+            &boid(0, (0.0, 10.0), (1.0, 0.0))
+        ),
+        Colour([0, 255, 0])
+    );
+
+    let world = World::from_pixels(100, 100);
+    let parameters = Parameters {
+        cell_size: 10.0,
+        ..Parameters::default()
+    };
+    let colours = |left: f32, right: f32| {
+        let mut boids = vec![
+            boid(0, (45.0, 10.0), (left, 0.0)),
+            boid(1, (55.0, 10.0), (right, 0.0)),
+            // Far enough away to be its own flock
+            boid(2, (95.0, 10.0), (1.0, 0.0)),
+        ];
+        recolour(
+            &mut boids,
+            ColourMode::VelocityDivergence,
+            &world,
+            &parameters,
+            &mut rand::rng(),
+        );
+        boids.iter().map(|boid| boid.colour).collect::<Vec<_>>()
+    };
+    let (red, green, blue) = (
+        Colour([255, 0, 0]),
+        Colour([0, 255, 0]),
+        Colour([0, 0, 255]),
+    );
+    assert_eq!(colours(-1.0, 1.0), vec![red, red, green]);
+    assert_eq!(colours(2.0, -2.0), vec![blue, blue, green]);
+    assert_eq!(colours(1.0, 1.0), vec![green, green, green]);
+
+    // Scaled to the largest divergence in the frame
+    let mut boids = vec![
+        boid(0, (5.0, 10.0), (-1.0, 0.0)),
+        boid(1, (15.0, 10.0), (1.0, 0.0)),
+        boid(2, (55.0, 10.0), (-0.5, 0.0)),
+        boid(3, (65.0, 10.0), (0.5, 0.0)),
+    ];
+    colour_by_divergence(&mut boids, &world, 10.0);
+    assert_eq!(boids[0].colour, red);
+    assert_eq!(boids[2].colour, divergence_colour(0.5));
+}
+
+#[test]
+fn kde_colours_crowded_boids_hottest() {
+    assert_eq!("kde".parse(), Ok(ColourMode::Kde));
+    assert!(ColourMode::Kde.is_dynamic());
+    let gradient = ColourGradient::default();
+    assert_eq!(
+        colour(ColourMode::Kde, &boid(0, (0.0, 10.0), (1.0, 0.0))),
+        gradient.at(0.0)
+    );
+
+    let world = World::from_pixels(200, 20);
+    let mut boids: Vec<Boid> = (0..5)
+        .map(|id| boid(id, (20.0 + id as f32, 10.0), (1.0, 0.0)))
+        .collect();
+    boids.push(boid(5, (150.0, 10.0), (1.0, 0.0)));
+    colour_by_density(&mut boids, &world, 10.0);
+    assert_eq!(boids[0].colour, gradient.at(1.0));
+    assert_ne!(boids[5].colour, boids[0].colour);
+
+    // The same through `recolour`, with the visible range as the bandwidth
+    let mut recoloured = boids.clone();
+    let parameters = Parameters {
+        visible_range: 10.0,
+        ..Parameters::default()
+    };
+    recolour(
+        &mut recoloured,
+        ColourMode::Kde,
+        &world,
+        &parameters,
+        &mut rand::rng(),
+    );
+    assert_eq!(recoloured, boids);
+}
+
+#[test]
+fn heading_changes_vary_the_short_way_round() {
+    let variance = |headings: &[f32]| heading_change_variance(&VecDeque::from(headings.to_vec()));
+    assert_eq!(variance(&[]), 0.0);
+    assert_eq!(variance(&[0.0, 1.0]), 0.0);
+    // Turning steadily is still smooth flight
+    assert!(variance(&[0.0, 0.1, 0.2, 0.3]) < 1e-12);
+    // Turns of 0.5, -0.5 and 0.5
+    assert!((variance(&[0.0, 0.5, 0.0, 0.5]) - 2.0 / 9.0).abs() < 1e-6);
+    // Across -pi to pi is a small turn, not most of the way round
+    let across = variance(&[3.0, -3.0, 3.0]);
+    let turn = std::f32::consts::TAU - 6.0;
+    assert!((across - turn * turn).abs() < 1e-5, "{across}");
+}
+
+#[test]
+fn trajectory_entropy_colours_erratic_boids_hottest() {
+    assert_eq!(
+        "trajectory-entropy".parse(),
+        Ok(ColourMode::TrajectoryEntropy)
+    );
+    assert!(ColourMode::TrajectoryEntropy.is_dynamic());
+    let gradient = ColourGradient::default();
+    assert_eq!(
+        colour(
+            ColourMode::TrajectoryEntropy,
+            &boid(0, (0.0, 10.0), (1.0, 0.0))
+        ),
+        gradient.at(0.0)
+    );
+
+    let mut boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (20.0, 10.0), (1.0, 0.0)),
+        boid(2, (30.0, 10.0), (1.0, 0.0)),
+    ];
+    let zigzags = [(1.0, 1.0), (1.0, -1.0)];
+    let wobbles = [(1.0, 0.2), (1.0, -0.2)];
+    for frame in 0..6 {
+        boids[1].set_velocity(Vector2::new(wobbles[frame % 2].0, wobbles[frame % 2].1));
+        boids[2].set_velocity(Vector2::new(zigzags[frame % 2].0, zigzags[frame % 2].1));
+        colour_by_trajectory_entropy(&mut boids, 4);
+    }
+    assert_eq!(boids[0].colour, gradient.at(0.0));
+    assert_eq!(boids[2].colour, gradient.at(1.0));
+    assert_ne!(boids[1].colour, boids[0].colour);
+    assert_ne!(boids[1].colour, boids[2].colour);
+
+    // Nothing is erratic without the history to tell
+    let mut forgetful = boids.clone();
+    forgetful[2].set_velocity(Vector2::new(1.0, 1.0));
+    colour_by_trajectory_entropy(&mut forgetful, 0);
+    assert!(forgetful.iter().all(|boid| boid.colour == gradient.at(0.0)));
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "parquet")]
+
+use std::fs::File;
+
+use nalgebra::Vector2;
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::RowAccessor;
+
+use boids::boids::Boid;
+use boids::colour::Colour;
+use boids::columnar::{write_frame_file, ParquetWriter};
+
+fn flock(shift: f32) -> Vec<Boid> {
+    (0..5)
+        .map(|id| {
+            Boid::new(
+                id,
+                Vector2::new(id as f32 + shift, 20.0),
+                Vector2::new(3.0, 4.0),
+                Colour([id as u8, 100, 200]),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn one_row_group_per_frame() {
+    let path = std::env::temp_dir().join(format!("boids_all_{}.parquet", std::process::id()));
+    let mut writer = ParquetWriter::create(&path).unwrap();
+    writer.write_frame(0, &flock(0.0)).unwrap();
+    writer.write_frame(1, &flock(10.0)).unwrap();
+    writer.close().unwrap();
+
+    let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    let rows: Vec<_> = reader
+        .get_row_iter(None)
+        .unwrap()
+        .map(|row| row.unwrap())
+        .collect();
+    assert_eq!(rows.len(), 10);
+    let row = &rows[7];
+    assert_eq!(row.get_int(0).unwrap(), 1);
+    assert_eq!(row.get_long(1).unwrap(), 2);
+    assert_eq!(row.get_float(2).unwrap(), 12.0);
+    assert_eq!(row.get_float(3).unwrap(), 20.0);
+    assert_eq!(row.get_float(4).unwrap(), 3.0);
+    assert_eq!(row.get_float(5).unwrap(), 4.0);
+    assert_eq!(row.get_float(6).unwrap(), 5.0);
+    assert_eq!(row.get_int(7).unwrap(), 2);
+    assert_eq!(row.get_int(8).unwrap(), 100);
+    assert_eq!(row.get_int(9).unwrap(), 200);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn single_frame_files() {
+    let path = std::env::temp_dir().join(format!("boids_frame_{}.parquet", std::process::id()));
+    write_frame_file(&path, 42, &flock(0.0)).unwrap();
+    let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 5);
+    let schema = reader.metadata().file_metadata().schema_descr();
+    let names: Vec<_> = schema
+        .columns()
+        .iter()
+        .map(|column| column.name())
+        .collect();
+    assert_eq!(
+        names,
+        ["frame", "boid_id", "pos_x", "pos_y", "vel_x", "vel_y", "speed", "r", "g", "b"]
+    );
+    std::fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,91 @@
+use nalgebra::Vector2;
+
+use boids::boids::{populate_grid, Boid};
+use boids::correlation::{CorrelationCsvWriter, CorrelationFunction};
+use boids::testing::boid;
+
+#[test]
+fn bins_pairs_by_distance() {
+    let boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (13.0, 10.0), (2.0, 0.0)),
+        boid(2, (10.0, 17.0), (-1.0, 0.0)),
+        // Standing still, so it has no heading to correlate
+        boid(3, (11.0, 11.0), (0.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 5.0, 50, 50);
+    let correlation = CorrelationFunction::compute(&boids, &grid, 10.0, 2);
+    // 0-1 is 3 apart, 0-2 is 7 and 1-2 is about 7.6
+    assert_eq!(correlation, vec![(2.5, 1.0), (7.5, -1.0)]);
+}
+
+#[test]
+fn matches_every_pair_when_reaching_past_a_cell() {
+    let headings: Vec<Vector2<f32>> = (0..40)
+        .map(|id| {
+            let angle = id as f32 * 0.7;
+            Vector2::new(angle.cos(), angle.sin())
+        })
+        .collect();
+    let boids: Vec<Boid> = headings
+        .iter()
+        .enumerate()
+        .map(|(id, heading)| {
+            boid(
+                id,
+                ((id * 37 % 100) as f32, (id * 53 % 100) as f32),
+                (heading.x * 2.0, heading.y * 2.0),
+            )
+        })
+        .collect();
+    let grid = populate_grid(&boids, 8.0, 100, 100);
+    let correlation = CorrelationFunction::compute(&boids, &grid, 30.0, 3);
+
+    let mut sums = [0.0f32; 3];
+    let mut counts = [0; 3];
+    for i in 0..boids.len() {
+        for j in i + 1..boids.len() {
+            let dist = (boids[i].pos - boids[j].pos).norm();
+            if dist < 30.0 {
+                let bin = (dist / 10.0) as usize;
+                sums[bin] += headings[i].dot(&headings[j]);
+                counts[bin] += 1;
+            }
+        }
+    }
+    for (bin, (r, value)) in correlation.into_iter().enumerate() {
+        assert_eq!(r, bin as f32 * 10.0 + 5.0);
+        let expected = if counts[bin] > 0 {
+            sums[bin] / counts[bin] as f32
+        } else {
+            0.0
+        };
+        assert!(
+            (value - expected).abs() < 1e-4,
+            "bin {bin}: {value} vs {expected}"
+        );
+    }
+}
+
+#[test]
+fn nothing_to_bin() {
+    let grid = populate_grid(&[], 5.0, 50, 50);
+    assert_eq!(
+        CorrelationFunction::compute(&[], &grid, 10.0, 2),
+        vec![(2.5, 0.0), (7.5, 0.0)]
+    );
+    assert!(CorrelationFunction::compute(&[], &grid, 10.0, 0).is_empty());
+    assert!(CorrelationFunction::compute(&[], &grid, 0.0, 2).is_empty());
+}
+
+#[test]
+fn writes_a_row_per_bin() {
+    let mut writer = CorrelationCsvWriter::new(Vec::new()).unwrap();
+    writer.write_frame(0, &[(2.5, 1.0), (7.5, -0.5)]).unwrap();
+    writer.write_frame(100, &[(2.5, 0.25)]).unwrap();
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    assert_eq!(
+        text,
+        "frame,r,correlation\n0,2.5,1\n0,7.5,-0.5\n100,2.5,0.25\n"
+    );
+}
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use boids::error::Position;
+#[cfg(feature = "render")]
+use boids::render::Renderer;
+use boids::state::{self, StateError};
+use boids::{Error, Parameters};
+
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("boids_error_{}_{name}", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn missing_files_name_the_path() {
+    let path = Path::new("no/such/dir/start.json");
+    let err = state::load(path).unwrap_err();
+    assert!(matches!(err, Error::Io { action: "read", .. }), "{err:?}");
+    let message = err.to_string();
+    assert!(
+        message.starts_with("unable to read no/such/dir/start.json: "),
+        "{message}"
+    );
+}
+
+#[test]
+fn bad_json_gives_the_line_and_column() {
+    let path = temp_file("bad.json", "{\n  \"version\": 2,\n  \"boids\": [}\n");
+    let err = state::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    let Error::Format {
+        position, source, ..
+    } = &err
+    else {
+        panic!("{err:?}");
+    };
+    assert_eq!(
+        *position,
+        Some(Position {
+            line: 3,
+            column: 13
+        })
+    );
+    assert!(matches!(
+        source.downcast_ref::<StateError>(),
+        Some(StateError::Json(_))
+    ));
+    assert!(
+        err.to_string()
+            .starts_with(&format!("{}:3:13: invalid JSON state", path.display())),
+        "{err}"
+    );
+}
+
+#[test]
+fn bad_toml_gives_the_line_and_column() {
+    let text = toml::to_string(&Parameters::default())
+        .unwrap()
+        .replace("margin = 10\n", "margin = \"wide\"\n");
+    let line = text
+        .lines()
+        .position(|line| line.starts_with("margin"))
+        .unwrap()
+        + 1;
+    let path = temp_file("bad.toml", &text);
+    let err = Parameters::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    let Error::Format { position, .. } = &err else {
+        panic!("{err:?}");
+    };
+    assert_eq!(*position, Some(Position { line, column: 10 }));
+    assert!(
+        err.to_string()
+            .starts_with(&format!("{}:{line}:10: ", path.display())),
+        "{err}"
+    );
+}
+
+#[test]
+fn invalid_parameters_are_validation_errors() {
+    let text = toml::to_string(&Parameters {
+        cell_size: 0.0,
+        ..Parameters::default()
+    })
+    .unwrap();
+    let path = temp_file("invalid.toml", &text);
+    let err = Parameters::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(err, Error::Validation(_)), "{err:?}");
+    assert_eq!(
+        err.to_string(),
+        "invalid parameters: the cell size must be more than 0, not 0, set it to the visible range"
+    );
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn failed_renders_name_the_frame() {
+    let renderer = Renderer::new(4, 4, 0);
+    let err = renderer
+        .save("no/such/dir/frames_00000001.png")
+        .unwrap_err();
+    assert!(matches!(err, Error::Render { .. }), "{err:?}");
+    assert!(
+        err.to_string()
+            .starts_with("unable to render no/such/dir/frames_00000001.png: "),
+        "{err}"
+    );
+}
+
+#[test]
+fn positions_count_from_one() {
+    let text = "first\nsecond line\nthird";
+    assert_eq!(Position::at(text, 0), Position { line: 1, column: 1 });
+    assert_eq!(Position::at(text, 6), Position { line: 2, column: 1 });
+    assert_eq!(Position::at(text, 13), Position { line: 2, column: 8 });
+    // Past the end is the end
+    assert_eq!(Position::at(text, 100), Position { line: 3, column: 6 });
+}
@@ -0,0 +1,157 @@
+#![cfg(feature = "ffi")]
+
+use std::ptr;
+
+use boids::boids::StallPolicy;
+use boids::ffi::{BoidsParameters, BoidsStatus};
+use boids::Parameters;
+
+// Opaque, as C sees it
+#[repr(C)]
+struct BoidsSim {
+    _private: [u8; 0],
+}
+
+// Through the exported symbols, as C sees them
+unsafe extern "C" {
+    fn boids_parameters_default(out: *mut BoidsParameters) -> BoidsStatus;
+    fn boids_sim_new(
+        width: u32,
+        height: u32,
+        count: usize,
+        seed: u64,
+        parameters: *const BoidsParameters,
+    ) -> *mut BoidsSim;
+    fn boids_sim_step(sim: *mut BoidsSim) -> BoidsStatus;
+    fn boids_sim_positions(sim: *const BoidsSim, out: *mut f32, cap: usize) -> usize;
+    fn boids_sim_free(sim: *mut BoidsSim);
+}
+
+fn default_parameters() -> BoidsParameters {
+    let mut parameters = BoidsParameters::from(&Parameters {
+        max_speed: 0.0,
+        ..Parameters::default()
+    });
+    assert_eq!(
+        unsafe { boids_parameters_default(&mut parameters) },
+        BoidsStatus::Ok
+    );
+    parameters
+}
+
+#[test]
+fn steps_keep_boids_in_the_world() {
+    let parameters = BoidsParameters {
+        visible_range: 30.0,
+        cell_size: 32.0,
+        ..default_parameters()
+    };
+    unsafe {
+        let sim = boids_sim_new(320, 240, 100, 7, &parameters);
+        assert!(!sim.is_null());
+        for _ in 0..20 {
+            assert_eq!(boids_sim_step(sim), BoidsStatus::Ok);
+        }
+        let count = boids_sim_positions(sim, ptr::null_mut(), 0);
+        assert_eq!(count, 100);
+        let mut positions = vec![f32::NAN; count * 2];
+        assert_eq!(
+            boids_sim_positions(sim, positions.as_mut_ptr(), positions.len()),
+            count
+        );
+        boids_sim_free(sim);
+        for pair in positions.chunks(2) {
+            assert!(pair[0].is_finite() && pair[1].is_finite(), "{pair:?}");
+            assert!((0.0..=320.0).contains(&pair[0]), "{pair:?}");
+            assert!((0.0..=240.0).contains(&pair[1]), "{pair:?}");
+        }
+    }
+}
+
+#[test]
+fn positions_only_fill_what_fits() {
+    unsafe {
+        let sim = boids_sim_new(100, 100, 3, 1, ptr::null());
+        assert!(!sim.is_null());
+        let mut out = [-1.0f32; 3];
+        assert_eq!(boids_sim_positions(sim, out.as_mut_ptr(), out.len()), 3);
+        boids_sim_free(sim);
+        assert!(out[0] >= 0.0 && out[1] >= 0.0);
+        assert_eq!(out[2], -1.0);
+    }
+}
+
+#[test]
+fn seeds_repeat() {
+    let run = |seed| unsafe {
+        let sim = boids_sim_new(200, 200, 30, seed, ptr::null());
+        for _ in 0..10 {
+            boids_sim_step(sim);
+        }
+        let mut positions = vec![0.0f32; 60];
+        boids_sim_positions(sim, positions.as_mut_ptr(), positions.len());
+        boids_sim_free(sim);
+        positions
+    };
+    assert_eq!(run(3), run(3));
+    assert_ne!(run(3), run(4));
+}
+
+#[test]
+fn nulls_are_turned_away() {
+    unsafe {
+        assert_eq!(
+            boids_parameters_default(ptr::null_mut()),
+            BoidsStatus::ErrorNull
+        );
+        assert_eq!(boids_sim_step(ptr::null_mut()), BoidsStatus::ErrorNull);
+        assert_eq!(boids_sim_positions(ptr::null(), ptr::null_mut(), 10), 0);
+        boids_sim_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn bad_setups_give_null() {
+    let no_cells = BoidsParameters {
+        cell_size: 0.0,
+        ..default_parameters()
+    };
+    unsafe {
+        assert!(boids_sim_new(100, 100, 10, 1, &no_cells).is_null());
+        assert!(boids_sim_new(0, 100, 10, 1, ptr::null()).is_null());
+    }
+}
+
+#[test]
+fn parameters_convert_both_ways() {
+    let parameters = Parameters {
+        max_neighbors_for_early_exit: Some(12),
+        aspect_cells: true,
+        heading_histogram_bins: 8,
+        rewind_depth: 5,
+        perturbation_fraction: 0.01,
+        perturbation_strength: 0.5,
+        trail_length: 4,
+        stall_policy: StallPolicy::KeepHeading,
+        ..Parameters::default()
+    };
+    let mirror = BoidsParameters::from(&parameters);
+    assert_eq!(mirror.max_neighbors_for_early_exit, 12);
+    assert_eq!(mirror.stall_policy, 1);
+    assert_eq!(Parameters::from(&mirror), parameters);
+    for (code, policy) in [
+        (0, StallPolicy::RandomNudge),
+        (2, StallPolicy::Error),
+        (7, StallPolicy::RandomNudge),
+    ] {
+        let mirror = BoidsParameters {
+            stall_policy: code,
+            ..mirror
+        };
+        assert_eq!(Parameters::from(&mirror).stall_policy, policy, "{code}");
+    }
+    assert_eq!(
+        Parameters::from(&default_parameters()),
+        Parameters::default()
+    );
+}
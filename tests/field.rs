@@ -0,0 +1,121 @@
+#[cfg(feature = "render")]
+use image::{Rgb, RgbImage};
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::colour::{Colour, ColourGradient};
+use boids::field::{compute_velocity_field, trace_streamline};
+use boids::testing::boid;
+
+#[test]
+fn field_averages_each_cell() {
+    let boids = vec![
+        boid(0, (1.0, 1.0), (1.0, 0.0)),
+        boid(1, (2.0, 2.0), (0.0, 1.0)),
+        boid(2, (15.0, 5.0), (2.0, 2.0)),
+    ];
+    let field = compute_velocity_field(&boids, 10.0, 30, 20);
+    assert_eq!((field.cols, field.rows), (3, 2));
+    assert_eq!(field.get(0, 0), Vector2::new(0.5, 0.5));
+    assert_eq!(field.get(1, 0), Vector2::new(2.0, 2.0));
+    assert_eq!(field.get(2, 1), Vector2::zeros());
+    assert_eq!(field.counts, vec![2, 1, 0, 0, 0, 0]);
+}
+
+#[test]
+fn divergence_from_occupied_neighbours() {
+    // Spreading out along x in the top row, and one boid on its own below
+    let boids = vec![
+        boid(0, (5.0, 5.0), (-1.0, 0.0)),
+        boid(1, (15.0, 5.0), (0.0, 0.0)),
+        boid(2, (25.0, 5.0), (1.0, 0.0)),
+        boid(3, (35.0, 5.0), (1.0, 0.0)),
+        boid(4, (5.0, 25.0), (3.0, 3.0)),
+    ];
+    let field = compute_velocity_field(&boids, 10.0, 40, 30);
+    let divergence = field.divergence();
+    // One sided at the ends, central in the middle
+    assert_eq!(&divergence[..4], &[0.1, 0.1, 0.05, 0.0]);
+    // Empty cells, and a lone boid with nothing around it to compare with
+    assert!(divergence[4..].iter().all(|cell| *cell == 0.0));
+    assert_eq!(field.index_at(Vector2::new(35.0, 5.0)), 3);
+    assert_eq!(field.index_at(Vector2::new(-3.0, 50.0)), 8);
+}
+
+#[test]
+fn sampling_is_bilinear_between_cell_centres() {
+    let boids = vec![
+        boid(0, (5.0, 5.0), (2.0, 0.0)),
+        boid(1, (15.0, 5.0), (0.0, 0.0)),
+    ];
+    let field = compute_velocity_field(&boids, 10.0, 20, 10);
+    assert_eq!(
+        field.sample(Vector2::new(5.0, 5.0)),
+        Some(Vector2::new(2.0, 0.0))
+    );
+    assert_eq!(
+        field.sample(Vector2::new(10.0, 5.0)),
+        Some(Vector2::new(1.0, 0.0))
+    );
+    // Held at the edge cell's value beyond the last centre
+    assert_eq!(
+        field.sample(Vector2::new(1.0, 9.0)),
+        Some(Vector2::new(2.0, 0.0))
+    );
+    assert_eq!(field.sample(Vector2::new(20.0, 5.0)), None);
+    assert_eq!(field.sample(Vector2::new(-0.1, 5.0)), None);
+}
+
+#[test]
+fn streamline_follows_field_out_of_the_world() {
+    let boids: Vec<Boid> = (0..10)
+        .map(|id| boid(id, (id as f32 * 10.0 + 5.0, 5.0), (1.0, 0.0)))
+        .collect();
+    let field = compute_velocity_field(&boids, 10.0, 100, 10);
+    let line = trace_streamline(&field, Vector2::new(5.0, 5.0), 2.0, 1000);
+    assert_eq!(line.len(), 48);
+    assert!(line
+        .iter()
+        .all(|(pos, speed)| pos.y == 5.0 && *speed == 1.0));
+
+    let line = trace_streamline(&field, Vector2::new(5.0, 5.0), 2.0, 10);
+    assert_eq!(line.len(), 11);
+    assert_eq!(line[10].0, Vector2::new(25.0, 5.0));
+}
+
+#[test]
+fn streamline_stops_in_still_air() {
+    let field = compute_velocity_field(&[], 10.0, 100, 100);
+    assert_eq!(
+        trace_streamline(&field, Vector2::new(50.0, 50.0), 2.0, 100).len(),
+        1
+    );
+}
+
+#[cfg(feature = "render")]
+#[test]
+fn field_lines_are_drawn_in_speed_colours() {
+    use boids::field::FieldLines;
+
+    let boids: Vec<Boid> = (0..10)
+        .map(|id| boid(id, (id as f32 * 10.0 + 5.0, 5.0), (3.0, 0.0)))
+        .collect();
+    let field = compute_velocity_field(&boids, 10.0, 100, 10);
+    let field_lines = FieldLines::new(10.0, 3.0);
+    assert_eq!(field_lines.seeds(100, 10).len(), 10);
+    let mut img = RgbImage::new(100, 10);
+    field_lines.draw(&mut img, &field);
+    assert_eq!(
+        *img.get_pixel(50, 5),
+        Rgb::from(ColourGradient::default().at(1.0))
+    );
+}
+
+#[test]
+fn gradient_interpolates_between_stops() {
+    let gradient = ColourGradient::new(vec![Colour([0, 0, 0]), Colour([200, 100, 0])]);
+    assert_eq!(gradient.at(0.0), Colour([0, 0, 0]));
+    assert_eq!(gradient.at(0.5), Colour([100, 50, 0]));
+    assert_eq!(gradient.at(2.0), Colour([200, 100, 0]));
+    assert_eq!(gradient.at(f32::NAN), Colour([0, 0, 0]));
+}
@@ -0,0 +1,108 @@
+use boids::boids::Boid;
+use boids::cluster::NOISE;
+use boids::flock::{components, FlockTracker, NO_FLOCK};
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::testing::boid;
+use boids::world::World;
+use boids::Parameters;
+
+// `count` boids in a column at `x`, a few pixels apart, numbered from `first`
+fn column(first: usize, count: usize, x: f32) -> Vec<Boid> {
+    (first..first + count)
+        .map(|id| boid(id, (x, 40.0 + (id - first) as f32 * 3.0), (0.0, 1.0)))
+        .collect()
+}
+
+#[test]
+fn components_leave_out_noise() {
+    let membership = [1, NOISE, 0, 1, NOISE, 0, 1];
+    assert_eq!(components(&membership), [vec![2, 5], vec![0, 3, 6]]);
+    assert!(components(&[NOISE, NOISE]).is_empty());
+    assert!(components(&[]).is_empty());
+}
+
+#[test]
+fn flocks_keep_their_ids_when_clusters_are_renumbered() {
+    let mut tracker = FlockTracker::new();
+    let ids = tracker.update(&[vec![0, 1, 2], vec![3, 4]]);
+    assert_eq!(ids, [1, 1, 1, 2, 2]);
+
+    // The same groups the other way round, one boid having wandered off
+    let ids = tracker.update(&[vec![3, 4], vec![0, 2]]);
+    assert_eq!(ids, [1, NO_FLOCK, 1, 2, 2]);
+    assert_eq!(tracker.live().iter().copied().collect::<Vec<_>>(), [1, 2]);
+    assert!(tracker.tombstones().is_empty());
+    assert_eq!(tracker.distinct_flocks(), 2);
+}
+
+#[test]
+fn the_smaller_half_of_a_split_is_a_new_flock() {
+    let mut tracker = FlockTracker::new();
+    tracker.update(&[vec![0, 1, 2, 3, 4], vec![5, 6]]);
+    let ids = tracker.update(&[vec![0, 1], vec![2, 3, 4], vec![5, 6]]);
+    assert_eq!(ids, [3, 3, 1, 1, 1, 2, 2]);
+    assert_eq!(tracker.distinct_flocks(), 3);
+    assert!(tracker.tombstones().is_empty());
+}
+
+#[test]
+fn merged_flocks_take_the_bigger_ones_id() {
+    let mut tracker = FlockTracker::new();
+    tracker.update(&[vec![0, 1], vec![2, 3, 4]]);
+    let ids = tracker.update(&[vec![0, 1, 2, 3, 4]]);
+    assert_eq!(ids, [2; 5]);
+    assert_eq!(tracker.live().iter().copied().collect::<Vec<_>>(), [2]);
+    assert_eq!(
+        tracker.tombstones().iter().copied().collect::<Vec<_>>(),
+        [1]
+    );
+
+    // Splitting back up doesn't bring the old id back
+    let ids = tracker.update(&[vec![0, 1], vec![2, 3, 4]]);
+    assert_eq!(ids, [3, 3, 2, 2, 2]);
+}
+
+#[test]
+fn flocks_that_die_out_are_tombstoned() {
+    let mut tracker = FlockTracker::new();
+    tracker.update(&[vec![0, 1], vec![2, 3]]);
+    let ids = tracker.update(&[vec![2, 3]]);
+    assert_eq!(ids, [NO_FLOCK, NO_FLOCK, 2, 2]);
+    assert_eq!(
+        tracker.tombstones().iter().copied().collect::<Vec<_>>(),
+        [1]
+    );
+
+    let ids = tracker.update(&[vec![0, 1], vec![2, 3]]);
+    assert_eq!(ids, [3, 3, 2, 2]);
+}
+
+#[test]
+fn assign_sets_each_boids_flock() {
+    let mut boids = column(0, 4, 50.0);
+    assert!(boids.iter().all(|boid| boid.flock_id().is_none()));
+    let mut tracker = FlockTracker::new();
+    tracker.assign(&mut boids, &[vec![1, 2]]);
+    let ids: Vec<Option<u32>> = boids.iter().map(Boid::flock_id).collect();
+    assert_eq!(ids, [None, Some(1), Some(1), None]);
+}
+
+#[test]
+fn simulations_count_tracked_flocks() {
+    let mut boids = column(0, 4, 50.0);
+    boids.extend(column(4, 4, 300.0));
+    let config = SimulationConfig::new(World::from_pixels(400, 200), Parameters::default(), 8);
+    let mut simulation = Simulation::from_boids(config, boids, 0).unwrap();
+    assert_eq!(simulation.step().distinct_flocks, 0);
+    assert!(simulation.flock_tracker().is_none());
+
+    simulation.track_flocks(20.0, 2);
+    for _ in 0..3 {
+        assert_eq!(simulation.step().distinct_flocks, 2);
+    }
+    let tracker = simulation.flock_tracker().unwrap();
+    assert!(tracker.tombstones().is_empty());
+    let ids: Vec<Option<u32>> = simulation.boids().iter().map(Boid::flock_id).collect();
+    assert_eq!(ids[..4], [Some(1); 4]);
+    assert_eq!(ids[4..], [Some(2); 4]);
+}
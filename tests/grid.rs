@@ -0,0 +1,135 @@
+use nalgebra::Vector2;
+
+use boids::boids::{populate_grid, populate_grid_rect, Boid, SpatialGrid};
+use boids::testing::boid;
+use boids::Parameters;
+
+#[test]
+fn grid_covers_world() {
+    let boids = vec![
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (5.0, 5.0), (0.0, 0.0)),
+        boid(2, (99.0, 49.0), (0.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 10.0, 100, 50);
+    assert_eq!((grid.grid_cols, grid.grid_rows), (10, 5));
+    assert_eq!(grid.get_cell(0, 0), Some(&[0, 1][..]));
+    assert_eq!(grid.get_cell(9, 4), Some(&[2][..]));
+    assert_eq!(grid.get_cell(1, 1), None);
+    assert_eq!(grid.total_boids(), boids.len());
+}
+
+#[test]
+fn out_of_bounds_cells_are_none() {
+    let boids = vec![boid(0, (0.0, 0.0), (0.0, 0.0))];
+    let grid = populate_grid(&boids, 10.0, 100, 50);
+    assert_eq!(grid.get_cell(10, 0), None);
+    assert_eq!(grid.get_cell(0, 5), None);
+    assert_eq!(grid.get_cell(u32::MAX, 0), None);
+}
+
+#[test]
+fn populated_grid_is_valid() {
+    let boids: Vec<Boid> = (0..50)
+        .map(|id| {
+            boid(
+                id,
+                ((id * 7 % 100) as f32, (id * 3 % 50) as f32),
+                (0.0, 0.0),
+            )
+        })
+        .collect();
+    let grid = populate_grid(&boids, 10.0, 100, 50);
+    grid.assert_valid(boids.len());
+    assert!(grid.verify_boid_placement(&boids));
+    assert_eq!(SpatialGrid::cell_of(7, &boids, 10.0), (4, 2));
+}
+
+#[test]
+#[should_panic(expected = "boid 0 appears in the grid more than once")]
+fn duplicate_index_is_invalid() {
+    let boids = vec![
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (50.0, 0.0), (0.0, 0.0)),
+    ];
+    let mut grid = populate_grid(&boids, 10.0, 100, 50);
+    grid.cells.entry((5, 0)).or_default().push(0);
+    grid.assert_valid(boids.len());
+}
+
+#[test]
+#[should_panic(expected = "boid 1 is missing from the grid")]
+fn missing_index_is_invalid() {
+    let boids = vec![
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (50.0, 0.0), (0.0, 0.0)),
+    ];
+    let mut grid = populate_grid(&boids, 10.0, 100, 50);
+    grid.cells.remove(&(5, 0));
+    grid.assert_valid(boids.len());
+}
+
+#[test]
+fn moved_boid_is_misplaced() {
+    let mut boids = vec![
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (50.0, 0.0), (0.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 10.0, 100, 50);
+    boids[1].pos.x = 5.0;
+    assert!(!grid.verify_boid_placement(&boids));
+}
+
+#[test]
+fn occupancy_statistics() {
+    let boids = vec![
+        boid(0, (0.0, 0.0), (0.0, 0.0)),
+        boid(1, (1.0, 1.0), (0.0, 0.0)),
+        boid(2, (2.0, 2.0), (0.0, 0.0)),
+        boid(3, (55.0, 5.0), (0.0, 0.0)),
+        boid(4, (95.0, 45.0), (0.0, 0.0)),
+        boid(5, (96.0, 46.0), (0.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 10.0, 100, 50);
+    assert_eq!(
+        grid.cell_counts(),
+        vec![(3, (0, 0)), (2, (9, 4)), (1, (5, 0))]
+    );
+    assert_eq!(grid.max_occupancy(), 3);
+    assert_eq!(grid.mean_occupancy(), 2.0);
+    assert_eq!(grid.occupied_fraction(), 3.0 / 50.0);
+
+    let empty = populate_grid(&[], 10.0, 100, 50);
+    assert_eq!(empty.max_occupancy(), 0);
+    assert_eq!(empty.mean_occupancy(), 0.0);
+    assert_eq!(empty.occupied_fraction(), 0.0);
+}
+
+#[test]
+fn rectangular_cells_balance_the_grid() {
+    let boids = vec![
+        boid(0, (5.0, 5.0), (0.0, 0.0)),
+        boid(1, (25.0, 15.0), (0.0, 0.0)),
+    ];
+    let grid = populate_grid_rect(&boids, 20.0, 10.0, 100, 50);
+    assert_eq!((grid.grid_cols, grid.grid_rows), (5, 5));
+    assert_eq!(grid.cell_at(Vector2::new(25.0, 15.0)), (1, 1));
+    assert_eq!(grid.get_cell(1, 1), Some(&[1][..]));
+    assert!(grid.verify_boid_placement(&boids));
+}
+
+#[test]
+fn aspect_cells_keep_the_shorter_side() {
+    let parameters = Parameters {
+        cell_size: 10.0,
+        aspect_cells: true,
+        ..Parameters::default()
+    };
+    assert_eq!(parameters.cell_dimensions(200, 100), (20.0, 10.0));
+    assert_eq!(parameters.cell_dimensions(100, 400), (10.0, 40.0));
+    let square = Parameters {
+        aspect_cells: false,
+        ..parameters
+    };
+    assert_eq!(square.cell_dimensions(200, 100), (10.0, 10.0));
+}
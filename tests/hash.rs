@@ -0,0 +1,37 @@
+#![cfg(feature = "render")]
+
+use image::{Rgb, RgbImage};
+
+use boids::hash::{combine, frame_hash, FrameHashes};
+
+#[test]
+fn hashes_follow_pixels_and_size() {
+    let img = RgbImage::new(4, 3);
+    assert_eq!(frame_hash(&img), frame_hash(&img.clone()));
+    let mut changed = img.clone();
+    changed.put_pixel(2, 1, Rgb([0, 0, 1]));
+    assert_ne!(frame_hash(&img), frame_hash(&changed));
+    // Same bytes, different shape
+    assert_ne!(frame_hash(&img), frame_hash(&RgbImage::new(3, 4)));
+}
+
+#[test]
+fn records_every_frame_and_combines_them() {
+    let frames = [
+        RgbImage::new(2, 2),
+        RgbImage::from_pixel(2, 2, Rgb([9, 9, 9])),
+    ];
+    let mut hashes = FrameHashes::new(Vec::new());
+    let recorded: Vec<u64> = frames
+        .iter()
+        .enumerate()
+        .map(|(frame, img)| hashes.record(frame, img).unwrap())
+        .collect();
+    assert_eq!(hashes.combined(), combine(&recorded));
+    let text = String::from_utf8(hashes.into_inner()).unwrap();
+    assert_eq!(
+        text,
+        format!("0 {:016x}\n1 {:016x}\n", recorded[0], recorded[1])
+    );
+    assert_ne!(combine(&recorded), combine(&[recorded[1], recorded[0]]));
+}
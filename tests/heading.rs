@@ -0,0 +1,61 @@
+use std::f32::consts::PI;
+
+use boids::boids::Boid;
+use boids::heading::{HeadingHistogram, HeadingHistogramCsvWriter};
+use boids::testing::boid;
+
+#[test]
+fn bins_headings_around_the_circle() {
+    let boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (10.0, 10.0), (1.0, 0.5)),
+        boid(2, (10.0, 10.0), (0.0, 2.0)),
+        boid(3, (10.0, 10.0), (-1.0, -0.5)),
+        boid(4, (10.0, 10.0), (0.5, -1.0)),
+        // Straight back along -x is pi, the same way as -pi
+        boid(5, (10.0, 10.0), (-1.0, 0.0)),
+    ];
+    let histogram = HeadingHistogram::compute(&boids, 4);
+    assert_eq!(histogram.counts, vec![2, 1, 2, 1]);
+    assert_eq!(histogram.counts.iter().sum::<usize>(), boids.len());
+    assert_eq!(
+        HeadingHistogram::centres(4),
+        vec![-0.75 * PI, -0.25 * PI, 0.25 * PI, 0.75 * PI]
+    );
+}
+
+#[test]
+fn aligned_flocks_pile_into_one_bin() {
+    let aligned: Vec<Boid> = (0..20)
+        .map(|id| boid(id, (10.0, 10.0), (0.2, 1.0)))
+        .collect();
+    let histogram = HeadingHistogram::compute(&aligned, 8);
+    assert_eq!(histogram.counts.iter().max(), Some(&20));
+
+    let spread: Vec<Boid> = (0..16)
+        .map(|id| {
+            let angle = -PI + (id as f32 + 0.5) * PI / 8.0;
+            boid(id, (10.0, 10.0), (angle.cos(), angle.sin()))
+        })
+        .collect();
+    assert_eq!(HeadingHistogram::compute(&spread, 8).counts, vec![2; 8]);
+    assert!(HeadingHistogram::compute(&spread, 0).counts.is_empty());
+}
+
+#[test]
+fn writes_a_column_per_bin() {
+    let mut writer = HeadingHistogramCsvWriter::new(Vec::new(), 2).unwrap();
+    let boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 1.0)),
+        boid(1, (10.0, 10.0), (1.0, -1.0)),
+    ];
+    writer
+        .write_frame(0, &HeadingHistogram::compute(&boids, 2))
+        .unwrap();
+    writer
+        .write_frame(1, &HeadingHistogram::compute(&boids[..1], 2))
+        .unwrap();
+    let text = String::from_utf8(writer.into_inner()).unwrap();
+    let centre = PI / 2.0;
+    assert_eq!(text, format!("frame,-{centre},{centre}\n0,1,1\n1,0,1\n"));
+}
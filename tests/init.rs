@@ -0,0 +1,191 @@
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::{Boid, BoidId};
+use boids::init::{spawn_boids, BoidSpawnDistribution, BoidSpawnVelocity};
+use boids::testing::spawn_seeded;
+use boids::world::World;
+use boids::Parameters;
+
+fn spawn(count: usize, distribution: &str) -> Vec<Boid> {
+    spawn_moving(count, distribution, "random")
+}
+
+fn spawn_moving(count: usize, distribution: &str, velocity: &str) -> Vec<Boid> {
+    spawn_boids(
+        count,
+        &distribution.parse().unwrap(),
+        velocity.parse().unwrap(),
+        &mut StdRng::seed_from_u64(11),
+        &Parameters::default(),
+        &World::from_pixels(1920, 1080),
+    )
+}
+
+fn mean(points: &[Vector2<f32>]) -> Vector2<f32> {
+    points.iter().sum::<Vector2<f32>>() / points.len() as f32
+}
+
+fn std(values: impl Iterator<Item = f32> + Clone) -> f32 {
+    let count = values.clone().count() as f32;
+    let mean = values.clone().sum::<f32>() / count;
+    (values.map(|value| (value - mean).powi(2)).sum::<f32>() / count).sqrt()
+}
+
+fn positions(boids: &[Boid]) -> Vec<Vector2<f32>> {
+    boids.iter().map(|boid| boid.pos).collect()
+}
+
+#[test]
+fn uniform_covers_the_world() {
+    let boids = spawn_seeded(20_000, &World::from_pixels(1920, 1080), 11);
+    let points = positions(&boids);
+    assert!((mean(&points) - Vector2::new(960.0, 540.0)).norm() < 15.0);
+    // Standard deviation of a uniform distribution is width / sqrt(12)
+    assert!((std(points.iter().map(|pos| pos.x)) - 1920.0 / 12f32.sqrt()).abs() < 10.0);
+    assert!(points
+        .iter()
+        .all(|pos| pos.x.fract() == 0.0 && pos.x < 1920.0));
+    let ids: Vec<BoidId> = boids.iter().map(Boid::id).collect();
+    assert_eq!(ids, (0..20_000).collect::<Vec<_>>());
+}
+
+#[test]
+fn gaussian_has_its_mean_and_std() {
+    let points = positions(&spawn(20_000, "gaussian:960,540,100"));
+    assert!((mean(&points) - Vector2::new(960.0, 540.0)).norm() < 3.0);
+    assert!((std(points.iter().map(|pos| pos.x)) - 100.0).abs() < 3.0);
+    assert!((std(points.iter().map(|pos| pos.y)) - 100.0).abs() < 3.0);
+}
+
+#[test]
+fn ring_is_centred_at_its_radius() {
+    let points = positions(&spawn(20_000, "ring:960,540,300,10"));
+    let centre = Vector2::new(960.0, 540.0);
+    assert!((mean(&points) - centre).norm() < 5.0);
+    let distances = points.iter().map(|pos| (pos - centre).norm());
+    assert!((distances.clone().sum::<f32>() / 20_000.0 - 300.0).abs() < 1.0);
+    assert!((std(distances) - 10.0).abs() < 0.5);
+}
+
+#[test]
+fn grid_fills_cell_centres() {
+    let points = positions(&spawn(12, "grid:3,4,0"));
+    assert_eq!(points[0], Vector2::new(240.0, 180.0));
+    assert_eq!(points[5], Vector2::new(720.0, 540.0));
+    assert_eq!(points[11], Vector2::new(1680.0, 900.0));
+
+    let jittered = positions(&spawn(12, "grid:3,4,5"));
+    for (point, jittered) in points.iter().zip(&jittered) {
+        let offset = jittered - point;
+        assert!(offset.x.abs() <= 5.0 && offset.y.abs() <= 5.0);
+    }
+    assert_ne!(points, jittered);
+}
+
+#[test]
+fn clusters_share_boids_out() {
+    let points = positions(&spawn(4000, "cluster:1000,20,400,300,1500,800"));
+    let first: Vec<Vector2<f32>> = points.chunks(1000).step_by(2).flatten().copied().collect();
+    let second: Vec<Vector2<f32>> = points
+        .chunks(1000)
+        .skip(1)
+        .step_by(2)
+        .flatten()
+        .copied()
+        .collect();
+    assert!((mean(&first) - Vector2::new(400.0, 300.0)).norm() < 3.0);
+    assert!((mean(&second) - Vector2::new(1500.0, 800.0)).norm() < 3.0);
+    assert!((std(first.iter().map(|pos| pos.y)) - 20.0).abs() < 1.5);
+}
+
+#[test]
+fn positions_stay_in_the_world() {
+    let points = positions(&spawn(1000, "gaussian:0,0,500"));
+    assert!(points
+        .iter()
+        .all(|pos| (0.0..=1919.0).contains(&pos.x) && (0.0..=1079.0).contains(&pos.y)));
+}
+
+#[test]
+fn parses_and_displays_distributions() {
+    for text in [
+        "uniform",
+        "gaussian:960,540,200",
+        "ring:960,540,300,12.5",
+        "grid:10,20,1.5",
+        "cluster:100,15,200,200,800,600",
+    ] {
+        let distribution: BoidSpawnDistribution = text.parse().unwrap();
+        assert_eq!(distribution.to_string(), text);
+    }
+    for bad in [
+        "gaussian:1,2",
+        "ring:1,2,3,-1",
+        "grid:0,4,0",
+        "cluster:10,5,1",
+        "spiral",
+    ] {
+        assert!(bad.parse::<BoidSpawnDistribution>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn ring_boids_head_in_out_or_around() {
+    let centre = Vector2::new(960.0, 540.0);
+    let inward = spawn_moving(500, "ring:960,540,300,10", "inward:2");
+    for boid in &inward {
+        let towards = (centre - boid.pos).normalize() * 2.0;
+        assert!((boid.velocity() - towards).norm() < 1e-4);
+    }
+    let outward = spawn_moving(500, "ring:960,540,300,10", "outward:2");
+    assert!(outward
+        .iter()
+        .zip(&inward)
+        .all(|(out, into)| (out.velocity() + into.velocity()).norm() < 1e-4));
+
+    // Right of the centre, clockwise on screen is down the screen
+    let clockwise = spawn_moving(1, "ring:960,540,300,0", "clockwise:3");
+    let offset = clockwise[0].pos - centre;
+    assert!(
+        (clockwise[0].velocity() - Vector2::new(-offset.y, offset.x).normalize() * 3.0).norm()
+            < 1e-4
+    );
+    let anticlockwise = spawn_moving(500, "ring:960,540,300,10", "anticlockwise:3");
+    for boid in &anticlockwise {
+        assert!((boid.velocity().norm() - 3.0).abs() < 1e-4);
+        assert!(boid.velocity().dot(&(boid.pos - centre)).abs() < 1e-2);
+    }
+}
+
+#[test]
+fn random_velocities_match_the_seed() {
+    let boids = spawn(500, "gaussian:960,540,100");
+    assert!(boids
+        .iter()
+        .all(|boid| boid.velocity().x.abs() < 1.5 && boid.velocity().y.abs() < 1.5));
+    assert_eq!(boids, spawn_moving(500, "gaussian:960,540,100", "random"));
+}
+
+#[test]
+fn parses_and_displays_velocities() {
+    for text in [
+        "random",
+        "inward:2",
+        "outward:1.5",
+        "clockwise:3",
+        "anticlockwise:0.5",
+    ] {
+        let velocity: BoidSpawnVelocity = text.parse().unwrap();
+        assert_eq!(velocity.to_string(), text);
+    }
+    for bad in [
+        "inward",
+        "outward:-1",
+        "clockwise:fast",
+        "spin:2",
+        "random:1",
+    ] {
+        assert!(bad.parse::<BoidSpawnVelocity>().is_err(), "{bad}");
+    }
+}
@@ -0,0 +1,35 @@
+use boids::manifest::Manifest;
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::world::World;
+use boids::Parameters;
+
+#[test]
+fn manifests_roundtrip() {
+    let parameters = Parameters {
+        trail_length: 4,
+        ..Parameters::default()
+    };
+    let config = SimulationConfig {
+        seed: 2,
+        ..SimulationConfig::new(World::from_pixels(200, 100), parameters.clone(), 20)
+    };
+    let mut simulation = Simulation::new(config).unwrap();
+    let mut manifest = Manifest::new(parameters);
+    for _ in 0..3 {
+        let stats = simulation.step();
+        manifest.frame(format!("frame_{}.png", stats.frame), &stats);
+    }
+    assert_eq!(manifest.frames[2].path, "frame_2.png");
+    assert_eq!(manifest.frames[2].metrics.population, 20);
+    assert!(manifest.frames[2].written.ends_with('Z'));
+
+    let dir = std::env::temp_dir().join(format!("boids_manifest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("manifest.json");
+    manifest.save(&path).unwrap();
+    // Saving again replaces it whole
+    manifest.save(&path).unwrap();
+    assert_eq!(Manifest::load(&path).unwrap(), manifest);
+    assert!(!dir.join("manifest.json.tmp").exists());
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "metrics")]
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use boids::metrics::{self, Metrics};
+use boids::summary::Stage;
+
+fn get(addr: std::net::SocketAddr, path: &str) -> String {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn renders_the_latest_values() {
+    let metrics = Metrics::new();
+    metrics.frame(41, 12, 0.5);
+    metrics.frame(42, 10, 0.25);
+    metrics.set_quality_score(0.75);
+    metrics.set_bytes_written(2048);
+    metrics.set_stage_time(Stage::Encode, Duration::from_millis(1500));
+    let text = metrics.render();
+    for line in [
+        "# TYPE boids_frame gauge",
+        "boids_frame 42",
+        "boids_frames_rendered_total 2",
+        "boids_population 10",
+        "boids_polarization 0.25",
+        "boids_quality_score 0.75",
+        "boids_encode_queue_depth 0",
+        "boids_bytes_written_total 2048",
+        "boids_stage_seconds_total{stage=\"encode\"} 1.5",
+        "boids_stage_seconds_total{stage=\"io\"} 0",
+    ] {
+        assert!(
+            text.lines().any(|l| l == line),
+            "{line} missing from\n{text}"
+        );
+    }
+    assert!(text.contains("\nboids_frames_per_second "));
+}
+
+#[test]
+fn serves_metrics_over_http() {
+    let metrics = Arc::new(Metrics::new());
+    let addr = metrics::serve("127.0.0.1:0", metrics.clone()).unwrap();
+    metrics.frame(7, 3, 1.0);
+    let response = get(addr, "/metrics");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    assert!(response.contains("\nboids_frame 7\n"), "{response}");
+    assert!(get(addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+}
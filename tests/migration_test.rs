@@ -0,0 +1,212 @@
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::colour::Colour;
+use boids::state::{self, Encoding, Format, Metadata, SAVE_FILE_VERSION};
+use boids::testing::spawn_seeded;
+use boids::world::World;
+
+fn flock() -> Vec<Boid> {
+    spawn_seeded(10, &World::from_pixels(200, 100), 3)
+}
+
+#[test]
+fn loads_version_1_without_metadata() {
+    let boids = flock();
+    let json = serde_json::json!({ "version": 1, "world_size": [200, 100], "boids": boids });
+    let loaded = state::from_bytes(json.to_string().as_bytes(), Format::Json).unwrap();
+    assert_eq!(loaded.metadata, Metadata::default());
+    assert_eq!(loaded.boids, boids);
+
+    // Bincode isn't self describing, so needs the old layout
+    let old = (1u32, Some((200u32, 100u32)), flock());
+    let bytes = bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Bincode).unwrap();
+    assert_eq!(loaded.version, SAVE_FILE_VERSION);
+    assert_eq!(loaded.world_size, Some((200, 100)));
+    assert_eq!(loaded.metadata, Metadata::default());
+    assert_eq!(loaded.boids, boids);
+}
+
+#[test]
+fn migrates_a_written_out_version_1_save() {
+    let json = r#"{
+        "version": 1,
+        "world_size": [640, 480],
+        "boids": [
+            {"id": 0, "pos": [10.0, 20.0], "vel": [1.0, 0.0], "current_speed": 1.0, "colour": [255, 0, 0]},
+            {"id": 1, "pos": [30.5, 40.0], "vel": [0.0, -2.0], "current_speed": 2.0, "colour": [0, 0, 255]}
+        ]
+    }"#;
+    let loaded = state::from_bytes_tolerant(json.as_bytes(), Format::Json).unwrap();
+    assert!(loaded.unknown_fields.is_empty());
+    let save = loaded.state;
+    assert_eq!(save.version, SAVE_FILE_VERSION);
+    assert_eq!(save.world(), Some(World::from_pixels(640, 480)));
+    assert_eq!(save.metadata, Metadata::default());
+    assert_eq!(save.boids.len(), 2);
+    assert_eq!(save.boids[1].id(), 1);
+    assert_eq!(save.boids[1].pos, Vector2::new(30.5, 40.0));
+    assert_eq!(save.boids[1].velocity(), Vector2::new(0.0, -2.0));
+    assert_eq!(save.boids[1].colour, Colour([0, 0, 255]));
+
+    // Each version is read in its own layout, so anything from a later one
+    // is unknown to it
+    let json = r#"{"version": 2, "world_size": null, "metadata": {"frame": 9}, "boids": []}"#;
+    let loaded = state::from_bytes_tolerant(json.as_bytes(), Format::Json).unwrap();
+    assert_eq!(loaded.unknown_fields, ["metadata.frame"]);
+    assert_eq!(loaded.state.metadata.frame, None);
+}
+
+#[test]
+fn migrated_saves_survive_saving_in_another_format() {
+    let v1 = serde_json::json!({ "version": 1, "world_size": [200, 100], "boids": flock() });
+    let v2 = serde_json::json!({
+        "version": 2,
+        "world_size": [200, 100],
+        "metadata": { "seed": 7, "note": "before frames" },
+        "boids": flock(),
+    });
+    let v3 = (
+        3u32,
+        Some((200u32, 100u32)),
+        (
+            Some(7u64),
+            None::<String>,
+            None::<String>,
+            None::<String>,
+            None::<String>,
+            Some(120u64),
+        ),
+        flock(),
+    );
+    let old = [
+        (v1.to_string().into_bytes(), Format::Json),
+        (v2.to_string().into_bytes(), Format::Json),
+        (
+            bincode::serde::encode_to_vec(&v3, bincode::config::standard()).unwrap(),
+            Format::Bincode,
+        ),
+    ];
+    let formats = [
+        Format::Json,
+        Format::Bincode,
+        Format::Ron,
+        #[cfg(feature = "rkyv")]
+        Format::Rkyv,
+    ];
+    for (bytes, from) in old {
+        let loaded = state::from_bytes(&bytes, from).unwrap();
+        assert_eq!(loaded.boids, flock());
+        for &format in &formats {
+            let encoding = Encoding {
+                format,
+                compressed: false,
+            };
+            let saved = state::to_bytes(&loaded, encoding).unwrap();
+            assert_eq!(
+                state::from_bytes(&saved, format).unwrap(),
+                loaded,
+                "{from:?} to {format:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn loads_version_2_bincode_without_frame() {
+    let metadata = (
+        Some(7u64),
+        Some("uniform"),
+        None::<String>,
+        None::<String>,
+        None::<String>,
+    );
+    let old = (2u32, Some((200u32, 100u32)), metadata, flock());
+    let bytes = bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Bincode).unwrap();
+    assert_eq!(loaded.version, SAVE_FILE_VERSION);
+    assert_eq!(loaded.metadata.seed, Some(7));
+    assert_eq!(loaded.metadata.spawn.as_deref(), Some("uniform"));
+    assert_eq!(loaded.metadata.frame, None);
+    assert_eq!(loaded.boids, flock());
+}
+
+#[test]
+fn loads_version_3_bincode_without_parameters() {
+    let metadata = (
+        Some(7u64),
+        None::<String>,
+        None::<String>,
+        None::<String>,
+        None::<String>,
+        Some(120u64),
+    );
+    let old = (3u32, Some((200u32, 100u32)), metadata, flock());
+    let bytes = bincode::serde::encode_to_vec(&old, bincode::config::standard()).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Bincode).unwrap();
+    assert_eq!(loaded.version, SAVE_FILE_VERSION);
+    assert_eq!(loaded.metadata.frame, Some(120));
+    assert_eq!(loaded.metadata.parameters, None);
+    assert_eq!(loaded.boids, flock());
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn rkyv_loads_version_1() {
+    #[derive(rkyv::Archive, rkyv::Serialize)]
+    struct SaveFileV1 {
+        version: u32,
+        world_size: Option<(u32, u32)>,
+        boids: Vec<Boid>,
+    }
+    let old = SaveFileV1 {
+        version: 1,
+        world_size: Some((200, 100)),
+        boids: flock(),
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&old).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Rkyv).unwrap();
+    assert_eq!(loaded.version, SAVE_FILE_VERSION);
+    assert_eq!(loaded.metadata, Metadata::default());
+    assert_eq!(loaded.boids, flock());
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn rkyv_loads_version_2() {
+    #[derive(rkyv::Archive, rkyv::Serialize)]
+    struct MetadataV2 {
+        seed: Option<u64>,
+        spawn: Option<String>,
+        crate_version: Option<String>,
+        created: Option<String>,
+        note: Option<String>,
+    }
+    #[derive(rkyv::Archive, rkyv::Serialize)]
+    struct SaveFileV2 {
+        version: u32,
+        world_size: Option<(u32, u32)>,
+        metadata: MetadataV2,
+        boids: Vec<Boid>,
+    }
+    let old = SaveFileV2 {
+        version: 2,
+        world_size: Some((200, 100)),
+        metadata: MetadataV2 {
+            seed: Some(7),
+            spawn: None,
+            crate_version: None,
+            created: None,
+            note: Some(String::from("before frames")),
+        },
+        boids: flock(),
+    };
+    let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&old).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Rkyv).unwrap();
+    assert_eq!(loaded.version, SAVE_FILE_VERSION);
+    assert_eq!(loaded.metadata.seed, Some(7));
+    assert_eq!(loaded.metadata.note.as_deref(), Some("before frames"));
+    assert_eq!(loaded.metadata.frame, None);
+    assert_eq!(loaded.boids, flock());
+}
@@ -0,0 +1,162 @@
+#![cfg(feature = "render")]
+
+use image::{Rgb, RgbImage};
+use nalgebra::Vector2;
+
+use boids::boids::populate_grid;
+use boids::colour::Colour;
+use boids::overlay::{
+    blend, convex_hull, draw_speed_zones, FlockConvexHull, NeighborCountMap, BOOST_ZONE_COLOUR,
+    ROLLING_FRAMES, SLOW_ZONE_COLOUR,
+};
+use boids::testing::boid;
+use boids::zone::SpeedLimitZone;
+
+#[test]
+fn blends_by_alpha() {
+    let mut pixel = Rgb([0, 100, 200]);
+    blend(&mut pixel, Rgb([200, 100, 0]), 0.25);
+    assert_eq!(pixel, Rgb([50, 100, 150]));
+    blend(&mut pixel, Rgb([1, 2, 3]), 1.0);
+    assert_eq!(pixel, Rgb([1, 2, 3]));
+    blend(&mut pixel, Rgb([255, 255, 255]), 0.0);
+    assert_eq!(pixel, Rgb([1, 2, 3]));
+}
+
+#[test]
+fn averages_counts_in_each_occupied_cell() {
+    let boids = vec![
+        boid(0, (1.0, 1.0), (1.0, 0.0)),
+        boid(1, (5.0, 5.0), (1.0, 0.0)),
+        boid(2, (15.0, 5.0), (1.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 10.0, 30, 20);
+    let mut means = NeighborCountMap::cell_means(&grid, &[2, 4, 6]);
+    means.sort_by_key(|(cell, _)| *cell);
+    assert_eq!(means, vec![((0, 0), 3.0), ((1, 0), 6.0)]);
+}
+
+#[test]
+fn paints_occupied_cells_against_a_rolling_max() {
+    let boids = vec![
+        boid(0, (5.0, 5.0), (1.0, 0.0)),
+        boid(1, (15.0, 5.0), (1.0, 0.0)),
+    ];
+    let grid = populate_grid(&boids, 10.0, 30, 20);
+    let mut map = NeighborCountMap::new(1.0);
+    let mut img = RgbImage::new(30, 20);
+    map.draw(&mut img, &grid, &[1, 4]);
+    assert_eq!(map.rolling_max(), 4.0);
+    assert_eq!(*img.get_pixel(9, 9), Rgb::from(map.gradient.at(0.25)));
+    assert_eq!(*img.get_pixel(10, 0), Rgb::from(map.gradient.at(1.0)));
+    // Empty cells are left alone
+    assert_eq!(*img.get_pixel(25, 15), Rgb([0, 0, 0]));
+
+    // A quieter frame is still scaled against the busy one, until it's
+    // been gone for long enough
+    for _ in 1..ROLLING_FRAMES {
+        map.draw(&mut RgbImage::new(30, 20), &grid, &[1, 2]);
+        assert_eq!(map.rolling_max(), 4.0);
+    }
+    map.draw(&mut img, &grid, &[1, 2]);
+    assert_eq!(map.rolling_max(), 2.0);
+    assert_eq!(*img.get_pixel(0, 0), Rgb::from(map.gradient.at(0.5)));
+}
+
+fn points(coords: &[(f32, f32)]) -> Vec<Vector2<f32>> {
+    coords.iter().map(|(x, y)| Vector2::new(*x, *y)).collect()
+}
+
+#[test]
+fn hull_leaves_out_inner_points() {
+    let square = points(&[
+        (10.0, 10.0),
+        (0.0, 0.0),
+        (5.0, 5.0),
+        (0.0, 10.0),
+        (10.0, 0.0),
+        // On an edge, so not a corner
+        (5.0, 0.0),
+        (0.0, 0.0),
+    ]);
+    // Anticlockwise on screen, where y grows downwards
+    assert_eq!(
+        convex_hull(&square),
+        points(&[(0.0, 0.0), (0.0, 10.0), (10.0, 10.0), (10.0, 0.0)])
+    );
+    assert_eq!(
+        convex_hull(&points(&[(3.0, 1.0), (1.0, 1.0)])),
+        points(&[(1.0, 1.0), (3.0, 1.0)])
+    );
+    // All in a line, so only the ends are left
+    assert_eq!(
+        convex_hull(&points(&[(0.0, 0.0), (2.0, 2.0), (1.0, 1.0)])),
+        points(&[(0.0, 0.0), (2.0, 2.0)])
+    );
+}
+
+#[test]
+fn hulls_for_each_flock_of_three_or_more() {
+    let mut boids = vec![
+        boid(0, (0.0, 0.0), (1.0, 0.0)),
+        boid(1, (4.0, 0.0), (1.0, 0.0)),
+        boid(2, (0.0, 3.0), (1.0, 0.0)),
+        boid(3, (50.0, 50.0), (1.0, 0.0)),
+        boid(4, (51.0, 50.0), (1.0, 0.0)),
+        boid(5, (80.0, 10.0), (1.0, 0.0)),
+    ];
+    boids[0].colour = Colour([0, 0, 0]);
+    boids[1].colour = Colour([90, 0, 30]);
+    let hulls = FlockConvexHull::from_membership(&boids, &[1, 1, 1, 0, 0, -1]);
+    assert_eq!(hulls.len(), 1);
+    assert_eq!(hulls[0].cluster, 1);
+    assert_eq!(hulls[0].colour, Rgb([115, 85, 95]));
+    assert_eq!(hulls[0].area(), 6.0);
+}
+
+#[test]
+fn draws_hull_outlines_blended() {
+    let hull = FlockConvexHull {
+        cluster: 0,
+        hull: points(&[(2.0, 2.0), (2.0, 12.0), (12.0, 12.0), (12.0, 2.0)]),
+        colour: Rgb([200, 100, 0]),
+    };
+    let mut img = RgbImage::new(16, 16);
+    hull.draw(&mut img, 0.5);
+    assert_eq!(*img.get_pixel(2, 7), Rgb([100, 50, 0]));
+    assert_eq!(*img.get_pixel(7, 12), Rgb([100, 50, 0]));
+    // Hollow inside
+    assert_eq!(*img.get_pixel(7, 7), Rgb([0, 0, 0]));
+}
+
+#[test]
+fn shades_slow_and_boost_zones() {
+    let zone = |rect, max_speed| SpeedLimitZone {
+        rect,
+        max_speed,
+        min_speed: None,
+    };
+    let mut img = RgbImage::new(16, 16);
+    let zones = [
+        zone((1.5, 2.0, 4.0, 3.0), 1.0),
+        // Hangs off the bottom right corner
+        zone((14.0, 14.0, 40.0, 40.0), 6.0),
+    ];
+    draw_speed_zones(&mut img, &zones, 3.0, 1.0);
+    let shaded: Vec<(u32, u32, Rgb<u8>)> = img
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0 != [0, 0, 0])
+        .map(|(x, y, pixel)| (x, y, *pixel))
+        .collect();
+    assert_eq!(
+        shaded,
+        vec![
+            (2, 2, SLOW_ZONE_COLOUR),
+            (3, 2, SLOW_ZONE_COLOUR),
+            (14, 14, BOOST_ZONE_COLOUR),
+            (15, 14, BOOST_ZONE_COLOUR),
+            (14, 15, BOOST_ZONE_COLOUR),
+            (15, 15, BOOST_ZONE_COLOUR),
+        ]
+    );
+}
@@ -0,0 +1,42 @@
+use std::time::{Duration, Instant};
+
+use boids::pacing::{FramePacer, Pace};
+
+fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[test]
+fn frames_are_due_from_the_start() {
+    let start = Instant::now();
+    let mut pacer = FramePacer::new(10, start);
+    assert_eq!(pacer.due(), start + ms(100));
+    assert_eq!(pacer.finish(start + ms(30)), Pace::Early(ms(70)));
+    // Sleeping past when it was due is taken off the next frame's wait
+    assert_eq!(pacer.finish(start + ms(150)), Pace::Early(ms(50)));
+    assert_eq!(pacer.frames(), 2);
+    assert_eq!(pacer.overruns(), 0);
+    assert!((pacer.achieved_fps(start + ms(200)) - 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn late_frames_are_caught_up() {
+    let start = Instant::now();
+    let mut pacer = FramePacer::new(10, start);
+    assert!(!pacer.behind(start + ms(100)));
+    assert!(pacer.behind(start + ms(250)));
+    assert_eq!(pacer.finish(start + ms(250)), Pace::Late(ms(150)));
+    // Still behind for the next frame, which was due at 200ms
+    assert!(pacer.behind(start + ms(260)));
+    assert_eq!(pacer.finish(start + ms(260)), Pace::Late(ms(60)));
+    assert_eq!(pacer.finish(start + ms(270)), Pace::Early(ms(30)));
+    assert_eq!(pacer.overruns(), 2);
+}
+
+#[test]
+fn no_fps_is_never_early() {
+    let start = Instant::now();
+    let mut pacer = FramePacer::new(0, start);
+    assert_eq!(pacer.finish(start), Pace::Early(Duration::ZERO));
+    assert_eq!(pacer.achieved_fps(start), 0.0);
+}
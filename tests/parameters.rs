@@ -0,0 +1,544 @@
+use boids::boids::{FlockTally, StallPolicy};
+use boids::boundary::BoundaryMode;
+use boids::parameters::{ParametersBuilder, ValidationError};
+use boids::schedule::RandomSeedSchedule;
+use boids::testing::{run_steps, spawn_seeded};
+use boids::world::World;
+use boids::zone::SpeedLimitZone;
+use boids::{Parameters, PRESETS};
+
+fn parameters() -> Parameters {
+    Parameters {
+        max_speed: 3.0,
+        min_speed: 0.5,
+        margin: 10,
+        visible_range: 20.0,
+        protected_range: 2.0,
+        avoid_factor: 0.10,
+        matching_factor: 0.05,
+        centering_factor: 0.0005,
+        turn_factor: 0.2,
+        cell_size: 22.0,
+        ..Parameters::default()
+    }
+}
+
+#[test]
+fn identical_parameters_have_no_diff() {
+    let diff = parameters().diff(&parameters());
+    assert!(diff.is_empty());
+    assert_eq!(diff.summary(), "No parameters differ");
+}
+
+#[test]
+fn diff_lists_changed_fields_in_order() {
+    let other = Parameters {
+        max_speed: 4.0,
+        margin: 20,
+        turn_factor: 0.2 + f32::EPSILON / 4.0,
+        max_neighbors_for_early_exit: Some(50),
+        ..parameters()
+    };
+    let diff = parameters().diff(&other);
+    assert_eq!(
+        diff.changed_fields,
+        [
+            (String::from("max_speed"), 3.0, 4.0),
+            (String::from("margin"), 10.0, 20.0),
+            (
+                String::from("max_neighbors_for_early_exit"),
+                f32::INFINITY,
+                50.0
+            ),
+        ]
+    );
+    assert_eq!(
+        diff.summary(),
+        "max_speed: 3 -> 4\nmargin: 10 -> 20\nmax_neighbors_for_early_exit: inf -> 50"
+    );
+    assert_eq!(other.diff(&parameters()).changed_fields[0].1, 4.0);
+}
+
+#[test]
+fn parameters_load_from_toml() {
+    let text = toml::to_string(&parameters()).unwrap();
+    let loaded: Parameters = toml::from_str(&text).unwrap();
+    assert!(parameters().diff(&loaded).is_empty());
+}
+
+#[test]
+fn default_matches_the_command_line() {
+    assert_eq!(Parameters::default(), parameters());
+    assert_eq!(Parameters::preset("default"), Ok(parameters()));
+    assert_eq!(Parameters::preset("dense"), Ok(Parameters::dense()));
+    assert!(Parameters::preset("hive").is_err());
+}
+
+#[test]
+fn presets_are_consistent() {
+    for name in PRESETS {
+        let preset = Parameters::preset(name).unwrap();
+        assert!(
+            0.0 < preset.min_speed && preset.min_speed < preset.max_speed,
+            "{name}"
+        );
+        assert!(preset.protected_range < preset.visible_range, "{name}");
+        assert_eq!(
+            preset.auto_cell_size(1.0),
+            Ok(preset.visible_range),
+            "{name}"
+        );
+        assert!(preset.cell_size >= preset.visible_range, "{name}");
+        for factor in [
+            preset.avoid_factor,
+            preset.matching_factor,
+            preset.centering_factor,
+            preset.turn_factor,
+        ] {
+            assert!((0.0..1.0).contains(&factor), "{name}");
+        }
+    }
+    // Each one is something different to look at
+    for (i, a) in PRESETS.iter().enumerate() {
+        for b in &PRESETS[i + 1..] {
+            let diff = Parameters::preset(a)
+                .unwrap()
+                .diff(&Parameters::preset(b).unwrap());
+            assert!(!diff.is_empty(), "{a} and {b}");
+        }
+    }
+}
+
+#[test]
+fn presets_keep_flocks_moving_in_bounds() {
+    for name in PRESETS {
+        assert!(Parameters::preset_description(name).is_some(), "{name}");
+        let parameters = Parameters::preset(name).unwrap();
+        let world = World::from_pixels(320, 240);
+        let mut boids = spawn_seeded(200, &world, 5);
+        let start = boids.clone();
+        let mut tally = FlockTally::default();
+        for frame in 0..200 {
+            tally = run_steps(&mut boids, &world, &parameters, 1);
+            for boid in &boids {
+                assert!(
+                    boid.pos
+                        .iter()
+                        .chain(boid.velocity().iter())
+                        .all(|v| v.is_finite()),
+                    "{name}: frame {frame}"
+                );
+                assert!(
+                    (0.0..320.0).contains(&boid.pos.x) && (0.0..240.0).contains(&boid.pos.y),
+                    "{name}: frame {frame}, {}",
+                    boid.pos
+                );
+            }
+        }
+        assert!(tally.mean_speed() >= parameters.min_speed * 0.99, "{name}");
+        assert!(tally.mean_speed() <= parameters.max_speed * 1.01, "{name}");
+        let moved = start
+            .iter()
+            .zip(&boids)
+            .filter(|(before, after)| (before.pos - after.pos).norm() > parameters.max_speed)
+            .count();
+        assert!(moved > 190, "{name}: only {moved} moved");
+    }
+    assert_eq!(Parameters::preset_description("hive"), None);
+}
+
+#[test]
+fn builder_sets_fields_over_the_defaults() {
+    let built = Parameters::builder()
+        .visible_range(30.0)
+        .protected_range(4.0)
+        .cell_size(33.0)
+        .voronoi_neighbors(true)
+        .build()
+        .unwrap();
+    assert_eq!(
+        built,
+        Parameters {
+            visible_range: 30.0,
+            protected_range: 4.0,
+            cell_size: 33.0,
+            voronoi_neighbors: true,
+            ..Parameters::default()
+        }
+    );
+    assert_eq!(
+        ParametersBuilder::from(parameters()).build(),
+        Ok(parameters())
+    );
+    for name in PRESETS {
+        assert_eq!(
+            Parameters::preset(name).unwrap().validate(None),
+            Ok(()),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn speeds_ranges_and_factors_must_be_finite_and_positive() {
+    assert!(Parameters::builder().avoid_factor(0.0).build().is_ok());
+    assert_eq!(
+        Parameters::builder().avoid_factor(-0.1).build(),
+        Err(vec![ValidationError::Invalid {
+            field: "avoid_factor",
+            value: -0.1,
+        }])
+    );
+    assert_eq!(
+        Parameters::builder().turn_factor(f32::INFINITY).build(),
+        Err(vec![ValidationError::Invalid {
+            field: "turn_factor",
+            value: f32::INFINITY,
+        }])
+    );
+    let nan = Parameters::builder().centering_factor(f32::NAN).build();
+    assert!(matches!(
+        nan.unwrap_err().as_slice(),
+        [ValidationError::Invalid {
+            field: "centering_factor",
+            ..
+        }]
+    ));
+    assert!(Parameters::builder().max_speed(-1.0).build().is_err());
+}
+
+#[test]
+fn min_speed_cannot_pass_max_speed() {
+    assert!(Parameters::builder()
+        .min_speed(3.0)
+        .max_speed(3.0)
+        .build()
+        .is_ok());
+    let err = Parameters::builder()
+        .min_speed(4.0)
+        .max_speed(3.0)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        vec![ValidationError::SpeedRange {
+            min_speed: 4.0,
+            max_speed: 3.0,
+        }]
+    );
+    assert_eq!(
+        err[0].to_string(),
+        "the min speed of 4 is more than the max speed of 3, lower it to at most 3"
+    );
+}
+
+#[test]
+fn speed_zones_need_sensible_speeds() {
+    let zone = |max_speed, min_speed| SpeedLimitZone {
+        rect: (0.0, 0.0, 10.0, 10.0),
+        max_speed,
+        min_speed,
+    };
+    let parameters = ParametersBuilder::from(parameters());
+    assert!(parameters
+        .clone()
+        .speed_zones(vec![zone(8.0, Some(0.0)), zone(0.5, None)])
+        .build()
+        .is_ok());
+    assert_eq!(
+        parameters
+            .clone()
+            .speed_zones(vec![zone(f32::INFINITY, None)])
+            .build(),
+        Err(vec![ValidationError::Invalid {
+            field: "speed_zones.max_speed",
+            value: f32::INFINITY,
+        }])
+    );
+    assert_eq!(
+        parameters.speed_zones(vec![zone(1.0, Some(2.0))]).build(),
+        Err(vec![ValidationError::SpeedRange {
+            min_speed: 2.0,
+            max_speed: 1.0,
+        }])
+    );
+}
+
+#[test]
+fn protected_range_cannot_pass_visible_range() {
+    let parameters = ParametersBuilder::from(parameters());
+    assert!(parameters.clone().protected_range(20.0).build().is_ok());
+    assert_eq!(
+        parameters.protected_range(21.0).build(),
+        Err(vec![ValidationError::ProtectedRange {
+            protected_range: 21.0,
+            visible_range: 20.0,
+        }])
+    );
+}
+
+#[test]
+fn cell_size_must_be_positive() {
+    assert!(Parameters::builder().cell_size(0.5).build().is_ok());
+    assert_eq!(
+        Parameters::builder().cell_size(0.0).build(),
+        Err(vec![ValidationError::CellSize(0.0)])
+    );
+    assert!(Parameters::builder().cell_size(f32::NAN).build().is_err());
+}
+
+#[test]
+fn margin_must_leave_room_in_the_world() {
+    // Not checked without a world to check it against
+    assert!(Parameters::builder().margin(1000).build().is_ok());
+    assert!(Parameters::builder()
+        .margin(23)
+        .world(64, 48)
+        .build()
+        .is_ok());
+    assert_eq!(
+        Parameters::builder().margin(24).world(64, 48).build(),
+        Err(vec![ValidationError::Margin {
+            margin: 24,
+            width: 64,
+            height: 48,
+        }])
+    );
+    assert!(Parameters::builder()
+        .margin(u32::MAX)
+        .world(64, 48)
+        .build()
+        .is_err());
+    let err = Parameters::builder()
+        .margin(24)
+        .world(64, 48)
+        .build()
+        .unwrap_err();
+    assert!(
+        err[0].to_string().ends_with("lower it to at most 23"),
+        "{}",
+        err[0]
+    );
+}
+
+#[test]
+fn turn_factor_cannot_pass_max_speed() {
+    let parameters = ParametersBuilder::from(parameters());
+    assert!(parameters
+        .clone()
+        .turn_factor(3.0)
+        .max_speed(3.0)
+        .build()
+        .is_ok());
+    assert_eq!(
+        parameters.turn_factor(3.5).max_speed(3.0).build(),
+        Err(vec![ValidationError::TurnFactor {
+            turn_factor: 3.5,
+            max_speed: 3.0,
+        }])
+    );
+}
+
+#[test]
+fn every_broken_rule_is_reported_at_once() {
+    let errors = Parameters::builder()
+        .min_speed(4.0)
+        .max_speed(3.0)
+        .protected_range(30.0)
+        .visible_range(20.0)
+        .avoid_factor(-1.0)
+        .cell_size(0.0)
+        .margin(40)
+        .world(64, 48)
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        errors,
+        vec![
+            ValidationError::Invalid {
+                field: "avoid_factor",
+                value: -1.0,
+            },
+            ValidationError::SpeedRange {
+                min_speed: 4.0,
+                max_speed: 3.0,
+            },
+            ValidationError::ProtectedRange {
+                protected_range: 30.0,
+                visible_range: 20.0,
+            },
+            ValidationError::CellSize(0.0),
+            ValidationError::Margin {
+                margin: 40,
+                width: 64,
+                height: 48,
+            },
+        ]
+    );
+    // A field that's invalid by itself isn't blamed again for breaking a
+    // rule with another
+    assert_eq!(
+        Parameters::builder()
+            .turn_factor(f32::INFINITY)
+            .build()
+            .unwrap_err()
+            .len(),
+        1
+    );
+    let err = boids::Error::from(errors);
+    assert!(err.to_string().starts_with(
+        "invalid parameters: avoid_factor must be a finite number of at least 0, not -1; the min speed"
+    ));
+}
+
+#[test]
+fn small_cells_miss_visible_boids() {
+    assert!(!parameters().misses_visible_boids());
+    let small = Parameters::builder().cell_size(10.0).build().unwrap();
+    assert!(small.misses_visible_boids());
+}
+
+#[test]
+fn compatible_parameters_share_a_grid() {
+    let ours = parameters();
+    assert!(ours.compatible_with(&ours, 0.0));
+    // Anything else can differ
+    let faster = Parameters {
+        max_speed: 6.0,
+        visible_range: 15.0,
+        ..parameters()
+    };
+    assert!(ours.compatible_with(&faster, 0.0));
+    assert!(faster.compatible_with(&ours, 0.0));
+
+    let bigger_cells = Parameters {
+        cell_size: 22.1,
+        ..parameters()
+    };
+    assert!(!ours.compatible_with(&bigger_cells, 0.0));
+    assert!(ours.compatible_with(&bigger_cells, 0.01));
+    assert!(bigger_cells.compatible_with(&ours, 0.01));
+
+    // Seeing past a cell means missing boids in the other flock's grid
+    let far_sighted = Parameters {
+        visible_range: 25.0,
+        ..parameters()
+    };
+    assert!(!ours.compatible_with(&far_sighted, 0.01));
+    assert!(!far_sighted.compatible_with(&ours, 0.01));
+    assert!(ours.compatible_with(&far_sighted, 0.2));
+}
+
+fn unusual() -> Parameters {
+    Parameters {
+        max_neighbors_for_early_exit: Some(8),
+        boundary: BoundaryMode::Sdf {
+            path: "shape.png".into(),
+        },
+        seed_schedule: RandomSeedSchedule::new(vec!["200:7".parse().unwrap()]),
+        speed_zones: vec![SpeedLimitZone {
+            rect: (0.0, 0.0, 100.0, 50.0),
+            max_speed: 1.0,
+            min_speed: Some(0.2),
+        }],
+        perturbation_fraction: 0.01,
+        stall_policy: StallPolicy::KeepHeading,
+        ..Parameters::dense()
+    }
+}
+
+#[test]
+fn parameters_roundtrip_through_json_and_toml() {
+    let json = serde_json::to_string(&unusual()).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Parameters>(&json).unwrap(),
+        unusual()
+    );
+    let toml = toml::to_string(&unusual()).unwrap();
+    assert!(toml.contains("stall_policy = \"keep-heading\""), "{toml}");
+    assert_eq!(toml::from_str::<Parameters>(&toml).unwrap(), unusual());
+
+    // Anything left out is the default
+    let partial: Parameters = toml::from_str("max_speed = 5.0").unwrap();
+    assert_eq!(
+        partial,
+        Parameters {
+            max_speed: 5.0,
+            ..Parameters::default()
+        }
+    );
+}
+
+#[test]
+fn loading_refuses_unknown_parameters() {
+    let dir = std::env::temp_dir().join(format!("boids_parameters_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("typo.toml");
+    std::fs::write(&path, "max_speed = 5.0\nvisible_rnage = 30.0\n").unwrap();
+    let err = Parameters::load(&path).unwrap_err();
+    assert!(
+        err.to_string()
+            .ends_with("unknown parameters visible_rnage"),
+        "{err}"
+    );
+
+    std::fs::write(&path, "max_speed = 5.0\n").unwrap();
+    assert_eq!(Parameters::load(&path).unwrap().max_speed, 5.0);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn changed_parameters_override_saved_ones() {
+    let ours = Parameters {
+        max_speed: 5.0,
+        ..Parameters::default()
+    };
+    let restored = ours.overriding(&unusual(), &Parameters::default());
+    assert_eq!(
+        restored,
+        Parameters {
+            max_speed: 5.0,
+            ..unusual()
+        }
+    );
+    // Nothing changed from the baseline keeps everything saved
+    assert_eq!(
+        Parameters::default().overriding(&unusual(), &Parameters::default()),
+        unusual()
+    );
+}
+
+#[test]
+fn fields_are_set_by_name() {
+    let defaults = Parameters::default();
+    assert_eq!(
+        defaults.with_field("avoid_factor", 0.3),
+        Ok(Parameters {
+            avoid_factor: 0.3,
+            ..Parameters::default()
+        })
+    );
+    assert_eq!(defaults.with_field("margin", 25.0).unwrap().margin, 25);
+    assert_eq!(
+        defaults.with_field("draw_radius", 4.0).unwrap().draw_radius,
+        4
+    );
+    assert!(
+        defaults
+            .with_field("aspect_cells", 1.0)
+            .unwrap()
+            .aspect_cells
+    );
+    assert_eq!(
+        defaults
+            .with_field("max_neighbors_for_early_exit", 6.0)
+            .unwrap()
+            .max_neighbors_for_early_exit,
+        Some(6)
+    );
+    assert!(defaults.with_field("avoid", 0.3).is_err());
+    assert!(defaults.with_field("margin", 2.5).is_err());
+    assert!(defaults.with_field("margin", -1.0).is_err());
+    assert!(defaults.with_field("aspect_cells", 0.5).is_err());
+    assert!(defaults.with_field("speed_zones", 1.0).is_err());
+}
@@ -0,0 +1,102 @@
+use boids::boids::Boid;
+use boids::quality::{mean_isolation, FlockingQualityScore, QualityWeights};
+use boids::testing::boid;
+
+fn flock(velocities: &[(f32, f32)]) -> Vec<Boid> {
+    velocities
+        .iter()
+        .enumerate()
+        .map(|(id, &vel)| boid(id, (id as f32 * 10.0, 0.0), vel))
+        .collect()
+}
+
+fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-5
+}
+
+#[test]
+fn isolation_falls_with_neighbours() {
+    assert_eq!(mean_isolation(&[]), 0.0);
+    assert_eq!(mean_isolation(&[0, 0]), 1.0);
+    assert!(close(
+        mean_isolation(&[0, 2]),
+        (1.0 + (-2.0f32).exp()) / 2.0
+    ));
+    assert!(mean_isolation(&[20; 4]) < 1e-6);
+}
+
+#[test]
+fn parses_weights() {
+    assert_eq!(
+        "0.5, 0.5, 0".parse(),
+        Ok(QualityWeights {
+            polarization: 0.5,
+            connectedness: 0.5,
+            smoothness: 0.0,
+            max_jerk: QualityWeights::default().max_jerk,
+        })
+    );
+    let weights: QualityWeights = "1,0,0,2.5".parse().unwrap();
+    assert_eq!(weights.max_jerk, 2.5);
+    assert_eq!(weights.to_string().parse(), Ok(weights));
+    let defaults = QualityWeights::default();
+    assert_eq!(defaults.to_string().parse(), Ok(defaults));
+    for text in [
+        "",
+        "1,1",
+        "1,1,1,1,1",
+        "1,-1,1",
+        "1,1,x",
+        "1,1,1,0",
+        "1,1,inf",
+    ] {
+        assert!(text.parse::<QualityWeights>().is_err(), "{text}");
+    }
+}
+
+#[test]
+fn score_weighs_its_parts() {
+    let quality = FlockingQualityScore::new(QualityWeights {
+        polarization: 0.5,
+        connectedness: 0.25,
+        smoothness: 0.25,
+        max_jerk: 2.0,
+    });
+    assert_eq!(quality.score(1.0, 0.0, 0.0), 1.0);
+    assert_eq!(quality.score(0.0, 1.0, 2.0), 0.0);
+    assert_eq!(quality.score(0.5, 0.5, 1.0), 0.5);
+    // Jerk past the max counts the same as the max
+    assert_eq!(quality.score(1.0, 0.0, 10.0), 0.75);
+}
+
+#[test]
+fn steady_flight_has_no_jerk() {
+    let mut quality = FlockingQualityScore::default();
+    let boids = flock(&[(1.0, 0.0), (2.0, 0.0)]);
+    let neighbours = [1, 1];
+    let expected = quality.score(1.0, mean_isolation(&neighbours), 0.0);
+    for _ in 0..4 {
+        assert!(close(quality.update(&boids, &neighbours), expected));
+    }
+}
+
+#[test]
+fn jerk_needs_three_frames() {
+    let mut quality = FlockingQualityScore::new(QualityWeights {
+        polarization: 0.0,
+        connectedness: 0.0,
+        smoothness: 1.0,
+        max_jerk: 1.0,
+    });
+    let neighbours = [0];
+    assert_eq!(quality.update(&flock(&[(0.0, 0.0)]), &neighbours), 1.0);
+    // Accelerating by 0.5, then not at all, is a jerk of 0.5
+    assert_eq!(quality.update(&flock(&[(0.5, 0.0)]), &neighbours), 1.0);
+    assert!(close(
+        quality.update(&flock(&[(0.5, 0.0)]), &neighbours),
+        0.5
+    ));
+    // A new flock starts the history again
+    let two = flock(&[(3.0, 0.0), (0.0, 3.0)]);
+    assert_eq!(quality.update(&two, &[0, 0]), 1.0);
+}
@@ -0,0 +1,109 @@
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::{populate_grid, Boid};
+use boids::query::{Grid, SpatialQuery};
+use boids::testing::boid;
+
+fn voronoi_cell(boids: &[Boid], idx: usize) -> Vec<usize> {
+    let grid = populate_grid(boids, 20.0, 200, 200);
+    SpatialQuery::voronoi_cell(boids[idx].pos, &grid, boids)
+}
+
+#[test]
+fn hexagon_around_a_boid_are_its_neighbours() {
+    let mut boids = vec![boid(0, (100.0, 100.0), (0.0, 0.0))];
+    for ring in [12.0, 30.0] {
+        for step in 0..6 {
+            let angle = (step as f32 * 60.0 + ring).to_radians();
+            let id = boids.len();
+            boids.push(boid(
+                id,
+                (100.0 + ring * angle.cos(), 100.0 + ring * angle.sin()),
+                (0.0, 0.0),
+            ));
+        }
+    }
+    // Only the inner ring touches the middle boid, the outer is behind it
+    assert_eq!(voronoi_cell(&boids, 0), [1, 2, 3, 4, 5, 6]);
+    assert!(voronoi_cell(&boids, 1).contains(&0));
+}
+
+#[test]
+fn boids_in_a_line_neighbour_either_side() {
+    let boids: Vec<Boid> = (0..5)
+        .map(|id| boid(id, (40.0 + id as f32 * 10.0, 50.0), (0.0, 0.0)))
+        .collect();
+    assert_eq!(voronoi_cell(&boids, 2), [1, 3]);
+    assert_eq!(voronoi_cell(&boids, 0), [1]);
+}
+
+#[test]
+fn only_nearby_cells_are_searched() {
+    let boids = vec![
+        boid(0, (10.0, 10.0), (0.0, 0.0)),
+        boid(1, (190.0, 190.0), (0.0, 0.0)),
+    ];
+    assert!(voronoi_cell(&boids, 0).is_empty());
+    let boids = vec![
+        boid(0, (10.0, 10.0), (0.0, 0.0)),
+        boid(1, (50.0, 10.0), (0.0, 0.0)),
+    ];
+    assert_eq!(voronoi_cell(&boids, 0), [1]);
+}
+
+fn brute_force(boids: &[Boid], pos: Vector2<f32>, radius: f32) -> Vec<usize> {
+    (0..boids.len())
+        .filter(|idx| (boids[*idx].pos - pos).norm_squared() < radius * radius)
+        .collect()
+}
+
+#[test]
+fn query_radius_matches_a_brute_force_scan() {
+    let mut rng = StdRng::seed_from_u64(182);
+    for _ in 0..200 {
+        let (width, height) = (rng.random_range(1.0..500.0), rng.random_range(1.0..500.0));
+        let boids: Vec<Boid> = (0..rng.random_range(0..150))
+            .map(|id| {
+                boid(
+                    id,
+                    (rng.random_range(0.0..width), rng.random_range(0.0..height)),
+                    (0.0, 0.0),
+                )
+            })
+            .collect();
+        let grid = Grid::build(&boids, rng.random_range(2.0..80.0));
+        for _ in 0..10 {
+            // Radii up to many cells across, around points inside and out
+            let pos = Vector2::new(
+                rng.random_range(-100.0..width + 100.0),
+                rng.random_range(-100.0..height + 100.0),
+            );
+            let radius = rng.random_range(0.0..300.0);
+            let mut found: Vec<usize> = grid.query_radius(pos, radius).collect();
+            found.sort_unstable();
+            assert_eq!(found, brute_force(&boids, pos, radius), "{pos} {radius}");
+        }
+    }
+}
+
+#[test]
+fn query_cell_matches_the_grid_it_wraps() {
+    let boids = vec![
+        boid(0, (5.0, 5.0), (0.0, 0.0)),
+        boid(1, (15.0, 5.0), (0.0, 0.0)),
+        boid(2, (25.0, 45.0), (0.0, 0.0)),
+        boid(3, (8.0, 2.0), (0.0, 0.0)),
+    ];
+    let grid = Grid::new(&boids, populate_grid(&boids, 10.0, 50, 50));
+    let mut first: Vec<usize> = grid.query_cell(0, 0).to_vec();
+    first.sort_unstable();
+    assert_eq!(first, [0, 3]);
+    assert_eq!(grid.query_cell(2, 4), [2]);
+    assert!(grid.query_cell(3, 3).is_empty());
+    assert!(grid.query_cell(5, 0).is_empty());
+    // Exactly the radius away doesn't count
+    let mut near: Vec<usize> = grid.query_radius(Vector2::new(5.0, 5.0), 10.0).collect();
+    near.sort_unstable();
+    assert_eq!(near, [0, 3]);
+}
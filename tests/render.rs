@@ -0,0 +1,101 @@
+#![cfg(feature = "render")]
+
+use image::{Rgb, RgbImage};
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::render::{render_boids, render_boids_to_buffer, Renderer};
+use boids::Parameters;
+
+const RED: Rgb<u8> = Rgb([255, 0, 0]);
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+
+fn boid(x: f32, y: f32) -> Boid {
+    Boid::new(0, Vector2::new(x, y), Vector2::new(1.0, 0.0), RED)
+}
+
+fn lit(renderer: &Renderer) -> Vec<(u32, u32)> {
+    renderer
+        .image()
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| **pixel != renderer.background)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+#[test]
+fn draws_a_single_pixel_boid() {
+    let mut renderer = Renderer::new(16, 16, 0);
+    let img = renderer.render(&[boid(5.0, 9.0)]);
+    assert_eq!(img.dimensions(), (16, 16));
+    assert_eq!(*img.get_pixel(5, 9), RED);
+    assert_eq!(lit(&renderer), vec![(5, 9)]);
+}
+
+#[test]
+fn draws_a_circle_around_the_boid() {
+    let mut renderer = Renderer::new(16, 16, 1);
+    renderer.render(&[boid(8.0, 8.0)]);
+    // Row by row, top to bottom
+    assert_eq!(lit(&renderer), vec![(8, 7), (7, 8), (8, 8), (9, 8), (8, 9)]);
+}
+
+#[test]
+fn clips_boids_at_the_edge() {
+    let mut renderer = Renderer::new(16, 16, 1);
+    renderer.render(&[boid(0.0, 15.0)]);
+    assert_eq!(lit(&renderer), vec![(0, 14), (0, 15), (1, 15)]);
+}
+
+#[test]
+fn starts_each_frame_from_the_background() {
+    let blue = Rgb([0, 0, 255]);
+    let mut renderer = Renderer::new(16, 16, 0).with_background(blue);
+    renderer.render(&[boid(2.0, 2.0)]);
+    renderer.render(&[boid(12.0, 3.0)]);
+    assert_eq!(*renderer.image().get_pixel(2, 2), blue);
+    assert_eq!(lit(&renderer), vec![(12, 3)]);
+}
+
+#[test]
+fn draws_at_the_positions_given() {
+    let white = Rgb([255, 255, 255]);
+    let mut renderer = Renderer::new(16, 16, 0).with_colour(white);
+    let img = renderer.render_at(&[boid(2.0, 2.0)], &[Vector2::new(10.4, 6.6)]);
+    assert_eq!(*img.get_pixel(10, 7), white);
+    assert_eq!(*img.get_pixel(2, 2), BLACK);
+}
+
+#[test]
+fn buffers_get_what_the_renderer_draws() {
+    let boids = [boid(3.0, 4.0), boid(15.0, 0.0), boid(8.4, 9.6)];
+    let parameters = Parameters {
+        draw_radius: 2,
+        ..Parameters::default()
+    };
+    let mut renderer = Renderer::new(16, 12, 2);
+    renderer.render(&boids);
+    let mut buf = vec![0; 16 * 12 * 3];
+    render_boids_to_buffer(&boids, &mut buf, 16, 12, &parameters);
+    assert_eq!(buf, renderer.image().as_raw().as_slice());
+}
+
+#[test]
+fn images_are_drawn_over() {
+    let grey = Rgb([9, 9, 9]);
+    let mut img = RgbImage::from_pixel(8, 8, grey);
+    let parameters = Parameters {
+        draw_radius: 0,
+        ..Parameters::default()
+    };
+    render_boids(&[boid(1.0, 6.0)], &mut img, &parameters);
+    assert_eq!(*img.get_pixel(1, 6), RED);
+    assert_eq!(img.pixels().filter(|pixel| **pixel == grey).count(), 63);
+}
+
+#[test]
+#[should_panic(expected = "a 4x4 frame needs 48 bytes, not 47")]
+fn short_buffers_are_refused() {
+    let mut buf = vec![0; 47];
+    render_boids_to_buffer(&[], &mut buf, 4, 4, &Parameters::default());
+}
@@ -0,0 +1,59 @@
+use std::io::Cursor;
+
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::colour::Colour;
+use boids::replay::{CsvTrajectoryWriter, ReplayReader};
+
+fn flock(frame: usize) -> Vec<Boid> {
+    (0..5)
+        .map(|id| {
+            Boid::new(
+                id,
+                Vector2::new(id as f32 * 10.1 + frame as f32, 0.3 * frame as f32),
+                Vector2::new(1.0, 0.3),
+                Colour([id as u8 * 50, 10, 200]),
+            )
+        })
+        .collect()
+}
+
+fn csv(frames: &[Vec<Boid>]) -> Vec<u8> {
+    let mut writer = CsvTrajectoryWriter::new(Vec::new()).unwrap();
+    for boids in frames {
+        writer.write_frame(boids).unwrap();
+    }
+    writer.into_inner()
+}
+
+#[test]
+fn csv_round_trips_exactly() {
+    let frames = vec![flock(0), flock(1), flock(2)];
+    let mut reader = ReplayReader::from_reader(Cursor::new(csv(&frames))).unwrap();
+    for (frame, expected) in frames.iter().enumerate() {
+        let boids = reader.next_frame().unwrap().unwrap();
+        assert_eq!(reader.frame(), Some(frame));
+        assert_eq!(&boids, expected);
+    }
+    assert!(reader.next_frame().is_none());
+}
+
+#[test]
+fn frames_can_change_size() {
+    let mut frames = vec![flock(0), flock(1)];
+    frames[1].truncate(2);
+    let mut reader = ReplayReader::from_reader(Cursor::new(csv(&frames))).unwrap();
+    assert_eq!(reader.next_frame().unwrap().unwrap().len(), 5);
+    assert_eq!(reader.next_frame().unwrap().unwrap().len(), 2);
+}
+
+#[test]
+fn bad_rows_are_errors() {
+    assert!(ReplayReader::from_reader(Cursor::new("x,y\n1,2\n")).is_err());
+
+    let csv = "frame,id,x,y,vx,vy,r,g,b\n0,0,1,2,0,0,255,0,0\n0,1,1,two,0,0,255,0,0\n";
+    let mut reader = ReplayReader::from_reader(Cursor::new(csv)).unwrap();
+    let err = reader.next_frame().unwrap().unwrap_err();
+    assert!(err.to_string().contains("line 3"), "{err}");
+}
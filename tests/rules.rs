@@ -0,0 +1,144 @@
+use nalgebra::Vector2;
+
+use boids::boids::{update_boids, update_boids_with_rules, Boid};
+use boids::rules::{
+    self, classic_rules, Alignment, Cohesion, EdgeTurn, GlobalCentering, Neighborhood, Separation,
+    SteeringRule, CLASSIC_RULES,
+};
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::testing::boid;
+use boids::world::World;
+use boids::Parameters;
+
+fn parameters() -> Parameters {
+    Parameters {
+        centering_factor: 0.5,
+        matching_factor: 0.25,
+        avoid_factor: 0.1,
+        global_centering_factor: 0.01,
+        turn_factor: 0.2,
+        margin: 10,
+        ..Parameters::default()
+    }
+}
+
+fn neighborhood() -> Neighborhood {
+    Neighborhood {
+        count: 2,
+        mean_pos: Vector2::new(54.0, 50.0),
+        mean_vel: Vector2::new(1.0, 1.0),
+        close_offset: Vector2::new(-3.0, 2.0),
+        centroid: Vector2::new(150.0, 50.0),
+    }
+}
+
+#[test]
+fn classic_rules_each_do_one_thing() {
+    let world = World::from_pixels(200, 100);
+    let parameters = parameters();
+    let boid = boid(0, (50.0, 50.0), (1.0, 0.0));
+    let nearby = neighborhood();
+    let steer = |rule: &dyn SteeringRule, nearby: &Neighborhood| {
+        rule.steer(&boid, nearby, &world, &parameters)
+    };
+    assert_eq!(steer(&Cohesion, &nearby), Vector2::new(2.0, 0.0));
+    assert_eq!(steer(&Alignment, &nearby), Vector2::new(0.0, 0.25));
+    assert_eq!(steer(&Separation, &nearby), Vector2::new(-0.3, 0.2));
+    assert_eq!(steer(&GlobalCentering, &nearby), Vector2::new(1.0, 0.0));
+    assert_eq!(steer(&EdgeTurn, &nearby), Vector2::zeros());
+
+    // Nothing to cohere or align with when alone
+    let alone = Neighborhood::default();
+    assert_eq!(steer(&Cohesion, &alone), Vector2::zeros());
+    assert_eq!(steer(&Alignment, &alone), Vector2::zeros());
+
+    let by_the_edge = self::boid(0, (195.0, 5.0), (1.0, 0.0));
+    assert_eq!(
+        EdgeTurn.steer(&by_the_edge, &alone, &world, &parameters),
+        Vector2::new(-0.2, 0.2)
+    );
+}
+
+#[test]
+fn rule_changes_add_to_the_velocity() {
+    let world = World::from_pixels(200, 100);
+    let parameters = parameters();
+    let boid = boid(0, (50.0, 50.0), (1.0, 0.0));
+    let nearby = neighborhood();
+    let steered = rules::steer(
+        &[&Cohesion, &Separation],
+        &boid,
+        &nearby,
+        &world,
+        &parameters,
+    );
+    assert!(
+        (steered - Vector2::new(2.7, 0.2)).norm() < 1e-6,
+        "{steered}"
+    );
+    assert_eq!(
+        rules::steer(&[], &boid, &nearby, &world, &parameters),
+        boid.velocity()
+    );
+}
+
+#[test]
+fn classic_rules_are_the_default() {
+    assert_eq!(classic_rules().len(), CLASSIC_RULES.len());
+    let world = World::from_pixels(200, 100);
+    let parameters = Parameters::default();
+    let flock: Vec<Boid> = (0..20)
+        .map(|id| {
+            let i = id as f32;
+            boid(
+                id,
+                (20.0 + i * 7.0, 30.0 + (i * 3.0) % 40.0),
+                (1.0, 0.5 - i * 0.05),
+            )
+        })
+        .collect();
+    let mut classic = flock.clone();
+    update_boids(&mut classic, &world, &parameters);
+    let mut ruled = flock.clone();
+    let boxed = classic_rules();
+    let rules: Vec<&dyn SteeringRule> = boxed.iter().map(Box::as_ref).collect();
+    update_boids_with_rules(&mut ruled, &world, &parameters, &rules);
+    assert_eq!(classic, ruled);
+}
+
+#[test]
+fn simulations_take_custom_rules() {
+    let config = SimulationConfig {
+        seed: 3,
+        ..SimulationConfig::new(World::from_pixels(400, 200), parameters(), 10)
+    };
+    let mut simulation = Simulation::new(config.clone()).unwrap();
+    assert_eq!(simulation.rules().len(), CLASSIC_RULES.len());
+
+    // With no rules at all boids fly straight on, only brought up to speed
+    simulation.set_rules(Vec::new());
+    let headings = |simulation: &Simulation| -> Vec<Vector2<f32>> {
+        simulation
+            .boids()
+            .iter()
+            .map(|boid| boid.velocity().normalize())
+            .collect()
+    };
+    let before = headings(&simulation);
+    simulation.step();
+    for (before, after) in before.iter().zip(headings(&simulation)) {
+        assert!((before - after).norm() < 1e-5, "{before} {after}");
+    }
+
+    // A strong enough pull down wins out over everything else
+    let mut pulled = Simulation::new(config).unwrap();
+    pulled
+        .add_rule(|_: &Boid, _: &Neighborhood, _: &World, _: &Parameters| Vector2::new(0.0, 100.0));
+    assert_eq!(pulled.rules().len(), CLASSIC_RULES.len() + 1);
+    pulled.step();
+    let max_speed = parameters().max_speed;
+    for boid in pulled.boids() {
+        let heading = boid.velocity() / max_speed;
+        assert!(heading.y > 0.99, "{boid}");
+    }
+}
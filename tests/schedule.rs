@@ -0,0 +1,95 @@
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::schedule::{RandomSeedSchedule, SeedEvent};
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::testing::boid;
+use boids::world::World;
+use boids::Parameters;
+
+fn flock() -> Vec<Boid> {
+    (0..10)
+        .map(|id| boid(id, (20.0 + id as f32 * 15.0, 50.0), (1.0, 0.0)))
+        .collect()
+}
+
+fn schedule() -> RandomSeedSchedule {
+    RandomSeedSchedule::new(vec![
+        SeedEvent { frame: 3, seed: 7 },
+        SeedEvent { frame: 5, seed: 8 },
+    ])
+}
+
+#[test]
+fn parses_frame_and_seed() {
+    assert_eq!(
+        "200:7".parse::<SeedEvent>(),
+        Ok(SeedEvent {
+            frame: 200,
+            seed: 7,
+        })
+    );
+    assert!("200".parse::<SeedEvent>().is_err());
+    assert!("200:x".parse::<SeedEvent>().is_err());
+    assert!("-1:7".parse::<SeedEvent>().is_err());
+}
+
+#[test]
+fn nudges_only_at_scheduled_frames() {
+    let schedule = schedule();
+    let mut boids = flock();
+    assert!(!schedule.apply(&mut boids, 4, 0.5));
+    assert_eq!(boids, flock());
+
+    assert!(schedule.apply(&mut boids, 3, 0.5));
+    for (nudged, before) in boids.iter().zip(flock()) {
+        let nudge = nudged.velocity() - before.velocity();
+        assert_ne!(nudge, Vector2::zeros());
+        assert!(nudge.x.abs() < 0.5 && nudge.y.abs() < 0.5, "{nudge}");
+    }
+    // Each boid is nudged its own way
+    assert_ne!(boids[0].velocity(), boids[1].velocity());
+
+    // The same seed nudges the same way, another seed doesn't
+    let mut again = flock();
+    schedule.apply(&mut again, 3, 0.5);
+    assert_eq!(again, boids);
+    let mut other = flock();
+    schedule.apply(&mut other, 5, 0.5);
+    assert_ne!(other, boids);
+}
+
+#[test]
+fn schedule_round_trips_through_toml() {
+    let parameters = Parameters {
+        seed_schedule: schedule(),
+        ..Parameters::default()
+    };
+    let text = toml::to_string(&parameters).unwrap();
+    assert!(text.contains("[[seed_schedule]]"), "{text}");
+    assert_eq!(toml::from_str::<Parameters>(&text).unwrap(), parameters);
+}
+
+#[test]
+fn scheduled_runs_repeat() {
+    let run = |seed_schedule: RandomSeedSchedule| {
+        let config = SimulationConfig {
+            seed: 1,
+            ..SimulationConfig::new(
+                World::from_pixels(200, 100),
+                Parameters {
+                    seed_schedule,
+                    ..Parameters::default()
+                },
+                0,
+            )
+        };
+        let mut simulation = Simulation::from_boids(config, flock(), 0).unwrap();
+        let events: Vec<bool> = (0..6).map(|_| simulation.step().seed_event).collect();
+        (events, simulation.into_boids())
+    };
+    let (events, boids) = run(schedule());
+    assert_eq!(events, [false, false, false, true, false, true]);
+    assert_eq!(run(schedule()).1, boids);
+    assert_ne!(run(RandomSeedSchedule::default()).1, boids);
+}
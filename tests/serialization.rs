@@ -0,0 +1,61 @@
+use nalgebra::Vector2;
+
+use boids::boids::{Boid, BoidId};
+use boids::colour::Colour;
+use boids::testing::spawn_seeded;
+use boids::world::World;
+use boids::Parameters;
+
+#[test]
+fn boid_roundtrip() {
+    let boids = spawn_seeded(50, &World::from_pixels(1920, 1080), 7);
+    let json = serde_json::to_string(&boids).unwrap();
+    let loaded: Vec<Boid> = serde_json::from_str(&json).unwrap();
+    assert_eq!(boids, loaded);
+}
+
+#[test]
+fn colour_serializes_as_array() {
+    let boid = Boid::new(0, Vector2::zeros(), Vector2::zeros(), Colour([0, 255, 128]));
+    let json = serde_json::to_string(&boid).unwrap();
+    assert!(json.contains(r#""colour":[0,255,128]"#), "{json}");
+    let loaded: Boid = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.colour, Colour([0, 255, 128]));
+}
+
+#[test]
+fn large_id_roundtrip() {
+    let boid = Boid::new(
+        BoidId::MAX,
+        Vector2::new(1.0, 2.0),
+        Vector2::new(0.5, 0.5),
+        Colour([1, 2, 3]),
+    );
+    let json = serde_json::to_string(&boid).unwrap();
+    assert!(json.contains(&format!(r#""id":{}"#, BoidId::MAX)), "{json}");
+    let loaded: Boid = serde_json::from_str(&json).unwrap();
+    assert_eq!(boid, loaded);
+}
+
+#[test]
+fn nan_velocity_fails_gracefully() {
+    let boid = Boid::new(
+        1,
+        Vector2::new(1.0, 2.0),
+        Vector2::new(f32::NAN, 0.5),
+        Colour([1, 2, 3]),
+    );
+    // serde_json has no representation for NaN so writes it out as null...
+    let json = serde_json::to_string(&boid).unwrap();
+    assert!(json.contains(r#""vel":[null,0.5]"#), "{json}");
+    // ...which then can't be read back as an f32, but is an error not a panic
+    assert!(serde_json::from_str::<Boid>(&json).is_err());
+}
+
+#[test]
+fn parameters_roundtrip() {
+    let parameters = Parameters::default();
+    let json = serde_json::to_string(&parameters).unwrap();
+    let loaded: Parameters = serde_json::from_str(&json).unwrap();
+    assert_eq!(parameters, loaded);
+}
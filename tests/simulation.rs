@@ -0,0 +1,581 @@
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::{update_boids, Boid, BoidId, StallPolicy};
+use boids::boundary::BoundaryMode;
+use boids::colour::{Colour, ColourMode};
+use boids::quality::QualityWeights;
+use boids::rules::Neighborhood;
+use boids::simulation::{
+    FrameStats, MergeError, Observer, RewindError, Simulation, SimulationConfig, SimulationError,
+    SimulationState,
+};
+use boids::stop::StopWhen;
+use boids::testing::{boid, column};
+use boids::world::World;
+use boids::Parameters;
+
+fn parameters() -> Parameters {
+    Parameters {
+        max_speed: 3.0,
+        min_speed: 0.5,
+        margin: 10,
+        visible_range: 20.0,
+        protected_range: 2.0,
+        avoid_factor: 0.10,
+        matching_factor: 0.05,
+        centering_factor: 0.0005,
+        turn_factor: 0.2,
+        cell_size: 22.0,
+        ..Parameters::default()
+    }
+}
+
+fn world() -> World {
+    World::from_pixels(200, 100)
+}
+
+fn ids(simulation: &SimulationState) -> Vec<BoidId> {
+    simulation.boids.iter().map(Boid::id).collect()
+}
+
+// Two columns of boids that will meet, keeping the last `rewind_depth`
+// frames
+fn rewindable(rewind_depth: usize) -> SimulationState {
+    let mut boids = column(8, 60.0);
+    boids.extend((0..8).map(|id| boid(id + 8, (80.0, id as f32 * 5.0), (-1.0, 0.0))));
+    let parameters = Parameters {
+        rewind_depth,
+        ..parameters()
+    };
+    SimulationState::new(boids, parameters, world())
+}
+
+#[test]
+fn rewinding_restores_earlier_frames() {
+    let mut state = rewindable(5);
+    let mut frames = vec![state.boids.clone()];
+    for _ in 0..8 {
+        state.step();
+        frames.push(state.boids.clone());
+    }
+    assert_eq!(state.frame(), 8);
+    state.rewind_to(6).unwrap();
+    assert_eq!(state.frame(), 6);
+    assert_eq!(state.boids, frames[6]);
+    assert!(state.grid.verify_boid_placement(&state.boids));
+
+    // The same frames come round again
+    state.step();
+    assert_eq!(state.boids, frames[7]);
+    state.step_backward();
+    assert_eq!(state.frame(), 6);
+    assert_eq!(state.boids, frames[6]);
+    state.rewind_to(6).unwrap();
+    assert_eq!(state.boids, frames[6]);
+}
+
+#[test]
+fn rewinding_stops_at_the_history() {
+    let mut state = rewindable(5);
+    for _ in 0..8 {
+        state.step();
+    }
+    assert_eq!(
+        state.rewind_to(9),
+        Err(RewindError::Ahead {
+            frame: 9,
+            current: 8
+        })
+    );
+    assert_eq!(
+        state.rewind_to(2),
+        Err(RewindError::Forgotten {
+            frame: 2,
+            earliest: 3
+        })
+    );
+    state.rewind_to(3).unwrap();
+    assert_eq!(
+        state.rewind_to(2),
+        Err(RewindError::Forgotten {
+            frame: 2,
+            earliest: 3
+        })
+    );
+
+    let mut forgetful = rewindable(0);
+    forgetful.step();
+    assert!(matches!(
+        forgetful.rewind_to(0),
+        Err(RewindError::Forgotten { earliest: 1, .. })
+    ));
+}
+
+#[test]
+fn stepping_backward_without_history_reverses_motion() {
+    let boid = boid(0, (100.0, 50.0), (1.0, 0.5));
+    let mut state = SimulationState::new(vec![boid.clone()], parameters(), world());
+    state.step();
+    assert_eq!(state.boids[0].pos, Vector2::new(101.0, 50.5));
+    state.step_backward();
+    assert_eq!(state.frame(), 0);
+    assert_eq!(state.boids[0].pos, boid.pos);
+    assert_eq!(state.boids[0].velocity(), boid.velocity());
+}
+
+#[test]
+fn merged_ids_are_unique() {
+    let a = SimulationState::new(column(10, 20.0), parameters(), world());
+    let b = SimulationState::new(column(15, 150.0), parameters(), world());
+    let merged = a.merge(b).unwrap();
+    let ids = ids(&merged);
+    assert_eq!(ids.len(), 25);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 25);
+    assert_eq!(ids[10], 10);
+
+    // The grid covers the whole merged flock
+    merged.grid.assert_valid(merged.boids.len());
+    assert!(merged.grid.verify_boid_placement(&merged.boids));
+}
+
+#[test]
+fn merge_offsets_past_sparse_ids() {
+    let mut boids = column(3, 20.0);
+    boids.swap_remove(0);
+    let a = SimulationState::new(boids, parameters(), world());
+    let b = SimulationState::new(column(3, 150.0), parameters(), world());
+    let merged = a.merge(b).unwrap();
+    assert_eq!(ids(&merged), [2, 1, 3, 4, 5]);
+}
+
+#[test]
+fn merge_needs_matching_worlds() {
+    let a = SimulationState::new(column(3, 20.0), parameters(), world());
+    let b = SimulationState::new(column(3, 20.0), parameters(), World::from_pixels(400, 100));
+    assert_eq!(
+        a.merge(b).unwrap_err(),
+        MergeError::DimensionMismatch {
+            ours: (200.0, 100.0),
+            theirs: (400.0, 100.0),
+        }
+    );
+}
+
+#[test]
+fn merge_needs_compatible_parameters() {
+    let a = SimulationState::new(column(3, 20.0), parameters(), world());
+    let coarse = Parameters {
+        cell_size: 40.0,
+        ..parameters()
+    };
+    let b = SimulationState::new(column(3, 20.0), coarse, world());
+    let err = a.merge(b).unwrap_err();
+    assert_eq!(
+        err,
+        MergeError::IncompatibleParameters {
+            cell_sizes: (22.0, 40.0),
+            visible_ranges: (20.0, 20.0),
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "can't merge flocks on a grid of 40 cells seeing 20 into one on a grid of 22 cells seeing 20"
+    );
+}
+
+#[test]
+fn merged_flock_keeps_stepping() {
+    let a = SimulationState::new(column(10, 20.0), parameters(), world());
+    let b = SimulationState::new(column(10, 30.0), parameters(), world());
+    let mut merged = a.merge(b).unwrap();
+    for _ in 0..10 {
+        merged.step();
+    }
+    merged.grid.assert_valid(20);
+}
+
+#[test]
+fn downsample_keeps_one_in_factor() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let mut full = SimulationState::new(column(20, 20.0), parameters(), world());
+    full.colour_mode = ColourMode::Heading;
+    let mut small = full.downsample(3, &mut rng);
+    let kept = ids(&small);
+    assert_eq!(kept.len(), 6);
+    // Still in the original order, with the original ids
+    assert!(kept.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(small.colour_mode, ColourMode::Heading);
+    small.grid.assert_valid(6);
+    assert!(small.grid.verify_boid_placement(&small.boids));
+
+    let again = full.downsample(3, &mut StdRng::seed_from_u64(4));
+    assert_eq!(ids(&again), kept);
+    for _ in 0..10 {
+        small.step();
+    }
+    small.grid.assert_valid(6);
+    assert_eq!(full.boids.len(), 20);
+}
+
+#[test]
+fn upsample_copies_each_boid_nearby() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let small = SimulationState::new(column(10, 20.0), parameters(), world());
+    let mut big = small.upsample(3, &mut rng);
+    let ids = ids(&big);
+    assert_eq!(ids.len(), 30);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 30);
+    // Originals are kept where they were, with their copies following them
+    assert_eq!(big.boids[0], small.boids[0]);
+    assert_eq!(big.boids[3], small.boids[1]);
+    let range = parameters().protected_range;
+    for (at, boid) in big.boids.iter().enumerate() {
+        let offset = boid.pos - small.boids[at / 3].pos;
+        assert!(offset.x.abs() <= range && offset.y.abs() <= range);
+    }
+    big.grid.assert_valid(30);
+    assert!(big.grid.verify_boid_placement(&big.boids));
+    for _ in 0..10 {
+        big.step();
+    }
+    big.grid.assert_valid(30);
+}
+
+#[test]
+fn recolor_replaces_saved_colours() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let mut loaded = SimulationState::new(column(10, 20.0), parameters(), world());
+    loaded.set_colour_mode(ColourMode::Speed, &mut rng);
+    let mut fresh = SimulationState::new(column(10, 20.0), parameters(), world());
+    fresh.colour_mode = ColourMode::Speed;
+    for boid in &mut fresh.boids {
+        boid.colour = ColourMode::Speed.colour(boid, 200, 3.0, &mut rng);
+    }
+    assert_eq!(loaded.boids, fresh.boids);
+    assert_ne!(loaded.boids[0].colour, Colour([255, 255, 255]));
+
+    // The same mode again leaves colours alone
+    loaded.boids[0].colour = Colour([1, 2, 3]);
+    loaded.set_colour_mode(ColourMode::Speed, &mut rng);
+    assert_eq!(loaded.boids[0].colour, Colour([1, 2, 3]));
+    loaded.recolor_boids(&mut rng);
+    assert_eq!(loaded.boids[0].colour, fresh.boids[0].colour);
+}
+
+#[test]
+fn dynamic_colours_follow_each_step() {
+    let mut simulation = SimulationState::new(column(10, 20.0), parameters(), world());
+    simulation.colour_mode = ColourMode::Heading;
+    simulation.step();
+    let expected: Vec<_> = simulation
+        .boids
+        .iter()
+        .map(|boid| ColourMode::Heading.colour(boid, 200, 3.0, &mut rand::rng()))
+        .collect();
+    let colours: Vec<_> = simulation.boids.iter().map(|boid| boid.colour).collect();
+    assert_eq!(colours, expected);
+}
+
+fn config(seed: u64) -> SimulationConfig {
+    SimulationConfig {
+        seed,
+        ..SimulationConfig::new(world(), parameters(), 30)
+    }
+}
+
+#[test]
+fn new_parameters_apply_from_the_next_step() {
+    let mut simulation = Simulation::new(config(2)).unwrap();
+    simulation.run_for(3);
+    let slower = Parameters {
+        max_speed: 1.0,
+        min_speed: 0.2,
+        ..parameters()
+    };
+    simulation.set_parameters(slower.clone());
+    assert_eq!(*simulation.parameters(), slower);
+    simulation.step();
+    assert!(simulation
+        .boids()
+        .iter()
+        .all(|boid| boid.speed() <= 1.0 + 1e-5));
+}
+
+#[test]
+fn simulations_repeat_for_a_seed() {
+    let run = |seed| {
+        let mut simulation = Simulation::new(config(seed)).unwrap();
+        simulation.run_for(5);
+        simulation.into_boids()
+    };
+    assert_eq!(run(7).len(), 30);
+    assert_eq!(run(7), run(7));
+    assert_ne!(run(7), run(8));
+}
+
+thread_local! {
+    static DRAWN: Cell<usize> = const { Cell::new(0) };
+}
+
+// A `SmallRng` counting how many numbers are drawn from it on this thread
+struct CountingRng(SmallRng);
+
+impl CountingRng {
+    fn drawn() -> usize {
+        DRAWN.get()
+    }
+}
+
+impl RngCore for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        DRAWN.set(DRAWN.get() + 1);
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        DRAWN.set(DRAWN.get() + 1);
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        DRAWN.set(DRAWN.get() + 1);
+        self.0.fill_bytes(dst);
+    }
+}
+
+impl SeedableRng for CountingRng {
+    type Seed = <SmallRng as SeedableRng>::Seed;
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        CountingRng(SmallRng::from_seed(seed))
+    }
+}
+
+#[test]
+fn simulations_draw_from_the_rng_they_are_given() {
+    let mut simulation = Simulation::<CountingRng>::with_rng(config(7)).unwrap();
+    let spawned = CountingRng::drawn();
+    assert!(spawned > 0);
+    // Steering a moving flock draws nothing at all
+    simulation.run_for(5);
+    assert_eq!(CountingRng::drawn(), spawned);
+    let mut again = Simulation::<CountingRng>::with_rng(config(7)).unwrap();
+    again.run_for(5);
+    assert_eq!(again.into_boids(), simulation.into_boids());
+
+    let turbulent = SimulationConfig {
+        parameters: Parameters {
+            perturbation_fraction: 0.5,
+            perturbation_strength: 1.0,
+            ..parameters()
+        },
+        ..config(7)
+    };
+    let mut simulation = Simulation::<CountingRng>::with_rng(turbulent).unwrap();
+    let spawned = CountingRng::drawn();
+    simulation.step();
+    assert!(CountingRng::drawn() > spawned);
+}
+
+#[test]
+fn stalled_boids_stop_the_run() {
+    let config = SimulationConfig {
+        parameters: Parameters {
+            stall_policy: StallPolicy::Error,
+            ..parameters()
+        },
+        ..config(3)
+    };
+    let mut simulation = Simulation::new(config).unwrap();
+    simulation.set_rules(vec![Box::new(
+        |boid: &Boid, _: &Neighborhood, _: &World, _: &Parameters| -boid.velocity(),
+    )]);
+    let stats = simulation.run_for(5).unwrap();
+    assert_eq!(stats.frame, 0);
+    assert_eq!(stats.stalled, 30);
+    assert_eq!(
+        simulation.stopped(),
+        Some("30 boids stopped dead, which stall_policy error doesn't allow")
+    );
+}
+
+#[test]
+fn step_counts_frames() {
+    let mut simulation = Simulation::new(config(1)).unwrap();
+    assert_eq!(simulation.frame(), 0);
+    assert_eq!(simulation.world(), &world());
+    let stats = simulation.step();
+    assert_eq!(stats.frame, 0);
+    assert_eq!(stats.population, 30);
+    assert_eq!(stats.re_steered, 30);
+    assert!((0.0..=1.0).contains(&stats.polarization));
+    assert!(stats.mean_speed > 0.0);
+    assert!(stats.speed_stddev >= 0.0);
+    let (min, max) = stats.bounds.unwrap();
+    assert!(min <= stats.centroid && stats.centroid <= max);
+    assert!(stats.mean_neighbors <= stats.max_neighbors as f32);
+    assert_eq!(simulation.frame(), 1);
+    assert_eq!(simulation.run_for(0), None);
+    assert_eq!(simulation.run_for(3).unwrap().frame, 3);
+    assert_eq!(simulation.frame(), 4);
+}
+
+#[test]
+fn from_boids_carries_on_the_column() {
+    let mut simulation = Simulation::from_boids(config(1), column(10, 20.0), 50).unwrap();
+    assert_eq!(simulation.boids(), column(10, 20.0));
+    assert_eq!(simulation.step().frame, 50);
+
+    let mut expected = column(10, 20.0);
+    update_boids(&mut expected, &world(), &parameters());
+    assert_eq!(simulation.boids(), expected);
+}
+
+#[test]
+fn current_stats_leave_the_flock_alone() {
+    let simulation = Simulation::from_boids(config(1), column(10, 20.0), 50).unwrap();
+    let stats = simulation.current_stats();
+    assert_eq!(simulation.boids(), column(10, 20.0));
+    assert_eq!(simulation.frame(), 50);
+    assert_eq!(stats.frame, 50);
+    assert_eq!(stats.population, 10);
+    assert_eq!((stats.re_steered, stats.max_neighbors), (0, 0));
+    let centroid = column(10, 20.0)
+        .iter()
+        .map(|boid| boid.pos)
+        .sum::<Vector2<f32>>()
+        / 10.0;
+    assert!((stats.centroid - centroid).norm() < 1e-4);
+}
+
+#[test]
+fn grid_follows_the_column() {
+    let mut simulation = Simulation::new(config(1)).unwrap();
+    for _ in 0..3 {
+        simulation.grid().assert_valid(30);
+        assert!(simulation.grid().verify_boid_placement(simulation.boids()));
+        simulation.step();
+    }
+}
+
+#[test]
+fn neighbor_counts_follow_each_step() {
+    // Two boids in sight of each other and one on its own
+    let mut boids = column(2, 50.0);
+    boids.push(column(3, 150.0).remove(2));
+    for update_threshold in [0.0, 5.0] {
+        let config = SimulationConfig {
+            parameters: Parameters {
+                update_threshold,
+                ..parameters()
+            },
+            ..config(1)
+        };
+        let mut simulation = Simulation::from_boids(config, boids.clone(), 0).unwrap();
+        assert!(simulation.neighbor_counts().is_empty());
+        simulation.step();
+        assert_eq!(simulation.neighbor_counts(), [1, 1, 0]);
+        simulation.step();
+        assert_eq!(simulation.neighbor_counts(), [1, 1, 0]);
+    }
+}
+
+#[test]
+fn run_with_stops_when_the_observer_breaks() {
+    let mut simulation = Simulation::new(config(2)).unwrap();
+    let mut seen = Vec::new();
+    let last = simulation.run_with(10, |frame: usize, boids: &[Boid], stats: &FrameStats| {
+        assert_eq!(boids.len(), stats.population);
+        seen.push(frame);
+        if frame == 3 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(seen, [0, 1, 2, 3]);
+    assert_eq!(last.map(|stats| stats.frame), Some(3));
+    assert_eq!(simulation.frame(), 4);
+    assert_eq!(simulation.stopped(), Some("stopped by an observer"));
+    // Stopping only lasts until the next step
+    simulation.step();
+    assert_eq!(simulation.stopped(), None);
+}
+
+#[test]
+fn added_observers_see_every_step() {
+    let mut simulation = Simulation::new(config(2)).unwrap();
+    simulation.add_observer(StopWhen::new(vec!["population<100".parse().unwrap()]));
+    assert!(simulation.run_for(10).is_some());
+    assert_eq!(simulation.frame(), 1);
+    assert_eq!(simulation.stopped(), Some("population<100 for 1 (now 30)"));
+}
+
+#[test]
+fn runs_can_stop_on_the_quality_score() {
+    let mut simulation = Simulation::new(config(2)).unwrap();
+    simulation.set_quality_weights(QualityWeights {
+        polarization: 0.0,
+        connectedness: 0.0,
+        smoothness: 1.0,
+        max_jerk: 1.0,
+    });
+    let condition = "quality-score>=0 for 2".parse().unwrap();
+    simulation.add_observer(StopWhen::new(vec![condition]));
+    let last = simulation.run_for(10).unwrap();
+    assert_eq!(simulation.frame(), 2);
+    // No jerk until there are three frames of velocities
+    assert_eq!(last.quality_score, 1.0);
+    let stats = simulation.step();
+    assert!((0.0..1.0).contains(&stats.quality_score), "{stats:?}");
+}
+
+// Takes the last boid away after every step
+struct Despawner;
+
+impl Observer for Despawner {
+    fn on_step(&mut self, _: usize, _: &[Boid], _: &FrameStats) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+
+    fn on_after_step(&mut self, boids: &mut Vec<Boid>) {
+        boids.pop();
+    }
+}
+
+#[test]
+fn observers_can_change_the_flock_after_a_step() {
+    let mut simulation = Simulation::new(config(2)).unwrap();
+    simulation.add_observer(Despawner);
+    let stats = simulation.run_for(5).unwrap();
+    // Each step sees the flock as the last one left it
+    assert_eq!(stats.population, 26);
+    assert_eq!(simulation.boids().len(), 25);
+    simulation.grid().assert_valid(25);
+    assert!(simulation.grid().verify_boid_placement(simulation.boids()));
+}
+
+#[test]
+fn bad_configs_are_refused() {
+    let empty = SimulationConfig::new(World::from_pixels(0, 100), parameters(), 30);
+    assert!(matches!(
+        Simulation::new(empty),
+        Err(SimulationError::EmptyWorld {
+            width: 0.0,
+            height: 100.0
+        })
+    ));
+    let mut missing = config(1);
+    missing.parameters.boundary = BoundaryMode::Sdf {
+        path: "does_not_exist.png".into(),
+    };
+    assert!(matches!(
+        Simulation::new(missing),
+        Err(SimulationError::Boundary(_))
+    ));
+}
@@ -0,0 +1,31 @@
+use nalgebra::Vector2;
+
+use boids::smoothing::TemporalSmoothing;
+use boids::testing::boid;
+
+#[test]
+fn starts_at_boid_positions() {
+    let mut smoothing = TemporalSmoothing::new();
+    smoothing.update(&[boid(0, (4.0, 8.0), (0.0, 0.0))], 0.5);
+    assert_eq!(smoothing.positions(), [Vector2::new(4.0, 8.0)]);
+}
+
+#[test]
+fn approaches_boid_exponentially() {
+    let mut smoothing = TemporalSmoothing::new();
+    smoothing.update(&[boid(0, (0.0, 0.0), (0.0, 0.0))], 0.5);
+    smoothing.update(&[boid(0, (8.0, 0.0), (0.0, 0.0))], 0.5);
+    assert_eq!(smoothing.positions(), [Vector2::new(4.0, 0.0)]);
+    smoothing.update(&[boid(0, (8.0, 0.0), (0.0, 0.0))], 0.5);
+    assert_eq!(smoothing.positions(), [Vector2::new(6.0, 0.0)]);
+}
+
+#[test]
+fn zero_and_one_snap() {
+    for rate in [0.0, 1.0] {
+        let mut smoothing = TemporalSmoothing::new();
+        smoothing.update(&[boid(0, (0.0, 0.0), (0.0, 0.0))], rate);
+        smoothing.update(&[boid(0, (8.0, 2.0), (0.0, 0.0))], rate);
+        assert_eq!(smoothing.positions(), [Vector2::new(8.0, 2.0)]);
+    }
+}
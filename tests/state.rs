@@ -0,0 +1,339 @@
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::{Boid, BoidId};
+use boids::colour::Colour;
+use boids::simulation::SimulationState;
+use boids::state::{
+    self, utc_timestamp, Encoding, Format, Metadata, SaveFile, StateError, SAVE_FILE_VERSION,
+};
+use boids::world::World;
+use boids::Parameters;
+
+fn flock() -> Vec<Boid> {
+    (0..10)
+        .map(|id| {
+            Boid::new(
+                id,
+                Vector2::new(id as f32 * 10.0, 50.0),
+                Vector2::new(1.0, -0.5),
+                Colour([id as u8, 0, 255]),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn roundtrip_every_encoding() {
+    let save = SaveFile::new(200, 100, flock());
+    for format in [Format::Json, Format::Bincode, Format::Ron] {
+        for compressed in [false, true] {
+            let encoding = Encoding { format, compressed };
+            let bytes = state::to_bytes(&save, encoding).unwrap();
+            // Only bincode isn't recognisable from its contents
+            let loaded = state::from_bytes(&bytes, Format::Bincode).unwrap();
+            assert_eq!(save, loaded, "{encoding:?}");
+        }
+    }
+}
+
+#[test]
+fn encoding_from_path() {
+    let cases = [
+        ("state.json", Format::Json, false),
+        ("state.json.zst", Format::Json, true),
+        ("state.bin", Format::Bincode, false),
+        ("state.bin.zst", Format::Bincode, true),
+        ("state.RON", Format::Ron, false),
+        ("state", Format::Json, false),
+    ];
+    for (path, format, compressed) in cases {
+        assert_eq!(
+            Encoding::from_path(path.as_ref()),
+            Encoding { format, compressed },
+            "{path}"
+        );
+    }
+}
+
+#[test]
+fn loads_bare_json_array() {
+    let bytes = serde_json::to_vec(&flock()).unwrap();
+    let loaded = state::from_bytes(&bytes, Format::Json).unwrap();
+    assert_eq!(loaded.boids, flock());
+    assert_eq!(loaded.world_size, None);
+}
+
+#[test]
+fn refuses_newer_versions() {
+    let mut save = SaveFile::new(200, 100, flock());
+    save.version = SAVE_FILE_VERSION + 1;
+    for format in [Format::Json, Format::Bincode, Format::Ron] {
+        let encoding = Encoding {
+            format,
+            compressed: false,
+        };
+        let bytes = state::to_bytes(&save, encoding).unwrap();
+        let err = state::from_bytes(&bytes, format).unwrap_err();
+        assert!(
+            matches!(err, StateError::UnsupportedVersion(v) if v == SAVE_FILE_VERSION + 1),
+            "{format:?}: {err}"
+        );
+    }
+}
+
+fn velocity(boid: &Boid) -> [f32; 2] {
+    boid.velocity().into()
+}
+
+#[test]
+fn scale_to_keeps_relative_positions() {
+    let mut save = SaveFile::new(200, 100, flock());
+    save.scale_to(400, 50, false).unwrap();
+    assert_eq!(save.world_size, Some((400, 50)));
+    assert_eq!(save.boids[3].pos, Vector2::new(60.0, 25.0));
+    assert_eq!(velocity(&save.boids[3]), [1.0, -0.5]);
+    save.scale_to(800, 100, true).unwrap();
+    assert_eq!(save.boids[3].pos, Vector2::new(120.0, 50.0));
+    assert_eq!(velocity(&save.boids[3]), [2.0, -1.0]);
+
+    let mut legacy = SaveFile {
+        world_size: None,
+        ..save
+    };
+    assert!(matches!(
+        legacy.scale_to(10, 10, false),
+        Err(StateError::MissingWorldSize)
+    ));
+}
+
+#[test]
+fn reseed_ids_renumbers_from_zero() {
+    let mut boids = flock();
+    boids.reverse();
+    let mut save = SaveFile::new(200, 100, boids);
+    save.reseed_ids();
+    let json = serde_json::to_string(&save.boids[0]).unwrap();
+    assert!(json.starts_with(r#"{"id":0,"pos":[90.0,50.0]"#), "{json}");
+}
+
+#[test]
+fn merge_offsets_and_renumbers() {
+    let a = SaveFile::new(200, 100, flock());
+    let b = SaveFile::new(200, 100, flock());
+    let (merged, mapping) = state::merge(
+        vec![(a, Vector2::zeros()), (b, Vector2::new(200.0, 0.0))],
+        400,
+        100,
+        false,
+    )
+    .unwrap();
+    assert_eq!(merged.world_size, Some((400, 100)));
+    assert_eq!(merged.boids.len(), 20);
+    assert_eq!(merged.boids[13].pos, Vector2::new(230.0, 50.0));
+    for (new_id, id) in mapping.iter().enumerate() {
+        assert_eq!(id.new_id, new_id as BoidId);
+        assert_eq!(id.source, new_id / 10);
+        assert_eq!(id.old_id, (new_id % 10) as BoidId);
+    }
+}
+
+#[test]
+fn merge_out_of_bounds() {
+    let sources = || vec![(SaveFile::new(200, 100, flock()), Vector2::new(150.0, 0.0))];
+    assert!(matches!(
+        state::merge(sources(), 200, 100, false),
+        Err(StateError::OutOfBounds(5))
+    ));
+    let (merged, _) = state::merge(sources(), 200, 100, true).unwrap();
+    assert!(merged.boids.iter().all(|boid| boid.pos.x <= 199.0));
+}
+
+fn ids(save: &SaveFile) -> Vec<BoidId> {
+    save.boids.iter().map(Boid::id).collect()
+}
+
+#[test]
+fn sample_keeps_count_and_order() {
+    let mut save = SaveFile::new(200, 100, flock());
+    save.sample(4, &mut StdRng::seed_from_u64(1));
+    assert_eq!(save.boids.len(), 4);
+    let ids = ids(&save);
+    assert!(ids.is_sorted(), "{ids:?}");
+
+    let mut everything = SaveFile::new(200, 100, flock());
+    everything.sample(100, &mut StdRng::seed_from_u64(1));
+    assert_eq!(everything.boids, flock());
+}
+
+#[test]
+fn sample_is_deterministic_given_seed() {
+    let sampled = |seed| {
+        let mut save = SaveFile::new(200, 100, flock());
+        save.sample(5, &mut StdRng::seed_from_u64(seed));
+        ids(&save)
+    };
+    assert_eq!(sampled(7), sampled(7));
+    assert_ne!(sampled(7), sampled(8));
+}
+
+#[test]
+fn retain_region_filters_by_position() {
+    let mut save = SaveFile::new(200, 100, flock());
+    // Boids are at x = 0, 10, 20...; the right and bottom edges are exclusive
+    save.retain_region(15.0, 0.0, 25.0, 60.0);
+    assert_eq!(ids(&save), [2, 3]);
+    save.retain_region(0.0, 0.0, 200.0, 50.0);
+    assert!(save.boids.is_empty());
+}
+
+#[test]
+fn metadata_roundtrips() {
+    let mut save = SaveFile::new(200, 100, flock());
+    save.metadata.seed = Some(42);
+    save.metadata.spawn = Some(String::from("uniform, 10 boids"));
+    save.metadata.note = Some(String::from("the good one"));
+    save.metadata.frame = Some(250);
+    save.metadata.parameters = Some(Parameters {
+        max_speed: 4.5,
+        max_neighbors_for_early_exit: Some(6),
+        ..Parameters::dense()
+    });
+    assert_eq!(
+        save.metadata.crate_version.as_deref(),
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+    assert!(save.metadata.created.is_some());
+    for format in [Format::Json, Format::Bincode, Format::Ron] {
+        let encoding = Encoding {
+            format,
+            compressed: false,
+        };
+        let bytes = state::to_bytes(&save, encoding).unwrap();
+        assert_eq!(state::from_bytes(&bytes, format).unwrap(), save);
+    }
+}
+
+#[test]
+fn unknown_parameters_follow_strict_loading() {
+    let json = serde_json::json!({
+        "version": SAVE_FILE_VERSION,
+        "world_size": [200, 100],
+        "metadata": { "parameters": { "max_speed": 4.0, "gust_factor": 2.0 } },
+        "boids": flock(),
+    });
+    let loaded = state::from_bytes_tolerant(json.to_string().as_bytes(), Format::Json).unwrap();
+    assert_eq!(loaded.unknown_fields, ["metadata.parameters.gust_factor"]);
+    assert_eq!(
+        loaded.state.metadata.parameters,
+        Some(Parameters {
+            max_speed: 4.0,
+            ..Parameters::default()
+        })
+    );
+    assert!(matches!(
+        loaded.strict(),
+        Err(StateError::UnknownFields(fields)) if fields == ["metadata.parameters.gust_factor"]
+    ));
+}
+
+#[test]
+fn timestamps_are_utc() {
+    assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+    assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    assert_eq!(utc_timestamp(1_790_000_000), "2026-09-21T14:13:20Z");
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn rkyv_roundtrip() {
+    let save = SaveFile::new(200, 100, flock());
+    for compressed in [false, true] {
+        let encoding = Encoding {
+            format: Format::Rkyv,
+            compressed,
+        };
+        let bytes = state::to_bytes(&save, encoding).unwrap();
+        assert_eq!(state::from_bytes(&bytes, Format::Rkyv).unwrap(), save);
+    }
+
+    // Uncompressed archives are loaded through a memory map
+    let path = std::env::temp_dir().join(format!("boids_rkyv_{}.rkyv", std::process::id()));
+    state::save(&path, &save).unwrap();
+    let loaded = state::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), save);
+}
+
+fn fixture(name: &str) -> state::Loaded {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    state::load_tolerant(&path).unwrap()
+}
+
+fn run(save: SaveFile) -> SimulationState {
+    let parameters = Parameters {
+        visible_range: 40.0,
+        cell_size: 40.0,
+        ..Parameters::default()
+    };
+    let mut simulation =
+        SimulationState::new(save.boids, parameters, World::from_pixels(1920, 1080));
+    for _ in 0..10 {
+        simulation.step();
+    }
+    simulation
+}
+
+#[test]
+fn pre_expansion_and_current_states_run_the_same() {
+    let old = fixture("pre_expansion.json");
+    let current = fixture("current.json");
+    assert!(old.unknown_fields.is_empty());
+    assert!(current.unknown_fields.is_empty());
+    assert_eq!(old.state.metadata, Metadata::default());
+
+    // Colours are the only thing the simulation doesn't touch
+    let positions = |simulation: SimulationState| {
+        simulation
+            .boids
+            .iter()
+            .map(|boid| (boid.pos, boid.velocity()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        positions(run(old.state)),
+        positions(run(current.state.clone()))
+    );
+    assert_eq!(current.state.metadata.seed, Some(1));
+}
+
+#[test]
+fn missing_boid_fields_fall_back() {
+    let json = r#"[{"id":4,"pos":[10.0,20.0]}]"#;
+    let loaded = state::from_bytes(json.as_bytes(), Format::Json).unwrap();
+    let boid = serde_json::to_value(&loaded.boids[0]).unwrap();
+    assert_eq!(boid["vel"], serde_json::json!([0.0, 0.0]));
+    assert_eq!(boid["current_speed"], serde_json::json!(0.0));
+    assert_eq!(loaded.boids[0].colour, Colour([255, 255, 255]));
+}
+
+#[test]
+fn unknown_fields_are_reported() {
+    let newer = fixture("newer.json");
+    assert_eq!(
+        newer.unknown_fields,
+        ["boids.*.species", "boids.*.energy", "generation"]
+    );
+    assert_eq!(newer.state.boids, fixture("current.json").state.boids);
+    assert!(matches!(
+        newer.strict(),
+        Err(StateError::UnknownFields(fields)) if fields.len() == 3
+    ));
+    assert!(fixture("current.json").strict().is_ok());
+
+    let ron = "(version: 2, world_size: None, boids: [], mood: \"calm\")";
+    let loaded = state::from_bytes_tolerant(ron.as_bytes(), Format::Ron).unwrap();
+    assert_eq!(loaded.unknown_fields, ["mood"]);
+}
@@ -0,0 +1,93 @@
+use boids::boids::{angular_momentum, mean_speed};
+use boids::stop::{Comparison, Metric, StopCondition, StopWhen};
+use boids::testing::boid;
+
+#[test]
+fn parses_conditions() {
+    assert_eq!(
+        "polarization>0.98 for 200".parse::<StopCondition>(),
+        Ok(StopCondition {
+            metric: Metric::Polarization,
+            comparison: Comparison::Greater,
+            threshold: 0.98,
+            sustain: 200,
+        })
+    );
+    let condition: StopCondition = " mean-speed <= 1.5".parse().unwrap();
+    assert_eq!(condition.comparison, Comparison::LessOrEqual);
+    assert_eq!(condition.sustain, 1);
+    for text in [
+        "angular-momentum>=0.5 for 10",
+        "population<3 for 1",
+        "quality-score>0.8 for 50",
+    ] {
+        let condition: StopCondition = text.parse().unwrap();
+        assert_eq!(condition.to_string(), text);
+        assert_eq!(condition.to_string().parse(), Ok(condition));
+    }
+}
+
+#[test]
+fn rejects_malformed_conditions() {
+    for text in [
+        "speed>1",
+        "polarization",
+        "polarization=0.5",
+        "polarization>high",
+        "polarization>0.5 for",
+        "polarization>0.5 for 0",
+        "polarization>0.5 for -3",
+        "polarization>inf",
+    ] {
+        assert!(text.parse::<StopCondition>().is_err(), "{text}");
+    }
+}
+
+#[test]
+fn conditions_have_to_hold_in_a_row() {
+    let condition: StopCondition = "population>2 for 3".parse().unwrap();
+    let mut stop_when = StopWhen::new(vec![condition]);
+    let small = vec![boid(0, (0.0, 0.0), (1.0, 0.0)); 2];
+    let large = vec![boid(0, (0.0, 0.0), (1.0, 0.0)); 3];
+    assert_eq!(stop_when.check(&large), None);
+    assert_eq!(stop_when.check(&large), None);
+    // Starts counting again
+    assert_eq!(stop_when.check(&small), None);
+    assert_eq!(stop_when.check(&large), None);
+    assert_eq!(stop_when.check(&large), None);
+    assert_eq!(
+        stop_when.check(&large).as_deref(),
+        Some("population>2 for 3 (now 3)")
+    );
+}
+
+#[test]
+fn any_condition_stops_the_run() {
+    let mut stop_when = StopWhen::new(vec![
+        "population>100".parse().unwrap(),
+        "mean-speed<1".parse().unwrap(),
+    ]);
+    let slow = [boid(0, (0.0, 0.0), (0.5, 0.0))];
+    assert!(stop_when.check(&slow).unwrap().starts_with("mean-speed<1"));
+    assert_eq!(StopWhen::default().check(&slow), None);
+}
+
+#[test]
+fn flock_metrics() {
+    // Circling the origin anticlockwise
+    let milling = [
+        boid(0, (10.0, 0.0), (0.0, 2.0)),
+        boid(1, (0.0, 10.0), (-2.0, 0.0)),
+        boid(2, (-10.0, 0.0), (0.0, -2.0)),
+        boid(3, (0.0, -10.0), (2.0, 0.0)),
+    ];
+    assert!((angular_momentum(&milling) - 1.0).abs() < 1e-6);
+    assert!((mean_speed(&milling) - 2.0).abs() < 1e-6);
+    let straight = [
+        boid(0, (10.0, 0.0), (1.0, 0.0)),
+        boid(1, (-10.0, 0.0), (1.0, 0.0)),
+    ];
+    assert!(angular_momentum(&straight).abs() < 1e-6);
+    assert_eq!(angular_momentum(&[]), 0.0);
+    assert_eq!(mean_speed(&[]), 0.0);
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use nalgebra::Vector2;
+
+use boids::boids::polarization;
+use boids::summary::{summarize, RunRecorder, Stage};
+use boids::testing::boid;
+
+#[test]
+fn polarization_measures_alignment() {
+    let aligned = [
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (10.0, 10.0), (3.0, 0.0)),
+    ];
+    assert!((polarization(&aligned) - 1.0).abs() < 1e-6);
+    let opposed = [
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (10.0, 10.0), (-2.0, 0.0)),
+    ];
+    assert!(polarization(&opposed).abs() < 1e-6);
+    let stopped = [
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (10.0, 10.0), (0.0, 0.0)),
+    ];
+    assert!((polarization(&stopped) - 0.5).abs() < 1e-6);
+    assert_eq!(polarization(&[]), 0.0);
+}
+
+#[test]
+fn recorder_totals_stages_and_frames() {
+    let mut recorder = RunRecorder::new();
+    for millis in [10, 30, 20] {
+        recorder.add(Stage::Simulate, Duration::from_millis(millis));
+        recorder.add(Stage::Encode, Duration::from_millis(1));
+        recorder.frame(Duration::from_millis(millis));
+        recorder.wrote(100);
+    }
+    recorder.artifact("does/not/exist.bin");
+    let summary = recorder.finish(&[boid(0, (10.0, 10.0), (0.0, 1.0))]);
+    assert!((summary.simulate_time - 0.06).abs() < 1e-9);
+    assert!((summary.encode_time - 0.003).abs() < 1e-9);
+    assert_eq!(summary.rasterize_time, 0.0);
+    assert_eq!(summary.frames_written, 3);
+    assert!((summary.mean_frame_time - 0.02).abs() < 1e-9);
+    assert!((summary.min_frame_time - 0.01).abs() < 1e-9);
+    assert!((summary.max_frame_time - 0.03).abs() < 1e-9);
+    // Missing artifacts are listed but add nothing to the bytes
+    assert_eq!(summary.bytes_written, 300);
+    assert_eq!(summary.artifacts, ["does/not/exist.bin"]);
+    assert_eq!(summary.final_population, 1);
+    assert_eq!(summary.final_polarization, 1.0);
+    assert_eq!(summary.achieved_fps, None);
+}
+
+#[test]
+fn recorder_reports_pacing() {
+    let mut recorder = RunRecorder::new();
+    recorder.frame(Duration::from_millis(10));
+    recorder.dropped();
+    recorder.dropped();
+    recorder.paced(29.5, 3);
+    let summary = recorder.finish(&[]);
+    assert_eq!(summary.frames_written, 1);
+    assert_eq!(summary.frames_dropped, 2);
+    assert_eq!(summary.frames_overrun, 3);
+    assert_eq!(summary.achieved_fps, Some(29.5));
+    assert!(summary.lines().contains(&String::from(
+        "Paced at 29.5 fps, 3 frames late, 2 not drawn"
+    )));
+}
+
+#[test]
+fn flock_summaries_read_on_one_line() {
+    let mut boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (10.0, 10.0), (0.0, 2.0)),
+        boid(2, (10.0, 10.0), (-1.0, 0.0)),
+    ];
+    boids[1].pos = Vector2::new(40.0, 5.0);
+    boids[2].pos = Vector2::new(25.0, 30.0);
+    let summary = summarize(&boids);
+    assert_eq!(summary.count, 3);
+    assert_eq!(
+        summary.bounds,
+        Some((Vector2::new(10.0, 5.0), Vector2::new(40.0, 30.0)))
+    );
+    assert_eq!(
+        summary.to_string(),
+        "3 boids in (10.0, 5.0)-(40.0, 30.0), centroid (25.0, 15.0), mean speed 1.33, polarization 0.333"
+    );
+    assert_eq!(
+        summarize(&boids[..1]).to_string(),
+        "1 boid in (10.0, 10.0)-(10.0, 10.0), centroid (10.0, 10.0), mean speed 1.00, polarization 1.000"
+    );
+    assert_eq!(summarize(&[]).to_string(), "no boids");
+}
@@ -0,0 +1,83 @@
+use boids::sweep::{apply, combinations, run_name, SweepAxis, SweepCsvWriter, SweepResult};
+use boids::Parameters;
+
+fn axis(text: &str) -> SweepAxis {
+    text.parse().unwrap()
+}
+
+#[test]
+fn parses_lists_and_ranges() {
+    assert_eq!(
+        axis("matching_factor=0.01,0.05,0.1"),
+        SweepAxis {
+            field: String::from("matching_factor"),
+            values: vec![0.01, 0.05, 0.1],
+        }
+    );
+    assert_eq!(axis("margin=0..20:5").values, [0.0, 5.0, 10.0, 15.0, 20.0]);
+    assert_eq!(axis("margin=4..20:1").values, [4.0]);
+    assert_eq!(
+        axis("matching_factor=0.01,0.05").to_string(),
+        "matching_factor=0.01,0.05"
+    );
+    for bad in [
+        "matching_factor",
+        "matching_factor=",
+        "matching_factor=fast",
+        "matching_factor=0.1..0.2",
+        "matching_factor=0.1..0.2:0",
+        "matching_factr=0.1",
+        "margin=2.5",
+        "voronoi_neighbors=2",
+        "boundary=1",
+    ] {
+        assert!(bad.parse::<SweepAxis>().is_err(), "{bad}");
+    }
+}
+
+#[test]
+fn combinations_cover_every_pair() {
+    let axes = [axis("matching_factor=0.01,0.05"), axis("margin=5,10,15")];
+    let runs = combinations(&axes);
+    assert_eq!(runs.len(), 6);
+    assert_eq!(
+        runs[1],
+        [
+            (String::from("matching_factor"), 0.01),
+            (String::from("margin"), 10.0)
+        ]
+    );
+    assert_eq!(run_name(&runs[5]), "matching_factor=0.05_margin=15");
+    assert_eq!(combinations(&[]), [Vec::new()]);
+    assert_eq!(run_name(&[]), "default");
+}
+
+#[test]
+fn combinations_apply_to_parameters() {
+    let combination = &combinations(&[axis("matching_factor=0.2"), axis("margin=30")])[0];
+    assert_eq!(
+        apply(&Parameters::dense(), combination),
+        Ok(Parameters {
+            matching_factor: 0.2,
+            margin: 30,
+            ..Parameters::dense()
+        })
+    );
+}
+
+#[test]
+fn writes_a_line_per_run() {
+    let axes = [axis("matching_factor=0.01,0.05")];
+    let mut csv = SweepCsvWriter::new(Vec::new(), &axes).unwrap();
+    let result = SweepResult {
+        polarization: 0.5,
+        angular_momentum: 0.25,
+        mean_speed: 2.0,
+    };
+    csv.write_run(&combinations(&axes)[1], &result).unwrap();
+    assert_eq!(
+        String::from_utf8(csv.into_inner()).unwrap(),
+        "run,matching_factor,polarization,angular_momentum,mean_speed\n\
+         matching_factor=0.05,0.05,0.5,0.25,2\n"
+    );
+}
@@ -0,0 +1,16 @@
+use boids::sys::memory_usage_bytes;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn memory_usage_follows_allocations() {
+    let before = memory_usage_bytes().unwrap();
+    assert!(before > 0);
+    // Touched, so it has to be resident
+    let block = vec![1u8; 64 * 1024 * 1024];
+    let after = memory_usage_bytes().unwrap();
+    assert!(after >= before + 32 * 1024 * 1024, "{before} -> {after}");
+    assert_eq!(
+        block.iter().map(|&byte| byte as usize).sum::<usize>(),
+        block.len()
+    );
+}
@@ -0,0 +1,51 @@
+use nalgebra::Vector2;
+
+use boids::testing::{run_steps, spawn_seeded, Scenario};
+use boids::{FlockTally, Parameters, World};
+
+#[test]
+fn seeded_flocks_repeat() {
+    let world = World::from_pixels(300, 200);
+    let flock = spawn_seeded(50, &world, 7);
+    assert_eq!(flock.len(), 50);
+    assert!(flock.iter().all(|boid| world.contains(boid.pos)));
+    assert_eq!(flock, spawn_seeded(50, &world, 7));
+    assert_ne!(flock, spawn_seeded(50, &world, 8));
+}
+
+#[test]
+fn runs_step_the_flock() {
+    let world = World::from_pixels(300, 200);
+    let mut flock = spawn_seeded(50, &world, 7);
+    let start = flock.clone();
+    assert_eq!(
+        run_steps(&mut flock, &world, &Parameters::default(), 0),
+        FlockTally::default()
+    );
+    assert_eq!(flock, start);
+    let tally = run_steps(&mut flock, &world, &Parameters::default(), 3);
+    assert_eq!(tally.population(), 50);
+    assert_ne!(flock, start);
+}
+
+#[test]
+fn scenarios_start_as_described() {
+    let head_on = Scenario::head_on();
+    assert_eq!(head_on.boids.len(), 2);
+    assert_eq!(head_on.boids[0].velocity(), -head_on.boids[1].velocity());
+    let offset = head_on.boids[1].pos - head_on.boids[0].pos;
+    assert_eq!(offset, Vector2::new(40.0, 0.0));
+
+    let wall = Scenario::aimed_at_wall();
+    let margin = wall.parameters.margin as f32;
+    assert!(wall.boids[0].pos.x < wall.world.width - margin);
+    assert!(wall.boids[0].velocity().x > 0.0);
+
+    let cluster = Scenario::dense_cluster();
+    assert_eq!(cluster.boids.len(), 25);
+    let tally = FlockTally::of(&cluster.boids, &[0; 25]);
+    assert_eq!(
+        tally.bounds(),
+        Some((Vector2::new(98.0, 48.0), Vector2::new(102.0, 52.0)))
+    );
+}
@@ -0,0 +1,185 @@
+use std::io::Cursor;
+
+use nalgebra::Vector2;
+
+use boids::boids::{update_boids, Boid};
+use boids::testing::{boid, spawn_seeded};
+use boids::trajectory::{
+    interpolate, Interpolation, TrajectoryReader, TrajectoryWriter, TRAJECTORY_VERSION,
+};
+use boids::world::World;
+use boids::Parameters;
+
+fn flock() -> Vec<Boid> {
+    spawn_seeded(200, &World::from_pixels(400, 300), 5)
+}
+
+// Simulates and records a run, returning the true frames and the file
+fn record(frames: usize, precision: u32, keyframe_interval: u32) -> (Vec<Vec<Boid>>, Vec<u8>) {
+    let mut boids = flock();
+    let mut out = Vec::new();
+    let mut writer =
+        TrajectoryWriter::new(&mut out, precision, keyframe_interval, 400, 300).unwrap();
+    let mut recorded = Vec::new();
+    for _ in 0..frames {
+        update_boids(
+            &mut boids,
+            &World::from_pixels(400, 300),
+            &Parameters::default(),
+        );
+        writer.write_frame(&boids).unwrap();
+        recorded.push(boids.clone());
+    }
+    writer.flush().unwrap();
+    drop(writer);
+    (recorded, out)
+}
+
+fn max_error(expected: &[Boid], actual: &[Boid]) -> f32 {
+    expected
+        .iter()
+        .zip(actual)
+        .map(|(expected, actual)| {
+            let error = expected.pos - actual.pos;
+            error.x.abs().max(error.y.abs())
+        })
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn round_trip_is_within_quantization_step() {
+    let (recorded, bytes) = record(250, 16, 100);
+    let mut reader = TrajectoryReader::new(Cursor::new(bytes)).unwrap();
+    let header = reader.header();
+    assert_eq!(header.version, TRAJECTORY_VERSION);
+    assert_eq!((header.width, header.height), (400, 300));
+    assert_eq!(header.quantization_step(), 1.0 / 16.0);
+
+    for (frame, expected) in recorded.iter().enumerate() {
+        let actual = reader.next_frame().unwrap().unwrap();
+        assert_eq!(actual.len(), expected.len());
+        let error = max_error(expected, &actual);
+        assert!(
+            error <= header.quantization_step(),
+            "frame {frame} is off by {error}"
+        );
+        // Keyframes are exact
+        if frame % 100 == 0 {
+            assert_eq!(error, 0.0);
+        }
+        assert_eq!(actual[3].colour, expected[3].colour);
+    }
+    assert!(reader.next_frame().is_none());
+}
+
+#[test]
+fn deltas_are_smaller_than_keyframes() {
+    let (_, every_frame) = record(50, 16, 1);
+    let (_, deltas) = record(50, 16, 100);
+    assert!(deltas.len() * 3 < every_frame.len());
+}
+
+#[test]
+fn seek_matches_reading_in_order() {
+    let (_, bytes) = record(120, 16, 25);
+    let mut reader = TrajectoryReader::new(Cursor::new(bytes.clone())).unwrap();
+    let frames: Vec<Vec<Boid>> = std::iter::from_fn(|| reader.next_frame())
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let mut reader = TrajectoryReader::new(Cursor::new(bytes)).unwrap();
+    for frame in [60, 10, 110, 111, 0, 75] {
+        reader.seek(frame).unwrap();
+        assert_eq!(reader.position(), frame);
+        let boids = reader.next_frame().unwrap().unwrap();
+        assert_eq!(max_error(&frames[frame], &boids), 0.0, "frame {frame}");
+    }
+    assert!(reader.seek(120).is_err());
+}
+
+#[test]
+fn rejects_other_files() {
+    assert!(TrajectoryReader::new(Cursor::new(b"not a trajectory".to_vec())).is_err());
+
+    let (_, mut bytes) = record(1, 16, 100);
+    bytes[4..8].copy_from_slice(&(TRAJECTORY_VERSION + 1).to_le_bytes());
+    assert!(TrajectoryReader::new(Cursor::new(bytes)).is_err());
+
+    assert!(TrajectoryWriter::new(Vec::new(), 0, 100, 400, 300).is_err());
+}
+
+#[cfg(feature = "large-ids")]
+#[test]
+fn ids_past_32_bits_survive_a_round_trip() {
+    let boids = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        Boid::at(Vector2::new(20.0, 20.0))
+            .with_id(u64::from(u32::MAX) + 7)
+            .with_velocity(Vector2::new(0.0, 1.0))
+            .build(),
+    ];
+    let mut out = Vec::new();
+    let mut writer = TrajectoryWriter::new(&mut out, 16, 100, 400, 300).unwrap();
+    writer.write_frame(&boids).unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+    let mut reader = TrajectoryReader::new(Cursor::new(out)).unwrap();
+    let ids: Vec<_> = reader
+        .next_frame()
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(Boid::id)
+        .collect();
+    assert_eq!(ids, [0, u64::from(u32::MAX) + 7]);
+}
+
+#[test]
+fn interpolation_between_frames() {
+    let from = vec![
+        boid(0, (10.0, 10.0), (2.0, 0.0)),
+        boid(1, (50.0, 50.0), (0.0, 1.0)),
+    ];
+    let to = vec![
+        boid(1, (50.0, 51.0), (0.0, 1.0)),
+        boid(0, (12.0, 10.0), (2.0, 0.0)),
+    ];
+
+    let linear = interpolate(&from, &to, 0.25, Interpolation::Linear);
+    assert_eq!(linear[0].pos, Vector2::new(10.5, 10.0));
+    assert_eq!(linear[1].pos, Vector2::new(50.0, 50.25));
+
+    // Steady motion is a straight line either way
+    let hermite = interpolate(&from, &to, 0.25, Interpolation::Hermite);
+    assert!((hermite[0].pos - linear[0].pos).norm() < 1e-5);
+    assert!((hermite[1].pos - linear[1].pos).norm() < 1e-5);
+
+    assert_eq!(interpolate(&from, &to, 0.0, Interpolation::Hermite), from);
+}
+
+#[test]
+fn hermite_follows_velocities() {
+    // Turning a corner, from heading right to heading down
+    let from = vec![boid(0, (0.0, 0.0), (1.0, 0.0))];
+    let to = vec![boid(0, (1.0, 1.0), (0.0, 1.0))];
+    let linear = interpolate(&from, &to, 0.5, Interpolation::Linear);
+    let hermite = interpolate(&from, &to, 0.5, Interpolation::Hermite);
+    assert_eq!(linear[0].pos, Vector2::new(0.5, 0.5));
+    assert_eq!(hermite[0].pos, Vector2::new(0.625, 0.375));
+}
+
+#[test]
+fn despawned_boids_are_not_interpolated() {
+    let from = vec![
+        boid(0, (10.0, 10.0), (1.0, 0.0)),
+        boid(1, (50.0, 50.0), (0.0, 1.0)),
+    ];
+    let to = vec![
+        boid(0, (11.0, 10.0), (1.0, 0.0)),
+        boid(2, (0.0, 0.0), (0.0, 1.0)),
+    ];
+    let between = interpolate(&from, &to, 0.5, Interpolation::Linear);
+    assert_eq!(between.len(), 1);
+    assert_eq!(between[0].pos, Vector2::new(10.5, 10.0));
+    assert_eq!(interpolate(&from, &to, 0.0, Interpolation::Linear).len(), 2);
+}
@@ -0,0 +1,82 @@
+use nalgebra::Vector2;
+
+use boids::boids::Boid;
+use boids::testing::boid;
+use boids::transform::{self, OutOfBounds, Transform};
+use boids::world::World;
+
+fn transformed(transform: &str, width: u32, height: u32, clamp: bool) -> Result<Boid, OutOfBounds> {
+    let mut boids = vec![boid(0, (1.0, 2.0), (1.0, 0.5))];
+    let world = World::from_pixels(width, height);
+    transform::apply(&mut boids, transform.parse().unwrap(), &world, clamp)?;
+    Ok(boids.remove(0))
+}
+
+#[test]
+fn parses_transforms() {
+    assert_eq!("fliph".parse(), Ok(Transform::FlipH));
+    assert_eq!("flipv".parse(), Ok(Transform::FlipV));
+    assert_eq!("rot90".parse(), Ok(Transform::Rotate(90.0)));
+    assert_eq!("rot180".parse(), Ok(Transform::Rotate(180.0)));
+    assert_eq!("rotate:37.5".parse(), Ok(Transform::Rotate(37.5)));
+    assert!("rotate:".parse::<Transform>().is_err());
+    assert!("rotate:NaN".parse::<Transform>().is_err());
+    assert!("spin".parse::<Transform>().is_err());
+}
+
+#[test]
+fn flips_about_the_centre() {
+    // 11x11 world, so the centre is at (5, 5)
+    assert_eq!(
+        transformed("fliph", 11, 11, false).unwrap(),
+        boid(0, (9.0, 2.0), (-1.0, 0.5))
+    );
+    assert_eq!(
+        transformed("flipv", 11, 11, false).unwrap(),
+        boid(0, (1.0, 8.0), (1.0, -0.5))
+    );
+}
+
+#[test]
+fn rotates_clockwise_on_screen() {
+    // Offset from the centre is (-4, -3), a quarter turn clockwise with y
+    // pointing down takes that to (3, -4)
+    assert_eq!(
+        transformed("rot90", 11, 11, false).unwrap(),
+        boid(0, (8.0, 1.0), (-0.5, 1.0))
+    );
+    assert_eq!(
+        transformed("rot180", 11, 11, false).unwrap(),
+        boid(0, (9.0, 8.0), (-1.0, -0.5))
+    );
+    let full_turn = transformed("rotate:360", 11, 11, false).unwrap();
+    assert_eq!(full_turn, boid(0, (1.0, 2.0), (1.0, 0.5)));
+}
+
+#[test]
+fn arbitrary_rotation() {
+    let rotated = transformed("rotate:45", 11, 11, false).unwrap();
+    // (-4, -3) rotated 45 degrees clockwise
+    let root_half = 0.5_f32.sqrt();
+    let expected = Vector2::new(5.0 - root_half, 5.0 - 7.0 * root_half);
+    assert!((rotated.pos - expected).norm() < 1e-5, "{}", rotated.pos);
+}
+
+#[test]
+fn rotating_out_of_the_world() {
+    // In a wide world the corner rotates out past the top
+    assert_eq!(transformed("rot90", 21, 11, false), Err(OutOfBounds(1)));
+    let clamped = transformed("rot90", 21, 11, true).unwrap();
+    assert_eq!(clamped.pos, Vector2::new(13.0, 0.0));
+}
+
+#[test]
+fn transforms_compose_in_order() {
+    let world = World::from_pixels(11, 11);
+    let mut boids = vec![boid(0, (1.0, 2.0), (1.0, 0.5))];
+    for transform in ["fliph", "rot90"] {
+        transform::apply(&mut boids, transform.parse().unwrap(), &world, false).unwrap();
+    }
+    // fliph takes (-4, -3) to (4, -3), then rot90 to (3, 4)
+    assert_eq!(boids[0], boid(0, (8.0, 9.0), (-0.5, -1.0)));
+}
@@ -0,0 +1,105 @@
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+use boids::boids::{Boid, BoidId, EventDrivenUpdate};
+use boids::colour::Colour;
+use boids::simulation::{Simulation, SimulationConfig};
+use boids::turbulence::TurbulenceInjector;
+use boids::world::World;
+use boids::Parameters;
+
+fn turbulence(fraction: f32, strength: f32) -> TurbulenceInjector {
+    TurbulenceInjector { fraction, strength }
+}
+
+#[test]
+fn a_rounded_share_of_the_flock_is_kicked() {
+    assert_eq!(turbulence(0.01, 1.0).count(1000), 10);
+    assert_eq!(turbulence(0.01, 1.0).count(150), 2);
+    assert_eq!(turbulence(0.01, 1.0).count(40), 0);
+    assert_eq!(turbulence(2.0, 1.0).count(40), 40);
+    assert_eq!(turbulence(0.0, 1.0).count(1000), 0);
+    assert_eq!(turbulence(0.5, 0.0).count(1000), 0);
+}
+
+#[test]
+fn kicks_have_the_strength_asked_for() {
+    let kicks = turbulence(0.1, 2.5).kicks(200, &mut StdRng::seed_from_u64(4));
+    assert_eq!(kicks.len(), 200);
+    let kicked: Vec<&Vector2<f32>> = kicks
+        .iter()
+        .filter(|kick| **kick != Vector2::zeros())
+        .collect();
+    assert_eq!(kicked.len(), 20);
+    for kick in kicked {
+        assert!((kick.norm() - 2.5).abs() < 1e-5, "{kick}");
+    }
+    assert_eq!(
+        kicks,
+        turbulence(0.1, 2.5).kicks(200, &mut StdRng::seed_from_u64(4))
+    );
+    assert!(turbulence(0.0, 2.5)
+        .kicks(200, &mut StdRng::seed_from_u64(4))
+        .is_empty());
+}
+
+fn simulation(perturbation_fraction: f32) -> Simulation {
+    let parameters = Parameters {
+        perturbation_fraction,
+        perturbation_strength: 1.0,
+        ..Parameters::default()
+    };
+    let config = SimulationConfig {
+        seed: 11,
+        ..SimulationConfig::new(World::from_pixels(400, 300), parameters, 100)
+    };
+    Simulation::new(config).unwrap()
+}
+
+#[test]
+fn simulations_count_the_boids_they_kick() {
+    let mut calm = simulation(0.0);
+    let mut kicked = simulation(0.05);
+    for _ in 0..5 {
+        assert_eq!(calm.step().perturbed_count, 0);
+        assert_eq!(kicked.step().perturbed_count, 5);
+    }
+    assert_ne!(calm.boids(), kicked.boids());
+
+    // The same seed kicks the same boids the same way
+    let mut again = simulation(0.05);
+    again.run_for(5);
+    assert_eq!(again.boids(), kicked.boids());
+}
+
+#[test]
+fn kicked_boids_are_steered_again() {
+    let world = World::from_pixels(400, 300);
+    let parameters = Parameters {
+        update_threshold: 100.0,
+        ..Parameters::default()
+    };
+    let boid = |id: BoidId, x: f32, y: f32| {
+        Boid::new(
+            id,
+            Vector2::new(x, y),
+            Vector2::new(0.5, 0.0),
+            Colour::WHITE,
+        )
+    };
+    let mut boids = vec![boid(0, 121.0, 121.0), boid(1, 299.0, 199.0)];
+    let mut updater = EventDrivenUpdate::new();
+    updater.update(&mut boids, &world, &parameters);
+    updater.update(&mut boids, &world, &parameters);
+    assert_eq!(updater.dirty_count(), 0);
+
+    updater.perturb(vec![Vector2::zeros(), Vector2::new(0.0, 1.0)]);
+    updater.update(&mut boids, &world, &parameters);
+    assert_eq!(updater.dirty_count(), 1);
+    assert_eq!(boids[0].velocity(), Vector2::new(0.5, 0.0));
+    assert_eq!(boids[1].velocity(), Vector2::new(0.5, 1.0));
+
+    // Only the next update is kicked
+    updater.update(&mut boids, &world, &parameters);
+    assert_eq!(boids[1].velocity(), Vector2::new(0.5, 1.0));
+}
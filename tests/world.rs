@@ -0,0 +1,100 @@
+use nalgebra::Vector2;
+
+use boids::world::World;
+
+#[test]
+fn worlds_from_pixels() {
+    let world = World::from_pixels(1920, 1080);
+    assert_eq!((world.width, world.height), (1920.0, 1080.0));
+    assert_eq!(world.pixels(), (1920, 1080));
+    assert_eq!(world.size(), Vector2::new(1920.0, 1080.0));
+    assert_eq!(world.max(), Vector2::new(1919.0, 1079.0));
+    // Part of a pixel still needs one to show it
+    assert_eq!(World::new(10.5, 4.0).pixels(), (11, 4));
+    assert!(!world.is_empty());
+    assert!(World::from_pixels(0, 1080).is_empty());
+    assert!(World::new(f32::NAN, 10.0).is_empty());
+}
+
+#[test]
+fn contains_and_clamps_to_its_pixels() {
+    let world = World::from_pixels(200, 100);
+    assert!(world.contains(Vector2::new(0.0, 0.0)));
+    assert!(world.contains(Vector2::new(199.5, 99.5)));
+    assert!(!world.contains(Vector2::new(200.0, 50.0)));
+    assert!(!world.contains(Vector2::new(-0.1, 50.0)));
+    assert_eq!(
+        world.clamp(Vector2::new(-5.0, 150.0)),
+        Vector2::new(0.0, 99.0)
+    );
+    assert_eq!(
+        world.clamp(Vector2::new(250.0, 40.0)),
+        Vector2::new(199.0, 40.0)
+    );
+    assert_eq!(
+        world.clamp(Vector2::new(12.5, 40.0)),
+        Vector2::new(12.5, 40.0)
+    );
+}
+
+fn margin(margin: u32) -> boids::Parameters {
+    boids::Parameters {
+        margin,
+        ..boids::Parameters::default()
+    }
+}
+
+#[test]
+fn margins_wider_than_the_world_turn_towards_the_middle() {
+    // Used to underflow working out where the far margin starts, and then
+    // to turn both ways at once, which cancelled out
+    let world = World::from_pixels(200, 100);
+    let turn = boids::Parameters::default().turn_factor;
+    let vel = Vector2::new(1.0, 1.0);
+    for margin in [100, 300, u32::MAX] {
+        let parameters = self::margin(margin);
+        assert_eq!(
+            world.turn(Vector2::new(10.0, 80.0), vel, &parameters),
+            Vector2::new(1.0 + turn, 1.0 - turn),
+            "{margin}"
+        );
+        assert_eq!(
+            world.turn(Vector2::new(190.0, 20.0), vel, &parameters),
+            Vector2::new(1.0 - turn, 1.0 + turn),
+            "{margin}"
+        );
+    }
+    assert!(world.margins_meet(100));
+}
+
+#[test]
+fn margins_just_past_half_the_world_still_turn_inwards() {
+    let world = World::from_pixels(200, 100);
+    let turn = boids::Parameters::default().turn_factor;
+    let parameters = margin(51);
+    let vel = Vector2::new(1.0, 1.0);
+    assert!(world.margins_meet(51));
+    assert_eq!(
+        world.turn(Vector2::new(100.0, 49.0), vel, &parameters),
+        Vector2::new(1.0, 1.0 + turn)
+    );
+    assert_eq!(
+        world.turn(Vector2::new(100.0, 51.0), vel, &parameters),
+        Vector2::new(1.0, 1.0 - turn)
+    );
+    assert!(!world.margins_meet(49));
+}
+
+#[test]
+fn no_margin_only_turns_boids_past_the_edge() {
+    let world = World::from_pixels(200, 100);
+    let turn = boids::Parameters::default().turn_factor;
+    let parameters = margin(0);
+    let vel = Vector2::new(1.0, 1.0);
+    assert!(!world.margins_meet(0));
+    assert_eq!(world.turn(Vector2::new(0.0, 0.0), vel, &parameters), vel);
+    assert_eq!(
+        world.turn(Vector2::new(200.5, -0.5), vel, &parameters),
+        Vector2::new(1.0 - turn, 1.0 + turn)
+    );
+}
@@ -0,0 +1,91 @@
+use nalgebra::Vector2;
+
+use boids::zone::SpeedLimitZone;
+use boids::Parameters;
+
+fn zone(rect: (f32, f32, f32, f32), max_speed: f32) -> SpeedLimitZone {
+    SpeedLimitZone {
+        rect,
+        max_speed,
+        min_speed: None,
+    }
+}
+
+#[test]
+fn parses_zones() {
+    assert_eq!(
+        "10,20,110,70,1.5".parse(),
+        Ok(zone((10.0, 20.0, 110.0, 70.0), 1.5))
+    );
+    // Corners either way round, with a minimum speed too
+    assert_eq!(
+        "110, 70, 10, 20, 6, 4".parse(),
+        Ok(SpeedLimitZone {
+            rect: (10.0, 20.0, 110.0, 70.0),
+            max_speed: 6.0,
+            min_speed: Some(4.0),
+        })
+    );
+    assert!("10,20,110,70".parse::<SpeedLimitZone>().is_err());
+    assert!("10,20,110,70,fast".parse::<SpeedLimitZone>().is_err());
+}
+
+#[test]
+fn contains_the_top_left_edges_only() {
+    let zone = zone((10.0, 20.0, 30.0, 40.0), 1.0);
+    assert!(zone.contains(Vector2::new(10.0, 20.0)));
+    assert!(zone.contains(Vector2::new(29.9, 39.9)));
+    assert!(!zone.contains(Vector2::new(30.0, 25.0)));
+    assert!(!zone.contains(Vector2::new(15.0, 40.0)));
+    assert!(!zone.contains(Vector2::new(9.9, 25.0)));
+}
+
+#[test]
+fn first_zone_sets_the_limits() {
+    let parameters = Parameters {
+        speed_zones: vec![
+            SpeedLimitZone {
+                min_speed: Some(5.0),
+                ..zone((0.0, 0.0, 10.0, 10.0), 8.0)
+            },
+            zone((5.0, 5.0, 20.0, 20.0), 1.0),
+            zone((0.0, 0.0, 100.0, 100.0), 0.2),
+        ],
+        ..Parameters::default()
+    };
+    assert_eq!(
+        SpeedLimitZone::limits_at(Vector2::new(6.0, 6.0), &parameters),
+        (5.0, 8.0)
+    );
+    assert_eq!(
+        SpeedLimitZone::limits_at(Vector2::new(15.0, 15.0), &parameters),
+        (0.5, 1.0)
+    );
+    // Slower than the usual minimum, which gives way
+    assert_eq!(
+        SpeedLimitZone::limits_at(Vector2::new(50.0, 50.0), &parameters),
+        (0.2, 0.2)
+    );
+    assert_eq!(
+        SpeedLimitZone::limits_at(Vector2::new(150.0, 50.0), &parameters),
+        (0.5, 3.0)
+    );
+    assert!(parameters.speed_zones[0].is_boost(parameters.max_speed));
+    assert!(!parameters.speed_zones[1].is_boost(parameters.max_speed));
+}
+
+#[test]
+fn zones_round_trip_through_toml() {
+    let parameters = Parameters {
+        speed_zones: vec![zone((100.0, 100.0, 300.0, 200.0), 1.0)],
+        ..Parameters::default()
+    };
+    let text = toml::to_string(&parameters).unwrap();
+    assert_eq!(toml::from_str::<Parameters>(&text).unwrap(), parameters);
+    // As it would be written by hand
+    let text = toml::to_string(&Parameters::default())
+        .unwrap()
+        .replace("speed_zones = []\n", "")
+        + "[[speed_zones]]\nrect = [100.0, 100.0, 300.0, 200.0]\nmax_speed = 1.0\n";
+    assert_eq!(toml::from_str::<Parameters>(&text).unwrap(), parameters);
+}